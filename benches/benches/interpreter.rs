@@ -0,0 +1,140 @@
+//! Compiling and running programs, to compare optimizations by.
+//!
+//! Run with `cargo bench` from the benches directory, criterion keeps the
+//! last results in target/criterion and reports the change against them.
+//! The programs are compiled once outside of the measured runs, so that
+//! these measure the interpreter and not the compiler or file IO.
+
+use brainfuck::optimizer::Pipeline;
+use brainfuck::{
+    compile, compile_with, execute, execute_dyn, Dispatch, DummyInputOutput, ExecutionConfig,
+    Program,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const MANDELBROT: &str = include_str!("../../programs/mandelbrot.bf");
+
+/// Three nested loops counting down from 254 in steps of two, about two
+/// million iterations of an inner loop that is no multiplication.
+const NESTED_LOOPS: &str = "--[>--[>--[>+<--]<--]<--]";
+
+/// Scans 256 cells that are not zero to the left and back to the right,
+/// 32258 times.
+fn scans() -> String {
+    format!(">{}>--[>--[<<<[<]>[>]>>-]<--]", "+>".repeat(256))
+}
+
+/// 127 runs of a loop over 40000 ops, about 1 MB as `Ops` and a sixth of
+/// that packed.
+fn long_program() -> String {
+    format!("--[{}--]", ">+<.".repeat(20_000))
+}
+
+/// Scans 8192 cells that are not zero to the left and back to the right,
+/// 254 times.
+fn long_scans() -> String {
+    format!(">{}>--[<<[<]>[>]>-]", "+>".repeat(8192))
+}
+
+/// About two million prints, 127 to the third.
+const PRINTS: &str = "--[>--[>--[.--]<--]<--]";
+
+fn run(program: &Program) {
+    execute(program, &mut DummyInputOutput).unwrap();
+}
+
+fn compiling(c: &mut Criterion) {
+    c.bench_function("compile mandelbrot", |b| {
+        b.iter(|| compile(black_box(MANDELBROT)).unwrap())
+    });
+}
+
+fn executing(c: &mut Criterion) {
+    let mandelbrot = compile(MANDELBROT).unwrap();
+    let nested_loops = compile(NESTED_LOOPS).unwrap();
+    let scans = compile(&scans()).unwrap();
+    let long_scans = compile(&long_scans()).unwrap();
+
+    let mut group = c.benchmark_group("execute");
+    group.bench_function("nested loops", |b| b.iter(|| run(&nested_loops)));
+    group.bench_function("scans", |b| b.iter(|| run(&scans)));
+    group.bench_function("long scans", |b| b.iter(|| run(&long_scans)));
+    // a run takes seconds
+    group.sample_size(10);
+    group.bench_function("mandelbrot", |b| b.iter(|| run(&mandelbrot)));
+    group.finish();
+}
+
+/// The same programs with every dispatch, see `--dispatch`.
+fn dispatching(c: &mut Criterion) {
+    let mandelbrot = compile(MANDELBROT).unwrap();
+    let nested_loops = compile(NESTED_LOOPS).unwrap();
+    let long_program = compile(&long_program()).unwrap();
+
+    let mut group = c.benchmark_group("dispatch");
+    group.sample_size(10);
+    let dispatches = [
+        ("match", Dispatch::Match),
+        ("threaded", Dispatch::Threaded),
+        ("packed", Dispatch::Packed),
+    ];
+    for (name, dispatch) in dispatches {
+        let config = ExecutionConfig::builder().dispatch(dispatch).build();
+        let run = |program| config.run(program, &mut DummyInputOutput).unwrap();
+        group.bench_function(format!("nested loops {}", name), |b| {
+            b.iter(|| run(&nested_loops))
+        });
+        group.bench_function(format!("long program {}", name), |b| {
+            b.iter(|| run(&long_program))
+        });
+        group.bench_function(format!("mandelbrot {}", name), |b| {
+            b.iter(|| run(&mandelbrot))
+        });
+    }
+    group.finish();
+}
+
+/// The programs with and without the `balanced_loops` pass, whose loops run
+/// without checking the tape.
+fn balancing(c: &mut Criterion) {
+    let plain = Pipeline::default().disable("balanced_loops").unwrap();
+    let mut group = c.benchmark_group("balanced loops");
+    group.sample_size(10);
+    for (name, pipeline) in [("balanced", Pipeline::default()), ("plain", plain)] {
+        let mandelbrot = compile_with(MANDELBROT, &pipeline).unwrap();
+        let nested_loops = compile_with(NESTED_LOOPS, &pipeline).unwrap();
+        group.bench_function(format!("nested loops {}", name), |b| {
+            b.iter(|| run(&nested_loops))
+        });
+        group.bench_function(format!("mandelbrot {}", name), |b| {
+            b.iter(|| run(&mandelbrot))
+        });
+    }
+    group.finish();
+}
+
+/// The programs with an interpreter compiled for `DummyInputOutput` and with
+/// the one behind `dyn InputOutput`.
+fn io_calls(c: &mut Criterion) {
+    let mandelbrot = compile(MANDELBROT).unwrap();
+    let prints = compile(PRINTS).unwrap();
+
+    let mut group = c.benchmark_group("io calls");
+    group.sample_size(10);
+    let run_dyn = |program| execute_dyn(program, &mut DummyInputOutput).unwrap();
+    group.bench_function("prints generic", |b| b.iter(|| run(&prints)));
+    group.bench_function("prints dyn", |b| b.iter(|| run_dyn(&prints)));
+    group.bench_function("mandelbrot generic", |b| b.iter(|| run(&mandelbrot)));
+    group.bench_function("mandelbrot dyn", |b| b.iter(|| run_dyn(&mandelbrot)));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    compiling,
+    executing,
+    dispatching,
+    balancing,
+    io_calls
+);
+criterion_main!(benches);
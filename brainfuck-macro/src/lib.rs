@@ -0,0 +1,248 @@
+//! `brainfuck!`, brainfuck embedded in Rust and compiled along with it. A
+//! program that does not compile is a compile error at its literal.
+//!
+//! ```
+//! use brainfuck_macro::brainfuck;
+//!
+//! // without input the program runs while the macro expands
+//! assert_eq!(brainfuck!("++++++++[>++++++++<-]>+."), "A");
+//! // with input it is compiled while the macro expands and runs at runtime
+//! let name = String::from("rust");
+//! assert_eq!(brainfuck!(",[.,]", input = name), "rust");
+//! ```
+//!
+//! The compile errors are in tests/ui. The expansion of a program with
+//! input refers to the `brainfuck` crate, which has to be a dependency as
+//! well, and whose `macro` feature re-exports the macro.
+
+use brainfuck_macro_library::{
+    compile, Error, ExecutionConfig, Position, Program, RuntimeError, StringInputOutput,
+};
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Programs without input that run longer than this while the macro expands
+/// are run at runtime instead.
+const EXPANSION_OPS: u64 = 10_000_000;
+
+/// `brainfuck!("program")` or `brainfuck!("program", input = expr)` is the
+/// output of the program as a `String`, `,` reads the chars of `expr`, which
+/// is anything that is `AsRef<str>`. The default [`ExecutionConfig`] is
+/// used, and runtime errors while the macro expands are compile errors, at
+/// runtime they panic.
+#[proc_macro]
+pub fn brainfuck(tokens: TokenStream) -> TokenStream {
+    match expand(tokens) {
+        Ok(tokens) => tokens,
+        Err((span, message)) => compile_error(span, &message),
+    }
+}
+
+fn expand(tokens: TokenStream) -> Result<TokenStream, (Span, String)> {
+    let mut tokens = tokens.into_iter();
+    let literal = match tokens.next().map(ungroup) {
+        Some(TokenTree::Literal(literal)) => literal,
+        Some(token) => return Err((token.span(), "expected a string literal".into())),
+        None => return Err((Span::call_site(), "expected a string literal".into())),
+    };
+    let span = literal.span();
+    let source =
+        string_value(&literal.to_string()).ok_or((span, "expected a string literal".into()))?;
+    let input = match tokens.next() {
+        None => None,
+        Some(TokenTree::Punct(comma)) if comma.as_char() == ',' => input(tokens)?,
+        Some(token) => return Err((token.span(), "expected `,`".into())),
+    };
+    let program = compile(&source).map_err(|errors| (span, Error::Compile(errors).to_string()))?;
+    if input.is_none() {
+        let config = ExecutionConfig::builder().max_ops(EXPANSION_OPS).build();
+        let mut in_out = StringInputOutput::new();
+        match config.run(&program, &mut in_out) {
+            Ok(()) => return Ok(string_from(in_out.output())),
+            Err(RuntimeError::FuelExhausted { .. }) => {}
+            Err(err) => return Err((span, runtime_error(&program, &source, err))),
+        }
+    }
+    Ok(run_time(&program, input))
+}
+
+/// The tokens of macro_rules arguments may come wrapped in a group without
+/// delimiters.
+fn ungroup(token: TokenTree) -> TokenTree {
+    match token {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+            match group.stream().into_iter().next() {
+                Some(inner) => ungroup(inner),
+                None => TokenTree::Group(group),
+            }
+        }
+        token => token,
+    }
+}
+
+/// The expression after `input =`, `None` for a trailing comma.
+fn input(
+    mut tokens: impl Iterator<Item = TokenTree>,
+) -> Result<Option<TokenStream>, (Span, String)> {
+    match tokens.next() {
+        None => return Ok(None),
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "input" => {}
+        Some(token) => return Err((token.span(), "expected `input = ...`".into())),
+    }
+    match tokens.next() {
+        Some(TokenTree::Punct(equals)) if equals.as_char() == '=' => {}
+        Some(token) => return Err((token.span(), "expected `=`".into())),
+        None => return Err((Span::call_site(), "expected `=`".into())),
+    }
+    let mut expr: Vec<TokenTree> = tokens.collect();
+    if let Some(TokenTree::Punct(comma)) = expr.last() {
+        if comma.as_char() == ',' {
+            expr.pop();
+        }
+    }
+    if expr.is_empty() {
+        return Err((Span::call_site(), "expected the input after `=`".into()));
+    }
+    Ok(Some(expr.into_iter().collect()))
+}
+
+/// Like the messages of the CLI, with the position in the literal.
+fn runtime_error(program: &Program, source: &str, err: RuntimeError) -> String {
+    match err.ip().and_then(|ip| program.source_span(ip)) {
+        Some(span) => format!(
+            "runtime error: {} ({})",
+            err,
+            Position::in_source(source, span.start)
+        ),
+        None => format!("runtime error: {}", err),
+    }
+}
+
+/// `String::from("...")`
+fn string_from(output: &str) -> TokenStream {
+    let mut tokens: TokenStream = "::std::string::String::from".parse().unwrap();
+    let literal = TokenTree::Literal(Literal::string(output));
+    tokens.extend(Some(TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        literal.into(),
+    ))));
+    tokens
+}
+
+/// A block that runs the compiled ops of `program` on `input`.
+fn run_time(program: &Program, input: Option<TokenStream>) -> TokenStream {
+    let ops: Vec<String> = program
+        .iter()
+        .map(|op| format!("::brainfuck::Ops::{:?}", op))
+        .collect();
+    let mut block: TokenStream = "let input: &str = ".parse().unwrap();
+    match input {
+        Some(expr) => {
+            block.extend("::std::convert::AsRef::<str>::as_ref".parse::<TokenStream>());
+            let borrowed: TokenStream = Some(TokenTree::Punct(Punct::new('&', Spacing::Alone)))
+                .into_iter()
+                .chain(Some(TokenTree::Group(Group::new(
+                    Delimiter::Parenthesis,
+                    expr,
+                ))))
+                .collect();
+            block.extend(Some(TokenTree::Group(Group::new(
+                Delimiter::Parenthesis,
+                borrowed,
+            ))));
+        }
+        None => block.extend("\"\"".parse::<TokenStream>()),
+    }
+    let rest = format!(
+        "; let program = ::brainfuck::Program::new(::std::vec![{}]).unwrap();
+        let mut in_out = ::brainfuck::StringInputOutput::with_input(input);
+        if let ::std::result::Result::Err(err) = ::brainfuck::execute(&program, &mut in_out) {{
+            ::std::panic!(\"brainfuck!: {{}}\", err);
+        }}
+        in_out.into_output()",
+        ops.join(", ")
+    );
+    block.extend(rest.parse::<TokenStream>());
+    TokenTree::Group(Group::new(Delimiter::Brace, block)).into()
+}
+
+/// `compile_error!(message)` at `span`.
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut arguments = Group::new(Delimiter::Parenthesis, TokenTree::Literal(literal).into());
+    arguments.set_span(span);
+    vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(arguments),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// The value of a string literal as written in the source, `None` for any
+/// other literal.
+fn string_value(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let quoted = &raw[hashes..raw.len().checked_sub(hashes)?];
+        return quoted
+            .strip_prefix('"')?
+            .strip_suffix('"')
+            .map(String::from);
+    }
+    let quoted = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            '0' => value.push('\0'),
+            '\\' => value.push('\\'),
+            '\'' => value.push('\''),
+            '"' => value.push('"'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                value.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            'u' => {
+                let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // a line continuation skips the line break and the indentation
+            '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_values() {
+        assert_eq!(string_value(r#""+[.]""#).as_deref(), Some("+[.]"));
+        assert_eq!(
+            string_value(r#""a\n\t\\\"\x41\u{e9}""#).as_deref(),
+            Some("a\n\t\\\"A\u{e9}")
+        );
+        assert_eq!(string_value("\"+\\\n    .\"").as_deref(), Some("+."));
+        assert_eq!(
+            string_value(r###"r#"say "hi""#"###).as_deref(),
+            Some(r#"say "hi""#)
+        );
+        assert_eq!(string_value("r\"+\"").as_deref(), Some("+"));
+        assert_eq!(string_value("b\"+\""), None);
+        assert_eq!(string_value("1"), None);
+    }
+}
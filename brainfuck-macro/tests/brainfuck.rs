@@ -0,0 +1,44 @@
+use brainfuck_macro::brainfuck;
+
+#[test]
+fn expands_at_compile_time() {
+    assert_eq!(brainfuck!("++++++++[>++++++++<-]>+."), "A");
+    let hello = brainfuck!(
+        r"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]
+          >>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++."
+    );
+    assert_eq!(hello, "Hello World!\n");
+}
+
+#[test]
+fn input() {
+    assert_eq!(brainfuck!(",[.,]", input = "cat"), "cat");
+    let name = String::from("rust");
+    assert_eq!(brainfuck!(",[+.,]", input = name,), "svtu");
+    assert_eq!(brainfuck!(",.", input = ""), "\0");
+}
+
+/// Macros pass their arguments on in groups without delimiters.
+#[test]
+fn from_macro_rules() {
+    macro_rules! echo {
+        ($source:expr, $input:expr) => {
+            brainfuck!($source, input = $input)
+        };
+    }
+    assert_eq!(echo!(",.", "x"), "x");
+}
+
+#[test]
+#[should_panic(expected = "brainfuck!: pointer moved out of bounds")]
+fn runtime_errors_panic() {
+    let input = "x";
+    brainfuck!(",<", input = input);
+}
+
+/// The compile errors and where they point, in the .stderr next to each
+/// program.
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let _out = brainfuck_macro::brainfuck!("+[");
+}
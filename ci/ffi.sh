@@ -0,0 +1,10 @@
+#!/bin/sh
+# Builds the C API as a shared library and runs tests/ffi.c against it, the
+# commands from the top of that file.
+set -eu
+cd "$(dirname "$0")/.."
+
+cargo rustc --lib --release --features ffi --crate-type cdylib
+cc -Iinclude -o target/ffi tests/ffi.c -Ltarget/release -lbrainfuck
+LD_LIBRARY_PATH=target/release target/ffi
+echo "the C API runs hello world"
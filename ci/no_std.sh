@@ -0,0 +1,20 @@
+#!/bin/sh
+# Builds the library without the std feature, as it is used on an embedded
+# target:
+#
+#     cargo build --lib --no-default-features --target thumbv7em-none-eabihf
+#
+# Without that target installed the library is built for the host instead,
+# still no_std, which catches a use of std but not a dependency that needs it.
+set -eu
+cd "$(dirname "$0")/.."
+
+target=thumbv7em-none-eabihf
+if rustup target list --installed 2>/dev/null | grep -qx "$target"; then
+    cargo build --lib --no-default-features --target "$target"
+    cargo build --lib --no-default-features --features wasm,async --target "$target"
+else
+    echo "no $target target, building for the host"
+    cargo build --lib --no-default-features
+    cargo build --lib --no-default-features --features wasm,async
+fi
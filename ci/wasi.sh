@@ -0,0 +1,25 @@
+#!/bin/sh
+# Builds the CLI for WASI and runs rot13 with it under wasmtime, reading the
+# program from a preopened directory and the input from stdin:
+#
+#     cargo build --release --target wasm32-wasip1
+#     wasmtime run --dir . target/wasm32-wasip1/release/brainfuck.wasm prog.bf
+#
+# Skipped, successfully, without wasmtime or the wasm32-wasip1 target.
+set -eu
+cd "$(dirname "$0")/.."
+
+if ! command -v wasmtime >/dev/null 2>&1 ||
+    ! rustup target list --installed 2>/dev/null | grep -qx wasm32-wasip1; then
+    echo "no wasmtime or wasm32-wasip1 target, skipping"
+    exit 0
+fi
+
+cargo build --release --target wasm32-wasip1
+output=$(mktemp)
+trap 'rm -f "$output"' EXIT
+wasmtime run --dir . target/wasm32-wasip1/release/brainfuck.wasm \
+    --eof=minus-one programs/rot13.bf <programs/rot13.in >"$output"
+# the CLI ends what the program printed with a banner
+{ cat programs/rot13.out; printf '\nDone\n'; } | cmp - "$output"
+echo "rot13 runs under wasmtime"
@@ -0,0 +1,28 @@
+//! Compiles arbitrary input and runs it under a small instruction budget,
+//! so that infinite loops end with `FuelExhausted` instead of hanging.
+//!
+//! Run with `cargo +nightly fuzz run execute` from the repository root.
+#![no_main]
+
+use brainfuck::{compile, CellSize, EofBehavior, ExecutionConfig, StringInputOutput, TapeMode};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let program = match compile(&String::from_utf8_lossy(data)) {
+        Ok(program) => program,
+        Err(_) => return,
+    };
+    let configs = [
+        ExecutionConfig::builder(),
+        ExecutionConfig::builder().eof(EofBehavior::Error),
+        ExecutionConfig::builder().cell_size(CellSize::U16),
+        ExecutionConfig::builder().tape(TapeMode::Wrap).tape_size(3),
+        ExecutionConfig::builder().tape(TapeMode::Grow).tape_size(1),
+        ExecutionConfig::builder().tape(TapeMode::Infinite).tape_size(1),
+    ];
+    for config in &configs {
+        let mut in_out = StringInputOutput::with_input("fuzz");
+        // errors are fine, only panics and hangs are bugs
+        let _ = config.max_ops(10_000).build().run(&program, &mut in_out);
+    }
+});
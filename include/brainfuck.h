@@ -0,0 +1,45 @@
+/*
+ * The C API of the brainfuck crate, built with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ */
+#ifndef BRAINFUCK_H
+#define BRAINFUCK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* the results of bf_run */
+#define BF_OK 0
+#define BF_RUNTIME_ERROR 1
+#define BF_INVALID_ARGUMENT (-1)
+#define BF_PANIC (-2)
+
+typedef struct BfProgram BfProgram;
+
+/*
+ * Compiles the NUL terminated source. Returns NULL if it does not compile,
+ * and then stores a message in *err_out unless err_out is NULL, free it with
+ * bf_string_free.
+ */
+BfProgram *bf_compile(const char *source, char **err_out);
+
+/*
+ * Runs program, `,` reads the input_len bytes at input and `.` calls
+ * output_cb with userdata. Returns one of the BF_ codes.
+ */
+int bf_run(const BfProgram *program, const uint8_t *input, size_t input_len,
+           void (*output_cb)(uint8_t byte, void *userdata), void *userdata);
+
+void bf_program_free(BfProgram *program);
+void bf_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
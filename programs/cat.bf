@@ -0,0 +1,4 @@
+Copies the input to the output
+Expects EOF to set the cell to minus one
+
+,+[-.,+]
@@ -0,0 +1,9 @@
+Counts to three on three lines with nested loops
+
++++
+[
+    >++++++[>++++++++<-]
+    >+.+.+.
+    [-]++++++++++.[-]
+    <<-
+]
@@ -0,0 +1,3 @@
+Hello World from the Wikipedia brainfuck article
+
+++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.
@@ -0,0 +1,29 @@
+rot13 from the Wikipedia brainfuck article
+Expects EOF to leave the cell unchanged or to set it to minus one
+
+-,+[
+    -[
+        >>++++[>++++++++<-]
+        <+<-[
+            >+>+>-[>>>]
+            <[[>+<-]>>+>]
+            <<<<<-
+        ]
+    ]>>>[-]+
+    >--[-[<->+++[-]]]<[
+        ++++++++++++<[
+            >-[>+>>]
+            >[+[<+>-]>+>>]
+            <<<<<-
+        ]
+        >>[<+>-]
+        >[
+            -[
+                -<<[-]>>
+            ]<<[<<->>-]>>
+        ]<<[<<+>>-]
+    ]
+    <[-]
+    <.[-]
+    <-,+
+]
@@ -0,0 +1,46 @@
+/*
+ * Translated by brainfuck:
+ * - 8 bit cells
+ * - a fixed tape of 30000 cells
+ * - `,` at the end of the input stores 0
+ */
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef uint8_t cell;
+
+static cell *tape;
+static size_t len = 30000;
+static size_t p;
+
+static void bf_fail(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "runtime error: %s\n", msg);
+    exit(1);
+}
+
+static void bf_read(void) {
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c != EOF) {
+        tape[p] = (cell)c;
+    } else {
+        tape[p] = 0;
+    }
+}
+
+int main(void) {
+    tape = calloc(len, sizeof(cell));
+    if (!tape) {
+        bf_fail("out of memory");
+    }
+    bf_read();
+    while (tape[p]) {
+        putchar(tape[p]);
+        bf_read();
+    }
+    return 0;
+}
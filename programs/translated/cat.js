@@ -0,0 +1,29 @@
+// Translated by brainfuck:
+// - 8 bit cells
+// - a fixed tape of 30000 cells
+// - `,` at the end of the input stores 0
+//
+// run(input) takes the input as a string or an array of bytes and returns
+// the output as a string.
+function run(input) {
+    const bytes = typeof input === "string" ? new TextEncoder().encode(input) : input || [];
+    let i = 0;
+    let tape = new Uint8Array(30000);
+    let p = 0;
+    const output = [];
+
+    function read() {
+        if (i < bytes.length) {
+            tape[p] = bytes[i++];
+        } else {
+            tape[p] = 0;
+        }
+    }
+
+    read();
+    while (tape[p]) {
+        output.push(tape[p]);
+        read();
+    }
+    return new TextDecoder().decode(new Uint8Array(output));
+}
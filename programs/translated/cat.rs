@@ -0,0 +1,64 @@
+// Translated by brainfuck:
+// - 8 bit cells
+// - a fixed tape of 30000 cells
+// - `,` at the end of the input stores 0
+
+use std::io::{Read, Write};
+
+type Cell = u8;
+
+struct Machine {
+    cells: Vec<Cell>,
+    p: usize,
+    input: std::io::Bytes<std::io::StdinLock<'static>>,
+    output: std::io::BufWriter<std::io::StdoutLock<'static>>,
+}
+
+impl Machine {
+    fn write(&mut self) -> Result<(), &'static str> {
+        let byte = self.cells[self.p] as u8;
+        self.output
+            .write_all(&[byte])
+            .map_err(|_| "cannot write the output")
+    }
+
+    fn read(&mut self) -> Result<(), &'static str> {
+        self.output
+            .flush()
+            .map_err(|_| "cannot write the output")?;
+        match self.input.next() {
+            Some(Ok(byte)) => self.cells[self.p] = Cell::from(byte),
+            Some(Err(_)) => return Err("cannot read the input"),
+            None => self.cells[self.p] = 0,
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let mut machine = Machine {
+        cells: vec![0; 30000],
+        p: 0,
+        input: std::io::stdin().lock().bytes(),
+        output: std::io::BufWriter::new(std::io::stdout().lock()),
+    };
+    let result = run(&mut machine);
+    let flushed = machine.output.flush();
+    if let Err(msg) = result {
+        eprintln!("runtime error: {}", msg);
+        std::process::exit(1);
+    }
+    if flushed.is_err() {
+        eprintln!("runtime error: cannot write the output");
+        std::process::exit(1);
+    }
+}
+
+fn run(m: &mut Machine) -> Result<(), &'static str> {
+    m.read()?;
+    while m.cells[m.p] != 0 {
+        m.write()?;
+        m.read()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+;; Translated by brainfuck:
+;; - 8 bit cells
+;; - a fixed tape of 30000 cells
+;; - `,` at the end of the input stores 0
+(module
+    (import "env" "read" (func $read (result i32)))
+    (import "env" "write" (func $write (param i32)))
+    (memory (export "memory") 1)
+    (global $len (mut i32) (i32.const 30000))
+    (func $run (export "run") (local $p i32) (local $t i32)
+        call $read
+        local.tee $t
+        i32.const 0
+        i32.ge_s
+        if
+            local.get $p
+            local.get $t
+            i32.store8
+        else
+            local.get $p
+            i32.const 0
+            i32.store8
+        end
+        block
+            local.get $p
+            i32.load8_u
+            i32.eqz
+            br_if 0
+            loop
+                local.get $p
+                i32.load8_u
+                call $write
+                call $read
+                local.tee $t
+                i32.const 0
+                i32.ge_s
+                if
+                    local.get $p
+                    local.get $t
+                    i32.store8
+                else
+                    local.get $p
+                    i32.const 0
+                    i32.store8
+                end
+                local.get $p
+                i32.load8_u
+                br_if 0
+            end
+        end
+    )
+)
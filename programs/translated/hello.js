@@ -0,0 +1,116 @@
+// Translated by brainfuck:
+// - 8 bit cells
+// - a fixed tape of 30000 cells
+// - `,` at the end of the input stores 0
+//
+// run(input) takes the input as a string or an array of bytes and returns
+// the output as a string.
+function run(input) {
+    let tape = new Uint8Array(30000);
+    let p = 0;
+    let t;
+    const output = [];
+
+    function outside(target) {
+        throw new Error("pointer moved out of bounds");
+    }
+
+    function move(offset) {
+        p += offset;
+        if (p < 0 || p >= tape.length) {
+            p = outside(p);
+        }
+    }
+
+    function searchLeft() {
+        while (tape[p]) {
+            const zero = tape.lastIndexOf(0, p);
+            if (zero >= 0) {
+                p = zero;
+            } else {
+                p = 0;
+                move(-1);
+            }
+        }
+    }
+
+    function at(offset) {
+        const target = p + offset;
+        return target >= 0 && target < tape.length ? target : outside(target);
+    }
+
+    function loop0() {
+        while (tape[p]) {
+            t = at(1);
+            tape[t] += 4;
+            move(1);
+            if (tape[p]) {
+                t = at(1);
+                tape[t] += tape[p] * 2;
+            }
+            if (tape[p]) {
+                t = at(2);
+                tape[t] += tape[p] * 3;
+            }
+            if (tape[p]) {
+                t = at(3);
+                tape[t] += tape[p] * 3;
+            }
+            if (tape[p]) {
+                t = at(4);
+                tape[t] += tape[p];
+            }
+            tape[p] = 0;
+            t = at(1);
+            tape[t] += 1;
+            t = at(2);
+            tape[t] += 1;
+            t = at(3);
+            tape[t] -= 1;
+            t = at(5);
+            tape[t] += 1;
+            move(5);
+            searchLeft();
+            t = at(-1);
+            tape[t] -= 1;
+            move(-1);
+        }
+    }
+
+    tape[p] += 8;
+    loop0();
+    move(2);
+    output.push(tape[p]);
+    t = at(1);
+    tape[t] -= 3;
+    move(1);
+    output.push(tape[p]);
+    tape[p] += 7;
+    output.push(tape[p]);
+    output.push(tape[p]);
+    tape[p] += 3;
+    output.push(tape[p]);
+    move(2);
+    output.push(tape[p]);
+    t = at(-1);
+    tape[t] -= 1;
+    move(-1);
+    output.push(tape[p]);
+    move(-1);
+    output.push(tape[p]);
+    tape[p] += 3;
+    output.push(tape[p]);
+    tape[p] -= 6;
+    output.push(tape[p]);
+    tape[p] -= 8;
+    output.push(tape[p]);
+    t = at(2);
+    tape[t] += 1;
+    move(2);
+    output.push(tape[p]);
+    t = at(1);
+    tape[t] += 2;
+    move(1);
+    output.push(tape[p]);
+    return new TextDecoder().decode(new Uint8Array(output));
+}
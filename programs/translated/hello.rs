@@ -0,0 +1,136 @@
+// Translated by brainfuck:
+// - 8 bit cells
+// - a fixed tape of 30000 cells
+// - `,` at the end of the input stores 0
+
+use std::io::Write;
+
+type Cell = u8;
+
+struct Machine {
+    cells: Vec<Cell>,
+    p: usize,
+    output: std::io::BufWriter<std::io::StdoutLock<'static>>,
+}
+
+impl Machine {
+    fn outside(&mut self, _target: usize) -> Result<usize, &'static str> {
+        Err("pointer moved out of bounds")
+    }
+
+    fn go(&mut self, offset: isize) -> Result<(), &'static str> {
+        self.p = self.p.wrapping_add(offset as usize);
+        if self.p >= self.cells.len() {
+            self.p = self.outside(self.p)?;
+        }
+        Ok(())
+    }
+
+    fn at(&mut self, offset: isize) -> Result<usize, &'static str> {
+        let target = self.p.wrapping_add(offset as usize);
+        if target < self.cells.len() {
+            return Ok(target);
+        }
+        self.outside(target)
+    }
+
+    fn write(&mut self) -> Result<(), &'static str> {
+        let byte = self.cells[self.p] as u8;
+        self.output
+            .write_all(&[byte])
+            .map_err(|_| "cannot write the output")
+    }
+}
+
+fn main() {
+    let mut machine = Machine {
+        cells: vec![0; 30000],
+        p: 0,
+        output: std::io::BufWriter::new(std::io::stdout().lock()),
+    };
+    let result = run(&mut machine);
+    let flushed = machine.output.flush();
+    if let Err(msg) = result {
+        eprintln!("runtime error: {}", msg);
+        std::process::exit(1);
+    }
+    if flushed.is_err() {
+        eprintln!("runtime error: cannot write the output");
+        std::process::exit(1);
+    }
+}
+
+fn run(m: &mut Machine) -> Result<(), &'static str> {
+    m.cells[m.p] = m.cells[m.p].wrapping_add(8);
+    while m.cells[m.p] != 0 {
+        let i = m.at(1)?;
+        m.cells[i] = m.cells[i].wrapping_add(4);
+        m.go(1)?;
+        if m.cells[m.p] != 0 {
+            let i = m.at(1)?;
+            m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p].wrapping_mul(2));
+        }
+        if m.cells[m.p] != 0 {
+            let i = m.at(2)?;
+            m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p].wrapping_mul(3));
+        }
+        if m.cells[m.p] != 0 {
+            let i = m.at(3)?;
+            m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p].wrapping_mul(3));
+        }
+        if m.cells[m.p] != 0 {
+            let i = m.at(4)?;
+            m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p]);
+        }
+        m.cells[m.p] = 0;
+        let i = m.at(1)?;
+        m.cells[i] = m.cells[i].wrapping_add(1);
+        let i = m.at(2)?;
+        m.cells[i] = m.cells[i].wrapping_add(1);
+        let i = m.at(3)?;
+        m.cells[i] = m.cells[i].wrapping_sub(1);
+        let i = m.at(5)?;
+        m.cells[i] = m.cells[i].wrapping_add(1);
+        m.go(5)?;
+        while m.cells[m.p] != 0 {
+            m.go(-1)?;
+        }
+        let i = m.at(-1)?;
+        m.cells[i] = m.cells[i].wrapping_sub(1);
+        m.go(-1)?;
+    }
+    m.go(2)?;
+    m.write()?;
+    let i = m.at(1)?;
+    m.cells[i] = m.cells[i].wrapping_sub(3);
+    m.go(1)?;
+    m.write()?;
+    m.cells[m.p] = m.cells[m.p].wrapping_add(7);
+    m.write()?;
+    m.write()?;
+    m.cells[m.p] = m.cells[m.p].wrapping_add(3);
+    m.write()?;
+    m.go(2)?;
+    m.write()?;
+    let i = m.at(-1)?;
+    m.cells[i] = m.cells[i].wrapping_sub(1);
+    m.go(-1)?;
+    m.write()?;
+    m.go(-1)?;
+    m.write()?;
+    m.cells[m.p] = m.cells[m.p].wrapping_add(3);
+    m.write()?;
+    m.cells[m.p] = m.cells[m.p].wrapping_sub(6);
+    m.write()?;
+    m.cells[m.p] = m.cells[m.p].wrapping_sub(8);
+    m.write()?;
+    let i = m.at(2)?;
+    m.cells[i] = m.cells[i].wrapping_add(1);
+    m.go(2)?;
+    m.write()?;
+    let i = m.at(1)?;
+    m.cells[i] = m.cells[i].wrapping_add(2);
+    m.go(1)?;
+    m.write()?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+/*
+ * Translated by brainfuck:
+ * - 16 bit cells
+ * - a tape that grows in both directions of 4 cells
+ * - `,` at the end of the input stops with an error
+ */
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef uint16_t cell;
+
+static cell *tape;
+static size_t len = 4;
+static size_t p;
+
+static void bf_fail(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "runtime error: %s\n", msg);
+    exit(1);
+}
+
+static size_t bf_outside(size_t target) {
+    size_t extra = -target > len ? -target : len;
+    size_t grown = target + 1 > len * 2 ? target + 1 : len * 2;
+    cell *shifted;
+    if ((ptrdiff_t)target >= 0) {
+        tape = realloc(tape, grown * sizeof(cell));
+        if (!tape) {
+            bf_fail("out of memory");
+        }
+        memset(tape + len, 0, (grown - len) * sizeof(cell));
+        len = grown;
+        return target;
+    }
+    /* prepend zeroed cells, which shifts every existing cell to the right */
+    shifted = calloc(len + extra, sizeof(cell));
+    if (!shifted) {
+        bf_fail("out of memory");
+    }
+    memcpy(shifted + extra, tape, len * sizeof(cell));
+    free(tape);
+    tape = shifted;
+    len += extra;
+    p += extra;
+    return target + extra;
+}
+
+static void bf_move(size_t offset) {
+    p += offset;
+    if (p >= len) {
+        p = bf_outside(p);
+    }
+}
+
+static size_t bf_at(size_t offset) {
+    size_t target = p + offset;
+    return target < len ? target : bf_outside(target);
+}
+
+static void bf_read(void) {
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c != EOF) {
+        tape[p] = (cell)c;
+    } else {
+        bf_fail("unexpected end of input");
+    }
+}
+
+int main(void) {
+    size_t t;
+    tape = calloc(len, sizeof(cell));
+    if (!tape) {
+        bf_fail("out of memory");
+    }
+    tape[p] += 1;
+    while (tape[p]) {
+        tape[p] -= 1;
+        bf_move(-1);
+    }
+    t = bf_at(2);
+    tape[t] += 3;
+    bf_move(2);
+    if (tape[p]) {
+        t = bf_at(1);
+        tape[t] += tape[p];
+    }
+    if (tape[p]) {
+        t = bf_at(2);
+        tape[t] -= tape[p] * 2;
+    }
+    tape[p] = 0;
+    bf_move(-1);
+    tape[p] = 1;
+    while (tape[p]) {
+        bf_move(1);
+    }
+    bf_move(-2);
+    if (tape[p]) {
+        t = bf_at(2);
+        tape[t] += tape[p];
+    }
+    tape[p] = 0;
+    t = bf_at(2);
+    tape[t] -= 3;
+    bf_move(2);
+    putchar(tape[p]);
+    bf_read();
+    tape[p] = 0;
+    return 0;
+}
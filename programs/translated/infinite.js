@@ -0,0 +1,110 @@
+// Translated by brainfuck:
+// - 16 bit cells
+// - a tape that grows in both directions of 4 cells
+// - `,` at the end of the input stops with an error
+//
+// run(input) takes the input as a string or an array of bytes and returns
+// the output as a string.
+function run(input) {
+    const bytes = typeof input === "string" ? new TextEncoder().encode(input) : input || [];
+    let i = 0;
+    let tape = new Uint16Array(4);
+    let p = 0;
+    let t;
+    const output = [];
+
+    function outside(target) {
+        if (target >= 0) {
+            const grown = new Uint16Array(Math.max(target + 1, tape.length * 2));
+            grown.set(tape);
+            tape = grown;
+            return target;
+        }
+        // prepend zeroed cells, which shifts every existing cell to the right
+        const extra = Math.max(-target, tape.length);
+        const shifted = new Uint16Array(tape.length + extra);
+        shifted.set(tape, extra);
+        tape = shifted;
+        p += extra;
+        return target + extra;
+    }
+
+    function move(offset) {
+        p += offset;
+        if (p < 0 || p >= tape.length) {
+            p = outside(p);
+        }
+    }
+
+    function searchRight() {
+        while (tape[p]) {
+            const zero = tape.indexOf(0, p);
+            if (zero >= 0) {
+                p = zero;
+            } else {
+                p = tape.length - 1;
+                move(1);
+            }
+        }
+    }
+
+    function searchLeft() {
+        while (tape[p]) {
+            const zero = tape.lastIndexOf(0, p);
+            if (zero >= 0) {
+                p = zero;
+            } else {
+                p = 0;
+                move(-1);
+            }
+        }
+    }
+
+    function at(offset) {
+        const target = p + offset;
+        return target >= 0 && target < tape.length ? target : outside(target);
+    }
+
+    function read() {
+        if (i < bytes.length) {
+            tape[p] = bytes[i++];
+        } else {
+            throw new Error("unexpected end of input");
+        }
+    }
+
+    tape[p] += 1;
+    while (tape[p]) {
+        tape[p] -= 1;
+        move(-1);
+    }
+    t = at(2);
+    tape[t] += 3;
+    move(2);
+    if (tape[p]) {
+        t = at(1);
+        tape[t] += tape[p];
+    }
+    if (tape[p]) {
+        t = at(2);
+        tape[t] -= tape[p] * 2;
+    }
+    tape[p] = 0;
+    move(-1);
+    tape[p] = 1;
+    searchRight();
+    move(-2);
+    if (tape[p]) {
+        t = at(2);
+        tape[t] += tape[p];
+    }
+    tape[p] = 0;
+    t = at(2);
+    tape[t] -= 3;
+    move(2);
+    output.push(tape[p]);
+    read();
+    tape[p] = 1;
+    searchLeft();
+    return new TextDecoder().decode(new Uint8Array(output));
+}
@@ -0,0 +1,123 @@
+// Translated by brainfuck:
+// - 16 bit cells
+// - a tape that grows in both directions of 4 cells
+// - `,` at the end of the input stops with an error
+
+use std::io::{Read, Write};
+
+type Cell = u16;
+
+struct Machine {
+    cells: Vec<Cell>,
+    p: usize,
+    input: std::io::Bytes<std::io::StdinLock<'static>>,
+    output: std::io::BufWriter<std::io::StdoutLock<'static>>,
+}
+
+impl Machine {
+    fn outside(&mut self, target: usize) -> Result<usize, &'static str> {
+        if (target as isize) >= 0 {
+            let len = (target + 1).max(self.cells.len() * 2);
+            self.cells.resize(len, 0);
+            return Ok(target);
+        }
+        // prepend zeroed cells, which shifts every existing cell to the right
+        let extra = target.wrapping_neg().max(self.cells.len());
+        self.cells.splice(0..0, std::iter::repeat(0).take(extra));
+        // the pointer is below 0 as well if it moved there
+        self.p = self.p.wrapping_add(extra);
+        Ok(target.wrapping_add(extra))
+    }
+
+    fn go(&mut self, offset: isize) -> Result<(), &'static str> {
+        self.p = self.p.wrapping_add(offset as usize);
+        if self.p >= self.cells.len() {
+            self.p = self.outside(self.p)?;
+        }
+        Ok(())
+    }
+
+    fn at(&mut self, offset: isize) -> Result<usize, &'static str> {
+        let target = self.p.wrapping_add(offset as usize);
+        if target < self.cells.len() {
+            return Ok(target);
+        }
+        self.outside(target)
+    }
+
+    fn write(&mut self) -> Result<(), &'static str> {
+        let byte = self.cells[self.p] as u8;
+        self.output
+            .write_all(&[byte])
+            .map_err(|_| "cannot write the output")
+    }
+
+    fn read(&mut self) -> Result<(), &'static str> {
+        self.output
+            .flush()
+            .map_err(|_| "cannot write the output")?;
+        match self.input.next() {
+            Some(Ok(byte)) => self.cells[self.p] = Cell::from(byte),
+            Some(Err(_)) => return Err("cannot read the input"),
+            None => return Err("unexpected end of input"),
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let mut machine = Machine {
+        cells: vec![0; 4],
+        p: 0,
+        input: std::io::stdin().lock().bytes(),
+        output: std::io::BufWriter::new(std::io::stdout().lock()),
+    };
+    let result = run(&mut machine);
+    let flushed = machine.output.flush();
+    if let Err(msg) = result {
+        eprintln!("runtime error: {}", msg);
+        std::process::exit(1);
+    }
+    if flushed.is_err() {
+        eprintln!("runtime error: cannot write the output");
+        std::process::exit(1);
+    }
+}
+
+fn run(m: &mut Machine) -> Result<(), &'static str> {
+    m.cells[m.p] = m.cells[m.p].wrapping_add(1);
+    while m.cells[m.p] != 0 {
+        m.cells[m.p] = m.cells[m.p].wrapping_sub(1);
+        m.go(-1)?;
+    }
+    let i = m.at(2)?;
+    m.cells[i] = m.cells[i].wrapping_add(3);
+    m.go(2)?;
+    if m.cells[m.p] != 0 {
+        let i = m.at(1)?;
+        m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p]);
+    }
+    if m.cells[m.p] != 0 {
+        let i = m.at(2)?;
+        m.cells[i] = m.cells[i].wrapping_sub(m.cells[m.p].wrapping_mul(2));
+    }
+    m.cells[m.p] = 0;
+    m.go(-1)?;
+    m.cells[m.p] = 1;
+    while m.cells[m.p] != 0 {
+        m.go(1)?;
+    }
+    m.go(-2)?;
+    if m.cells[m.p] != 0 {
+        let i = m.at(2)?;
+        m.cells[i] = m.cells[i].wrapping_add(m.cells[m.p]);
+    }
+    m.cells[m.p] = 0;
+    let i = m.at(2)?;
+    m.cells[i] = m.cells[i].wrapping_sub(3);
+    m.go(2)?;
+    m.write()?;
+    m.read()?;
+    m.cells[m.p] = 0;
+    Ok(())
+}
@@ -0,0 +1,422 @@
+;; Translated by brainfuck:
+;; - 16 bit cells
+;; - a tape that grows in both directions of 4 cells
+;; - `,` at the end of the input stops with an error
+(module
+    (import "env" "read" (func $read (result i32)))
+    (import "env" "write" (func $write (param i32)))
+    (memory (export "memory") 1)
+    (global $len (mut i32) (i32.const 4))
+    (global $shift (mut i32) (i32.const 0))
+    (func $run (export "run") (local $p i32) (local $t i32)
+        local.get $p
+        i32.const 1
+        i32.shl
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        i32.const 1
+        i32.add
+        i32.store16
+        block
+            local.get $p
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            i32.eqz
+            br_if 0
+            loop
+                local.get $p
+                i32.const 1
+                i32.shl
+                local.get $p
+                i32.const 1
+                i32.shl
+                i32.load16_u
+                i32.const -1
+                i32.add
+                i32.store16
+                local.get $p
+                i32.const -1
+                i32.add
+                local.tee $p
+                global.get $len
+                i32.ge_u
+                if
+                    local.get $p
+                    call $outside
+                    local.set $p
+                end
+                local.get $p
+                i32.const 1
+                i32.shl
+                i32.load16_u
+                br_if 0
+            end
+        end
+        local.get $p
+        i32.const 2
+        i32.add
+        local.tee $t
+        global.get $len
+        i32.ge_u
+        if
+            local.get $t
+            call $outside
+            local.set $t
+            global.get $shift
+            local.get $p
+            i32.add
+            local.set $p
+        end
+        local.get $t
+        i32.const 1
+        i32.shl
+        local.get $t
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        i32.const 3
+        i32.add
+        i32.store16
+        local.get $p
+        i32.const 2
+        i32.add
+        local.tee $p
+        global.get $len
+        i32.ge_u
+        if
+            local.get $p
+            call $outside
+            local.set $p
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        if
+            local.get $p
+            i32.const 1
+            i32.add
+            local.tee $t
+            global.get $len
+            i32.ge_u
+            if
+                local.get $t
+                call $outside
+                local.set $t
+                global.get $shift
+                local.get $p
+                i32.add
+                local.set $p
+            end
+            local.get $t
+            i32.const 1
+            i32.shl
+            local.get $t
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            local.get $p
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            i32.add
+            i32.store16
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        if
+            local.get $p
+            i32.const 2
+            i32.add
+            local.tee $t
+            global.get $len
+            i32.ge_u
+            if
+                local.get $t
+                call $outside
+                local.set $t
+                global.get $shift
+                local.get $p
+                i32.add
+                local.set $p
+            end
+            local.get $t
+            i32.const 1
+            i32.shl
+            local.get $t
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            local.get $p
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            i32.const -2
+            i32.mul
+            i32.add
+            i32.store16
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.const 0
+        i32.store16
+        local.get $p
+        i32.const -1
+        i32.add
+        local.tee $p
+        global.get $len
+        i32.ge_u
+        if
+            local.get $p
+            call $outside
+            local.set $p
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.const 1
+        i32.store16
+        block
+            loop
+                local.get $p
+                i32.const 1
+                i32.shl
+                i32.load16_u
+                i32.eqz
+                br_if 1
+                local.get $p
+                i32.const 1
+                i32.add
+                local.tee $p
+                global.get $len
+                i32.ge_u
+                if
+                    local.get $p
+                    call $outside
+                    local.set $p
+                end
+                br 0
+            end
+        end
+        local.get $p
+        i32.const -2
+        i32.add
+        local.tee $p
+        global.get $len
+        i32.ge_u
+        if
+            local.get $p
+            call $outside
+            local.set $p
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        if
+            local.get $p
+            i32.const 2
+            i32.add
+            local.tee $t
+            global.get $len
+            i32.ge_u
+            if
+                local.get $t
+                call $outside
+                local.set $t
+                global.get $shift
+                local.get $p
+                i32.add
+                local.set $p
+            end
+            local.get $t
+            i32.const 1
+            i32.shl
+            local.get $t
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            local.get $p
+            i32.const 1
+            i32.shl
+            i32.load16_u
+            i32.add
+            i32.store16
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.const 0
+        i32.store16
+        local.get $p
+        i32.const 2
+        i32.add
+        local.tee $t
+        global.get $len
+        i32.ge_u
+        if
+            local.get $t
+            call $outside
+            local.set $t
+            global.get $shift
+            local.get $p
+            i32.add
+            local.set $p
+        end
+        local.get $t
+        i32.const 1
+        i32.shl
+        local.get $t
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        i32.const -3
+        i32.add
+        i32.store16
+        local.get $p
+        i32.const 2
+        i32.add
+        local.tee $p
+        global.get $len
+        i32.ge_u
+        if
+            local.get $p
+            call $outside
+            local.set $p
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.load16_u
+        i32.const 255
+        i32.and
+        call $write
+        call $read
+        local.tee $t
+        i32.const 0
+        i32.ge_s
+        if
+            local.get $p
+            i32.const 1
+            i32.shl
+            local.get $t
+            i32.store16
+        else
+            unreachable
+        end
+        local.get $p
+        i32.const 1
+        i32.shl
+        i32.const 0
+        i32.store16
+    )
+    (func $outside (param $target i32) (result i32) (local $n i32) (local $pages i32)
+        local.get $target
+        i32.const 0
+        i32.ge_s
+        if
+            local.get $target
+            i32.const 1
+            i32.add
+            local.tee $n
+            global.get $len
+            i32.const 1
+            i32.shl
+            local.get $n
+            global.get $len
+            i32.const 1
+            i32.shl
+            i32.gt_u
+            select
+            local.tee $n
+            i32.const 1
+            i32.shl
+            i32.const 65535
+            i32.add
+            i32.const 16
+            i32.shr_u
+            memory.size
+            i32.sub
+            local.tee $pages
+            i32.const 0
+            i32.gt_s
+            if
+                local.get $pages
+                memory.grow
+                i32.const -1
+                i32.eq
+                if
+                    unreachable
+                end
+            end
+            local.get $n
+            global.set $len
+            i32.const 0
+            global.set $shift
+            local.get $target
+            return
+        end
+        i32.const 0
+        local.get $target
+        i32.sub
+        local.tee $n
+        global.get $len
+        local.get $n
+        global.get $len
+        i32.gt_u
+        select
+        local.set $n
+        global.get $len
+        local.get $n
+        i32.add
+        i32.const 1
+        i32.shl
+        i32.const 65535
+        i32.add
+        i32.const 16
+        i32.shr_u
+        memory.size
+        i32.sub
+        local.tee $pages
+        i32.const 0
+        i32.gt_s
+        if
+            local.get $pages
+            memory.grow
+            i32.const -1
+            i32.eq
+            if
+                unreachable
+            end
+        end
+        local.get $n
+        i32.const 1
+        i32.shl
+        i32.const 0
+        global.get $len
+        i32.const 1
+        i32.shl
+        memory.copy
+        i32.const 0
+        i32.const 0
+        local.get $n
+        i32.const 1
+        i32.shl
+        memory.fill
+        global.get $len
+        local.get $n
+        i32.add
+        global.set $len
+        local.get $n
+        global.set $shift
+        local.get $target
+        local.get $n
+        i32.add
+    )
+)
@@ -0,0 +1,189 @@
+/*
+ * Translated by brainfuck:
+ * - 8 bit cells
+ * - a fixed tape of 30000 cells
+ * - `,` at the end of the input stores -1
+ */
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef uint8_t cell;
+
+static cell *tape;
+static size_t len = 30000;
+static size_t p;
+
+static void bf_fail(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "runtime error: %s\n", msg);
+    exit(1);
+}
+
+static size_t bf_outside(size_t target) {
+    (void)target;
+    bf_fail("pointer moved out of bounds");
+    return 0;
+}
+
+static void bf_move(size_t offset) {
+    p += offset;
+    if (p >= len) {
+        p = bf_outside(p);
+    }
+}
+
+static size_t bf_at(size_t offset) {
+    size_t target = p + offset;
+    return target < len ? target : bf_outside(target);
+}
+
+static void bf_read(void) {
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c != EOF) {
+        tape[p] = (cell)c;
+    } else {
+        tape[p] = (cell)-1;
+    }
+}
+
+int main(void) {
+    size_t t;
+    tape = calloc(len, sizeof(cell));
+    if (!tape) {
+        bf_fail("out of memory");
+    }
+    tape[p] -= 1;
+    bf_read();
+    tape[p] += 1;
+    while (tape[p]) {
+        tape[p] -= 1;
+        if (tape[p]) {
+            t = bf_at(2);
+            tape[t] += 4;
+            bf_move(2);
+            if (tape[p]) {
+                t = bf_at(1);
+                tape[t] += tape[p] * 8;
+            }
+            tape[p] = 0;
+            t = bf_at(-1);
+            tape[t] += 1;
+            t = bf_at(-2);
+            tape[t] -= 1;
+            bf_move(-2);
+            while (tape[p]) {
+                t = bf_at(1);
+                tape[t] += 1;
+                t = bf_at(2);
+                tape[t] += 1;
+                t = bf_at(3);
+                tape[t] -= 1;
+                bf_move(3);
+                while (tape[p]) {
+                    bf_move(3);
+                }
+                bf_move(-1);
+                while (tape[p]) {
+                    if (tape[p]) {
+                        t = bf_at(1);
+                        tape[t] += tape[p];
+                    }
+                    tape[p] = 0;
+                    t = bf_at(2);
+                    tape[t] += 1;
+                    bf_move(3);
+                }
+                t = bf_at(-5);
+                tape[t] -= 1;
+                bf_move(-5);
+            }
+        }
+        bf_move(3);
+        tape[p] = 1;
+        t = bf_at(1);
+        tape[t] -= 2;
+        bf_move(1);
+        if (tape[p]) {
+            tape[p] -= 1;
+            if (tape[p]) {
+                t = bf_at(-1);
+                tape[t] -= 1;
+                tape[p] = 0;
+            }
+        }
+        bf_move(-1);
+        if (tape[p]) {
+            tape[p] += 12;
+            bf_move(-1);
+            while (tape[p]) {
+                t = bf_at(1);
+                tape[t] -= 1;
+                bf_move(1);
+                while (tape[p]) {
+                    t = bf_at(1);
+                    tape[t] += 1;
+                    bf_move(3);
+                }
+                bf_move(1);
+                while (tape[p]) {
+                    tape[p] += 1;
+                    if (tape[p]) {
+                        t = bf_at(-1);
+                        tape[t] += tape[p];
+                    }
+                    tape[p] = 0;
+                    t = bf_at(1);
+                    tape[t] += 1;
+                    bf_move(3);
+                }
+                t = bf_at(-5);
+                tape[t] -= 1;
+                bf_move(-5);
+            }
+            bf_move(2);
+            if (tape[p]) {
+                t = bf_at(-1);
+                tape[t] += tape[p];
+            }
+            tape[p] = 0;
+            bf_move(1);
+            while (tape[p]) {
+                tape[p] -= 1;
+                while (tape[p]) {
+                    tape[p] -= 1;
+                    bf_move(-2);
+                    tape[p] = 0;
+                    bf_move(2);
+                }
+                bf_move(-2);
+                if (tape[p]) {
+                    t = bf_at(-2);
+                    tape[t] -= tape[p];
+                }
+                tape[p] = 0;
+                bf_move(2);
+            }
+            bf_move(-2);
+            if (tape[p]) {
+                t = bf_at(-2);
+                tape[t] += tape[p];
+            }
+            tape[p] = 0;
+        }
+        bf_move(-1);
+        tape[p] = 0;
+        bf_move(-1);
+        putchar(tape[p]);
+        tape[p] = 0;
+        t = bf_at(-1);
+        tape[t] -= 1;
+        bf_move(-1);
+        bf_read();
+        tape[p] += 1;
+    }
+    return 0;
+}
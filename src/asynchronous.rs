@@ -0,0 +1,289 @@
+//! Running a program as a future with the `async` feature, for services that
+//! must not block their executor while a program waits for input.
+//!
+//! The future runs the same dispatch loop as [`ExecutionConfig::run`] and
+//! awaits at every `,` and `.`. In between, it returns to the executor every
+//! [`YIELD_INTERVAL`] ops, so a long computation does not starve the other
+//! tasks. Counting the ops makes it about as fast as a run with `max_ops`.
+//!
+//! The `tokio` feature adds [`AsyncIoAdapter`], which works on tokio's
+//! streams, like the halves of a socket.
+
+use crate::{
+    DummyInputOutput, ExecutionConfig, InputOutput, Memory, NoProbe, Ops, Program, Run,
+    RuntimeError, Stop,
+};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// How many ops run between two returns to the executor.
+pub const YIELD_INTERVAL: u64 = 1 << 16;
+
+/// [`crate::InputOutput`] for async code. The futures have to be `Send`, so
+/// that a running program can move between the threads of an executor.
+pub trait AsyncInputOutput {
+    /// The next byte of input, `None` once the input is exhausted.
+    fn read_byte(&mut self) -> impl Future<Output = Option<u8>> + Send;
+    fn write_byte(&mut self, byte: u8) -> impl Future<Output = ()> + Send;
+
+    /// Writes out buffered output, nothing to do by default.
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> impl Future<Output = std::io::Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// Like [`crate::execute`], but awaits the input and output.
+pub async fn execute_async<T: AsyncInputOutput + ?Sized>(
+    program: &Program,
+    in_out: &mut T,
+) -> Result<(), RuntimeError> {
+    ExecutionConfig::default().run_async(program, in_out).await
+}
+
+impl ExecutionConfig {
+    /// Like [`ExecutionConfig::run`], but awaits the input and output. The
+    /// `timeout` includes the time spent waiting for them.
+    pub async fn run_async<T: AsyncInputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut T,
+    ) -> Result<(), RuntimeError> {
+        let mut memory = Memory::new(self);
+        let mut run = Run::new(self, true);
+        loop {
+            run.fuel.pause_after(YIELD_INTERVAL);
+            // the I/O is suspended, so it is the interpreter behind a
+            // reference that runs
+            let stopped = self.dispatch_from(
+                program,
+                &mut DummyInputOutput as &mut dyn InputOutput,
+                &mut memory,
+                &mut NoProbe,
+                &mut run,
+            );
+            match stopped {
+                Ok(()) => return Ok(()),
+                Err(Stop::Error(err)) => return Err(err),
+                Err(Stop::Pause) => YieldNow(false).await,
+                Err(Stop::Io) => {
+                    if let Ops::Print = program[run.ip] {
+                        in_out.write_byte(memory.current() as u8).await;
+                    } else {
+                        let byte = in_out.read_byte().await;
+                        memory.store_read(Ok(byte.map(i64::from)), self.eof, run.ip)?;
+                    }
+                    run.ip += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Reads the input of `,` from an [`AsyncRead`] and writes the output of `.`
+/// to an [`AsyncWrite`], the async [`IoAdapter`](crate::IoAdapter).
+///
+/// ```
+/// use brainfuck::asynchronous::AsyncIoAdapter;
+/// use brainfuck::compile;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut in_out = AsyncIoAdapter::new(&b"hi"[..], Vec::new());
+/// brainfuck::asynchronous::execute_async(&compile(",[.,]").unwrap(), &mut in_out)
+///     .await
+///     .unwrap();
+/// assert_eq!(in_out.output(), b"hi");
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+pub struct AsyncIoAdapter<R, W> {
+    input: BufReader<R>,
+    output: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead, W: AsyncWrite> AsyncIoAdapter<R, W> {
+    /// The input is buffered, `output` should be buffered as well unless it
+    /// is a `Vec` or similar.
+    pub fn new(input: R, output: W) -> AsyncIoAdapter<R, W> {
+        AsyncIoAdapter {
+            input: BufReader::new(input),
+            output,
+            error: None,
+        }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// The writer, the reader and any input it buffered are dropped.
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// The first error reading or writing failed with. A failed read counts as
+    /// the end of the input, failed writes are dropped.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R, W> AsyncInputOutput for AsyncIoAdapter<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn read_byte(&mut self) -> Option<u8> {
+        // a prompt has to reach the other side before waiting for the answer
+        if self.input.buffer().is_empty() {
+            if let Err(err) = self.output.flush().await {
+                self.error.get_or_insert(err);
+            }
+        }
+        match self.input.read_u8().await {
+            Ok(byte) => Some(byte),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+    async fn write_byte(&mut self, byte: u8) {
+        if let Err(err) = self.output.write_u8(byte).await {
+            self.error.get_or_insert(err);
+        }
+    }
+    async fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush().await
+    }
+}
+
+/// Returns to the executor once, which polls again right away unless other
+/// tasks are waiting.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{compile, compile_with, EofBehavior};
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::thread::Thread;
+
+    /// Input that is there right away, and the output.
+    #[derive(Default)]
+    struct Bytes {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+    }
+
+    impl AsyncInputOutput for Bytes {
+        async fn read_byte(&mut self) -> Option<u8> {
+            self.input.pop_front()
+        }
+        async fn write_byte(&mut self, byte: u8) {
+            self.output.push(byte);
+        }
+    }
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal executor, which parks the thread until it is woken.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        let mut pending = 0;
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return (output, pending);
+            }
+            pending += 1;
+            std::thread::park();
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn cat() {
+        let (mut client, server) = tokio::io::duplex(16);
+        let (input, output) = tokio::io::split(server);
+        let mut in_out = AsyncIoAdapter::new(input, output);
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        // spawned tasks move between threads on most executors, so they are Send
+        let server = tokio::spawn(async move {
+            let cat = compile(",+[-.,+]").unwrap();
+            let result = config.run_async(&cat, &mut in_out).await;
+            in_out.flush().await.unwrap();
+            result
+        });
+        // every line comes back before the next one is sent
+        for line in [&b"ab\n"[..], b"c\n"] {
+            client.write_all(line).await.unwrap();
+            let mut echoed = vec![0; line.len()];
+            client.read_exact(&mut echoed).await.unwrap();
+            assert_eq!(echoed, line);
+        }
+        // the program ends with the input and drops its side of the stream
+        client.shutdown().await.unwrap();
+        assert_eq!(server.await.unwrap(), Ok(()));
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"");
+    }
+
+    #[test]
+    fn long_computations_yield() {
+        let mut in_out = Bytes::default();
+        // 255 * 255 iterations of the inner loop, which is kept at -O0
+        let program = compile_with("-[>-[-]<-]>.", &Pipeline::new(OptLevel::O0)).unwrap();
+        let (result, pending) = block_on(execute_async(&program, &mut in_out));
+        assert_eq!(result, Ok(()));
+        assert_eq!(in_out.output, [0]);
+        assert!(pending as u64 >= 255 * 255 * 3 / YIELD_INTERVAL);
+    }
+
+    #[test]
+    fn errors() {
+        let mut in_out = Bytes::default();
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::Error)
+            .max_ops(100)
+            .build();
+        let (result, _) = block_on(config.run_async(&compile("+.,").unwrap(), &mut in_out));
+        assert_eq!(result, Err(RuntimeError::UnexpectedEof { ip: 2 }));
+        assert_eq!(in_out.output, [1]);
+        let (result, _) = block_on(config.run_async(&compile("+[]").unwrap(), &mut in_out));
+        assert_eq!(result, Err(RuntimeError::FuelExhausted { executed: 100 }));
+    }
+}
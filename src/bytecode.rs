@@ -0,0 +1,304 @@
+//! Compiled programs as `.bfc` files, for programs that take long to compile
+//! or are shipped without their source.
+//!
+//! ```
+//! let program = brainfuck::compile("+[->+<]>.").unwrap();
+//! let mut file = Vec::new();
+//! program.save(&mut file).unwrap();
+//! assert!(file.starts_with(brainfuck::bytecode::MAGIC));
+//! assert_eq!(brainfuck::Program::load(&file[..]).unwrap(), program);
+//! ```
+//!
+//! A file starts with [`MAGIC`], followed by a version byte and the number of
+//! ops as 8 bytes. Every op is a byte for its kind followed by its operands,
+//! all little endian: offsets, steps and jump targets take 8 bytes, values
+//! and factors 4 and come last. The source spans are not saved.
+
+use crate::{Ops, Program, Span};
+use std::convert::{TryFrom, TryInto};
+use std::io::{self, Read, Write};
+
+/// The first bytes of every `.bfc` file.
+pub const MAGIC: &[u8; 4] = b"BFBC";
+const VERSION: u8 = 1;
+
+impl Program {
+    /// Writes the ops in the format described in [`crate::bytecode`].
+    pub fn save(&self, mut w: impl Write) -> io::Result<()> {
+        w.write_all(&self.encode())
+    }
+
+    /// Like [`Program::save`], followed by the number of spans and the start
+    /// and end of each as 8 bytes, for the compile cache.
+    pub(crate) fn save_spanned(&self, mut w: impl Write) -> io::Result<()> {
+        let mut bytes = self.encode();
+        bytes.extend((self.spans.len() as u64).to_le_bytes());
+        for span in &self.spans {
+            bytes.extend((span.start as u64).to_le_bytes());
+            bytes.extend((span.end as u64).to_le_bytes());
+        }
+        w.write_all(&bytes)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend((self.ops.len() as u64).to_le_bytes());
+        for &op in &self.ops {
+            let (kind, wide, target, narrow) = match op {
+                Ops::Move(offset) => (0, Some(offset as i64), None, None),
+                Ops::Mod(value) => (1, None, None, Some(value as u32)),
+                Ops::ModAt(offset, value) => (2, Some(offset as i64), None, Some(value as u32)),
+                Ops::AddTo(offset, factor) => (3, Some(offset as i64), None, Some(factor as u32)),
+                Ops::LoopOpen(target) => (4, None, Some(target), None),
+                Ops::LoopClose(target) => (5, None, Some(target), None),
+                Ops::If(target) => (6, None, Some(target), None),
+                Ops::SetCell(value) => (7, None, None, Some(value)),
+                Ops::SearchZeroCell(step) => (8, Some(step as i64), None, None),
+                Ops::Print => (9, None, None, None),
+                Ops::Read => (10, None, None, None),
+                Ops::Break => (11, None, None, None),
+                Ops::End => (12, None, None, None),
+                Ops::ProcDefStart(target) => (13, None, Some(target), None),
+                Ops::ProcDefEnd => (14, None, None, None),
+                Ops::Call => (15, None, None, None),
+                Ops::Dump => (16, None, None, None),
+                Ops::MoveClose(offset, target) => (17, Some(offset as i64), Some(target), None),
+                Ops::ModMove(value, offset) => (18, Some(offset as i64), None, Some(value as u32)),
+                Ops::BalancedLoop(target, min, max) => {
+                    (19, Some(i64::from(min)), Some(target), Some(max as u32))
+                }
+            };
+            bytes.push(kind);
+            if let Some(wide) = wide {
+                bytes.extend(wide.to_le_bytes());
+            }
+            if let Some(target) = target {
+                bytes.extend((target as i64).to_le_bytes());
+            }
+            if let Some(narrow) = narrow {
+                bytes.extend(narrow.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Reads a program written by [`Program::save`]. The jumps are checked as
+    /// in [`Program::new`], so a corrupted file cannot make a run index out of
+    /// bounds.
+    ///
+    /// Files that are cut off fail with [`io::ErrorKind::UnexpectedEof`],
+    /// everything else that is not a valid program with
+    /// [`io::ErrorKind::InvalidData`].
+    pub fn load(mut r: impl Read) -> io::Result<Program> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+        let mut bytes = Fields(&bytes);
+        let ops = Program::decode(&mut bytes)?;
+        bytes.end()?;
+        Program::new(ops).map_err(|err| invalid(&err.to_string()))
+    }
+
+    /// Reads a program written by [`Program::save_spanned`]. The spans are
+    /// only checked to be ranges, not to fit the source.
+    pub(crate) fn load_spanned(bytes: &[u8]) -> io::Result<Program> {
+        let mut bytes = Fields(bytes);
+        let ops = Program::decode(&mut bytes)?;
+        let count = bytes.index()?;
+        if count != 0 && count != ops.len() {
+            return Err(invalid("the spans do not match the ops"));
+        }
+        let spans = (0..count)
+            .map(|_| {
+                let span = Span {
+                    start: bytes.index()?,
+                    end: bytes.index()?,
+                };
+                match span.start <= span.end {
+                    true => Ok(span),
+                    false => Err(invalid("invalid span")),
+                }
+            })
+            .collect::<io::Result<_>>()?;
+        bytes.end()?;
+        let program = Program::new(ops).map_err(|err| invalid(&err.to_string()))?;
+        Ok(Program { spans, ..program })
+    }
+
+    fn decode(bytes: &mut Fields) -> io::Result<Vec<Ops>> {
+        if bytes.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a compiled program"));
+        }
+        if bytes.take(1)? != [VERSION] {
+            return Err(invalid("unsupported bytecode version"));
+        }
+        let count = bytes.index()?;
+        // every op takes at least a byte, a corrupted count must not allocate
+        let mut ops = Vec::with_capacity(count.min(bytes.0.len()));
+        for _ in 0..count {
+            let op = match bytes.take(1)?[0] {
+                0 => Ops::Move(bytes.wide()? as isize),
+                1 => Ops::Mod(bytes.narrow()? as i32),
+                2 => Ops::ModAt(bytes.wide()? as isize, bytes.narrow()? as i32),
+                3 => Ops::AddTo(bytes.wide()? as isize, bytes.narrow()? as i32),
+                4 => Ops::LoopOpen(bytes.target()?),
+                5 => Ops::LoopClose(bytes.target()?),
+                6 => Ops::If(bytes.target()?),
+                7 => Ops::SetCell(bytes.narrow()?),
+                8 => Ops::SearchZeroCell(bytes.wide()? as isize),
+                9 => Ops::Print,
+                10 => Ops::Read,
+                11 => Ops::Break,
+                12 => Ops::End,
+                13 => Ops::ProcDefStart(bytes.target()?),
+                14 => Ops::ProcDefEnd,
+                15 => Ops::Call,
+                16 => Ops::Dump,
+                17 => Ops::MoveClose(bytes.wide()? as isize, bytes.target()?),
+                18 => {
+                    let offset = bytes.wide()? as isize;
+                    Ops::ModMove(bytes.narrow()? as i32, offset)
+                }
+                19 => {
+                    let min = bytes.wide()? as i32;
+                    let target = bytes.target()?;
+                    Ops::BalancedLoop(target, min, bytes.narrow()? as i32)
+                }
+                kind => return Err(invalid(&format!("unknown op kind {}", kind))),
+            };
+            ops.push(op);
+        }
+        Ok(ops)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the compiled program is cut off",
+            ));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn wide(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn narrow(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn target(&mut self) -> io::Result<usize> {
+        usize::try_from(self.wide()?).map_err(|_| invalid("jump target out of range"))
+    }
+
+    fn index(&mut self) -> io::Result<usize> {
+        usize::try_from(self.wide()?).map_err(|_| invalid("number out of range"))
+    }
+
+    fn end(&self) -> io::Result<()> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(invalid("trailing bytes after the last op")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile_file, compile_with, execute, StringInputOutput};
+
+    fn saved(program: &Program) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        program.save(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let program = compile_file("programs/mandelbrot.bf", &Pipeline::default()).unwrap();
+        let loaded = Program::load(&saved(&program)[..]).unwrap();
+        assert_eq!(loaded, program);
+        assert_eq!(loaded.fingerprint(), program.fingerprint());
+
+        // every kind of op, with operands that need all their bytes
+        let ops = vec![
+            Ops::Move(isize::MIN),
+            Ops::Mod(i32::MIN),
+            Ops::ModAt(-3, i32::MAX),
+            Ops::AddTo(isize::MAX, -2),
+            Ops::LoopOpen(6),
+            Ops::If(6),
+            Ops::LoopClose(4),
+            Ops::LoopOpen(9),
+            Ops::ModMove(i32::MIN, isize::MAX),
+            Ops::MoveClose(isize::MIN, 7),
+            Ops::SetCell(u32::MAX),
+            Ops::SearchZeroCell(-1),
+            Ops::Print,
+            Ops::Read,
+            Ops::Break,
+            Ops::Dump,
+            Ops::BalancedLoop(18, i32::MIN, i32::MAX),
+            Ops::ModAt(-2, 1),
+            Ops::LoopClose(16),
+            Ops::End,
+        ];
+        let program = Program::new(ops).unwrap();
+        assert_eq!(Program::load(&saved(&program)[..]).unwrap(), program);
+
+        let program = compile_with("++++++++[>++++++++<-]>+.", &Pipeline::default()).unwrap();
+        let mut in_out = StringInputOutput::new();
+        execute(&Program::load(&saved(&program)[..]).unwrap(), &mut in_out).unwrap();
+        assert_eq!(in_out.into_output(), "A");
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = saved(&compile_with("+[->+<]>.", &Pipeline::default()).unwrap());
+        for len in 0..bytes.len() {
+            let err = Program::load(&bytes[..len]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "{} bytes", len);
+        }
+        let mut longer = bytes;
+        longer.push(0);
+        let err = Program::load(&longer[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let bytes = saved(&Program::new(vec![Ops::LoopOpen(1), Ops::LoopClose(0)]).unwrap());
+        let load = |bytes: &[u8]| Program::load(bytes).unwrap_err().to_string();
+        assert_eq!(load(b"+[-]+++++"), "not a compiled program");
+
+        let mut newer = bytes.clone();
+        newer[4] = VERSION + 1;
+        assert_eq!(load(&newer), "unsupported bytecode version");
+
+        let mut unknown = bytes.clone();
+        unknown[13] = 20;
+        assert_eq!(load(&unknown), "unknown op kind 20");
+
+        // the loop jumps past the end of the program
+        let mut corrupted = bytes.clone();
+        corrupted[14] = 7;
+        assert_eq!(load(&corrupted), "invalid jump target at op 0");
+        let mut corrupted = bytes;
+        corrupted[21] = 0x80;
+        assert_eq!(load(&corrupted), "jump target out of range");
+    }
+}
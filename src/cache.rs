@@ -0,0 +1,183 @@
+//! A directory of compiled programs, so that running the same large program
+//! again skips the optimizer.
+//!
+//! An entry is named after a hash of the source, the commands of the
+//! [`Extensions`], the passes of the [`Pipeline`] and the version of the
+//! crate, so changing any of them compiles the program anew. It holds the
+//! program as [`crate::bytecode`] followed by the source spans, so errors and
+//! profiles of a cached program still point into the source. Entries that
+//! cannot be read or do not fit the source are compiled and written again.
+
+use crate::optimizer::Pipeline;
+use crate::{compile_extended, CompileError, Extensions, Fnv, Program};
+#[cfg(not(target_os = "wasi"))]
+use std::env::temp_dir;
+use std::path::{Path, PathBuf};
+
+/// Whether [`CompileCache::compile`] found the program in the cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+/// `std::env::temp_dir` panics on WASI.
+#[cfg(target_os = "wasi")]
+fn temp_dir() -> PathBuf {
+    PathBuf::from(".cache")
+}
+
+#[derive(Clone, Debug)]
+pub struct CompileCache {
+    dir: PathBuf,
+}
+
+impl CompileCache {
+    /// A cache in `dir`, which is created with the first entry.
+    pub fn new(dir: impl Into<PathBuf>) -> CompileCache {
+        CompileCache { dir: dir.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/brainfuck`, falling back to `~/.cache/brainfuck` and
+    /// then to the temporary directory. WASI has none, and runtimes pass no
+    /// environment by default, so there it falls back to `.cache/brainfuck`
+    /// in the working directory.
+    pub fn default_dir() -> PathBuf {
+        let home = std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache"));
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or(home)
+            .unwrap_or_else(temp_dir)
+            .join("brainfuck")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Like [`compile_extended`], but takes the program from the cache if it
+    /// was compiled the same way before. A program that was not cached yet is
+    /// written to it, a cache that cannot be written to is only slower.
+    pub fn compile(
+        &self,
+        source: &str,
+        extensions: &Extensions,
+        pipeline: &Pipeline,
+    ) -> Result<(Program, CacheStatus), Vec<CompileError>> {
+        let path = self.entry(source, extensions, pipeline);
+        let cached = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| Program::load_spanned(&bytes).ok())
+            .filter(|program| fits(program, source));
+        if let Some(program) = cached {
+            return Ok((program, CacheStatus::Hit));
+        }
+        let program = compile_extended(source, extensions, pipeline)?;
+        let _ = self.store(&path, &program);
+        Ok((program, CacheStatus::Miss))
+    }
+
+    fn entry(&self, source: &str, extensions: &Extensions, pipeline: &Pipeline) -> PathBuf {
+        let mut hash = Fnv::new();
+        // every part ends with a byte that cannot be part of it
+        let mut part = |part: &str| {
+            hash.feed(part.as_bytes());
+            hash.feed(&[0xff]);
+        };
+        part(env!("CARGO_PKG_VERSION"));
+        part(&format!("{:?}", extensions));
+        pipeline.passes().for_each(&mut part);
+        part(source);
+        self.dir.join(format!("{:016x}.bfc", hash.finish()))
+    }
+
+    /// Writes to a temporary file first, so that a run that reads the entry at
+    /// the same time never sees half of it.
+    fn store(&self, path: &Path, program: &Program) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut bytes = Vec::new();
+        program.save_spanned(&mut bytes)?;
+        std::fs::write(&partial, bytes)?;
+        std::fs::rename(&partial, path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })
+    }
+}
+
+/// Whether every op has a span inside `source`, which is what a program
+/// compiled from it has.
+fn fits(program: &Program, source: &str) -> bool {
+    program.spans().len() == program.len()
+        && program.spans().iter().all(|span| {
+            span.end <= source.len()
+                && source.is_char_boundary(span.start)
+                && source.is_char_boundary(span.end)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::OptLevel;
+    use crate::read_source;
+
+    #[test]
+    fn hits_and_misses() {
+        let dir = std::env::temp_dir().join(format!("brainfuck-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = CompileCache::new(&dir);
+        let source = read_source("programs/mandelbrot.bf").unwrap();
+        let compile = |source: &str, level| {
+            cache
+                .compile(source, &Extensions::default(), &Pipeline::new(level))
+                .unwrap()
+        };
+
+        let (compiled, status) = compile(&source, OptLevel::O2);
+        assert_eq!(status, CacheStatus::Miss);
+        let (cached, status) = compile(&source, OptLevel::O2);
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(cached, compiled);
+        assert_eq!(cached.spans(), compiled.spans());
+
+        // other passes produce other ops
+        assert_eq!(compile(&source, OptLevel::O0).1, CacheStatus::Miss);
+        let pipeline = Pipeline::new(OptLevel::O2).disable("scan_loops").unwrap();
+        let status = cache.compile(&source, &Extensions::default(), &pipeline);
+        assert_eq!(status.unwrap().1, CacheStatus::Miss);
+        let breakpoints = Extensions {
+            breakpoints: true,
+            ..Extensions::default()
+        };
+        let status = cache.compile(&source, &breakpoints, &Pipeline::default());
+        assert_eq!(status.unwrap().1, CacheStatus::Miss);
+        assert_eq!(compile(&source[1..], OptLevel::O2).1, CacheStatus::Miss);
+        assert_eq!(compile(&source, OptLevel::O0).1, CacheStatus::Hit);
+
+        // a corrupted entry is compiled again and replaced
+        let path = cache.entry(&source, &Extensions::default(), &Pipeline::default());
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        std::fs::write(&path, bytes).unwrap();
+        let (recompiled, status) = compile(&source, OptLevel::O2);
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(recompiled, compiled);
+        assert_eq!(compile(&source, OptLevel::O2).1, CacheStatus::Hit);
+
+        // compile errors are not cached
+        let err = cache.compile("[", &Extensions::default(), &Pipeline::default());
+        assert!(err.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spans_have_to_fit() {
+        let source = "+[->+<]";
+        let program = compile_extended(source, &Extensions::default(), &Pipeline::default());
+        let program = program.unwrap();
+        assert!(fits(&program, source));
+        assert!(!fits(&program, "+[->"));
+        assert!(!fits(&Program::new(program.to_vec()).unwrap(), source));
+    }
+}
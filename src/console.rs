@@ -0,0 +1,62 @@
+//! [`ConsoleInputOutput`], which is left out on wasm32-unknown-unknown, where
+//! there is no console.
+
+use crate::InputOutput;
+use std::io::prelude::*;
+use std::io::{BufWriter, StdoutLock};
+
+/// How many bytes [`ConsoleInputOutput`] collects before writing them to
+/// stdout.
+const CONSOLE_BUFFER: usize = 8 * 1024;
+
+/// Reads from stdin and writes to stdout.
+///
+/// The output is buffered and written when the buffer is full, before `,`
+/// waits for input, on [`InputOutput::flush`] and when the console is
+/// dropped. The console holds the lock of stdout until then, so the output
+/// of other threads cannot end up in between, and it stays on the thread
+/// that created it.
+///
+/// To stop a program on Ctrl+C without losing the buffered output, set
+/// [`ExecutionConfigBuilder::interrupt`](crate::ExecutionConfigBuilder::interrupt)
+/// from the handler and drop the console once the run returned.
+pub struct ConsoleInputOutput {
+    stdout: BufWriter<StdoutLock<'static>>,
+}
+
+impl ConsoleInputOutput {
+    pub fn new() -> ConsoleInputOutput {
+        ConsoleInputOutput {
+            stdout: BufWriter::with_capacity(CONSOLE_BUFFER, std::io::stdout().lock()),
+        }
+    }
+}
+
+impl Default for ConsoleInputOutput {
+    fn default() -> ConsoleInputOutput {
+        ConsoleInputOutput::new()
+    }
+}
+
+impl InputOutput for ConsoleInputOutput {
+    fn read_byte(&mut self) -> Option<u8> {
+        // make sure prompts are visible before blocking on input
+        let _ = self.flush();
+        // stdin is buffered internally, so reading a single byte is cheap
+        let mut byte = [0u8];
+        std::io::stdin().read_exact(&mut byte).ok().map(|_| byte[0])
+    }
+    fn write_byte(&mut self, byte: u8) {
+        // the bytes are written as they are, even if they are not valid UTF-8
+        let _ = self.stdout.write_all(&[byte]);
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for ConsoleInputOutput {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,261 @@
+//! A line based debugger for programs compiled with
+//! [`Extensions::breakpoints`], attached by [`ExecutionConfig::run_debugged`].
+//!
+//! Execution stops at every `#` and shows a prompt. The commands are read
+//! from any iterator of lines, the binary uses the terminal:
+//!
+//! - `s` or `step`: execute one op and stop again
+//! - `c` or `continue`: run to the next breakpoint
+//! - `p START..END` or `p INDEX`: print cells of the tape
+//! - `pos`: print the data pointer
+//! - `q` or `quit`: stop the program with [`RuntimeError::Aborted`]
+//!
+//! Running out of commands continues the program without stopping again.
+//!
+//! ```
+//! use brainfuck::debugger::Debugger;
+//! use brainfuck::{compile_extended, optimizer::Pipeline, Extensions};
+//! use brainfuck::{ExecutionConfig, StringInputOutput};
+//!
+//! let extensions = Extensions {
+//!     breakpoints: true,
+//!     ..Extensions::default()
+//! };
+//! let program = compile_extended("+++>++#", &extensions, &Pipeline::default()).unwrap();
+//! let mut commands = vec!["p 0..2".to_string(), "c".to_string()].into_iter();
+//! let mut log = Vec::new();
+//! let mut debugger = Debugger::new(&mut commands, &mut log);
+//! ExecutionConfig::default()
+//!     .run_debugged(&program, &mut StringInputOutput::new(), &mut debugger)
+//!     .unwrap();
+//! assert!(String::from_utf8(log).unwrap().contains("0..2: 3 2"));
+//! ```
+//!
+//! [`Extensions::breakpoints`]: crate::Extensions::breakpoints
+//! [`ExecutionConfig::run_debugged`]: crate::ExecutionConfig::run_debugged
+//! [`RuntimeError::Aborted`]: crate::RuntimeError::Aborted
+
+use crate::{Ops, Probe, RuntimeError, Tape};
+use std::io::Write;
+
+const HELP: &str = "commands: s(tep), c(ontinue), p START..END, pos, q(uit)";
+
+/// Reads commands from `commands` and writes prompts and answers to `out`.
+pub struct Debugger<'a> {
+    commands: &'a mut dyn Iterator<Item = String>,
+    out: &'a mut dyn Write,
+    /// Stop before the next op, not only at breakpoints.
+    stepping: bool,
+    /// Set once the commands ran out, nothing stops the program after that.
+    detached: bool,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(
+        commands: &'a mut dyn Iterator<Item = String>,
+        out: &'a mut dyn Write,
+    ) -> Debugger<'a> {
+        Debugger {
+            commands,
+            out,
+            stepping: false,
+            detached: false,
+        }
+    }
+
+    /// Shows the prompt until a command resumes execution.
+    fn prompt(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        // the debugger only talks to the user, a broken terminal is not the program's fault
+        let cell = tape.cell(pos).unwrap_or(0);
+        let _ = writeln!(
+            self.out,
+            "stopped at ip={:04} pos={} cell={} op={:?}",
+            ip, pos, cell, op
+        );
+        loop {
+            let _ = write!(self.out, "(bf) ");
+            let _ = self.out.flush();
+            let line = match self.commands.next() {
+                Some(line) => line,
+                None => {
+                    // nobody is left to ask, run the program to its end
+                    self.detached = true;
+                    return Ok(());
+                }
+            };
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("s"), None) | (Some("step"), None) => {
+                    self.stepping = true;
+                    return Ok(());
+                }
+                (Some("c"), None) | (Some("continue"), None) => {
+                    self.stepping = false;
+                    return Ok(());
+                }
+                (Some("q"), None) | (Some("quit"), None) => {
+                    return Err(RuntimeError::Aborted { ip })
+                }
+                (Some("pos"), None) => {
+                    let _ = writeln!(self.out, "pos={}", pos);
+                }
+                (Some("p"), Some(range)) => match parse_range(range, tape.len()) {
+                    Some((start, end)) => {
+                        let cells: Vec<String> = (start..end)
+                            .map(|i| tape.cell(i).unwrap_or(0).to_string())
+                            .collect();
+                        let _ = writeln!(self.out, "{}..{}: {}", start, end, cells.join(" "));
+                    }
+                    None => {
+                        let _ = writeln!(
+                            self.out,
+                            "invalid range '{}', the tape has {} cells",
+                            range,
+                            tape.len()
+                        );
+                    }
+                },
+                _ => {
+                    let _ = writeln!(self.out, "{}", HELP);
+                }
+            }
+        }
+    }
+}
+
+impl Probe for Debugger<'_> {
+    fn on_op(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        if !self.detached && (self.stepping || op == Ops::Break) {
+            self.prompt(ip, op, pos, tape)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses "START..END" or a single index, ranges reaching past the tape are
+/// cut off at its end.
+fn parse_range(range: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = match range.split_once("..") {
+        Some((start, end)) => (start.parse().ok()?, end.parse::<usize>().ok()?),
+        None => {
+            let index: usize = range.parse().ok()?;
+            (index, index + 1)
+        }
+    };
+    if start < end && start < len {
+        Some((start, end.min(len)))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{compile_extended, ExecutionConfig, Extensions, StringInputOutput};
+
+    /// Runs `source` with breakpoints, answering the prompts with `commands`,
+    /// and returns what the debugger wrote and the program printed.
+    fn debug(source: &str, commands: &[&str]) -> (Result<(), RuntimeError>, String, String) {
+        let extensions = Extensions {
+            breakpoints: true,
+            ..Extensions::default()
+        };
+        let program = compile_extended(source, &extensions, &Pipeline::new(OptLevel::O0)).unwrap();
+        let mut commands = commands.iter().map(|command| command.to_string());
+        let mut log = Vec::new();
+        let mut in_out = StringInputOutput::new();
+        let result = ExecutionConfig::default().run_debugged(
+            &program,
+            &mut in_out,
+            &mut Debugger::new(&mut commands, &mut log),
+        );
+        (
+            result,
+            String::from_utf8(log).unwrap(),
+            in_out.output().into(),
+        )
+    }
+
+    #[test]
+    fn inspect() {
+        let (result, log, _) = debug("+++>++#<", &["p 0..3", "pos", "p 1", "p 5..9", "x", "c"]);
+        assert_eq!(result, Ok(()));
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "stopped at ip=0006 pos=1 cell=2 op=Break",
+                "(bf) 0..3: 3 2 0",
+                "(bf) pos=1",
+                "(bf) 1..2: 2",
+                "(bf) 5..9: 0 0 0 0",
+                "(bf) commands: s(tep), c(ontinue), p START..END, pos, q(uit)",
+                "(bf) ",
+            ]
+        );
+    }
+
+    #[test]
+    fn step() {
+        // stops at the breakpoint, then before each of the two ops after it
+        let (result, log, output) = debug("+#+.", &["s", "s", "c"]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "\u{2}");
+        let stops: Vec<&str> = log
+            .lines()
+            .filter(|line| line.contains("stopped"))
+            .collect();
+        assert_eq!(
+            stops,
+            vec![
+                "stopped at ip=0001 pos=0 cell=1 op=Break",
+                "(bf) stopped at ip=0002 pos=0 cell=1 op=Mod(1)",
+                "(bf) stopped at ip=0003 pos=0 cell=2 op=Print",
+            ]
+        );
+    }
+
+    #[test]
+    fn quit() {
+        let (result, _, output) = debug("+.#.", &["q"]);
+        assert_eq!(result, Err(RuntimeError::Aborted { ip: 2 }));
+        assert_eq!(output, "\u{1}");
+    }
+
+    #[test]
+    fn without_commands() {
+        // every breakpoint is hit, and the program runs on once there is no input left
+        let (result, log, output) = debug("#+#.", &[]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "\u{1}");
+        assert_eq!(log.matches("stopped").count(), 1);
+    }
+
+    #[test]
+    fn comments() {
+        // without the extension # is a comment like any other character
+        assert_eq!(crate::compile("#+#").unwrap(), vec![Ops::Mod(1), Ops::End]);
+        let extensions = Extensions {
+            breakpoints: true,
+            ..Extensions::default()
+        };
+        assert_eq!(
+            compile_extended("#+#", &extensions, &Pipeline::default()).unwrap(),
+            vec![Ops::Break, Ops::Mod(1), Ops::Break, Ops::End]
+        );
+    }
+}
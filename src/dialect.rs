@@ -0,0 +1,321 @@
+//! Brainfuck with other tokens for the eight commands, like Blub or the
+//! in-jokes of a team. A [`Dialect`] maps every command to a token of any
+//! length, and [`Language::Dialect`] compiles sources written in it.
+//!
+//! ```
+//! use brainfuck::dialect::Dialect;
+//! use brainfuck::{compile_extended, optimizer::Pipeline, Extensions, Language};
+//!
+//! let dialect = Dialect::new(["right", "left", "up", "down", "say", "ask", "while", "end"]);
+//! let extensions = Extensions {
+//!     language: Language::Dialect(dialect.unwrap()),
+//!     ..Extensions::default()
+//! };
+//! let source = "up up up, then while down, right up, left end, right, say";
+//! let program = compile_extended(source, &extensions, &Pipeline::default()).unwrap();
+//! assert_eq!(program, brainfuck::compile("+++[->+<]>.").unwrap());
+//! ```
+//!
+//! A dialect file has one `COMMAND=TOKEN` line for each of the commands,
+//! the token is trimmed. Empty lines and lines starting with `#` are
+//! skipped:
+//!
+//! ```text
+//! # Blub
+//! > = Blub. Blub?
+//! < = Blub? Blub.
+//! ...
+//! ```
+//!
+//! A source is scanned for the longest token at every position, and like in
+//! brainfuck whatever is not a token is a comment. No token may be a prefix
+//! of another one, so that a source reads the same however it is scanned.
+//!
+//! [`Language::Dialect`]: crate::Language::Dialect
+
+use crate::{shebang_len, Span};
+use alloc::string::{String, ToString};
+
+/// The commands in the order [`Dialect::new`] takes the tokens in.
+pub const COMMANDS: [char; 8] = ['>', '<', '+', '-', '.', ',', '[', ']'];
+
+/// The tokens of the eight commands.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dialect {
+    tokens: [String; 8],
+}
+
+/// Why a mapping is no dialect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DialectError {
+    /// The token of `command` is empty.
+    EmptyToken { command: char },
+    /// Two commands have the same token.
+    SameToken { first: char, second: char },
+    /// The token of `prefix` starts the one of `command`.
+    Prefix { prefix: char, command: char },
+    /// A line of a dialect file is not `COMMAND=TOKEN`.
+    InvalidLine { line: usize },
+    /// A dialect file maps `command` a second time.
+    MappedTwice { line: usize, command: char },
+    /// A dialect file does not map `command`.
+    Unmapped { command: char },
+}
+
+impl core::fmt::Display for DialectError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            DialectError::EmptyToken { command } => {
+                write!(f, "the token of '{}' is empty", command)
+            }
+            DialectError::SameToken { first, second } => {
+                write!(f, "'{}' and '{}' have the same token", first, second)
+            }
+            DialectError::Prefix { prefix, command } => write!(
+                f,
+                "the token of '{}' is the start of the one of '{}'",
+                prefix, command
+            ),
+            DialectError::InvalidLine { line } => write!(
+                f,
+                "line {} is not COMMAND=TOKEN with one of the commands > < + - . , [ ]",
+                line
+            ),
+            DialectError::MappedTwice { line, command } => {
+                write!(f, "line {} maps '{}' a second time", line, command)
+            }
+            DialectError::Unmapped { command } => write!(f, "there is no token for '{}'", command),
+        }
+    }
+}
+
+impl core::error::Error for DialectError {}
+
+impl Dialect {
+    /// The dialect with `tokens` for the [`COMMANDS`], in their order.
+    ///
+    /// ```
+    /// use brainfuck::dialect::{Dialect, DialectError};
+    ///
+    /// let dialect = Dialect::new(["r", "l", "+1", "-1", "out", "in", "(", ")"]);
+    /// assert!(dialect.is_ok());
+    /// let dialect = Dialect::new(["r", "l", "+", "+1", "out", "in", "(", ")"]);
+    /// assert_eq!(dialect, Err(DialectError::Prefix { prefix: '+', command: '-' }));
+    /// ```
+    pub fn new(tokens: [&str; 8]) -> Result<Dialect, DialectError> {
+        if let Some(i) = tokens.iter().position(|token| token.is_empty()) {
+            return Err(DialectError::EmptyToken {
+                command: COMMANDS[i],
+            });
+        }
+        for (i, token) in tokens.iter().enumerate() {
+            for (j, other) in tokens.iter().enumerate().skip(i + 1) {
+                let (first, second) = (COMMANDS[i], COMMANDS[j]);
+                if token == other {
+                    return Err(DialectError::SameToken { first, second });
+                }
+                if other.starts_with(token) {
+                    return Err(DialectError::Prefix {
+                        prefix: first,
+                        command: second,
+                    });
+                }
+                if token.starts_with(other) {
+                    return Err(DialectError::Prefix {
+                        prefix: second,
+                        command: first,
+                    });
+                }
+            }
+        }
+        Ok(Dialect {
+            tokens: tokens.map(String::from),
+        })
+    }
+
+    /// The token of `command`, one of the [`COMMANDS`].
+    pub fn token(&self, command: char) -> Option<&str> {
+        let i = COMMANDS.iter().position(|&c| c == command)?;
+        Some(&self.tokens[i])
+    }
+
+    /// The command of every token in `source` after a `#!` line, with the
+    /// span of the token.
+    pub(crate) fn commands<'a>(
+        &'a self,
+        source: &'a str,
+    ) -> impl Iterator<Item = (char, Span)> + 'a {
+        let mut start = shebang_len(source);
+        core::iter::from_fn(move || {
+            while start < source.len() {
+                let rest = &source[start..];
+                let longest = self
+                    .tokens
+                    .iter()
+                    .zip(COMMANDS)
+                    .filter(|(token, _)| rest.starts_with(token.as_str()))
+                    .max_by_key(|(token, _)| token.len());
+                match longest {
+                    Some((token, command)) => {
+                        let span = Span {
+                            start,
+                            end: start + token.len(),
+                        };
+                        start = span.end;
+                        return Some((command, span));
+                    }
+                    None => start += rest.chars().next().map_or(1, char::len_utf8),
+                }
+            }
+            None
+        })
+    }
+}
+
+/// Reads a dialect file, see the [module documentation](self).
+impl core::str::FromStr for Dialect {
+    type Err = DialectError;
+
+    fn from_str(s: &str) -> Result<Dialect, DialectError> {
+        let mut tokens: [Option<String>; 8] = Default::default();
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (command, token) = line
+                .split_once('=')
+                .and_then(|(command, token)| {
+                    let command = command.trim();
+                    let i = COMMANDS
+                        .iter()
+                        .position(|&c| command.strip_prefix(c) == Some(""))?;
+                    Some((i, token.trim()))
+                })
+                .ok_or(DialectError::InvalidLine { line: line_number })?;
+            if tokens[command].replace(token.to_string()).is_some() {
+                return Err(DialectError::MappedTwice {
+                    line: line_number,
+                    command: COMMANDS[command],
+                });
+            }
+        }
+        let mut mapped = [""; 8];
+        for (i, token) in tokens.iter().enumerate() {
+            mapped[i] = token.as_deref().ok_or(DialectError::Unmapped {
+                command: COMMANDS[i],
+            })?;
+        }
+        Dialect::new(mapped)
+    }
+}
+
+/// A dialect file that [`str::parse`] reads back into the same dialect.
+impl core::fmt::Display for Dialect {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for (command, token) in COMMANDS.iter().zip(&self.tokens) {
+            writeln!(f, "{} = {}", command, token)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile, compile_extended, Extensions, Language, Program, StringInputOutput};
+    use alloc::vec::Vec;
+
+    fn compile_in(dialect: &Dialect, source: &str) -> Program {
+        let extensions = Extensions {
+            language: Language::Dialect(dialect.clone()),
+            ..Extensions::default()
+        };
+        compile_extended(source, &extensions, &Pipeline::default()).unwrap()
+    }
+
+    #[test]
+    fn hello_world() {
+        let bf = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let dialect = Dialect::new([
+            "moo moo", "moo MOO", "MOO moo", "MOO MOO", "moo!", "MOO!", "moo?", "MOO?",
+        ])
+        .unwrap();
+        let source: Vec<&str> = bf.chars().filter_map(|c| dialect.token(c)).collect();
+        let program = compile_in(&dialect, &source.join(" "));
+        assert_eq!(program, compile(bf).unwrap());
+        let mut in_out = StringInputOutput::new();
+        crate::execute(&program, &mut in_out).unwrap();
+        assert_eq!(in_out.output(), "Hello World!\n");
+    }
+
+    #[test]
+    fn scanning() {
+        let dialect = Dialect::new(["→", "←", "inc", "dec", "out", "get", "(", ")"]).unwrap();
+        // the tokens need no whitespace, everything else is a comment
+        let program = compile_in(&dialect, "#!x\nincinc, (decinc→) i n, →out");
+        assert_eq!(program, compile("++[-+>]>.").unwrap());
+        assert_eq!(program.source_span(0), Some(4..10));
+    }
+
+    #[test]
+    fn errors() {
+        let new = |tokens| Dialect::new(tokens).unwrap_err();
+        assert_eq!(
+            new([">", "<", "", "-", ".", ",", "[", "]"]),
+            DialectError::EmptyToken { command: '+' }
+        );
+        assert_eq!(
+            new(["a", "b", "c", "d", "e", "f", "g", "a"]),
+            DialectError::SameToken {
+                first: '>',
+                second: ']'
+            }
+        );
+        assert_eq!(
+            new(["ab", "b", "c", "d", "e", "f", "g", "a"]),
+            DialectError::Prefix {
+                prefix: ']',
+                command: '>'
+            }
+        );
+        assert_eq!(
+            new(["ab", "b", "c", "d", "e", "f", "g", "a"]).to_string(),
+            "the token of ']' is the start of the one of '>'"
+        );
+
+        let mapping = "# comment\n\n> = r\n< = l\n+ = u\n- = d\n. = o\n, = i\n[ = (\n] = )\n";
+        let dialect: Dialect = mapping.parse().unwrap();
+        assert_eq!(dialect.token('['), Some("("));
+        assert_eq!(dialect.to_string().parse(), Ok(dialect));
+        let parse = |mapping: &str| mapping.parse::<Dialect>().unwrap_err();
+        assert_eq!(
+            parse(&mapping.replace("> = r", "x = r")),
+            DialectError::InvalidLine { line: 3 }
+        );
+        assert_eq!(
+            parse(&mapping.replace("> = r", "> r")),
+            DialectError::InvalidLine { line: 3 }
+        );
+        assert_eq!(
+            parse(&format!("{}< = x\n", mapping)),
+            DialectError::MappedTwice {
+                line: 11,
+                command: '<'
+            }
+        );
+        assert_eq!(
+            parse(&mapping.replace(". = o\n", "")),
+            DialectError::Unmapped { command: '.' }
+        );
+        assert_eq!(
+            parse(&mapping.replace("= o", "= u")),
+            DialectError::SameToken {
+                first: '+',
+                second: '.'
+            }
+        );
+    }
+}
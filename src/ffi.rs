@@ -0,0 +1,202 @@
+//! A C API, with the `ffi` feature. `include/brainfuck.h` declares it, and
+//!
+//! ```text
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! builds the shared library that C, or Python with ctypes, links against.
+//!
+//! No panic crosses the boundary, it is caught and returned as
+//! [`BF_PANIC`]. Programs run with the default [`ExecutionConfig`].
+//!
+//! [`ExecutionConfig`]: crate::ExecutionConfig
+
+use crate::{compile, execute_dyn, Error, InputOutput, Program};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// The program ended.
+pub const BF_OK: c_int = 0;
+/// The program stopped with a runtime error.
+pub const BF_RUNTIME_ERROR: c_int = 1;
+/// A null pointer where there must be none.
+pub const BF_INVALID_ARGUMENT: c_int = -1;
+/// The library panicked, which is a bug.
+pub const BF_PANIC: c_int = -2;
+
+/// A compiled program, owned by the caller until [`bf_program_free`].
+pub struct BfProgram(Program);
+
+/// What `bf_run` calls for every byte the program prints.
+pub type OutputCallback = extern "C" fn(byte: u8, userdata: *mut c_void);
+
+/// Compiles the NUL terminated `source`. Returns null if `source` is null,
+/// not UTF-8 or does not compile, and then stores a message in `*err_out`
+/// unless `err_out` is null, which the caller frees with [`bf_string_free`].
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL terminated string, `err_out` must
+/// be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn bf_compile(
+    source: *const c_char,
+    err_out: *mut *mut c_char,
+) -> *mut BfProgram {
+    let compiled = catch_unwind(|| {
+        if source.is_null() {
+            return Err("the source is null".to_string());
+        }
+        let source = CStr::from_ptr(source)
+            .to_str()
+            .map_err(|_| "the source is not UTF-8".to_string())?;
+        compile(source).map_err(|errors| Error::Compile(errors).to_string())
+    });
+    let err = match compiled {
+        Ok(Ok(program)) => return Box::into_raw(Box::new(BfProgram(program))),
+        Ok(Err(err)) => err,
+        Err(_) => "the library panicked".to_string(),
+    };
+    if !err_out.is_null() {
+        // the messages contain no NUL
+        *err_out = CString::new(err).unwrap_or_default().into_raw();
+    }
+    std::ptr::null_mut()
+}
+
+/// Runs `program`, `,` reads the `input_len` bytes at `input`, and `.` calls
+/// `output_cb` with `userdata`. Returns one of the `BF_` codes.
+///
+/// # Safety
+///
+/// `program` must come from [`bf_compile`] and not be freed yet, `input`
+/// must be null or point to `input_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run(
+    program: *const BfProgram,
+    input: *const u8,
+    input_len: usize,
+    output_cb: Option<OutputCallback>,
+    userdata: *mut c_void,
+) -> c_int {
+    let output = match output_cb {
+        Some(output) if !program.is_null() && (!input.is_null() || input_len == 0) => output,
+        _ => return BF_INVALID_ARGUMENT,
+    };
+    let input = match input_len {
+        0 => &[],
+        _ => std::slice::from_raw_parts(input, input_len),
+    };
+    let mut in_out = Callback {
+        input: input.iter(),
+        output,
+        userdata,
+    };
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        // the callbacks are calls through pointers anyway, a copy of the
+        // interpreter just for them would gain nothing
+        execute_dyn(&(*program).0, &mut in_out)
+    }));
+    match result {
+        Ok(Ok(())) => BF_OK,
+        Ok(Err(_)) => BF_RUNTIME_ERROR,
+        Err(_) => BF_PANIC,
+    }
+}
+
+/// Frees a program from [`bf_compile`], null is ignored.
+///
+/// # Safety
+///
+/// `program` must be null or come from [`bf_compile`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_program_free(program: *mut BfProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Frees a message from [`bf_compile`], null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null or come from [`bf_compile`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+struct Callback<'a> {
+    input: std::slice::Iter<'a, u8>,
+    output: OutputCallback,
+    userdata: *mut c_void,
+}
+
+impl InputOutput for Callback<'_> {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.next().copied()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        (self.output)(byte, self.userdata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    extern "C" fn collect(byte: u8, userdata: *mut c_void) {
+        unsafe { (*(userdata as *mut Vec<u8>)).push(byte) }
+    }
+
+    fn run(program: *const BfProgram, input: &[u8]) -> (c_int, Vec<u8>) {
+        let mut output = Vec::new();
+        let userdata = &mut output as *mut Vec<u8> as *mut c_void;
+        let code = unsafe {
+            bf_run(
+                program,
+                input.as_ptr(),
+                input.len(),
+                Some(collect),
+                userdata,
+            )
+        };
+        (code, output)
+    }
+
+    #[test]
+    fn hello_world() {
+        let source = CString::new(
+            "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+        )
+        .unwrap();
+        let program = unsafe { bf_compile(source.as_ptr(), ptr::null_mut()) };
+        assert!(!program.is_null());
+        assert_eq!(run(program, b""), (BF_OK, b"Hello World!\n".to_vec()));
+        unsafe { bf_program_free(program) };
+    }
+
+    #[test]
+    fn errors() {
+        let mut err = ptr::null_mut();
+        let source = CString::new("+[").unwrap();
+        let program = unsafe { bf_compile(source.as_ptr(), &mut err) };
+        assert!(program.is_null());
+        let message = unsafe { CStr::from_ptr(err) }.to_str().unwrap();
+        assert!(message.contains("line 1, column 2"), "{}", message);
+        unsafe { bf_string_free(err) };
+        assert!(unsafe { bf_compile(ptr::null(), ptr::null_mut()) }.is_null());
+
+        let source = CString::new(",.,.<").unwrap();
+        let program = unsafe { bf_compile(source.as_ptr(), ptr::null_mut()) };
+        assert_eq!(run(program, b"a"), (BF_RUNTIME_ERROR, b"a\0".to_vec()));
+        let code = unsafe { bf_run(program, ptr::null(), 0, None, ptr::null_mut()) };
+        assert_eq!(code, BF_INVALID_ARGUMENT);
+        assert_eq!(run(ptr::null(), b"").0, BF_INVALID_ARGUMENT);
+        unsafe { bf_program_free(program) };
+    }
+}
@@ -0,0 +1,372 @@
+//! `brainfuck fmt`, which lays a program out with one group of commands per
+//! line, indented by the loops and procedures it is in.
+//!
+//! A group is everything between two brackets, comments or blank lines.
+//! Runs of the same command stay together, a group that is wider than the
+//! line goes on over as many lines as it needs. A loop without comments or
+//! other loops in it stays in its group if it fits on the line, all others
+//! have their brackets on lines of their own. Comments keep their place: a
+//! comment behind a command ends its line, all others are lines of their
+//! own, and a blank line stays a blank line.
+
+use brainfuck::optimizer::{OptLevel, Pipeline};
+use brainfuck::{compile_extended, CompileError, Extensions, Language, Ops};
+
+/// How many characters of commands a line holds, the indentation aside.
+pub(crate) const DEFAULT_WIDTH: usize = 80;
+
+const INDENT: &str = "    ";
+
+/// What the line that is being written holds.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Line {
+    Empty,
+    Commands,
+    Bracket,
+}
+
+struct Formatter<'a> {
+    out: String,
+    width: usize,
+    /// What goes between two tokens on a line, nothing unless the tokens
+    /// are words.
+    separator: &'a str,
+    line: String,
+    /// The length of `line` in characters.
+    len: usize,
+    kind: Line,
+    depth: usize,
+}
+
+impl Formatter<'_> {
+    fn flush(&mut self) {
+        if self.kind == Line::Empty {
+            return;
+        }
+        for _ in 0..self.depth {
+            self.out += INDENT;
+        }
+        self.out += &self.line;
+        self.out.push('\n');
+        self.line.clear();
+        self.len = 0;
+        self.kind = Line::Empty;
+    }
+
+    fn start(&mut self, kind: Line, depth: usize) {
+        self.flush();
+        self.kind = kind;
+        self.depth = depth;
+    }
+
+    fn push(&mut self, token: &str) {
+        if self.len > 0 {
+            self.line += self.separator;
+            self.len += self.separator.len();
+        }
+        self.line += token;
+        self.len += token.chars().count();
+    }
+
+    fn bracket(&mut self, token: &str, depth: usize) {
+        self.start(Line::Bracket, depth);
+        self.push(token);
+    }
+
+    /// Adds `tokens` to the group, on a line of their own if they do not
+    /// fit behind the ones before.
+    fn run(&mut self, tokens: &[&str], depth: usize) {
+        let len = width(tokens, self.separator);
+        if self.kind != Line::Commands || self.len + self.separator.len() + len > self.width {
+            self.start(Line::Commands, depth);
+        }
+        for token in tokens {
+            let len = token.chars().count();
+            if self.len > 0 && self.len + self.separator.len() + len > self.width {
+                self.start(Line::Commands, depth);
+            }
+            self.push(token);
+        }
+    }
+
+    fn comment(&mut self, comment: &str, depth: usize) {
+        self.start(Line::Empty, depth);
+        for _ in 0..depth {
+            self.out += INDENT;
+        }
+        self.out += comment;
+        self.out.push('\n');
+    }
+
+    fn blank(&mut self) {
+        self.flush();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// The comments and blank lines of what is between two tokens.
+    fn gap(&mut self, gap: &str, depth: usize) {
+        let mut lines = gap.split('\n');
+        // the text up to the first line break ends the line before
+        let behind = lines.next().unwrap_or_default().trim();
+        if !behind.is_empty() {
+            match self.kind {
+                Line::Empty => self.comment(behind, depth),
+                _ => {
+                    self.line.push(' ');
+                    self.line += behind;
+                    self.flush();
+                }
+            }
+        }
+        let lines: Vec<&str> = lines.map(str::trim).collect();
+        if let Some((before, between)) = lines.split_last() {
+            for &line in between {
+                match line {
+                    "" => self.blank(),
+                    _ => self.comment(line, depth),
+                }
+            }
+            if !before.is_empty() {
+                self.comment(before, depth);
+            }
+        }
+    }
+}
+
+/// The length of `tokens` on a line.
+fn width(tokens: &[&str], separator: &str) -> usize {
+    let len: usize = tokens.iter().map(|token| token.chars().count()).sum();
+    len + separator.len() * tokens.len().saturating_sub(1)
+}
+
+/// Whether a gap between two tokens has neither comments nor blank lines.
+fn quiet(gap: &str) -> bool {
+    gap.trim().is_empty() && gap.matches('\n').count() < 2
+}
+
+fn opens(op: Ops) -> bool {
+    matches!(op, Ops::LoopOpen(_) | Ops::ProcDefStart(_))
+}
+
+fn closes(op: Ops) -> bool {
+    matches!(op, Ops::LoopClose(_) | Ops::ProcDefEnd)
+}
+
+/// `source` laid out with lines of at most `width` commands, see the
+/// [module documentation](self). It compiles to the same ops as `source`.
+pub(crate) fn format(
+    source: &str,
+    extensions: &Extensions,
+    width: usize,
+) -> Result<String, Vec<CompileError>> {
+    // every command is an op of its own at O0, with the span of its token
+    let program = compile_extended(source, extensions, &Pipeline::new(OptLevel::O0))?;
+    let mut ops = Vec::new();
+    let mut texts = Vec::new();
+    let mut gaps = Vec::new();
+    let mut end = 0;
+    for (ip, &op) in program.iter().enumerate() {
+        let span = program.source_span(ip).unwrap();
+        if op == Ops::End {
+            break;
+        }
+        ops.push(op);
+        texts.push(&source[span.clone()]);
+        gaps.push(&source[end..span.start]);
+        end = span.end;
+    }
+
+    let separator = match extensions.language {
+        Language::Brainfuck | Language::Pbrain => "",
+        Language::Ook | Language::Dialect(_) => " ",
+    };
+    let mut formatter = Formatter {
+        out: String::with_capacity(source.len()),
+        width,
+        separator,
+        line: String::new(),
+        len: 0,
+        kind: Line::Empty,
+        depth: 0,
+    };
+    // the loop or procedure that starts at `open` on a single line, if it
+    // has nothing but commands and fits
+    let closing = |open: usize| {
+        let close = (open + 1..ops.len()).find(|&i| opens(ops[i]) || closes(ops[i]))?;
+        let inline = closes(ops[close])
+            && gaps[open + 1..=close].iter().all(|gap| quiet(gap))
+            && self::width(&texts[open..=close], separator) <= width;
+        inline.then_some(close)
+    };
+    let mut depth = 0;
+    let mut i = 0;
+    while i < ops.len() {
+        formatter.gap(gaps[i], depth);
+        let op = ops[i];
+        let next = if opens(op) {
+            match closing(i) {
+                Some(close) => {
+                    formatter.run(&texts[i..=close], depth);
+                    close + 1
+                }
+                None => {
+                    formatter.bracket(texts[i], depth);
+                    depth += 1;
+                    i + 1
+                }
+            }
+        } else if closes(op) {
+            depth -= 1;
+            formatter.bracket(texts[i], depth);
+            i + 1
+        } else {
+            let run = (i + 1..ops.len())
+                .find(|&next| ops[next] != op || !quiet(gaps[next]))
+                .unwrap_or(ops.len());
+            formatter.run(&texts[i..run], depth);
+            run
+        };
+        i = next;
+    }
+    formatter.gap(&source[end..], depth);
+    formatter.flush();
+    let mut out = formatter.out;
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brainfuck::Program;
+
+    fn brainfuck(source: &str, width: usize) -> String {
+        format(source, &Extensions::default(), width).unwrap()
+    }
+
+    fn compile(source: &str, extensions: &Extensions, level: OptLevel) -> Program {
+        compile_extended(source, extensions, &Pipeline::new(level)).unwrap()
+    }
+
+    #[test]
+    fn nested_loops() {
+        let source = "Counts to three\n+++[>++++++[>++++++++<-]>+.+.+.[-]++++++++++.[-]<<-]";
+        assert_eq!(
+            brainfuck(source, DEFAULT_WIDTH),
+            "Counts to three
++++
+[
+    >++++++[>++++++++<-]>+.+.+.[-]++++++++++.[-]<<-
+]
+"
+        );
+        // runs stay together as long as they fit
+        assert_eq!(
+            brainfuck(source, 13),
+            "Counts to three
++++
+[
+    >++++++
+    [>++++++++<-]
+    >+.+.+.[-]
+    ++++++++++.
+    [-]<<-
+]
+"
+        );
+        let source = "#!/usr/bin/env brainfuck
++[->[ inner loop
+ >++ add two
+
+ <<  back] clear [-]  ]. done\n\n\n";
+        assert_eq!(
+            brainfuck(source, 4),
+            "#!/usr/bin/env brainfuck
++
+[
+    ->
+    [ inner loop
+        >++ add two
+
+        << back
+    ] clear
+    [-]
+]
+. done
+"
+        );
+        // a run wider than the line is split
+        assert_eq!(brainfuck("++++++>>", 4), "++++\n++>>\n");
+    }
+
+    #[test]
+    fn languages() {
+        let pbrain = Extensions {
+            language: Language::Pbrain,
+            ..Extensions::default()
+        };
+        assert_eq!(
+            format("+(-[>.<-]+)say it:", &pbrain, 6).unwrap(),
+            "+\n(\n    -\n    [>.<-]\n    +\n) say it\n:\n"
+        );
+        let ook = Extensions {
+            language: Language::Ook,
+            ..Extensions::default()
+        };
+        let source = "Ook. Ook. Ook. Ook.\n Ook! Ook? Ook! Ook! Ook? Ook!";
+        assert_eq!(
+            format(source, &ook, 60).unwrap(),
+            "Ook. Ook. Ook. Ook. Ook! Ook? Ook! Ook! Ook? Ook!\n"
+        );
+        assert_eq!(
+            format(source, &ook, 20).unwrap(),
+            "Ook. Ook. Ook. Ook.\nOok! Ook?\n    Ook! Ook!\nOok? Ook!\n"
+        );
+        assert_eq!(brainfuck("", DEFAULT_WIDTH), "");
+        assert_eq!(
+            brainfuck("only a comment\n", DEFAULT_WIDTH),
+            "only a comment\n"
+        );
+        assert!(format("+[", &Extensions::default(), DEFAULT_WIDTH).is_err());
+    }
+
+    /// Formats the programs at a few widths and checks that they compile to
+    /// the same ops and that formatting them again changes nothing.
+    #[test]
+    fn same_ops() {
+        let mut paths: Vec<_> = std::fs::read_dir("programs")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "bf" || ext == "ook")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let source = std::fs::read_to_string(&path).unwrap();
+            let extensions = Extensions {
+                language: Language::from_path(path.to_str().unwrap()),
+                ..Extensions::default()
+            };
+            for width in [1, 16, DEFAULT_WIDTH] {
+                let formatted = format(&source, &extensions, width).unwrap();
+                let context = format!("{} at width {}", path.display(), width);
+                for level in [OptLevel::O0, OptLevel::O2] {
+                    assert_eq!(
+                        compile(&formatted, &extensions, level),
+                        compile(&source, &extensions, level),
+                        "{}",
+                        context
+                    );
+                }
+                let again = format(&formatted, &extensions, width).unwrap();
+                assert_eq!(again, formatted, "{}", context);
+            }
+        }
+    }
+}
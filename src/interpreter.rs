@@ -0,0 +1,644 @@
+//! Executing a program one op at a time with [`Interpreter`], for tools that
+//! look at the tape between the ops.
+//!
+//! Every step goes through the same dispatch loop as
+//! [`ExecutionConfig::run`], which stops again after one op, so stepping
+//! behaves exactly like a run, just slower. [`Outputs`] is in between, it
+//! runs a program until its next `.`.
+//!
+//! A [`Snapshot`] saves the state of an interpreter, to go back to it or to
+//! continue in another process from [`Snapshot::to_bytes`].
+//!
+//! ```
+//! use brainfuck::interpreter::{Interpreter, StepResult};
+//! use brainfuck::{compile, ExecutionConfig, StringInputOutput};
+//!
+//! let program = compile("++>+").unwrap();
+//! let mut interpreter = Interpreter::new(&program, &ExecutionConfig::default());
+//! let mut in_out = StringInputOutput::new();
+//! assert_eq!(interpreter.step(&mut in_out), Ok(StepResult::Running));
+//! assert_eq!((interpreter.ip(), interpreter.cell(0)), (1, Some(2)));
+//! interpreter.run_until(&mut in_out, |_| false).unwrap();
+//! assert_eq!((interpreter.pointer(), interpreter.cell(1)), (1, Some(1)));
+//! ```
+
+use crate::{
+    Calls, Cells, DummyInputOutput, ExecutionConfig, InputOutput, Memory, NoProbe, Ops, Program,
+    Run, RuntimeError, Stop,
+};
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+
+/// Whether the program can take another step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    Running,
+    Finished,
+}
+
+/// A program together with its tape, data pointer and next op.
+#[derive(Clone, Debug)]
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    config: ExecutionConfig,
+    memory: Memory,
+    run: Run,
+    finished: bool,
+    failed: Option<RuntimeError>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Stands before the first op of `program`, on a tape that is set up as
+    /// `config` says. `max_ops` and `timeout` apply across all steps.
+    pub fn new(program: &'a Program, config: &ExecutionConfig) -> Interpreter<'a> {
+        Interpreter {
+            program,
+            config: *config,
+            memory: Memory::new(config),
+            run: Run::new(config, false),
+            finished: false,
+            failed: None,
+        }
+    }
+
+    /// Executes the next op. Once an op failed, the interpreter stays at it
+    /// and every step returns the same error.
+    pub fn step(&mut self, in_out: &mut dyn InputOutput) -> Result<StepResult, RuntimeError> {
+        if let Some(err) = self.failed {
+            return Err(err);
+        }
+        if self.finished {
+            return Ok(StepResult::Finished);
+        }
+        self.run.fuel.pause_after(1);
+        let stopped = self.config.dispatch_from(
+            self.program,
+            in_out,
+            &mut self.memory,
+            &mut NoProbe,
+            &mut self.run,
+        );
+        match stopped {
+            Ok(()) => {
+                self.finished = true;
+                Ok(StepResult::Finished)
+            }
+            Err(Stop::Pause) => Ok(StepResult::Running),
+            Err(Stop::Error(err)) => {
+                self.failed = Some(err);
+                Err(err)
+            }
+            Err(Stop::Io) => unreachable!("the interpreter does the input and output"),
+        }
+    }
+
+    /// Steps until the program ends or `stop` returns true, which is asked
+    /// before every op.
+    pub fn run_until(
+        &mut self,
+        in_out: &mut dyn InputOutput,
+        mut stop: impl FnMut(&Interpreter) -> bool,
+    ) -> Result<StepResult, RuntimeError> {
+        while !self.finished {
+            if stop(self) {
+                return Ok(StepResult::Running);
+            }
+            self.step(in_out)?;
+        }
+        Ok(StepResult::Finished)
+    }
+
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// The index of the op that is executed next.
+    pub fn ip(&self) -> usize {
+        self.run.ip
+    }
+
+    /// The index of the current cell.
+    pub fn pointer(&self) -> usize {
+        self.memory.pos()
+    }
+
+    pub fn cell(&self, index: usize) -> Option<u32> {
+        self.memory.cell(index)
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The state of the program, to continue from later with
+    /// [`Interpreter::restore`].
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            fingerprint: self.program.fingerprint(),
+            ip: self.run.ip,
+            executed: self.run.fuel.executed(),
+            memory: self.memory.clone(),
+            calls: self.run.calls.clone(),
+        }
+    }
+
+    /// Goes back to the state of `snapshot`, which has to be taken from the
+    /// same program. The executed ops count on towards `max_ops` as before.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.fingerprint != self.program.fingerprint() {
+            return Err(SnapshotError::WrongProgram);
+        }
+        let calls = &snapshot.calls;
+        let ips = calls.procedures.values().chain(&calls.stack);
+        if snapshot.ip > self.program.len() || ips.max() >= Some(&self.program.len()) {
+            return Err(SnapshotError::Invalid);
+        }
+        self.memory = snapshot.memory.clone();
+        self.run.calls = snapshot.calls.clone();
+        self.run.ip = snapshot.ip;
+        self.run.fuel.set_executed(snapshot.executed);
+        self.finished = false;
+        self.failed = None;
+        Ok(())
+    }
+}
+
+/// The tape, the data pointer, the next op and the pbrain procedures of an
+/// [`Interpreter`], and a hash of its program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    fingerprint: u64,
+    ip: usize,
+    executed: u64,
+    memory: Memory,
+    calls: Calls,
+}
+
+/// Starts [`Snapshot::to_bytes`], followed by the version of the layout.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"BFSN";
+/// Version 1 had no procedures.
+const SNAPSHOT_VERSION: u8 = 2;
+
+impl Snapshot {
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// The magic bytes `BFSN`, a version byte, then the hash of the program,
+    /// the ip, the executed ops, the data pointer, the cell width in bytes,
+    /// the number of cells and the cells. Then the number of procedures, the
+    /// number and ip of each, the number of calls that did not return yet and
+    /// their ips. All little endian, every number but the cells takes 8
+    /// bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.push(SNAPSHOT_VERSION);
+        let width: u8 = match self.memory.cells {
+            Cells::U8(_) => 1,
+            Cells::U16(_) => 2,
+            Cells::U32(_) => 4,
+        };
+        for value in [
+            self.fingerprint,
+            self.ip as u64,
+            self.executed,
+            self.memory.pos as u64,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.push(width);
+        bytes.extend((self.memory.len() as u64).to_le_bytes());
+        match &self.memory.cells {
+            Cells::U8(cells) => bytes.extend(cells),
+            Cells::U16(cells) => bytes.extend(cells.iter().flat_map(|cell| cell.to_le_bytes())),
+            Cells::U32(cells) => bytes.extend(cells.iter().flat_map(|cell| cell.to_le_bytes())),
+        }
+        bytes.extend((self.calls.procedures.len() as u64).to_le_bytes());
+        for (&id, &ip) in &self.calls.procedures {
+            bytes.extend(u64::from(id).to_le_bytes());
+            bytes.extend((ip as u64).to_le_bytes());
+        }
+        bytes.extend((self.calls.stack.len() as u64).to_le_bytes());
+        for &ip in &self.calls.stack {
+            bytes.extend((ip as u64).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a snapshot written by [`Snapshot::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut bytes = Fields(
+            bytes
+                .strip_prefix(&SNAPSHOT_MAGIC[..])
+                .ok_or(SnapshotError::Invalid)?,
+        );
+        let version = bytes.take(1)?[0];
+        if !(1..=SNAPSHOT_VERSION).contains(&version) {
+            return Err(SnapshotError::UnsupportedVersion);
+        }
+        let fingerprint = bytes.number()?;
+        let ip = bytes.index()?;
+        let executed = bytes.number()?;
+        let pos = bytes.index()?;
+        let width = bytes.take(1)?[0];
+        let len = bytes.index()?;
+        let size = len.checked_mul(usize::from(width));
+        let cells = bytes.take(size.ok_or(SnapshotError::Invalid)?)?;
+        let cells = match width {
+            1 => Cells::U8(cells.to_vec()),
+            2 => Cells::U16(
+                cells
+                    .chunks_exact(2)
+                    .map(|cell| u16::from_le_bytes([cell[0], cell[1]]))
+                    .collect(),
+            ),
+            4 => Cells::U32(
+                cells
+                    .chunks_exact(4)
+                    .map(|cell| u32::from_le_bytes(cell.try_into().unwrap()))
+                    .collect(),
+            ),
+            _ => return Err(SnapshotError::Invalid),
+        };
+        let mut calls = Calls::default();
+        if version > 1 {
+            for _ in 0..bytes.number()? {
+                let id = u32::try_from(bytes.number()?).map_err(|_| SnapshotError::Invalid)?;
+                calls.procedures.insert(id, bytes.index()?);
+            }
+            for _ in 0..bytes.number()? {
+                calls.stack.push(bytes.index()?);
+            }
+        }
+        if !bytes.0.is_empty() || pos >= len {
+            return Err(SnapshotError::Invalid);
+        }
+        Ok(Snapshot {
+            fingerprint,
+            ip,
+            executed,
+            memory: Memory { cells, pos },
+            calls,
+        })
+    }
+}
+
+/// The fields of a snapshot that were not read yet.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < len {
+            return Err(SnapshotError::Invalid);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn number(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn index(&mut self) -> Result<usize, SnapshotError> {
+        usize::try_from(self.number()?).map_err(|_| SnapshotError::Invalid)
+    }
+}
+
+/// Why a snapshot could not be read or restored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The snapshot was taken from a different program.
+    WrongProgram,
+    /// The bytes are cut off or not a snapshot at all.
+    Invalid,
+    /// The snapshot was written by a newer version.
+    UnsupportedVersion,
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            SnapshotError::WrongProgram => write!(f, "the snapshot belongs to another program"),
+            SnapshotError::Invalid => write!(f, "not a valid snapshot"),
+            SnapshotError::UnsupportedVersion => {
+                write!(f, "the snapshot was written by an unsupported version")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+/// The output of a program, produced as it is asked for, see
+/// [`Program::outputs`].
+pub struct Outputs<'a, I> {
+    program: &'a Program,
+    config: ExecutionConfig,
+    memory: Memory,
+    run: Run,
+    input: I,
+    finished: bool,
+    error: Option<RuntimeError>,
+}
+
+impl<'a, I: Iterator<Item = u8>> Outputs<'a, I> {
+    pub(crate) fn new(program: &'a Program, config: &ExecutionConfig, input: I) -> Outputs<'a, I> {
+        Outputs {
+            program,
+            config: *config,
+            memory: Memory::new(config),
+            run: Run::new(config, true),
+            input,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// The error that ended the output early.
+    pub fn error(&self) -> Option<RuntimeError> {
+        self.error
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Outputs<'_, I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while !self.finished {
+            // the I/O is suspended, so it is the interpreter behind a
+            // reference that runs
+            let stopped = self.config.dispatch_from(
+                self.program,
+                &mut DummyInputOutput as &mut dyn InputOutput,
+                &mut self.memory,
+                &mut NoProbe,
+                &mut self.run,
+            );
+            let ip = self.run.ip;
+            let result = match stopped {
+                Ok(()) => {
+                    self.finished = true;
+                    Ok(())
+                }
+                Err(Stop::Error(err)) => Err(err),
+                Err(Stop::Io) if self.program[ip] == Ops::Print => {
+                    self.run.ip += 1;
+                    return Some(self.memory.current() as u8);
+                }
+                Err(Stop::Io) => {
+                    let byte = self.input.next();
+                    self.run.ip += 1;
+                    self.memory
+                        .store_read(Ok(byte.map(i64::from)), self.config.eof, ip)
+                }
+                Err(Stop::Pause) => unreachable!("the run never pauses"),
+            };
+            if let Err(err) = result {
+                self.error = Some(err);
+                self.finished = true;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{
+        compile, compile_extended, compile_with, CellSize, EofBehavior, Extensions, Language, Ops,
+        StringInputOutput,
+    };
+
+    #[test]
+    fn single_steps() {
+        let program = compile_with("+>++<-[>.<]", &Pipeline::new(OptLevel::O0)).unwrap();
+        let mut interpreter = Interpreter::new(&program, &ExecutionConfig::default());
+        let mut in_out = StringInputOutput::new();
+        // ip, pointer and the first two cells after each step
+        let expected = [
+            (1, 0, 1, 0),
+            (2, 1, 1, 0),
+            (3, 1, 1, 1),
+            (4, 1, 1, 2),
+            (5, 0, 1, 2),
+            (6, 0, 0, 2),
+            // the loop is skipped
+            (11, 0, 0, 2),
+        ];
+        for &(ip, pointer, first, second) in &expected {
+            assert_eq!(interpreter.step(&mut in_out), Ok(StepResult::Running));
+            assert_eq!(
+                (
+                    interpreter.ip(),
+                    interpreter.pointer(),
+                    interpreter.cell(0),
+                    interpreter.cell(1)
+                ),
+                (ip, pointer, Some(first), Some(second))
+            );
+        }
+        assert_eq!(program[11], Ops::End);
+        assert_eq!(interpreter.step(&mut in_out), Ok(StepResult::Finished));
+        assert!(interpreter.is_finished());
+        assert_eq!(interpreter.step(&mut in_out), Ok(StepResult::Finished));
+        assert_eq!(in_out.output(), "");
+    }
+
+    #[test]
+    fn same_as_run() {
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        let program = compile(",+[-[->+<]>[-<++>]<.,+]").unwrap();
+        let mut expected = StringInputOutput::with_input("abc");
+        config.run(&program, &mut expected).unwrap();
+
+        let mut interpreter = Interpreter::new(&program, &config);
+        let mut in_out = StringInputOutput::with_input("abc");
+        let mut steps = 0;
+        while interpreter.step(&mut in_out).unwrap() == StepResult::Running {
+            steps += 1;
+        }
+        assert_eq!(in_out.output(), expected.output());
+        assert!(steps > 3 * 4);
+    }
+
+    #[test]
+    fn run_until() {
+        let program = compile("+[>+<+]").unwrap();
+        let mut interpreter = Interpreter::new(&program, &ExecutionConfig::default());
+        let mut in_out = StringInputOutput::new();
+        let result =
+            interpreter.run_until(&mut in_out, |interpreter| interpreter.cell(1) == Some(10));
+        assert_eq!(result, Ok(StepResult::Running));
+        assert_eq!(interpreter.cell(0), Some(10));
+        assert_eq!(
+            interpreter.run_until(&mut in_out, |_| false),
+            Ok(StepResult::Finished)
+        );
+        assert_eq!(interpreter.cell(1), Some(255));
+    }
+
+    #[test]
+    fn outputs() {
+        // an endless program is only run as far as the output is taken
+        let printer = compile("+[.]").unwrap();
+        let first: Vec<u8> = printer.outputs(std::iter::empty()).take(10).collect();
+        assert_eq!(first, [1; 10]);
+
+        let silent = compile("+++>++").unwrap();
+        let mut outputs = silent.outputs(std::iter::empty());
+        assert_eq!(outputs.next(), None);
+        assert_eq!(outputs.next(), None);
+        assert_eq!(outputs.memory().cell(0), Some(3));
+
+        let cat = compile(",[.,]").unwrap();
+        assert_eq!(
+            cat.outputs(b"cat".iter().copied()).collect::<Vec<u8>>(),
+            b"cat"
+        );
+        let config = ExecutionConfig::builder().eof(EofBehavior::Error).build();
+        let mut outputs = config.outputs(&cat, b"c".iter().copied());
+        assert_eq!(outputs.next(), Some(b'c'));
+        assert_eq!(outputs.next(), None);
+        assert_eq!(outputs.error(), Some(RuntimeError::UnexpectedEof { ip: 3 }));
+    }
+
+    #[test]
+    fn snapshots() {
+        let source = "++++++++[>++++++++<-]>+.+.>,[.,]";
+        let program = compile_with(source, &Pipeline::new(OptLevel::O0)).unwrap();
+        let config = ExecutionConfig::builder().cell_size(CellSize::U16).build();
+        let mut interpreter = Interpreter::new(&program, &config);
+        let finish = |interpreter: &mut Interpreter| {
+            let mut in_out = StringInputOutput::with_input("xy");
+            interpreter.run_until(&mut in_out, |_| false).unwrap();
+            in_out.into_output()
+        };
+        for _ in 0..20 {
+            interpreter.step(&mut StringInputOutput::new()).unwrap();
+        }
+        let snapshot = interpreter.snapshot();
+        // in the first iteration of the loop
+        assert_eq!(snapshot.ip(), interpreter.ip());
+        assert_eq!(snapshot.memory().cells()[..2], [7, 8]);
+        let output = finish(&mut interpreter);
+        assert_eq!(output, "ABxy");
+
+        interpreter.restore(&snapshot).unwrap();
+        assert_eq!(finish(&mut interpreter), output);
+        let bytes = snapshot.to_bytes();
+        let mut restored = Interpreter::new(&program, &config);
+        restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(finish(&mut restored), output);
+        assert_eq!(restored.snapshot(), interpreter.snapshot());
+
+        let other = compile("+").unwrap();
+        assert_eq!(
+            Interpreter::new(&other, &config).restore(&snapshot),
+            Err(SnapshotError::WrongProgram)
+        );
+        for len in [0, 4, 5, 20, bytes.len() - 1] {
+            assert_eq!(
+                Snapshot::from_bytes(&bytes[..len]),
+                Err(SnapshotError::Invalid)
+            );
+        }
+        let mut newer = bytes.clone();
+        newer[4] = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            Snapshot::from_bytes(&newer),
+            Err(SnapshotError::UnsupportedVersion)
+        );
+        let mut longer = bytes;
+        longer.push(0);
+        assert_eq!(Snapshot::from_bytes(&longer), Err(SnapshotError::Invalid));
+    }
+
+    #[test]
+    fn snapshot_procedures() {
+        let extensions = Extensions {
+            language: Language::Pbrain,
+            ..Extensions::default()
+        };
+        // procedure 1 prints its cell and calls procedure 2, which adds one
+        let source = "+(.>:<)>++(+)<:>.";
+        let program = compile_extended(source, &extensions, &Pipeline::default()).unwrap();
+        let config = ExecutionConfig::default();
+        let mut interpreter = Interpreter::new(&program, &config);
+        let finish = |interpreter: &mut Interpreter| {
+            let mut in_out = StringInputOutput::new();
+            interpreter.run_until(&mut in_out, |_| false).unwrap();
+            in_out.into_output()
+        };
+        interpreter
+            .run_until(&mut StringInputOutput::new(), |interpreter| {
+                interpreter.snapshot().calls.stack.len() == 2
+            })
+            .unwrap();
+        let snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.calls.procedures.len(), 2);
+        let output = finish(&mut interpreter);
+        assert_eq!(output.as_bytes(), [3]);
+
+        let mut restored = Interpreter::new(&program, &config);
+        let bytes = snapshot.to_bytes();
+        restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(finish(&mut restored), output);
+
+        // version 1 had no procedures
+        let plain = Interpreter::new(&program, &config).snapshot().to_bytes();
+        let mut v1 = plain[..plain.len() - 16].to_vec();
+        v1[4] = 1;
+        assert_eq!(
+            Snapshot::from_bytes(&v1).map(|snapshot| snapshot.calls),
+            Ok(Calls::default())
+        );
+        let other = compile_extended("+(.)", &extensions, &Pipeline::default()).unwrap();
+        let mut snapshot = snapshot;
+        snapshot.fingerprint = other.fingerprint();
+        assert_eq!(
+            Interpreter::new(&other, &config).restore(&snapshot),
+            Err(SnapshotError::Invalid)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let program = compile(">+<<").unwrap();
+        let config = ExecutionConfig::builder().max_ops(10).build();
+        let mut interpreter = Interpreter::new(&program, &config);
+        let mut in_out = StringInputOutput::new();
+        assert_eq!(interpreter.step(&mut in_out), Ok(StepResult::Running));
+        let err = RuntimeError::PointerOutOfBounds { ip: 1, pos: -1 };
+        assert_eq!(interpreter.step(&mut in_out), Err(err));
+        assert_eq!(interpreter.ip(), 1);
+        assert_eq!(interpreter.run_until(&mut in_out, |_| false), Err(err));
+
+        let program = compile("+[]").unwrap();
+        let mut interpreter = Interpreter::new(&program, &config);
+        assert_eq!(
+            interpreter.run_until(&mut in_out, |_| false),
+            Err(RuntimeError::FuelExhausted { executed: 10 })
+        );
+    }
+}
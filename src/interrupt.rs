@@ -0,0 +1,86 @@
+//! Ctrl+C sets [`INTERRUPTED`], which the runs look at through
+//! [`ExecutionConfigBuilder::interrupt`], so that the program stops at the
+//! next loop and the buffered output and the reports are written before the
+//! process exits with status 130. A program that is waiting for input has
+//! written its output already, and a second Ctrl+C gives up on the first,
+//! both end the process right away.
+//!
+//! [`ExecutionConfigBuilder::interrupt`]: brainfuck::ExecutionConfigBuilder::interrupt
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by Ctrl+C. The REPL resets it after every entry.
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the process is in [`while_reading`].
+static READING: AtomicBool = AtomicBool::new(false);
+
+/// The exit status of an interrupted program.
+pub const INTERRUPTED_STATUS: i32 = 130;
+
+/// Has Ctrl+C set [`INTERRUPTED`]. Only supported on unix, elsewhere Ctrl+C
+/// keeps its default behavior.
+pub fn install() {
+    #[cfg(unix)]
+    handler::install();
+}
+
+/// Runs `read`, which may block on stdin, so that Ctrl+C ends the process
+/// instead of waiting for it. Anything that is buffered has to be written
+/// before.
+pub fn while_reading<T>(read: impl FnOnce() -> T) -> T {
+    // the handler sees the flag, or this sees an interrupt that came first
+    READING.store(true, Ordering::SeqCst);
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        exit();
+    }
+    let read = read();
+    READING.store(false, Ordering::SeqCst);
+    read
+}
+
+/// Ends the process without running destructors, which is safe in the
+/// handler.
+fn exit() -> ! {
+    crate::terminal::restore();
+    #[cfg(unix)]
+    handler::exit();
+    #[cfg(not(unix))]
+    std::process::exit(INTERRUPTED_STATUS);
+}
+
+#[cfg(unix)]
+mod handler {
+    use super::{INTERRUPTED, INTERRUPTED_STATUS, READING};
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+
+    const SIGINT: c_int = 2;
+    const SIG_IGN: usize = 1;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+        fn _exit(status: c_int) -> !;
+    }
+
+    pub(super) fn install() {
+        let handler: extern "C" fn(c_int) = on_interrupt;
+        unsafe {
+            // background jobs ignore Ctrl+C, and so should the program
+            if signal(SIGINT, handler as usize) == SIG_IGN {
+                signal(SIGINT, SIG_IGN);
+            }
+        }
+    }
+
+    pub(super) fn exit() -> ! {
+        unsafe { _exit(INTERRUPTED_STATUS) }
+    }
+
+    /// Only touches atomics and calls functions that are async-signal-safe.
+    extern "C" fn on_interrupt(_: c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) || READING.load(Ordering::SeqCst) {
+            super::exit();
+        }
+    }
+}
@@ -0,0 +1,706 @@
+//! Machine code for [`ExecutionConfig::run_jit`], generated with Cranelift.
+//!
+//! Every op becomes a few instructions that work on the cells in place, the
+//! address of the first cell, the number of cells and the data pointer are
+//! variables of the function. What the interpreter does out of line,
+//! growing or wrapping the tape and `,` and `.`, calls back into Rust with
+//! the data pointer stored in the [`Context`], so runtime errors are exactly
+//! the ones of the interpreter. Panics of the [`InputOutput`] are caught in
+//! the callbacks and resumed once the machine code returned.
+
+use crate::{
+    cell_at, outside_tape, store_read, Cell, ExecutionConfig, InputOutput, Ops, Program,
+    RuntimeError,
+};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block, FuncRef, InstBuilder, MemFlagsData, Signature, Type, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
+
+/// What the machine code and the callbacks share. The code reads and writes
+/// the first three fields at fixed offsets.
+#[repr(C)]
+struct Context<'a, C> {
+    base: *mut C,
+    len: usize,
+    pos: usize,
+    cells: Vec<C>,
+    in_out: &'a mut dyn InputOutput,
+    config: &'a ExecutionConfig,
+    error: Option<RuntimeError>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+// the code reads the fields the same for every type of cells
+const BASE: i32 = std::mem::offset_of!(Context<u8>, base) as i32;
+const LEN: i32 = std::mem::offset_of!(Context<u8>, len) as i32;
+const POS: i32 = std::mem::offset_of!(Context<u8>, pos) as i32;
+
+/// Runs `program` on `cells`, `None` if Cranelift cannot generate code for
+/// the host or the program calls procedures, which only the interpreter can.
+pub(crate) fn run<C: Cell>(
+    program: &Program,
+    config: &ExecutionConfig,
+    in_out: &mut dyn InputOutput,
+    cells: &mut Vec<C>,
+    pos: &mut usize,
+) -> Option<Result<(), RuntimeError>> {
+    if program.has_procedures() || program.has_dumps() {
+        return None;
+    }
+    let (module, main) = compile::<C>(program, config.interrupt)?;
+    let mut context = Context {
+        base: std::ptr::null_mut(),
+        len: cells.len(),
+        pos: *pos,
+        cells: std::mem::take(cells),
+        in_out,
+        config,
+        error: None,
+        panic: None,
+    };
+    context.base = context.cells.as_mut_ptr();
+    // Safety: the code was generated for cells of type C and only touches
+    // cells below `len`, it calls back into Rust for everything else.
+    let entry: extern "C" fn(*mut Context<C>) -> u64 =
+        unsafe { std::mem::transmute(module.get_finalized_function(main)) };
+    entry(&mut context);
+    // Safety: the code has returned and nothing refers to it any more
+    unsafe { module.free_memory() };
+    *cells = context.cells;
+    *pos = context.pos;
+    if let Some(payload) = context.panic {
+        std::panic::resume_unwind(payload);
+    }
+    Some(match context.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    })
+}
+
+/// Runs a callback, after which the cells may have moved. `Err` is returned
+/// to the machine code as `failed`.
+fn guard<C>(
+    context: *mut Context<C>,
+    failed: u64,
+    callback: impl FnOnce(&mut Context<C>) -> Result<u64, RuntimeError>,
+) -> u64 {
+    // Safety: the machine code passes on the pointer `run` called it with
+    let context = unsafe { &mut *context };
+    let result = catch_unwind(AssertUnwindSafe(|| callback(context)));
+    context.base = context.cells.as_mut_ptr();
+    context.len = context.cells.len();
+    match result {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            context.error = Some(err);
+            failed
+        }
+        Err(payload) => {
+            context.panic = Some(payload);
+            failed
+        }
+    }
+}
+
+/// The data pointer moved past one of the ends of the tape.
+extern "C" fn moved<C: Cell>(context: *mut Context<C>, ip: usize) -> u64 {
+    guard(context, 1, |context| {
+        let tape = context.config.tape;
+        context.pos = outside_tape(&mut context.cells, context.pos, ip, tape)?;
+        Ok(0)
+    })
+}
+
+/// The index of the cell `offset` cells away, which is past one of the ends
+/// of the tape.
+extern "C" fn at<C: Cell>(context: *mut Context<C>, offset: isize, ip: usize) -> u64 {
+    guard(context, u64::MAX, |context| {
+        let tape = context.config.tape;
+        let target = cell_at(&mut context.cells, &mut context.pos, offset, ip, tape)?;
+        Ok(target as u64)
+    })
+}
+
+extern "C" fn print<C: Cell>(context: *mut Context<C>) -> u64 {
+    guard(context, 1, |context| {
+        context
+            .in_out
+            .write_value(context.cells[context.pos].to_u32());
+        Ok(0)
+    })
+}
+
+extern "C" fn read<C: Cell>(context: *mut Context<C>, ip: usize) -> u64 {
+    guard(context, 1, |context| {
+        let read = context.in_out.read_value();
+        let eof = context.config.eof;
+        store_read(&mut context.cells[context.pos], read, eof, ip)?;
+        Ok(0)
+    })
+}
+
+/// The loop closed at `ip` found the interrupt flag set.
+extern "C" fn interrupted<C: Cell>(context: *mut Context<C>, ip: usize) -> u64 {
+    guard(context, 1, |_| Err(RuntimeError::Interrupted { ip }))
+}
+
+/// The callbacks as the generated code calls them.
+struct Callbacks {
+    moved: FuncRef,
+    at: FuncRef,
+    print: FuncRef,
+    read: FuncRef,
+    interrupted: FuncRef,
+}
+
+/// Generates the code of `program` for cells of type `C`, a function that
+/// takes the [`Context`] and returns 1 after an error. The loops look at
+/// `interrupt` at its address, a constant of the code.
+fn compile<C: Cell>(
+    program: &Program,
+    interrupt: &'static AtomicBool,
+) -> Option<(JITModule, FuncId)> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").ok()?;
+    // the code and the callbacks may be far apart in memory
+    flags.set("use_colocated_libcalls", "false").ok()?;
+    flags.set("is_pic", "false").ok()?;
+    let isa = cranelift_native::builder()
+        .ok()?
+        .finish(settings::Flags::new(flags))
+        .ok()?;
+    let mut jit = JITBuilder::with_isa(isa, default_libcall_names());
+    jit.symbol("moved", moved::<C> as *const u8)
+        .symbol("at", at::<C> as *const u8)
+        .symbol("print", print::<C> as *const u8)
+        .symbol("read", read::<C> as *const u8)
+        .symbol("interrupted", interrupted::<C> as *const u8);
+    let mut module = JITModule::new(jit);
+    let ptr = module.target_config().pointer_type();
+
+    let call_conv = module.isa().default_call_conv();
+    let signature = |params: usize| {
+        let mut signature = Signature::new(call_conv);
+        signature.params = vec![AbiParam::new(ptr); params];
+        signature.returns.push(AbiParam::new(types::I64));
+        signature
+    };
+    let mut declare = |name: &str, params: usize| {
+        module
+            .declare_function(name, Linkage::Import, &signature(params))
+            .ok()
+    };
+    let imports = [
+        declare("moved", 2)?,
+        declare("at", 3)?,
+        declare("print", 1)?,
+        declare("read", 2)?,
+        declare("interrupted", 2)?,
+    ];
+    let main = module
+        .declare_function("main", Linkage::Local, &signature(1))
+        .ok()?;
+
+    let mut function = module.make_context();
+    function.func.signature = signature(1);
+    let mut builder_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut function.func, &mut builder_context);
+    let [moved, at, print, read, interrupted] =
+        imports.map(|import| module.declare_func_in_func(import, builder.func));
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let context = builder.block_params(entry)[0];
+    let mut translator = Translator {
+        base: builder.declare_var(ptr),
+        len: builder.declare_var(ptr),
+        pos: builder.declare_var(ptr),
+        target: builder.declare_var(ptr),
+        error: builder.create_block(),
+        builder,
+        context,
+        interrupt,
+        ptr,
+        cell: Type::int_with_byte_size(std::mem::size_of::<C>() as u16)?,
+        callbacks: Callbacks {
+            moved,
+            at,
+            print,
+            read,
+            interrupted,
+        },
+    };
+    translator.reload();
+    translator.translate(program);
+    translator.builder.seal_all_blocks();
+    translator.builder.finalize(module.target_config());
+
+    module.define_function(main, &mut function).ok()?;
+    module.clear_context(&mut function);
+    module.finalize_definitions().ok()?;
+    Some((module, main))
+}
+
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    /// The pointer to the [`Context`].
+    context: Value,
+    interrupt: &'static AtomicBool,
+    base: Variable,
+    len: Variable,
+    pos: Variable,
+    /// The index of the cell an op works on besides the current one.
+    target: Variable,
+    /// Where the code goes after a callback failed.
+    error: Block,
+    ptr: Type,
+    cell: Type,
+    callbacks: Callbacks,
+}
+
+impl Translator<'_> {
+    fn translate(&mut self, program: &Program) {
+        // a block for every op a jump goes to and for the exit after the last
+        let mut targets = vec![None; program.len() + 1];
+        for &op in program.iter() {
+            let target = match op {
+                Ops::LoopOpen(end) | Ops::BalancedLoop(end, ..) | Ops::If(end) => end + 1,
+                Ops::LoopClose(start) | Ops::MoveClose(_, start) => start + 1,
+                Ops::End => program.len(),
+                _ => continue,
+            };
+            if targets[target].is_none() {
+                targets[target] = Some(self.builder.create_block());
+            }
+        }
+        let exit = *targets[program.len()].get_or_insert_with(|| self.builder.create_block());
+        let target = |ip: usize| targets[ip].unwrap();
+
+        for (ip, &op) in program.iter().enumerate() {
+            if let Some(block) = targets[ip] {
+                self.builder.ins().jump(block, &[]);
+                self.builder.switch_to_block(block);
+            }
+            match op {
+                Ops::Move(offset) => self.go(offset, ip),
+                Ops::Mod(value) => {
+                    let pos = self.builder.use_var(self.pos);
+                    self.add_to_cell(pos, value as u32);
+                }
+                Ops::ModAt(offset, value) => {
+                    let target = self.at(offset, ip);
+                    self.add_to_cell(target, value as u32);
+                }
+                Ops::AddTo(offset, factor) => {
+                    // the loop this replaces never touches other cells if it is skipped
+                    let skip = self.builder.create_block();
+                    let value = self.current();
+                    self.branch(value, None, Some(skip));
+                    let target = self.at(offset, ip);
+                    let value = self.current();
+                    let factor = self.immediate(factor as u32);
+                    let product = self.builder.ins().imul(value, factor);
+                    let sum = self.load(target);
+                    let sum = self.builder.ins().iadd(sum, product);
+                    self.store(target, sum);
+                    self.builder.ins().jump(skip, &[]);
+                    self.builder.switch_to_block(skip);
+                }
+                Ops::LoopOpen(end) | Ops::BalancedLoop(end, ..) | Ops::If(end) => {
+                    let value = self.current();
+                    self.branch(value, None, Some(target(end + 1)));
+                }
+                Ops::LoopClose(start) => self.loop_close(target(start + 1), ip),
+                Ops::MoveClose(offset, start) => {
+                    self.go(offset, ip);
+                    self.loop_close(target(start + 1), ip);
+                }
+                Ops::ModMove(value, offset) => {
+                    let pos = self.builder.use_var(self.pos);
+                    self.add_to_cell(pos, value as u32);
+                    self.go(offset, ip);
+                }
+                Ops::SetCell(value) => {
+                    let pos = self.builder.use_var(self.pos);
+                    let value = self.immediate(value);
+                    self.store(pos, value);
+                }
+                Ops::SearchZeroCell(step) => {
+                    let (check, found) = (self.builder.create_block(), self.builder.create_block());
+                    self.builder.ins().jump(check, &[]);
+                    self.builder.switch_to_block(check);
+                    let value = self.current();
+                    self.branch(value, None, Some(found));
+                    self.go(step, ip);
+                    self.builder.ins().jump(check, &[]);
+                    self.builder.switch_to_block(found);
+                }
+                Ops::Print => {
+                    let failed = self.call(self.callbacks.print, &[]);
+                    self.fail_unless_zero(failed);
+                }
+                Ops::Read => {
+                    let ip = self.builder.ins().iconst(self.ptr, ip as i64);
+                    let failed = self.call(self.callbacks.read, &[ip]);
+                    self.fail_unless_zero(failed);
+                }
+                Ops::Break => {}
+                Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call | Ops::Dump => {
+                    unreachable!("run falls back")
+                }
+                Ops::End => {
+                    self.builder.ins().jump(exit, &[]);
+                    let next = self.next();
+                    self.builder.switch_to_block(next);
+                }
+            }
+        }
+        self.builder.ins().jump(exit, &[]);
+        self.builder.switch_to_block(exit);
+        let pos = self.builder.use_var(self.pos);
+        self.builder
+            .ins()
+            .store(MemFlagsData::trusted(), pos, self.context, POS);
+        let done = self.builder.ins().iconst(types::I64, 0);
+        self.builder.ins().return_(&[done]);
+        // the callback stored the data pointer already
+        self.builder.switch_to_block(self.error);
+        let failed = self.builder.ins().iconst(types::I64, 1);
+        self.builder.ins().return_(&[failed]);
+    }
+
+    fn next(&mut self) -> Block {
+        self.builder.create_block()
+    }
+
+    /// Goes on at `nonzero` if `value` is not 0 and at `zero` if it is, the
+    /// code that follows for `None`.
+    fn branch(&mut self, value: Value, nonzero: Option<Block>, zero: Option<Block>) {
+        let next = self.next();
+        let (nonzero, zero) = (nonzero.unwrap_or(next), zero.unwrap_or(next));
+        self.builder.ins().brif(value, nonzero, &[], zero, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    /// Goes back to `start` if the current cell is not 0, unless the interrupt
+    /// flag is set, which stops with an error at the loop closed at `ip`.
+    fn loop_close(&mut self, start: Block, ip: usize) {
+        let value = self.current();
+        let (again, done) = (self.next(), self.next());
+        self.builder.ins().brif(value, again, &[], done, &[]);
+        self.builder.switch_to_block(again);
+        let stop = self.next();
+        let address = self
+            .builder
+            .ins()
+            .iconst(self.ptr, self.interrupt.as_ptr() as i64);
+        let flag = self
+            .builder
+            .ins()
+            .load(types::I8, MemFlagsData::trusted(), address, 0);
+        self.builder.ins().brif(flag, stop, &[], start, &[]);
+        self.builder.switch_to_block(stop);
+        let ip = self.builder.ins().iconst(self.ptr, ip as i64);
+        self.call(self.callbacks.interrupted, &[ip]);
+        self.builder.ins().jump(self.error, &[]);
+        self.builder.switch_to_block(done);
+    }
+
+    /// Takes the cells and the data pointer from the context, after a
+    /// callback may have changed them.
+    fn reload(&mut self) {
+        for (var, offset) in [(self.base, BASE), (self.len, LEN), (self.pos, POS)] {
+            let value =
+                self.builder
+                    .ins()
+                    .load(self.ptr, MemFlagsData::trusted(), self.context, offset);
+            self.builder.def_var(var, value);
+        }
+    }
+
+    /// `value` as a constant of the cell width.
+    fn immediate(&mut self, value: u32) -> Value {
+        let mask = u64::MAX >> (64 - self.cell.bits());
+        let value = i64::from(value) & mask as i64;
+        self.builder.ins().iconst(self.cell, value)
+    }
+
+    fn address(&mut self, index: Value) -> Value {
+        let base = self.builder.use_var(self.base);
+        let offset = self
+            .builder
+            .ins()
+            .imul_imm_u(index, i64::from(self.cell.bytes()));
+        self.builder.ins().iadd(base, offset)
+    }
+
+    fn load(&mut self, index: Value) -> Value {
+        let address = self.address(index);
+        self.builder
+            .ins()
+            .load(self.cell, MemFlagsData::trusted(), address, 0)
+    }
+
+    fn store(&mut self, index: Value, value: Value) {
+        let address = self.address(index);
+        self.builder
+            .ins()
+            .store(MemFlagsData::trusted(), value, address, 0);
+    }
+
+    fn current(&mut self) -> Value {
+        let pos = self.builder.use_var(self.pos);
+        self.load(pos)
+    }
+
+    fn add_to_cell(&mut self, index: Value, value: u32) {
+        let cell = self.load(index);
+        let value = self.immediate(value);
+        let sum = self.builder.ins().iadd(cell, value);
+        self.store(index, sum);
+    }
+
+    /// Calls `callback` with the context and `args`, the data pointer is
+    /// stored first so the callback sees it.
+    fn call(&mut self, callback: FuncRef, args: &[Value]) -> Value {
+        let pos = self.builder.use_var(self.pos);
+        self.builder
+            .ins()
+            .store(MemFlagsData::trusted(), pos, self.context, POS);
+        let mut all = vec![self.context];
+        all.extend_from_slice(args);
+        let call = self.builder.ins().call(callback, &all);
+        self.builder.inst_results(call)[0]
+    }
+
+    /// Goes to the exit for errors unless the callback returned 0.
+    fn fail_unless_zero(&mut self, result: Value) {
+        let error = self.error;
+        self.branch(result, Some(error), None);
+        self.reload();
+    }
+
+    /// Moves the data pointer, a move below 0 wraps around to a huge value,
+    /// so a single comparison catches both ends of the tape.
+    fn go(&mut self, offset: isize, ip: usize) {
+        let pos = self.builder.use_var(self.pos);
+        let pos = self.builder.ins().iadd_imm_s(pos, offset as i64);
+        self.builder.def_var(self.pos, pos);
+        let len = self.builder.use_var(self.len);
+        let outside = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, pos, len);
+        let (slow, inside) = (self.next(), self.next());
+        self.builder.ins().brif(outside, slow, &[], inside, &[]);
+        self.builder.switch_to_block(slow);
+        let ip = self.builder.ins().iconst(self.ptr, ip as i64);
+        let failed = self.call(self.callbacks.moved, &[ip]);
+        self.fail_unless_zero(failed);
+        self.builder.ins().jump(inside, &[]);
+        self.builder.switch_to_block(inside);
+    }
+
+    /// The index of the cell `offset` cells away.
+    fn at(&mut self, offset: isize, ip: usize) -> Value {
+        let pos = self.builder.use_var(self.pos);
+        let target = self.builder.ins().iadd_imm_s(pos, offset as i64);
+        self.builder.def_var(self.target, target);
+        let len = self.builder.use_var(self.len);
+        let outside = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, target, len);
+        let (slow, inside) = (self.next(), self.next());
+        self.builder.ins().brif(outside, slow, &[], inside, &[]);
+        self.builder.switch_to_block(slow);
+        let offset = self.builder.ins().iconst(self.ptr, offset as i64);
+        let ip = self.builder.ins().iconst(self.ptr, ip as i64);
+        let target = self.call(self.callbacks.at, &[offset, ip]);
+        let failed = self.builder.ins().icmp_imm_s(IntCC::Equal, target, -1);
+        let error = self.error;
+        self.branch(failed, Some(error), None);
+        self.reload();
+        self.builder.def_var(self.target, target);
+        self.builder.ins().jump(inside, &[]);
+        self.builder.switch_to_block(inside);
+        self.builder.use_var(self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{
+        compile, compile_with, CellSize, EofBehavior, Memory, StringInputOutput, TapeMode,
+    };
+
+    /// Runs `program` with the interpreter and with the machine code, which
+    /// have to agree on the output, the result and the tape. The machine code
+    /// counts no ops, so programs that do not end soon are skipped.
+    fn assert_same(program: &Program, input: &str, config: &ExecutionConfig) {
+        let limited = ExecutionConfig {
+            max_ops: Some(1_000_000),
+            ..*config
+        };
+        let mut memory = Memory::new(config);
+        let mut in_out = StringInputOutput::with_input(input);
+        let expected = limited.run_on(program, &mut in_out, &mut memory);
+        if let Err(RuntimeError::FuelExhausted { .. }) = expected {
+            return;
+        }
+        let mut jit_memory = Memory::new(config);
+        let mut jit_in_out = StringInputOutput::with_input(input);
+        let result = config.run_jit_on(program, &mut jit_in_out, &mut jit_memory);
+        let context = format!("{:?} with {:?}", program.to_vec(), config);
+        assert_eq!(result, expected, "{}", context);
+        assert_eq!(jit_in_out.output(), in_out.output(), "{}", context);
+        assert_eq!(jit_memory, memory, "{}", context);
+    }
+
+    fn configs() -> Vec<ExecutionConfig> {
+        vec![
+            ExecutionConfig::default(),
+            ExecutionConfig::builder()
+                .eof(EofBehavior::MinusOne)
+                .build(),
+            ExecutionConfig::builder()
+                .eof(EofBehavior::Unchanged)
+                .build(),
+            ExecutionConfig::builder().eof(EofBehavior::Error).build(),
+            ExecutionConfig::builder().cell_size(CellSize::U16).build(),
+            ExecutionConfig::builder()
+                .cell_size(CellSize::U32)
+                .eof(EofBehavior::MinusOne)
+                .build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Wrap)
+                .tape_size(4)
+                .build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Grow)
+                .tape_size(2)
+                .build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Infinite)
+                .tape_size(2)
+                .cell_size(CellSize::U16)
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn differential_programs() {
+        for entry in std::fs::read_dir("programs").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("bf".as_ref()) || path.ends_with("mandelbrot.bf") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+                let program = compile_with(&source, &Pipeline::new(level)).unwrap();
+                for config in configs() {
+                    assert_same(&program, "Hello, World!\n", &config);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mandelbrot() {
+        let source = std::fs::read_to_string("programs/mandelbrot.bf").unwrap();
+        let program = compile(&source).unwrap();
+        let mut in_out = StringInputOutput::new();
+        ExecutionConfig::default()
+            .run_jit(&program, &mut in_out)
+            .unwrap();
+        let expected = std::fs::read_to_string("programs/mandelbrot.out").unwrap();
+        assert_eq!(in_out.output(), expected);
+    }
+
+    #[test]
+    fn errors() {
+        let snippets = [
+            "<",
+            ">>+<<<",
+            "+[<]",
+            "+[>]",
+            "+[>>>+]",
+            ",,,,,.",
+            "+[->>>>>+<<<<<]>>>>>.",
+            "+[-<<+>>]<<.",
+        ];
+        for source in snippets.iter() {
+            let program = compile(source).unwrap();
+            for config in configs() {
+                assert_same(&program, "ab", &config);
+            }
+        }
+        // offsets that do not fit into an instruction
+        let program = Program::new(vec![Ops::Move(1 << 40), Ops::End]).unwrap();
+        assert_same(&program, "", &ExecutionConfig::default());
+        let program = Program::new(vec![Ops::Mod(1), Ops::ModAt(-(1 << 40), 1)]).unwrap();
+        assert_same(&program, "", &ExecutionConfig::default());
+    }
+
+    #[test]
+    fn interrupt() {
+        static SET: AtomicBool = AtomicBool::new(true);
+        let config = ExecutionConfig::builder().interrupt(&SET).build();
+        for source in ["+.[]", "+.[>>+<+<]", "+.[>+[-<]>]", "+[.,]"] {
+            assert_same(&compile(source).unwrap(), "ab", &config);
+        }
+    }
+
+    #[test]
+    fn panics_are_resumed() {
+        struct Panics;
+        impl InputOutput for Panics {
+            fn read_byte(&mut self) -> Option<u8> {
+                panic!("no input")
+            }
+            fn write_byte(&mut self, _: u8) {}
+        }
+        let program = compile("+.,").unwrap();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ExecutionConfig::default().run_jit(&program, &mut Panics)
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"no input"));
+    }
+
+    #[test]
+    fn random_programs() {
+        // a fixed xorshift sequence, so that failures can be reproduced
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let tokens = b"+-<>[].,";
+        for _ in 0..2000 {
+            let len = next() % 40;
+            let source: Vec<u8> = (0..len)
+                .map(|_| tokens[(next() % tokens.len() as u64) as usize])
+                .collect();
+            let program = match compile(std::str::from_utf8(&source).unwrap()) {
+                Ok(program) => program,
+                Err(_) => continue,
+            };
+            for config in configs() {
+                assert_same(&program, "fuzz", &config);
+            }
+        }
+    }
+}
@@ -0,0 +1,5159 @@
+//! A brainfuck interpreter.
+//!
+//! The output of a program is one call away:
+//!
+//! ```
+//! assert_eq!(brainfuck::run_to_string("++++++++[>++++++++<-]>+.").unwrap(), "A");
+//! ```
+//!
+//! A program is compiled once into a [`Program`] of [`Ops`] and can then be
+//! executed any number of times with different [`InputOutput`] implementations.
+//!
+//! ```
+//! use brainfuck::{compile, execute, InputOutput};
+//!
+//! struct Collect(Vec<u8>);
+//! impl InputOutput for Collect {
+//!     fn read_byte(&mut self) -> Option<u8> {
+//!         None
+//!     }
+//!     fn write_byte(&mut self, byte: u8) {
+//!         self.0.push(byte);
+//!     }
+//! }
+//!
+//! let program = compile("++++++++[>++++++++<-]>+.").unwrap();
+//! let mut out = Collect(Vec::new());
+//! execute(&program, &mut out).unwrap();
+//! assert_eq!(out.0, b"A");
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std` and needs only
+//! `alloc`. What is left out works with files, streams, threads or the
+//! clock: the console, [`IoAdapter`] and the other adapters of
+//! `std::io`, reading programs from files, timeouts, and the modules that
+//! write reports.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "std")]
+pub mod bytecode;
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub mod cache;
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+mod console;
+#[cfg(feature = "std")]
+pub mod debugger;
+pub mod dialect;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod interpreter;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod observer;
+mod ook;
+pub mod optimizer;
+mod packed;
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod record;
+pub mod reference;
+#[cfg(feature = "std")]
+pub mod stats;
+mod threaded;
+#[cfg(feature = "std")]
+pub mod trace;
+pub mod translate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use debugger::Debugger;
+use dialect::Dialect;
+use interpreter::Outputs;
+use observer::{Observed, Observer};
+use optimizer::Pipeline;
+use packed::Packed;
+use profile::Profile;
+#[cfg(feature = "std")]
+use stats::TapeStats;
+#[cfg(feature = "std")]
+use std::io::prelude::*;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "std")]
+use trace::Trace;
+
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub use console::ConsoleInputOutput;
+
+/// The `brainfuck!` macro of the `brainfuck-macro` crate, with the `macro`
+/// feature.
+///
+/// ```
+/// assert_eq!(brainfuck::brainfuck!("++++++++[>++++++++<-]>+."), "A");
+/// assert_eq!(brainfuck::brainfuck!(",+.", input = "@"), "A");
+/// ```
+#[cfg(feature = "macro")]
+pub use brainfuck_macro::brainfuck;
+
+/// Where `,` reads from and `.` writes to. Brainfuck works on bytes, `.`
+/// writes the lowest 8 bits of the current cell.
+///
+/// Only [`Numbers`] overrides the value methods. Wrappers like [`Newlines`]
+/// and [`TeeIo`] work on the bytes and keep the defaults, so they go inside of
+/// a `Numbers`, which shows them the values as text.
+pub trait InputOutput {
+    /// The next byte of input, `None` once the input is exhausted.
+    fn read_byte(&mut self) -> Option<u8>;
+    fn write_byte(&mut self, byte: u8);
+
+    /// Writes out buffered output, nothing to do by default.
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// The value `,` stores in the current cell, by default the next byte.
+    /// Values that do not fit into a cell stop the program with
+    /// [`RuntimeError::NumberOutOfRange`], and [`NotANumber`] with
+    /// [`RuntimeError::InvalidNumber`].
+    fn read_value(&mut self) -> Result<Option<i64>, NotANumber> {
+        Ok(self.read_byte().map(i64::from))
+    }
+
+    /// Writes the current cell for `.`, by default its lowest 8 bits.
+    fn write_value(&mut self, value: u32) {
+        self.write_byte(value as u8)
+    }
+
+    /// Shows the cells of an [`Ops::Dump`], by default on stderr, and not at
+    /// all without the `std` feature.
+    fn dump(&mut self, dump: &CellDump) {
+        #[cfg(feature = "std")]
+        eprint!("{}", dump);
+        #[cfg(not(feature = "std"))]
+        let _ = dump;
+    }
+}
+
+/// What an [`Ops::Dump`] shows: up to [`CellDump::CELLS`] cells around the
+/// data pointer.
+///
+/// ```
+/// let dump = brainfuck::CellDump {
+///     ip: 2,
+///     pointer: 1,
+///     first: 0,
+///     cells: vec![72, 0, 105],
+/// };
+/// assert_eq!(dump.to_string(), "ip 2, pointer 1, cells 0..3\n72 [0] 105\n H [.]   i\n");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellDump {
+    /// The index of the op.
+    pub ip: usize,
+    pub pointer: usize,
+    /// The index of the first of the `cells`.
+    pub first: usize,
+    pub cells: Vec<u32>,
+}
+
+impl CellDump {
+    /// How many cells are shown, if the tape has as many.
+    pub const CELLS: usize = 16;
+
+    fn new<C: Cell>(ip: usize, pointer: usize, memory: &[C]) -> CellDump {
+        let first = pointer
+            .saturating_sub(CellDump::CELLS / 2)
+            .min(memory.len().saturating_sub(CellDump::CELLS));
+        let end = memory.len().min(first + CellDump::CELLS);
+        CellDump {
+            ip,
+            pointer,
+            first,
+            cells: memory[first..end]
+                .iter()
+                .map(|cell| cell.to_u32())
+                .collect(),
+        }
+    }
+}
+
+/// A line with the cells in decimal and one with them as chars below it,
+/// `.` for those that are no printable ASCII. The current cell is in
+/// brackets.
+impl core::fmt::Display for CellDump {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let end = self.first + self.cells.len();
+        writeln!(
+            f,
+            "ip {}, pointer {}, cells {}..{}",
+            self.ip, self.pointer, self.first, end
+        )?;
+        let columns = || {
+            self.cells.iter().enumerate().map(|(i, &cell)| {
+                let current = self.first + i == self.pointer;
+                let decimal = cell.to_string();
+                let char = match char::from_u32(cell) {
+                    Some(char) if char.is_ascii_graphic() || char == ' ' => char,
+                    _ => '.',
+                };
+                (i, current, decimal, char)
+            })
+        };
+        for (i, current, decimal, _) in columns() {
+            let separator = if i == 0 { "" } else { " " };
+            match current {
+                true => write!(f, "{}[{}]", separator, decimal)?,
+                false => write!(f, "{}{}", separator, decimal)?,
+            }
+        }
+        writeln!(f)?;
+        for (i, current, decimal, char) in columns() {
+            let separator = if i == 0 { "" } else { " " };
+            let width = decimal.len();
+            match current {
+                true => write!(f, "{}[{:>width$}]", separator, char, width = width)?,
+                false => write!(f, "{}{:>width$}", separator, char, width = width)?,
+            }
+        }
+        writeln!(f)
+    }
+}
+
+/// The input of [`InputOutput::read_value`] could not be read as a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotANumber;
+
+macro_rules! forward_input_output {
+    ($($pointer:ty),*) => {$(
+        impl<T: InputOutput + ?Sized> InputOutput for $pointer {
+            fn read_byte(&mut self) -> Option<u8> {
+                (**self).read_byte()
+            }
+            fn write_byte(&mut self, byte: u8) {
+                (**self).write_byte(byte)
+            }
+            fn read_value(&mut self) -> Result<Option<i64>, NotANumber> {
+                (**self).read_value()
+            }
+            fn write_value(&mut self, value: u32) {
+                (**self).write_value(value)
+            }
+            fn dump(&mut self, dump: &CellDump) {
+                (**self).dump(dump)
+            }
+            #[cfg(feature = "std")]
+            fn flush(&mut self) -> std::io::Result<()> {
+                (**self).flush()
+            }
+        }
+    )*};
+}
+
+forward_input_output!(Box<T>, &mut T);
+
+/// Reads EOF and drops the output, for the benchmarks in the benches
+/// directory.
+pub struct DummyInputOutput;
+impl InputOutput for DummyInputOutput {
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+    fn write_byte(&mut self, _: u8) {}
+}
+
+/// Input and output as strings in which every char stands for one byte, so
+/// characters above U+00FF in the input are truncated.
+#[derive(Default)]
+pub struct StringInputOutput {
+    input: alloc::vec::IntoIter<u8>,
+    output: String,
+}
+impl StringInputOutput {
+    pub fn new() -> StringInputOutput {
+        StringInputOutput::default()
+    }
+
+    /// Feeds `input` to `,` one character at a time, as the lowest 8 bits of
+    /// each, so "\u{fe}" is the byte 0xfe and '€' (U+20AC) becomes 0xac. Use
+    /// [`StringInputOutput::with_bytes`] for input that is not Latin-1.
+    pub fn with_input(input: &str) -> StringInputOutput {
+        StringInputOutput {
+            input: input
+                .chars()
+                .map(|ch| ch as u8)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            output: String::new(),
+        }
+    }
+
+    /// Feeds `input` to `,` as it is, like the UTF-8 of a string or binary
+    /// data.
+    ///
+    /// ```
+    /// use brainfuck::{compile, execute, StringInputOutput};
+    ///
+    /// let mut in_out = StringInputOutput::with_bytes("€".as_bytes());
+    /// execute(&compile(",.,.,.").unwrap(), &mut in_out).unwrap();
+    /// assert_eq!(in_out.output(), "\u{e2}\u{82}\u{ac}");
+    /// ```
+    pub fn with_bytes(input: &[u8]) -> StringInputOutput {
+        StringInputOutput {
+            input: Vec::from(input).into_iter(),
+            output: String::new(),
+        }
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn into_output(self) -> String {
+        self.output
+    }
+}
+impl InputOutput for StringInputOutput {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.next()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.output.push(byte as char);
+    }
+}
+
+/// Reads the input of `,` from any [`Read`] and writes the output of `.` to
+/// any [`Write`], like files, sockets or a `Cursor`.
+///
+/// ```
+/// use brainfuck::{compile, execute, IoAdapter};
+/// use std::io::Cursor;
+///
+/// let mut in_out = IoAdapter::new(Cursor::new(b"hi"), Vec::new());
+/// execute(&compile(",[.,]").unwrap(), &mut in_out).unwrap();
+/// assert_eq!(in_out.output(), b"hi");
+/// ```
+#[cfg(feature = "std")]
+pub struct IoAdapter<R: Read, W: Write> {
+    input: std::io::BufReader<R>,
+    output: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, W: Write> IoAdapter<R, W> {
+    /// The input is buffered, `output` should be buffered as well unless it
+    /// is a `Vec` or similar.
+    pub fn new(input: R, output: W) -> IoAdapter<R, W> {
+        IoAdapter {
+            input: std::io::BufReader::new(input),
+            output,
+            error: None,
+        }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// The writer, the reader and any input it buffered are dropped.
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// The first error reading or writing failed with. A failed read counts as
+    /// the end of the input, failed writes are dropped.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, W: Write> InputOutput for IoAdapter<R, W> {
+    fn read_byte(&mut self) -> Option<u8> {
+        // a prompt has to be visible before waiting for the answer
+        if self.input.buffer().is_empty() {
+            if let Err(err) = self.output.flush() {
+                self.error.get_or_insert(err);
+            }
+        }
+        let mut byte = [0u8];
+        match self.input.read_exact(&mut byte) {
+            Ok(()) => Some(byte[0]),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+    fn write_byte(&mut self, byte: u8) {
+        if let Err(err) = self.output.write_all(&[byte]) {
+            self.error.get_or_insert(err);
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Reads from and writes to `in_out` and writes a copy of the output to
+/// `copy`, to watch a program and keep its output at the same time. Both
+/// get bytes, a [`Numbers`] goes around the tee.
+///
+/// ```
+/// use brainfuck::{compile, execute, StringInputOutput, TeeIo};
+///
+/// let mut in_out = TeeIo::new(StringInputOutput::with_input("hi"), Vec::new());
+/// execute(&compile(",[.,]").unwrap(), &mut in_out).unwrap();
+/// let (in_out, copy) = in_out.into_parts();
+/// assert_eq!((in_out.output(), &copy[..]), ("hi", &b"hi"[..]));
+/// ```
+#[cfg(feature = "std")]
+pub struct TeeIo<T: InputOutput, W: Write> {
+    in_out: T,
+    copy: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<T: InputOutput, W: Write> TeeIo<T, W> {
+    pub fn new(in_out: T, copy: W) -> TeeIo<T, W> {
+        TeeIo {
+            in_out,
+            copy,
+            error: None,
+        }
+    }
+
+    /// The first error writing the copy failed with, the program keeps
+    /// running.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_parts(self) -> (T, W) {
+        (self.in_out, self.copy)
+    }
+
+    fn keep(&mut self, result: std::io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: InputOutput, W: Write> InputOutput for TeeIo<T, W> {
+    fn read_byte(&mut self) -> Option<u8> {
+        // both have to show the prompt before the program waits for input
+        let flushed = self.copy.flush();
+        self.keep(flushed);
+        self.in_out.read_byte()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.in_out.write_byte(byte);
+        let copied = self.copy.write_all(&[byte]);
+        self.keep(copied);
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.in_out.dump(dump)
+    }
+    /// Flushes both, and fails if writing the copy failed at any point.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.in_out.flush()?;
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.copy.flush()
+    }
+}
+
+/// Calls `read` for `,` and `write` for `.`, for embedders that want to feed
+/// the program from an event queue or show its output in a widget.
+///
+/// ```
+/// use brainfuck::{compile, execute, ClosureIo};
+///
+/// let mut keys = b"hi".iter().copied();
+/// let mut shown = String::new();
+/// execute(
+///     &compile(",[.,]").unwrap(),
+///     &mut ClosureIo::new(|| keys.next(), |byte| shown.push(byte as char)),
+/// )
+/// .unwrap();
+/// assert_eq!(shown, "hi");
+/// ```
+pub struct ClosureIo<R: FnMut() -> Option<u8>, W: FnMut(u8)> {
+    read: R,
+    write: W,
+}
+
+/// A [`ClosureIo`] whose closures are only known at runtime.
+pub type BoxedClosureIo<'a> =
+    ClosureIo<Box<dyn FnMut() -> Option<u8> + 'a>, Box<dyn FnMut(u8) + 'a>>;
+
+impl<R: FnMut() -> Option<u8>, W: FnMut(u8)> ClosureIo<R, W> {
+    pub fn new(read: R, write: W) -> ClosureIo<R, W> {
+        ClosureIo { read, write }
+    }
+}
+
+impl<'a> BoxedClosureIo<'a> {
+    pub fn boxed(
+        read: impl FnMut() -> Option<u8> + 'a,
+        write: impl FnMut(u8) + 'a,
+    ) -> BoxedClosureIo<'a> {
+        ClosureIo::new(Box::new(read), Box::new(write))
+    }
+}
+
+impl<R: FnMut() -> Option<u8>, W: FnMut(u8)> InputOutput for ClosureIo<R, W> {
+    fn read_byte(&mut self) -> Option<u8> {
+        (self.read)()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        (self.write)(byte)
+    }
+}
+
+/// Talks to a program running on another thread through channels: `,`
+/// receives the bytes the host sends, and `.` sends its bytes to the host.
+///
+/// `,` blocks until the host sends a byte, and reads the end of the input
+/// once the host dropped its sender. A host that waits for output while the
+/// program waits for input deadlocks both threads, so either drop the sender
+/// once all input is sent, or use [`ChannelIo::read_timeout`].
+///
+/// ```
+/// use brainfuck::{compile, execute, ChannelIo};
+///
+/// let (mut in_out, input, output) = ChannelIo::new();
+/// let worker = std::thread::spawn(move || execute(&compile(",[.,]").unwrap(), &mut in_out));
+/// input.send(b'a').unwrap();
+/// assert_eq!(output.recv(), Ok(b'a'));
+/// drop(input);
+/// worker.join().unwrap().unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub struct ChannelIo {
+    input: std::sync::mpsc::Receiver<u8>,
+    output: std::sync::mpsc::Sender<u8>,
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "std")]
+impl ChannelIo {
+    /// The io for the program, the sender for its input and the receiver of
+    /// its output.
+    pub fn new() -> (
+        ChannelIo,
+        std::sync::mpsc::Sender<u8>,
+        std::sync::mpsc::Receiver<u8>,
+    ) {
+        let (input, program_input) = std::sync::mpsc::channel();
+        let (program_output, output) = std::sync::mpsc::channel();
+        let in_out = ChannelIo {
+            input: program_input,
+            output: program_output,
+            timeout: None,
+        };
+        (in_out, input, output)
+    }
+
+    /// Makes `,` read the end of the input if no byte arrived within
+    /// `timeout`, instead of waiting forever.
+    pub fn read_timeout(mut self, timeout: Duration) -> ChannelIo {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl InputOutput for ChannelIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        match self.timeout {
+            Some(timeout) => self.input.recv_timeout(timeout).ok(),
+            None => self.input.recv().ok(),
+        }
+    }
+    /// Bytes nobody receives any more are dropped.
+    fn write_byte(&mut self, byte: u8) {
+        let _ = self.output.send(byte);
+    }
+}
+
+/// The line ending [`Newlines`] writes for a 10 printed by the program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Newline {
+    Lf,
+    CrLf,
+}
+
+impl core::str::FromStr for Newline {
+    type Err = String;
+
+    /// Besides "lf" and "crlf", "native" is the line ending of the platform.
+    fn from_str(s: &str) -> Result<Newline, String> {
+        match s {
+            "lf" => Ok(Newline::Lf),
+            "crlf" => Ok(Newline::CrLf),
+            "native" if cfg!(windows) => Ok(Newline::CrLf),
+            "native" => Ok(Newline::Lf),
+            _ => Err(format!(
+                "invalid newline '{}', expected lf, crlf or native",
+                s
+            )),
+        }
+    }
+}
+
+/// Translates line endings for programs that expect every line to end with
+/// a single 10, whatever the terminal or file behind `in_out` uses.
+///
+/// `,` reads "\r\n" and a lone "\r" as 10, and with [`Newline::CrLf`] a 10
+/// written by `.` becomes "\r\n", unless the program wrote the "\r" itself.
+/// Line endings are bytes, so values are read and written as bytes as well,
+/// a [`Numbers`] belongs on top of the `Newlines`.
+///
+/// ```
+/// use brainfuck::{compile, execute, Newline, Newlines, StringInputOutput};
+///
+/// let mut in_out = Newlines::new(StringInputOutput::with_input("a\r\nb"), Newline::CrLf);
+/// execute(&compile(",[.,]").unwrap(), &mut in_out).unwrap();
+/// assert_eq!(in_out.into_inner().output(), "a\r\nb");
+/// ```
+pub struct Newlines<T: InputOutput> {
+    in_out: T,
+    newline: Newline,
+    /// The last byte read was a `\r`, so a `\n` right after it is dropped.
+    read_cr: bool,
+    /// The last byte written was a `\r`, so a `\n` is written as it is.
+    wrote_cr: bool,
+}
+
+impl<T: InputOutput> Newlines<T> {
+    pub fn new(in_out: T, newline: Newline) -> Newlines<T> {
+        Newlines {
+            in_out,
+            newline,
+            read_cr: false,
+            wrote_cr: false,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.in_out
+    }
+}
+
+impl<T: InputOutput> InputOutput for Newlines<T> {
+    fn read_byte(&mut self) -> Option<u8> {
+        loop {
+            let byte = self.in_out.read_byte()?;
+            let read_cr = core::mem::replace(&mut self.read_cr, byte == b'\r');
+            match byte {
+                b'\n' if read_cr => continue,
+                b'\r' => return Some(b'\n'),
+                byte => return Some(byte),
+            }
+        }
+    }
+    fn write_byte(&mut self, byte: u8) {
+        let wrote_cr = core::mem::replace(&mut self.wrote_cr, byte == b'\r');
+        if byte == b'\n' && self.newline == Newline::CrLf && !wrote_cr {
+            self.in_out.write_byte(b'\r');
+        }
+        self.in_out.write_byte(byte);
+    }
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.in_out.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.in_out.dump(dump)
+    }
+}
+
+/// Numbers instead of characters for math programs: `.` prints the value of
+/// the cell in decimal followed by a newline, and `,` reads the next decimal
+/// number of the input, which is separated from the others by whitespace.
+///
+/// The numbers are read and written as bytes of `in_out`, so wrappers like
+/// [`Newlines`] go inside, where they see the text.
+///
+/// ```
+/// use brainfuck::{compile, execute, Numbers, StringInputOutput};
+///
+/// let mut in_out = Numbers::new(StringInputOutput::with_input("3 4"));
+/// execute(&compile(",>,[-<+>]<.").unwrap(), &mut in_out).unwrap();
+/// assert_eq!(in_out.into_inner().output(), "7\n");
+/// ```
+pub struct Numbers<T: InputOutput> {
+    in_out: T,
+}
+
+impl<T: InputOutput> Numbers<T> {
+    pub fn new(in_out: T) -> Numbers<T> {
+        Numbers { in_out }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.in_out
+    }
+}
+
+impl<T: InputOutput> InputOutput for Numbers<T> {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.read_value().ok().flatten().map(|value| value as u8)
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.write_value(byte.into())
+    }
+    fn read_value(&mut self) -> Result<Option<i64>, NotANumber> {
+        let mut token = Vec::new();
+        // the whitespace after the number is consumed along with it
+        while let Some(byte) = self.in_out.read_byte() {
+            if !byte.is_ascii_whitespace() {
+                token.push(byte);
+            } else if !token.is_empty() {
+                break;
+            }
+        }
+        if token.is_empty() {
+            return Ok(None);
+        }
+        let token = core::str::from_utf8(&token).map_err(|_| NotANumber)?;
+        match token.parse::<i64>() {
+            Ok(value) => Ok(Some(value)),
+            // too large for any cell, which is all that matters
+            Err(err) if *err.kind() == core::num::IntErrorKind::PosOverflow => Ok(Some(i64::MAX)),
+            Err(err) if *err.kind() == core::num::IntErrorKind::NegOverflow => Ok(Some(i64::MIN)),
+            Err(_) => Err(NotANumber),
+        }
+    }
+    fn write_value(&mut self, value: u32) {
+        for byte in value.to_string().bytes().chain([b'\n']) {
+            self.in_out.write_byte(byte);
+        }
+    }
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.in_out.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.in_out.dump(dump)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ops {
+    Move(isize),
+    Mod(i32),
+    ModAt(isize, i32), // modifies the cell at the offset without moving the pointer
+    AddTo(isize, i32), // adds the current cell times the factor to the cell at the offset
+    LoopOpen(usize),
+    LoopClose(usize),
+    If(usize), // a loop whose body always clears the cell, skips to its last op if the cell is zero
+    SetCell(u32), // cells are unsigned, the value is truncated to the cell width
+    SearchZeroCell(isize), // stores the step with
+    Print,
+    Read,
+    Break,                   // a `#` breakpoint, does nothing unless a debugger is attached
+    Dump, // a `#` that shows the cells around the pointer, see `InputOutput::dump`
+    ProcDefStart(usize), // a pbrain `(`, defines the procedure numbered by the cell and skips its body
+    ProcDefEnd,          // the `)` that ends a procedure, returns from it
+    Call,                // a pbrain `:`, calls the procedure numbered by the cell
+    MoveClose(isize, usize), // a `Move` and the `LoopClose` right after it
+    ModMove(i32, isize), // a `Mod` and the `Move` right after it
+    BalancedLoop(usize, i32, i32), // a `LoopOpen` whose body reaches the cells between the offsets
+    End,
+}
+
+/// Compiled ops whose jump targets are known to be consistent, created by
+/// [`compile`] or checked by [`Program::new`].
+///
+/// Derefs to `[Ops]`, and its `Display` implementation is a disassembly with
+/// one op per line:
+///
+/// ```
+/// let program = brainfuck::compile(",[.,]").unwrap();
+/// assert_eq!(
+///     program.to_string(),
+///     "0000  read\n0001  balanced_loop 0..0 -> 0004\n0002    print\n0003    read\n0004  loop_close -> 0001\n0005  end\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    ops: Vec<Ops>,
+    /// Where each op came from, empty for programs that were not compiled
+    /// from source.
+    spans: Vec<Span>,
+}
+
+/// The FNV-1a hash, which unlike the hashers of std stays the same across
+/// builds and platforms.
+pub(crate) struct Fnv(u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A byte range of the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Span {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl Span {
+    /// The smallest span covering both.
+    pub(crate) fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// Whether every loop that opens in `ops[start..end]` closes there as well,
+/// and the other way around.
+fn nests(ops: &[Ops], start: usize, end: usize) -> bool {
+    let mut opens = Vec::new();
+    for (ip, op) in ops.iter().enumerate().take(end).skip(start) {
+        match *op {
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) => opens.push(ip),
+            Ops::LoopClose(open) | Ops::MoveClose(_, open) if opens.pop() != Some(open) => {
+                return false
+            }
+            _ => {}
+        }
+    }
+    opens.is_empty()
+}
+
+impl Program {
+    /// Checks that every jump in `ops` targets its counterpart and that the
+    /// body of every [`Ops::BalancedLoop`] stays in its range, an `End` at
+    /// the end is optional.
+    pub fn new(ops: Vec<Ops>) -> Result<Program, CompileError> {
+        for (ip, op) in ops.iter().enumerate() {
+            let valid = match *op {
+                Ops::LoopOpen(close) => match ops.get(close) {
+                    Some(&Ops::LoopClose(open)) | Some(&Ops::MoveClose(_, open)) => open == ip,
+                    _ => false,
+                },
+                // the body runs without checking the cells it reaches, so
+                // they have to be the ones the analysis finds
+                Ops::BalancedLoop(close, min, max) => match ops.get(close) {
+                    Some(&Ops::LoopClose(open)) | Some(&Ops::MoveClose(_, open)) if open == ip => {
+                        let body = &ops[ip + 1..=close];
+                        nests(&ops, ip + 1, close)
+                            && optimizer::excursion(body)
+                                .is_some_and(|(lo, hi)| min <= lo && hi <= max)
+                    }
+                    _ => false,
+                },
+                Ops::LoopClose(open) | Ops::MoveClose(_, open) => matches!(
+                    ops.get(open),
+                    Some(&Ops::LoopOpen(close)) | Some(&Ops::BalancedLoop(close, ..)) if close == ip
+                ),
+                Ops::If(end) => end > ip && end < ops.len(),
+                Ops::ProcDefStart(end) => end > ip && ops.get(end) == Some(&Ops::ProcDefEnd),
+                _ => true,
+            };
+            if !valid {
+                return Err(CompileError::InvalidJump { ip });
+            }
+        }
+        Ok(Program {
+            ops,
+            spans: Vec::new(),
+        })
+    }
+
+    pub fn into_ops(self) -> Vec<Ops> {
+        self.ops
+    }
+
+    pub fn disassemble(&self) -> String {
+        self.to_string()
+    }
+
+    /// Brainfuck source that does what the ops do, with the loops nested
+    /// again from the jump targets. It is not the source the program was
+    /// compiled from, but compiles to a program that behaves the same, as
+    /// pbrain if it has procedures.
+    ///
+    /// ```
+    /// let program = brainfuck::compile("+++[->++<]>[<]x--[-]++.").unwrap();
+    /// assert_eq!(program.to_brainfuck(), "+++[->++<][-]>[<][-]++.");
+    /// ```
+    ///
+    /// `SetCell` is `[-]` followed by `value` pluses, or by the minuses that
+    /// wrap around to values above `i32::MAX`. A run of `AddTo`s is the loop
+    /// it replaced, which clears the current cell even if the optimizer left
+    /// out the `SetCell(0)` after it, as it does when the cell is overwritten
+    /// before anything reads it. Breakpoints and dumps are a `#`. The jumps
+    /// of a program built by [`Program::new`] that cross each other have no
+    /// such source.
+    pub fn to_brainfuck(&self) -> String {
+        fn moves(source: &mut String, offset: isize) {
+            let c = if offset < 0 { '<' } else { '>' };
+            source.extend(core::iter::repeat_n(c, offset.unsigned_abs()));
+        }
+        fn adds(source: &mut String, value: i32) {
+            let c = if value < 0 { '-' } else { '+' };
+            source.extend(core::iter::repeat_n(c, value.unsigned_abs() as usize));
+        }
+
+        let mut source = String::new();
+        // the last op of every open `If` body
+        let mut ends: Vec<usize> = Vec::new();
+        // whether the op before ended the body of an `If`
+        let mut after_end = false;
+        for (ip, &op) in self.ops.iter().enumerate() {
+            match op {
+                Ops::Move(n) => moves(&mut source, n),
+                Ops::Mod(v) => adds(&mut source, v),
+                Ops::ModAt(offset, v) => {
+                    moves(&mut source, offset);
+                    adds(&mut source, v);
+                    moves(&mut source, -offset);
+                }
+                Ops::AddTo(offset, factor) => {
+                    // all of a run read the same cell, so they share a loop
+                    let before = ip.checked_sub(1).map(|i| self.ops[i]);
+                    if after_end || !matches!(before, Some(Ops::AddTo(..))) {
+                        source.push_str("[-");
+                    }
+                    moves(&mut source, offset);
+                    adds(&mut source, factor);
+                    moves(&mut source, -offset);
+                    if ends.last() == Some(&ip)
+                        || !matches!(self.ops.get(ip + 1), Some(Ops::AddTo(..)))
+                    {
+                        source.push(']');
+                    }
+                }
+                Ops::LoopOpen(_) | Ops::BalancedLoop(..) => source.push('['),
+                Ops::LoopClose(_) => source.push(']'),
+                Ops::MoveClose(n, _) => {
+                    moves(&mut source, n);
+                    source.push(']');
+                }
+                Ops::ModMove(v, n) => {
+                    adds(&mut source, v);
+                    moves(&mut source, n);
+                }
+                Ops::If(end) => {
+                    ends.push(end);
+                    source.push('[');
+                }
+                // values above i32::MAX are closer to zero from below
+                Ops::SetCell(value) => {
+                    source.push_str("[-]");
+                    adds(&mut source, value as i32);
+                }
+                Ops::SearchZeroCell(step) => {
+                    source.push('[');
+                    moves(&mut source, step);
+                    source.push(']');
+                }
+                Ops::Print => source.push('.'),
+                Ops::Read => source.push(','),
+                Ops::Break | Ops::Dump => source.push('#'),
+                Ops::ProcDefStart(_) => source.push('('),
+                Ops::ProcDefEnd => source.push(')'),
+                Ops::Call => source.push(':'),
+                Ops::End => {}
+            }
+            after_end = false;
+            while ends.last() == Some(&ip) {
+                ends.pop();
+                source.push(']');
+                after_end = true;
+            }
+        }
+        source
+    }
+
+    /// The byte range of the source the op at `ip` was compiled from, `None`
+    /// if `ip` is out of range or the program was built by [`Program::new`].
+    ///
+    /// Folded ops span the whole run, ops that replace a loop span the loop
+    /// from `[` to `]`, and `End` is an empty range behind the last token that
+    /// made it into an op.
+    ///
+    /// ```
+    /// let program = brainfuck::compile("x+++[-]+.").unwrap();
+    /// assert_eq!(program.source_span(0), Some(4..8)); // set 1
+    /// assert_eq!(program.source_span(1), Some(8..9)); // print
+    /// ```
+    pub fn source_span(&self, ip: usize) -> Option<core::ops::Range<usize>> {
+        self.spans.get(ip).map(|span| span.start..span.end)
+    }
+
+    pub(crate) fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Whether the program has an [`Ops::Dump`].
+    pub(crate) fn has_dumps(&self) -> bool {
+        self.ops.contains(&Ops::Dump)
+    }
+
+    /// Whether the program uses pbrain procedures.
+    pub(crate) fn has_procedures(&self) -> bool {
+        self.ops
+            .iter()
+            .any(|op| matches!(op, Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call))
+    }
+
+    /// A hash of the ops that stays the same across builds and platforms,
+    /// FNV-1a over their kinds and operands.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv::new();
+        let mut feed = |value: i64| hash.feed(&value.to_le_bytes());
+        for &op in &self.ops {
+            let (kind, a, b) = match op {
+                Ops::Move(offset) => (0, offset as i64, 0),
+                Ops::Mod(value) => (1, i64::from(value), 0),
+                Ops::ModAt(offset, value) => (2, offset as i64, i64::from(value)),
+                Ops::AddTo(offset, factor) => (3, offset as i64, i64::from(factor)),
+                Ops::LoopOpen(target) => (4, target as i64, 0),
+                Ops::LoopClose(target) => (5, target as i64, 0),
+                Ops::If(target) => (6, target as i64, 0),
+                Ops::SetCell(value) => (7, i64::from(value), 0),
+                Ops::SearchZeroCell(step) => (8, step as i64, 0),
+                Ops::Print => (9, 0, 0),
+                Ops::Read => (10, 0, 0),
+                Ops::Break => (11, 0, 0),
+                Ops::End => (12, 0, 0),
+                Ops::ProcDefStart(target) => (13, target as i64, 0),
+                Ops::ProcDefEnd => (14, 0, 0),
+                Ops::Call => (15, 0, 0),
+                Ops::Dump => (16, 0, 0),
+                Ops::MoveClose(offset, target) => (17, offset as i64, target as i64),
+                Ops::ModMove(value, offset) => (18, i64::from(value), offset as i64),
+                Ops::BalancedLoop(target, min, max) => (
+                    19,
+                    target as i64,
+                    i64::from(min) << 32 | i64::from(max as u32),
+                ),
+            };
+            feed(kind);
+            feed(a);
+            feed(b);
+        }
+        hash.finish()
+    }
+
+    /// Runs the program as [`execute`] does, but only as far as needed for
+    /// the next byte of output. `,` takes the bytes of `input`.
+    ///
+    /// ```
+    /// let program = brainfuck::compile("+[.+]").unwrap();
+    /// let first: Vec<u8> = program.outputs(std::iter::empty()).take(3).collect();
+    /// assert_eq!(first, [1, 2, 3]);
+    /// ```
+    pub fn outputs<I: IntoIterator<Item = u8>>(&self, input: I) -> Outputs<'_, I::IntoIter> {
+        ExecutionConfig::default().outputs(self, input)
+    }
+}
+
+impl core::ops::Deref for Program {
+    type Target = [Ops];
+
+    fn deref(&self) -> &[Ops] {
+        &self.ops
+    }
+}
+
+/// Programs are equal if they have the same ops, no matter where they came
+/// from.
+impl PartialEq for Program {
+    fn eq(&self, other: &Program) -> bool {
+        self.ops == other.ops
+    }
+}
+
+impl Eq for Program {}
+
+impl PartialEq<Vec<Ops>> for Program {
+    fn eq(&self, other: &Vec<Ops>) -> bool {
+        &self.ops == other
+    }
+}
+
+impl core::fmt::Display for Program {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // for every open body the index of the first op that is outside of it again,
+        // a loop close is printed at the level of its open
+        let mut ends: Vec<usize> = Vec::new();
+        for (i, op) in self.ops.iter().enumerate() {
+            while ends.last() == Some(&i) {
+                ends.pop();
+            }
+            write!(f, "{:04}  {}", i, "  ".repeat(ends.len()))?;
+            match *op {
+                Ops::Move(n) => writeln!(f, "move {}", n)?,
+                Ops::Mod(v) => writeln!(f, "add {}", v)?,
+                Ops::ModAt(offset, v) => writeln!(f, "add_at {}, {}", offset, v)?,
+                Ops::AddTo(offset, factor) => writeln!(f, "add_to {}, {}", offset, factor)?,
+                Ops::LoopOpen(close) => {
+                    ends.push(close);
+                    writeln!(f, "loop_open -> {:04}", close)?
+                }
+                Ops::BalancedLoop(close, min, max) => {
+                    ends.push(close);
+                    writeln!(f, "balanced_loop {}..{} -> {:04}", min, max, close)?
+                }
+                Ops::LoopClose(open) => writeln!(f, "loop_close -> {:04}", open)?,
+                Ops::MoveClose(n, open) => writeln!(f, "move_close {} -> {:04}", n, open)?,
+                Ops::ModMove(v, n) => writeln!(f, "add_move {}, {}", v, n)?,
+                Ops::If(end) => {
+                    ends.push(end + 1);
+                    writeln!(f, "if -> {:04}", end)?
+                }
+                Ops::SetCell(value) => writeln!(f, "set {}", value)?,
+                Ops::SearchZeroCell(step) => writeln!(f, "scan {}", step)?,
+                Ops::Print => writeln!(f, "print")?,
+                Ops::Read => writeln!(f, "read")?,
+                Ops::Break => writeln!(f, "break")?,
+                Ops::Dump => writeln!(f, "dump")?,
+                Ops::ProcDefStart(end) => {
+                    ends.push(end);
+                    writeln!(f, "proc -> {:04}", end)?
+                }
+                Ops::ProcDefEnd => writeln!(f, "proc_end")?,
+                Ops::Call => writeln!(f, "call")?,
+                Ops::End => writeln!(f, "end")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A location in the source, both start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of the byte `offset` of `source`, columns count characters.
+    pub fn in_source(source: &str, offset: usize) -> Position {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl core::fmt::Display for Position {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// A `[` that is never closed.
+    UnmatchedOpen { pos: Position },
+    /// A `]` without a `[` before it.
+    UnmatchedClose { pos: Position },
+    /// A pbrain `(` that is never closed.
+    UnmatchedProcedureOpen { pos: Position },
+    /// A pbrain `)` without a `(` before it.
+    UnmatchedProcedureClose { pos: Position },
+    /// The op at `ip` of a hand-built [`Program`] jumps to the wrong place.
+    InvalidJump { ip: usize },
+    /// The Ook! token number `index`, counting from 0, is none of `Ook.`,
+    /// `Ook?` and `Ook!`.
+    InvalidOokToken { index: usize, pos: Position },
+    /// The pair starting at token `index` is `Ook? Ook?`, which is no
+    /// command.
+    UnknownOokPair { index: usize, pos: Position },
+    /// The last Ook! token has no partner.
+    UnpairedOokToken { index: usize, pos: Position },
+}
+
+impl CompileError {
+    /// Where in the source the error is, if it comes from a source.
+    pub fn pos(&self) -> Option<Position> {
+        match *self {
+            CompileError::UnmatchedOpen { pos }
+            | CompileError::UnmatchedClose { pos }
+            | CompileError::UnmatchedProcedureOpen { pos }
+            | CompileError::UnmatchedProcedureClose { pos }
+            | CompileError::InvalidOokToken { pos, .. }
+            | CompileError::UnknownOokPair { pos, .. }
+            | CompileError::UnpairedOokToken { pos, .. } => Some(pos),
+            CompileError::InvalidJump { .. } => None,
+        }
+    }
+}
+
+impl core::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            CompileError::UnmatchedOpen { pos } => write!(f, "unmatched '[' at {}", pos),
+            CompileError::UnmatchedClose { pos } => write!(f, "unmatched ']' at {}", pos),
+            CompileError::UnmatchedProcedureOpen { pos } => write!(f, "unmatched '(' at {}", pos),
+            CompileError::UnmatchedProcedureClose { pos } => {
+                write!(f, "unmatched ')' at {}", pos)
+            }
+            CompileError::InvalidJump { ip } => write!(f, "invalid jump target at op {}", ip),
+            CompileError::InvalidOokToken { index, pos } => {
+                write!(f, "token {} at {} is not Ook., Ook? or Ook!", index, pos)
+            }
+            CompileError::UnknownOokPair { index, pos } => write!(
+                f,
+                "'Ook? Ook?' at token {}, {}, is not a command",
+                index, pos
+            ),
+            CompileError::UnpairedOokToken { index, pos } => {
+                write!(f, "unpaired Ook! token {} at {}", index, pos)
+            }
+        }
+    }
+}
+
+impl core::error::Error for CompileError {}
+
+/// Translates every token into an op and checks that the brackets match,
+/// which has to happen here because only the source knows the positions.
+/// Every unmatched bracket is reported, in the order of the source. A first
+/// line starting with `#!` is skipped, so that programs can be run as scripts.
+///
+/// The jump targets of the ops are not set yet, see [`optimize`].
+pub fn parse(source: &str) -> Result<Vec<Ops>, Vec<CompileError>> {
+    parse_extended(source, &Extensions::default())
+}
+
+/// [`parse`] with the commands of `extensions` on top of the standard ones.
+pub fn parse_extended(
+    source: &str,
+    extensions: &Extensions,
+) -> Result<Vec<Ops>, Vec<CompileError>> {
+    Ok(parse_spanned(source, extensions)?
+        .into_iter()
+        .map(|(op, _)| op)
+        .collect())
+}
+
+/// The length in bytes of a `#!` line at the start of `source`, without its
+/// newline.
+pub(crate) fn shebang_len(source: &str) -> usize {
+    if source.starts_with("#!") {
+        source.find('\n').unwrap_or(source.len())
+    } else {
+        0
+    }
+}
+
+/// [`parse`], but every op keeps the span of its token.
+fn parse_spanned(
+    source: &str,
+    extensions: &Extensions,
+) -> Result<Vec<(Ops, Span)>, Vec<CompileError>> {
+    match &extensions.language {
+        Language::Brainfuck | Language::Pbrain => {
+            let shebang = shebang_len(source);
+            let tokens = source
+                .char_indices()
+                .skip_while(|&(start, _)| start < shebang)
+                .map(|(start, token)| {
+                    let end = start + token.len_utf8();
+                    (token, Span { start, end })
+                });
+            parse_tokens(source, tokens, extensions)
+        }
+        Language::Ook => parse_tokens(source, ook::tokens(source)?.into_iter(), extensions),
+        Language::Dialect(dialect) => parse_tokens(source, dialect.commands(source), extensions),
+    }
+}
+
+/// Translates the brainfuck commands in `tokens` into ops, everything else is
+/// a comment.
+fn parse_tokens(
+    source: &str,
+    tokens: impl Iterator<Item = (char, Span)>,
+    extensions: &Extensions,
+) -> Result<Vec<(Ops, Span)>, Vec<CompileError>> {
+    let mut ops = Vec::new();
+    // every open bracket or parenthesis that is not closed yet
+    let mut open: Vec<(char, Position)> = Vec::new();
+    let mut errors = Vec::new();
+    let mut positions = Positions::new(source);
+    let procedures = extensions.language == Language::Pbrain;
+    for (token, span) in tokens {
+        let op = match token {
+            '<' => Ops::Move(-1),
+            '>' => Ops::Move(1),
+            '-' => Ops::Mod(-1),
+            '+' => Ops::Mod(1),
+            '#' if extensions.breakpoints => Ops::Break,
+            '#' if extensions.dumps => Ops::Dump,
+            '.' => Ops::Print,
+            ',' => Ops::Read,
+            '[' => {
+                open.push(('[', positions.at(span.start)));
+                Ops::LoopOpen(0)
+            }
+            '(' if procedures => {
+                open.push(('(', positions.at(span.start)));
+                Ops::ProcDefStart(0)
+            }
+            ']' | ')' if token == ']' || procedures => {
+                // loops and procedures have to nest
+                let opening = if token == ']' { '[' } else { '(' };
+                if open.last().map(|&(open, _)| open) == Some(opening) {
+                    open.pop();
+                } else {
+                    let pos = positions.at(span.start);
+                    errors.push(match token {
+                        ']' => CompileError::UnmatchedClose { pos },
+                        _ => CompileError::UnmatchedProcedureClose { pos },
+                    });
+                }
+                match token {
+                    ']' => Ops::LoopClose(0),
+                    _ => Ops::ProcDefEnd,
+                }
+            }
+            ':' if procedures => Ops::Call,
+            _ => continue,
+        };
+        ops.push((op, span));
+    }
+    errors.extend(open.into_iter().map(|(open, pos)| match open {
+        '[' => CompileError::UnmatchedOpen { pos },
+        _ => CompileError::UnmatchedProcedureOpen { pos },
+    }));
+
+    if errors.is_empty() {
+        Ok(ops)
+    } else {
+        errors.sort_unstable_by_key(CompileError::pos);
+        Err(errors)
+    }
+}
+
+/// The positions of ever larger offsets into a source, without counting the
+/// lines from its start every time.
+pub(crate) struct Positions<'a> {
+    source: &'a str,
+    offset: usize,
+    pos: Position,
+}
+
+impl<'a> Positions<'a> {
+    pub(crate) fn new(source: &'a str) -> Positions<'a> {
+        Positions {
+            source,
+            offset: 0,
+            pos: Position { line: 1, column: 1 },
+        }
+    }
+
+    /// The position of the byte `offset`, which must not be before the one
+    /// of the previous call.
+    pub(crate) fn at(&mut self, offset: usize) -> Position {
+        for c in self.source[self.offset..offset].chars() {
+            if c == '\n' {
+                self.pos.line += 1;
+                self.pos.column = 1;
+            } else {
+                self.pos.column += 1;
+            }
+        }
+        self.offset = offset;
+        self.pos
+    }
+}
+
+/// Compiles `source` with the default [`Pipeline`].
+pub fn compile(source: &str) -> Result<Program, Vec<CompileError>> {
+    compile_with(source, &Pipeline::default())
+}
+
+/// Translates every token of `source` into an op, runs the passes of
+/// `pipeline` and resolves the jump targets.
+pub fn compile_with(source: &str, pipeline: &Pipeline) -> Result<Program, Vec<CompileError>> {
+    compile_extended(source, &Extensions::default(), pipeline)
+}
+
+/// [`compile_with`] with the commands of `extensions` on top of the standard
+/// ones.
+pub fn compile_extended(
+    source: &str,
+    extensions: &Extensions,
+    pipeline: &Pipeline,
+) -> Result<Program, Vec<CompileError>> {
+    Ok(link(
+        pipeline.run_spanned(parse_spanned(source, extensions)?),
+    ))
+}
+
+/// How the source is read: its [`Language`] and the commands beyond the
+/// standard eight, all of them are off by default so that their characters
+/// stay comments.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Extensions {
+    pub language: Language,
+    /// `#` compiles to [`Ops::Break`], see [`debugger`]. Ook! and dialects
+    /// have no `#`.
+    pub breakpoints: bool,
+    /// `#` compiles to [`Ops::Dump`], unless it is a breakpoint.
+    pub dumps: bool,
+}
+
+/// The languages that are brainfuck with other tokens, they all compile to
+/// the same ops.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    Brainfuck,
+    /// Pairs of the whitespace separated tokens `Ook.`, `Ook?` and `Ook!`,
+    /// `Ook. Ook?` is `>` and so on. Anything else is an error, there are no
+    /// comments.
+    Ook,
+    /// Brainfuck with procedures: `(` defines the procedure numbered by the
+    /// current cell up to the matching `)`, and `:` calls the one numbered by
+    /// the current cell.
+    Pbrain,
+    /// Any other tokens, see [`dialect`].
+    Dialect(Dialect),
+}
+
+impl Language {
+    /// Ook! for files ending in `.ook`, brainfuck for all others.
+    pub fn from_path(path: &str) -> Language {
+        match path.rsplit_once('.') {
+            Some((_, extension)) if extension.eq_ignore_ascii_case("ook") => Language::Ook,
+            _ => Language::Brainfuck,
+        }
+    }
+}
+
+impl core::str::FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Language, String> {
+        match s {
+            "bf" => Ok(Language::Brainfuck),
+            "ook" => Ok(Language::Ook),
+            "pbrain" => Ok(Language::Pbrain),
+            _ => Err(format!(
+                "invalid language '{}', expected bf, ook or pbrain",
+                s
+            )),
+        }
+    }
+}
+
+/// Runs the default [`Pipeline`] over the output of [`parse`], resolves the
+/// jump targets and appends `End`. The loops and procedures of `ops` have to
+/// be balanced, since they need not come from [`parse`]: every bracket that
+/// is not is an [`CompileError::InvalidJump`] at its index, in their order.
+///
+/// ```
+/// use brainfuck::{optimize, CompileError, Ops};
+///
+/// let ops = vec![Ops::Mod(1), Ops::LoopClose(0)];
+/// assert_eq!(optimize(ops), Err(vec![CompileError::InvalidJump { ip: 1 }]));
+/// ```
+pub fn optimize(ops: Vec<Ops>) -> Result<Vec<Ops>, Vec<CompileError>> {
+    check_brackets(&ops)?;
+    let code = ops.into_iter().map(|op| (op, Span::default())).collect();
+    Ok(link(Pipeline::default().run_spanned(code)).into_ops())
+}
+
+/// The check of [`optimize`], a `]` only closes a `[` and a `)` a `(`.
+fn check_brackets(ops: &[Ops]) -> Result<(), Vec<CompileError>> {
+    let mut unmatched = Vec::new();
+    // the open brackets, with whether they start a procedure
+    let mut opens = Vec::new();
+    for (ip, op) in ops.iter().enumerate() {
+        let procedure = match op {
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) => {
+                opens.push((ip, false));
+                continue;
+            }
+            Ops::ProcDefStart(_) => {
+                opens.push((ip, true));
+                continue;
+            }
+            Ops::LoopClose(_) | Ops::MoveClose(..) => false,
+            Ops::ProcDefEnd => true,
+            _ => continue,
+        };
+        match opens.last() {
+            Some(&(_, open)) if open == procedure => {
+                opens.pop();
+            }
+            _ => unmatched.push(ip),
+        }
+    }
+    unmatched.extend(opens.into_iter().map(|(ip, _)| ip));
+    if unmatched.is_empty() {
+        return Ok(());
+    }
+    unmatched.sort_unstable();
+    Err(unmatched
+        .into_iter()
+        .map(|ip| CompileError::InvalidJump { ip })
+        .collect())
+}
+
+/// Calculates all loop jump destinations, the brackets have to be balanced.
+fn link(code: Vec<(Ops, Span)>) -> Program {
+    let (mut ops, mut spans): (Vec<Ops>, Vec<Span>) = code.into_iter().unzip();
+    let mut stack: Vec<usize> = vec![];
+    for i in 0..ops.len() {
+        match ops[i] {
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) | Ops::ProcDefStart(_) => stack.push(i),
+            Ops::If(len) => ops[i] = Ops::If(i + len),
+            Ops::ProcDefEnd => {
+                let start_pos = stack.pop().expect("unmatched )");
+                ops[start_pos] = Ops::ProcDefStart(i);
+            }
+            Ops::LoopClose(_) | Ops::MoveClose(..) => {
+                // parse or optimize checked the brackets and the passes keep
+                // them balanced
+                let start_pos = stack.pop().expect("unmatched ]");
+                ops[start_pos] = match ops[start_pos] {
+                    Ops::BalancedLoop(_, min, max) => Ops::BalancedLoop(i, min, max),
+                    _ => Ops::LoopOpen(i),
+                };
+                ops[i] = match ops[i] {
+                    Ops::MoveClose(n, _) => Ops::MoveClose(n, start_pos),
+                    _ => Ops::LoopClose(start_pos),
+                };
+            }
+            _ => {
+                // not relevant for this optimization
+            }
+        };
+    }
+
+    debug_assert!(stack.is_empty(), "unmatched [");
+    // End is placed right after the last token that made it into an op
+    let end = spans.iter().map(|span| span.end).max().unwrap_or(0);
+    ops.push(Ops::End);
+    spans.push(Span { start: end, end });
+    Program { ops, spans }
+}
+
+/// What `,` does to the current cell once the input is exhausted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EofBehavior {
+    #[default]
+    Zero,
+    MinusOne,
+    Unchanged,
+    /// Abort execution with [`RuntimeError::UnexpectedEof`].
+    Error,
+}
+
+impl core::str::FromStr for EofBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EofBehavior, String> {
+        match s {
+            "zero" => Ok(EofBehavior::Zero),
+            "minus-one" => Ok(EofBehavior::MinusOne),
+            "unchanged" => Ok(EofBehavior::Unchanged),
+            "error" => Ok(EofBehavior::Error),
+            _ => Err(format!(
+                "invalid eof behavior '{}', expected zero, minus-one, unchanged or error",
+                s
+            )),
+        }
+    }
+}
+
+/// What happens when the data pointer moves past the end of the tape.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TapeMode {
+    /// Stop with [`RuntimeError::PointerOutOfBounds`].
+    #[default]
+    Fixed,
+    /// Extend the tape with zeroed cells on demand.
+    Grow,
+    /// Continue on the other end of the tape.
+    Wrap,
+    /// Extend the tape with zeroed cells on demand in both directions.
+    Infinite,
+}
+
+impl core::str::FromStr for TapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TapeMode, String> {
+        match s {
+            "fixed" => Ok(TapeMode::Fixed),
+            "grow" => Ok(TapeMode::Grow),
+            "wrap" => Ok(TapeMode::Wrap),
+            "infinite" => Ok(TapeMode::Infinite),
+            _ => Err(format!(
+                "invalid tape mode '{}', expected fixed, grow, wrap or infinite",
+                s
+            )),
+        }
+    }
+}
+
+/// Width of a tape cell, all cell arithmetic wraps around.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellSize {
+    #[default]
+    U8,
+    U16,
+    U32,
+}
+
+impl core::str::FromStr for CellSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CellSize, String> {
+        match s {
+            "8" => Ok(CellSize::U8),
+            "16" => Ok(CellSize::U16),
+            "32" => Ok(CellSize::U32),
+            _ => Err(format!("invalid cell size '{}', expected 8, 16 or 32", s)),
+        }
+    }
+}
+
+/// How the interpreter gets from one op to the next. All but `Match` are
+/// only used for runs that count no ops, have no probe and no pbrain
+/// procedures, the others are matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dispatch {
+    /// A `match` on every op.
+    #[default]
+    Match,
+    /// Translate the ops to the functions that execute them before the run,
+    /// and call one for every op.
+    Threaded,
+    /// Pack the ops into 4 bytes each before the run and match on those.
+    Packed,
+}
+
+impl core::str::FromStr for Dispatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dispatch, String> {
+        match s {
+            "match" => Ok(Dispatch::Match),
+            "threaded" => Ok(Dispatch::Threaded),
+            "packed" => Ok(Dispatch::Packed),
+            _ => Err(format!(
+                "invalid dispatch '{}', expected match, threaded or packed",
+                s
+            )),
+        }
+    }
+}
+
+/// The largest tape [`ExecutionConfigBuilder::tape_size`] accepts, anything
+/// above is most likely a typo.
+pub const MAX_TAPE_SIZE: usize = 1 << 30;
+
+/// Settings for executing a compiled program, created with
+/// [`ExecutionConfig::builder`].
+///
+/// ```
+/// use brainfuck::{compile, EofBehavior, ExecutionConfig, StringInputOutput};
+///
+/// let config = ExecutionConfig::builder()
+///     .tape_size(65536)
+///     .eof(EofBehavior::Zero)
+///     .build();
+/// let ops = compile(",[.,]").unwrap();
+/// let mut in_out = StringInputOutput::with_input("hi");
+/// config.run(&ops, &mut in_out).unwrap();
+/// assert_eq!(in_out.output(), "hi");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ExecutionConfig {
+    eof: EofBehavior,
+    tape: TapeMode,
+    tape_size: usize,
+    cell_size: CellSize,
+    max_ops: Option<u64>,
+    timeout: Option<Duration>,
+    call_depth: usize,
+    dispatch: Dispatch,
+    interrupt: &'static AtomicBool,
+}
+
+/// The flag of a config without [`ExecutionConfigBuilder::interrupt`].
+static NEVER_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+impl Default for ExecutionConfig {
+    fn default() -> ExecutionConfig {
+        ExecutionConfig {
+            eof: EofBehavior::default(),
+            tape: TapeMode::default(),
+            tape_size: 30000,
+            cell_size: CellSize::default(),
+            max_ops: None,
+            timeout: None,
+            call_depth: 1 << 16,
+            dispatch: Dispatch::default(),
+            interrupt: &NEVER_INTERRUPTED,
+        }
+    }
+}
+
+impl ExecutionConfig {
+    pub fn builder() -> ExecutionConfigBuilder {
+        ExecutionConfigBuilder {
+            config: ExecutionConfig::default(),
+        }
+    }
+
+    pub fn eof(&self) -> EofBehavior {
+        self.eof
+    }
+
+    pub fn tape(&self) -> TapeMode {
+        self.tape
+    }
+
+    pub fn tape_size(&self) -> usize {
+        self.tape_size
+    }
+
+    pub fn cell_size(&self) -> CellSize {
+        self.cell_size
+    }
+
+    pub fn max_ops(&self) -> Option<u64> {
+        self.max_ops
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    pub fn dispatch(&self) -> Dispatch {
+        self.dispatch
+    }
+
+    /// Runs `program` on a tape of `tape_size` cells that all start at zero.
+    ///
+    /// Unless the tape is configured to [`TapeMode::Wrap`] or
+    /// [`TapeMode::Infinite`], moving the data pointer left of cell 0, either by
+    /// a `Move` or while scanning in a `SearchZeroCell`, stops execution with
+    /// [`RuntimeError::PointerOutOfBounds`]. Moving right of the last cell does
+    /// the same for [`TapeMode::Fixed`]. Moves are checked after folding, so
+    /// `<>` at cell 0 is fine, even though the [`reference`] interpreter,
+    /// which checks every single move, stops there.
+    ///
+    /// Each op counts once against `max_ops`, a `SearchZeroCell` once per cell
+    /// it visits. The clock is only read every few thousand ops for the
+    /// `timeout`, and a program waiting for input is not interrupted.
+    pub fn run<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+    ) -> Result<(), RuntimeError> {
+        self.run_probed(program, in_out, &mut Memory::new(self), &mut NoProbe)
+    }
+
+    /// Like [`ExecutionConfig::run`], but starts with the cells and the data
+    /// pointer in `memory` and leaves them there, also when the program stops
+    /// with an error. The cell size of `memory` wins over the configured one.
+    /// The `dead_loops` pass assumes that a program starts on a cleared tape,
+    /// disable it for programs that continue where another one stopped.
+    ///
+    /// ```
+    /// use brainfuck::{compile, ExecutionConfig, Memory, StringInputOutput};
+    ///
+    /// let config = ExecutionConfig::default();
+    /// let mut memory = Memory::new(&config);
+    /// let mut in_out = StringInputOutput::new();
+    /// config.run_on(&compile("+++>++").unwrap(), &mut in_out, &mut memory).unwrap();
+    /// config.run_on(&compile("<.").unwrap(), &mut in_out, &mut memory).unwrap();
+    /// assert_eq!(in_out.output(), "\u{3}");
+    /// assert_eq!((memory.pos(), memory.cell(1)), (0, Some(2)));
+    /// ```
+    pub fn run_on<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+    ) -> Result<(), RuntimeError> {
+        self.run_probed(program, in_out, memory, &mut NoProbe)
+    }
+
+    /// Like [`ExecutionConfig::run`], but compiles `program` to machine code
+    /// first, which runs long programs several times as fast. Only with the
+    /// `jit` feature on the hosts Cranelift supports, and machine code counts
+    /// no ops, so with a `max_ops` or `timeout` this is
+    /// [`ExecutionConfig::run`] as well, as it is for programs with pbrain
+    /// procedures or dumps. Output and runtime errors are the same either
+    /// way.
+    pub fn run_jit<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+    ) -> Result<(), RuntimeError> {
+        self.run_jit_on(program, in_out, &mut Memory::new(self))
+    }
+
+    /// [`ExecutionConfig::run_jit`] on `memory`, see [`ExecutionConfig::run_on`].
+    pub fn run_jit_on<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+    ) -> Result<(), RuntimeError> {
+        #[cfg(feature = "jit")]
+        if self.max_ops.is_none() && self.timeout.is_none() {
+            // the machine code calls the I/O through one vtable for every type
+            let mut dyn_in_out = &mut *in_out;
+            let pos = &mut memory.pos;
+            let ran = match &mut memory.cells {
+                Cells::U8(cells) => jit::run(program, self, &mut dyn_in_out, cells, pos),
+                Cells::U16(cells) => jit::run(program, self, &mut dyn_in_out, cells, pos),
+                Cells::U32(cells) => jit::run(program, self, &mut dyn_in_out, cells, pos),
+            };
+            if let Some(result) = ran {
+                return result;
+            }
+        }
+        self.run_on(program, in_out, memory)
+    }
+
+    /// Like [`ExecutionConfig::run`], but counts how often every op was
+    /// executed. The counts are kept if the program stops with an error.
+    pub fn run_profiled<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        profile: &mut Profile,
+    ) -> Result<(), RuntimeError> {
+        profile.reset(program);
+        self.run_probed(program, in_out, &mut Memory::new(self), profile)
+    }
+
+    /// Like [`ExecutionConfig::run`], but logs the ops to `trace` before they
+    /// are executed.
+    #[cfg(feature = "std")]
+    pub fn run_traced<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        trace: &mut Trace<'_>,
+    ) -> Result<(), RuntimeError> {
+        trace.reset(program);
+        self.run_probed(program, in_out, &mut Memory::new(self), trace)
+    }
+
+    /// Like [`ExecutionConfig::run`], but stops at every [`Ops::Break`] and
+    /// lets the `debugger` inspect the tape.
+    #[cfg(feature = "std")]
+    pub fn run_debugged<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        debugger: &mut Debugger<'_>,
+    ) -> Result<(), RuntimeError> {
+        self.run_probed(program, in_out, &mut Memory::new(self), debugger)
+    }
+
+    /// Like [`ExecutionConfig::run`], but calls `observer` before every op.
+    pub fn run_observed<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        observer: &mut dyn Observer,
+    ) -> Result<(), RuntimeError> {
+        let mut observed = Observed(observer);
+        self.run_probed(program, in_out, &mut Memory::new(self), &mut observed)
+    }
+
+    /// Like [`ExecutionConfig::run_on`], but records which cells the program
+    /// reached and wrote in `stats`.
+    #[cfg(feature = "std")]
+    pub fn run_tape_stats<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+        stats: &mut TapeStats,
+    ) -> Result<(), RuntimeError> {
+        stats.reset(memory);
+        let result = self.run_probed(program, in_out, memory, stats);
+        stats.finish(memory);
+        result
+    }
+
+    /// Like [`ExecutionConfig::run`], but instead of reading, every `,`
+    /// suspends the program and returns its state. [`ExecState::resume`]
+    /// continues with the input, so a program can wait for a user without
+    /// blocking a thread. The `timeout` includes the time spent suspended.
+    ///
+    /// ```
+    /// use brainfuck::{compile, Execution, ExecutionConfig, StringInputOutput};
+    ///
+    /// let program = compile(",+.").unwrap();
+    /// let config = ExecutionConfig::default();
+    /// let mut in_out = StringInputOutput::new();
+    /// let state = match config.run_suspending(&program, &mut in_out).unwrap() {
+    ///     Execution::NeedsInput(state) => state,
+    ///     Execution::Finished => unreachable!(),
+    /// };
+    /// let finished = state.resume(&program, Some(b'@'), &mut in_out).unwrap();
+    /// assert!(matches!(finished, Execution::Finished));
+    /// assert_eq!(in_out.output(), "A");
+    /// ```
+    pub fn run_suspending<IO: InputOutput + ?Sized>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+    ) -> Result<Execution, RuntimeError> {
+        ExecState {
+            config: *self,
+            memory: Memory::new(self),
+            run: Run::new(self, true),
+        }
+        .continue_run(program, in_out)
+    }
+
+    /// Like [`Program::outputs`], but with this configuration.
+    pub fn outputs<'a, I: IntoIterator<Item = u8>>(
+        &self,
+        program: &'a Program,
+        input: I,
+    ) -> Outputs<'a, I::IntoIter> {
+        Outputs::new(program, self, input.into_iter())
+    }
+
+    pub(crate) fn run_probed<IO: InputOutput + ?Sized, P: Probe>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+        probe: &mut P,
+    ) -> Result<(), RuntimeError> {
+        let mut run = Run::new(self, false);
+        match self.dispatch_from(program, in_out, memory, probe, &mut run) {
+            Ok(()) => Ok(()),
+            Err(Stop::Error(err)) => Err(err),
+            Err(Stop::Io | Stop::Pause) => unreachable!("only runs that suspend stop early"),
+        }
+    }
+
+    /// Continues `run` until the program ends or stops early.
+    pub(crate) fn dispatch_from<IO: InputOutput + ?Sized, P: Probe>(
+        &self,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+        probe: &mut P,
+        run: &mut Run,
+    ) -> Result<(), Stop> {
+        // counting ops costs measurable time, so it is only compiled in when needed
+        let limited = run.fuel.is_limited();
+        let pos = &mut memory.pos;
+        if self.dispatch != Dispatch::Match
+            && !limited
+            && !P::ENABLED
+            && !run.suspend_io
+            && !program.has_procedures()
+        {
+            let ip = &mut run.ip;
+            let ran = match (&mut memory.cells, self.dispatch) {
+                (Cells::U8(cells), Dispatch::Threaded) => {
+                    threaded::run(program, self, in_out, cells, pos, ip)
+                }
+                (Cells::U16(cells), Dispatch::Threaded) => {
+                    threaded::run(program, self, in_out, cells, pos, ip)
+                }
+                (Cells::U32(cells), Dispatch::Threaded) => {
+                    threaded::run(program, self, in_out, cells, pos, ip)
+                }
+                (cells, _) => {
+                    let packed = Packed::new(program);
+                    match cells {
+                        Cells::U8(cells) => packed::run(&packed, self, in_out, cells, pos, ip),
+                        Cells::U16(cells) => packed::run(&packed, self, in_out, cells, pos, ip),
+                        Cells::U32(cells) => packed::run(&packed, self, in_out, cells, pos, ip),
+                    }
+                }
+            };
+            return ran.map_err(Stop::from);
+        }
+        match (&mut memory.cells, limited) {
+            (Cells::U8(cells), false) => {
+                execute_cells::<u8, false, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+            (Cells::U16(cells), false) => {
+                execute_cells::<u16, false, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+            (Cells::U32(cells), false) => {
+                execute_cells::<u32, false, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+            (Cells::U8(cells), true) => {
+                execute_cells::<u8, true, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+            (Cells::U16(cells), true) => {
+                execute_cells::<u16, true, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+            (Cells::U32(cells), true) => {
+                execute_cells::<u32, true, P, _>(program, in_out, self, probe, cells, pos, run)
+            }
+        }
+    }
+}
+
+/// The tape and the data pointer of a program, which can outlive a single run,
+/// see [`ExecutionConfig::run_on`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory {
+    cells: Cells,
+    pos: usize,
+}
+
+// a vec is much faster than a normal array
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Cells {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Memory {
+    /// `tape_size` zeroed cells of `cell_size`, the data pointer at cell 0.
+    pub fn new(config: &ExecutionConfig) -> Memory {
+        let len = config.tape_size;
+        let cells = match config.cell_size {
+            CellSize::U8 => Cells::U8(vec![0; len]),
+            CellSize::U16 => Cells::U16(vec![0; len]),
+            CellSize::U32 => Cells::U32(vec![0; len]),
+        };
+        Memory { cells, pos: 0 }
+    }
+
+    /// The index of the current cell.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn cell(&self, index: usize) -> Option<u32> {
+        self.tape().cell(index)
+    }
+
+    /// The number of cells, a growing tape may have more than it started with.
+    pub fn len(&self) -> usize {
+        self.tape().len()
+    }
+
+    /// Always false, a tape has at least one cell.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A copy of all cells.
+    pub fn cells(&self) -> Vec<u32> {
+        (0..self.len()).filter_map(|i| self.cell(i)).collect()
+    }
+
+    /// The value of the current cell.
+    pub(crate) fn current(&self) -> u32 {
+        self.tape().cell(self.pos).unwrap_or(0)
+    }
+
+    /// Does what `,` does with `read` at `ip`.
+    pub(crate) fn store_read(
+        &mut self,
+        read: Result<Option<i64>, NotANumber>,
+        eof: EofBehavior,
+        ip: usize,
+    ) -> Result<(), RuntimeError> {
+        let pos = self.pos;
+        match &mut self.cells {
+            Cells::U8(cells) => store_read(&mut cells[pos], read, eof, ip),
+            Cells::U16(cells) => store_read(&mut cells[pos], read, eof, ip),
+            Cells::U32(cells) => store_read(&mut cells[pos], read, eof, ip),
+        }
+    }
+
+    fn tape(&self) -> &dyn Tape {
+        match &self.cells {
+            Cells::U8(cells) => cells,
+            Cells::U16(cells) => cells,
+            Cells::U32(cells) => cells,
+        }
+    }
+}
+
+/// Whether a program of [`ExecutionConfig::run_suspending`] ended or waits
+/// for input.
+// a run is suspended once per input, boxing the state would not save much
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum Execution {
+    Finished,
+    /// Suspended at a `,`.
+    NeedsInput(ExecState),
+}
+
+/// A suspended program: the tape, the data pointer and the `,` it stopped at.
+#[derive(Clone, Debug)]
+pub struct ExecState {
+    config: ExecutionConfig,
+    memory: Memory,
+    run: Run,
+}
+
+impl ExecState {
+    /// The index of the `,` that waits for input.
+    pub fn ip(&self) -> usize {
+        self.run.ip
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Continues `program`, which has to be the one that was suspended, with
+    /// `input` read by the `,`. `None` is the end of the input and does what
+    /// the configured [`EofBehavior`] says.
+    pub fn resume<IO: InputOutput + ?Sized>(
+        mut self,
+        program: &Program,
+        input: Option<u8>,
+        in_out: &mut IO,
+    ) -> Result<Execution, RuntimeError> {
+        self.memory
+            .store_read(Ok(input.map(i64::from)), self.config.eof, self.run.ip)?;
+        self.run.ip += 1;
+        self.continue_run(program, in_out)
+    }
+
+    fn continue_run<IO: InputOutput + ?Sized>(
+        mut self,
+        program: &Program,
+        in_out: &mut IO,
+    ) -> Result<Execution, RuntimeError> {
+        loop {
+            let config = self.config;
+            match config.dispatch_from(
+                program,
+                in_out,
+                &mut self.memory,
+                &mut NoProbe,
+                &mut self.run,
+            ) {
+                Ok(()) => return Ok(Execution::Finished),
+                Err(Stop::Error(err)) => return Err(err),
+                Err(Stop::Io) if program[self.run.ip] == Ops::Read => {
+                    return Ok(Execution::NeedsInput(self))
+                }
+                Err(Stop::Io) => {
+                    in_out.write_value(self.memory.current());
+                    self.run.ip += 1;
+                }
+                Err(Stop::Pause) => unreachable!("the run never pauses"),
+            }
+        }
+    }
+}
+
+/// Looks at every op right before it is executed, execution with a disabled
+/// probe is exactly as fast as without one.
+pub(crate) trait Probe {
+    /// Whether `on_op` is called at all.
+    const ENABLED: bool = true;
+
+    /// `pos` is the index of the current cell of `tape`, an error stops the
+    /// program before the op runs.
+    fn on_op(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError>;
+}
+
+struct NoProbe;
+
+impl Probe for NoProbe {
+    const ENABLED: bool = false;
+
+    fn on_op(&mut self, _: usize, _: Ops, _: usize, _: &dyn Tape) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+}
+
+/// Both probes look at every op, the first one gets to stop the program first.
+impl<A: Probe, B: Probe> Probe for (A, B) {
+    const ENABLED: bool = A::ENABLED || B::ENABLED;
+
+    #[inline(always)]
+    fn on_op(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        if A::ENABLED {
+            self.0.on_op(ip, op, pos, tape)?;
+        }
+        if B::ENABLED {
+            self.1.on_op(ip, op, pos, tape)?;
+        }
+        Ok(())
+    }
+}
+
+/// The cells of the tape, whatever their size.
+pub(crate) trait Tape {
+    fn cell(&self, index: usize) -> Option<u32>;
+    fn len(&self) -> usize;
+}
+
+impl<C: Cell> Tape for Vec<C> {
+    fn cell(&self, index: usize) -> Option<u32> {
+        self.get(index).map(|cell| cell.to_u32())
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+/// Builder for [`ExecutionConfig`], every setting defaults to the behavior of
+/// [`execute`].
+#[derive(Clone, Copy, Debug)]
+pub struct ExecutionConfigBuilder {
+    config: ExecutionConfig,
+}
+
+impl ExecutionConfigBuilder {
+    /// What `,` does once the input is exhausted, defaults to [`EofBehavior::Zero`].
+    pub fn eof(mut self, eof: EofBehavior) -> ExecutionConfigBuilder {
+        self.config.eof = eof;
+        self
+    }
+
+    /// Defaults to [`TapeMode::Fixed`].
+    pub fn tape(mut self, tape: TapeMode) -> ExecutionConfigBuilder {
+        self.config.tape = tape;
+        self
+    }
+
+    /// Number of cells the tape starts with, from 1 to [`MAX_TAPE_SIZE`].
+    /// Defaults to 30000.
+    pub fn tape_size(mut self, tape_size: usize) -> ExecutionConfigBuilder {
+        self.config.tape_size = tape_size;
+        self
+    }
+
+    /// Defaults to [`CellSize::U8`].
+    pub fn cell_size(mut self, cell_size: CellSize) -> ExecutionConfigBuilder {
+        self.config.cell_size = cell_size;
+        self
+    }
+
+    /// Stops with [`RuntimeError::FuelExhausted`] instead of executing more
+    /// than `max_ops` ops, unlimited by default.
+    pub fn max_ops(mut self, max_ops: u64) -> ExecutionConfigBuilder {
+        self.config.max_ops = Some(max_ops);
+        self
+    }
+
+    /// Stops with [`RuntimeError::Timeout`] once the program ran for longer
+    /// than `timeout`, unlimited by default.
+    #[cfg(feature = "std")]
+    pub fn timeout(mut self, timeout: Duration) -> ExecutionConfigBuilder {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Stops with [`RuntimeError::CallDepthExceeded`] instead of nesting more
+    /// than `call_depth` pbrain procedure calls, which would otherwise take
+    /// up memory until none is left. Defaults to 65536.
+    pub fn call_depth(mut self, call_depth: usize) -> ExecutionConfigBuilder {
+        self.config.call_depth = call_depth;
+        self
+    }
+
+    /// Defaults to [`Dispatch::Match`], both run a program the same way.
+    pub fn dispatch(mut self, dispatch: Dispatch) -> ExecutionConfigBuilder {
+        self.config.dispatch = dispatch;
+        self
+    }
+
+    /// Stops with [`RuntimeError::Interrupted`] once `flag` is set, by a Ctrl+C
+    /// handler for example. Loops look at it whenever they jump back to their
+    /// start, a program that is waiting for input is not stopped.
+    pub fn interrupt(mut self, flag: &'static AtomicBool) -> ExecutionConfigBuilder {
+        self.config.interrupt = flag;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// If the tape size is 0 or larger than [`MAX_TAPE_SIZE`].
+    pub fn build(self) -> ExecutionConfig {
+        let tape_size = self.config.tape_size;
+        assert!(
+            (1..=MAX_TAPE_SIZE).contains(&tape_size),
+            "invalid tape size {}, expected a number between 1 and {}",
+            tape_size,
+            MAX_TAPE_SIZE
+        );
+        self.config
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// The data pointer left the tape; `pos` is the cell it tried to reach.
+    PointerOutOfBounds { ip: usize, pos: isize },
+    /// `,` was executed after the input was exhausted.
+    UnexpectedEof { ip: usize },
+    /// The program used up its [`ExecutionConfigBuilder::max_ops`].
+    FuelExhausted { executed: u64 },
+    /// The program ran for longer than [`ExecutionConfigBuilder::timeout`].
+    Timeout { executed: u64 },
+    /// The user quit the [`debugger`] before the op at `ip`.
+    Aborted { ip: usize },
+    /// The loop closed at `ip` found [`ExecutionConfigBuilder::interrupt`]
+    /// set.
+    Interrupted { ip: usize },
+    /// `,` got [`NotANumber`] from [`InputOutput::read_value`].
+    InvalidNumber { ip: usize },
+    /// `,` read a value that does not fit into a cell.
+    NumberOutOfRange { ip: usize },
+    /// A pbrain `:` called procedure `id`, which was not defined yet.
+    UndefinedProcedure { ip: usize, id: u32 },
+    /// A pbrain `:` would nest more than
+    /// [`ExecutionConfigBuilder::call_depth`] calls.
+    CallDepthExceeded { ip: usize, depth: usize },
+}
+
+impl core::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            RuntimeError::PointerOutOfBounds { ip, pos } => write!(
+                f,
+                "pointer moved out of bounds to cell {} at instruction {}",
+                pos, ip
+            ),
+            RuntimeError::UnexpectedEof { ip } => {
+                write!(f, "unexpected end of input at instruction {}", ip)
+            }
+            RuntimeError::FuelExhausted { executed } => {
+                write!(f, "stopped after executing {} instructions", executed)
+            }
+            RuntimeError::Timeout { executed } => {
+                write!(f, "timed out after executing {} instructions", executed)
+            }
+            RuntimeError::Aborted { ip } => write!(f, "aborted at instruction {}", ip),
+            RuntimeError::Interrupted { ip } => write!(f, "interrupted at instruction {}", ip),
+            RuntimeError::InvalidNumber { ip } => {
+                write!(f, "the input is not a number at instruction {}", ip)
+            }
+            RuntimeError::NumberOutOfRange { ip } => write!(
+                f,
+                "the number read at instruction {} does not fit into a cell",
+                ip
+            ),
+            RuntimeError::UndefinedProcedure { ip, id } => write!(
+                f,
+                "call of the undefined procedure {} at instruction {}",
+                id, ip
+            ),
+            RuntimeError::CallDepthExceeded { ip, depth } => {
+                write!(f, "more than {} nested calls at instruction {}", depth, ip)
+            }
+        }
+    }
+}
+
+impl RuntimeError {
+    /// The op that failed, if the error belongs to a single op.
+    pub fn ip(&self) -> Option<usize> {
+        match *self {
+            RuntimeError::PointerOutOfBounds { ip, .. }
+            | RuntimeError::UnexpectedEof { ip }
+            | RuntimeError::Aborted { ip }
+            | RuntimeError::Interrupted { ip }
+            | RuntimeError::InvalidNumber { ip }
+            | RuntimeError::NumberOutOfRange { ip }
+            | RuntimeError::UndefinedProcedure { ip, .. }
+            | RuntimeError::CallDepthExceeded { ip, .. } => Some(ip),
+            RuntimeError::FuelExhausted { .. } | RuntimeError::Timeout { .. } => None,
+        }
+    }
+}
+
+impl core::error::Error for RuntimeError {}
+
+/// Everything that can go wrong in [`run`].
+#[derive(Debug)]
+pub enum Error {
+    /// The program could not be read.
+    #[cfg(feature = "std")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Compile(Vec<CompileError>),
+    Runtime(RuntimeError),
+}
+
+impl From<Vec<CompileError>> for Error {
+    fn from(errors: Vec<CompileError>) -> Error {
+        Error::Compile(errors)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Error {
+        Error::Runtime(err)
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io { path, source } => {
+                write!(f, "cannot open '{}': {}", path, io_message(source))
+            }
+            // one error per line
+            Error::Compile(errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", err)?;
+                }
+                Ok(())
+            }
+            Error::Runtime(err) => err.fmt(f),
+        }
+    }
+}
+
+/// The message of `err` without the " (os error N)" that std appends.
+#[cfg(feature = "std")]
+fn io_message(err: &std::io::Error) -> String {
+    let message = err.to_string();
+    match err.raw_os_error() {
+        Some(code) => match message.strip_suffix(&format!(" (os error {})", code)) {
+            Some(stripped) => stripped.to_string(),
+            None => message,
+        },
+        None => message,
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io { source, .. } => Some(source),
+            Error::Compile(_) => None,
+            Error::Runtime(err) => Some(err),
+        }
+    }
+}
+
+/// Runs `program` with the default [`ExecutionConfig`]. The interpreter is
+/// compiled for the type of `in_out`, so that the calls to it can be
+/// inlined, see [`execute_dyn`] for one that is only known at runtime.
+pub fn execute<IO: InputOutput>(program: &Program, in_out: &mut IO) -> Result<(), RuntimeError> {
+    let config = ExecutionConfig::default();
+    config.run_probed(program, in_out, &mut Memory::new(&config), &mut NoProbe)
+}
+
+/// Like [`execute`], with the one interpreter for every `InputOutput`
+/// behind a reference, for I/O that is chosen at runtime or crosses the C
+/// FFI.
+pub fn execute_dyn(program: &Program, in_out: &mut dyn InputOutput) -> Result<(), RuntimeError> {
+    ExecutionConfig::default().run(program, in_out)
+}
+
+/// Like [`execute`], but as machine code if possible, see
+/// [`ExecutionConfig::run_jit`].
+pub fn execute_jit<IO: InputOutput + ?Sized>(
+    program: &Program,
+    in_out: &mut IO,
+) -> Result<(), RuntimeError> {
+    ExecutionConfig::default().run_jit(program, in_out)
+}
+
+/// Like [`execute`], but calls `observer` before every op, see
+/// [`ExecutionConfig::run_observed`].
+pub fn execute_with_observer<IO: InputOutput + ?Sized>(
+    program: &Program,
+    in_out: &mut IO,
+    observer: &mut dyn Observer,
+) -> Result<(), RuntimeError> {
+    ExecutionConfig::default().run_observed(program, in_out, observer)
+}
+
+/// A tape cell of one of the widths in [`CellSize`].
+trait Cell: Copy + Default + PartialEq {
+    const MAX: u32;
+    /// Truncates `value` to the cell width.
+    fn from_u32(value: u32) -> Self;
+    fn to_u32(self) -> u32;
+    fn wrapping_add_i32(self, delta: i32) -> Self;
+    /// `self + value * factor`
+    fn wrapping_add_mul(self, value: Self, factor: i32) -> Self;
+    /// The index of the first zero in `cells`.
+    fn find_zero(cells: &[Self]) -> Option<usize>;
+    /// The index of the last zero in `cells`.
+    fn rfind_zero(cells: &[Self]) -> Option<usize>;
+}
+
+macro_rules! impl_cell {
+    ($($t:ty: $find:path, $rfind:path),*) => {$(
+        impl Cell for $t {
+            const MAX: u32 = <$t>::MAX as u32;
+            fn from_u32(value: u32) -> $t {
+                value as $t
+            }
+            fn to_u32(self) -> u32 {
+                self as u32
+            }
+            fn wrapping_add_i32(self, delta: i32) -> $t {
+                // truncating the delta keeps it correct modulo the cell width
+                self.wrapping_add(delta as $t)
+            }
+            fn wrapping_add_mul(self, value: $t, factor: i32) -> $t {
+                self.wrapping_add(value.wrapping_mul(factor as $t))
+            }
+            fn find_zero(cells: &[$t]) -> Option<usize> {
+                $find(cells)
+            }
+            fn rfind_zero(cells: &[$t]) -> Option<usize> {
+                $rfind(cells)
+            }
+        }
+    )*};
+}
+
+impl_cell!(
+    u8: find_zero_byte, rfind_zero_byte,
+    u16: find_zero, rfind_zero,
+    u32: find_zero, rfind_zero
+);
+
+fn find_zero<C: Cell>(cells: &[C]) -> Option<usize> {
+    cells.iter().position(|&cell| cell == C::default())
+}
+
+fn rfind_zero<C: Cell>(cells: &[C]) -> Option<usize> {
+    cells.iter().rposition(|&cell| cell == C::default())
+}
+
+// bytes are searched many at once
+fn find_zero_byte(cells: &[u8]) -> Option<usize> {
+    memchr::memchr(0, cells)
+}
+
+fn rfind_zero_byte(cells: &[u8]) -> Option<usize> {
+    memchr::memrchr(0, cells)
+}
+
+/// How many ops run between two looks at the clock, reading it is far more
+/// expensive than executing an op.
+const TIMEOUT_CHECK_INTERVAL: u64 = 1 << 16;
+
+/// Why the dispatch loop returned before the program ended.
+pub(crate) enum Stop {
+    Error(RuntimeError),
+    /// At a `,` or `.` of a run that suspends there, the caller does the
+    /// input or output and continues after the op.
+    Io,
+    /// [`Fuel::pause_after`] ran out, the caller continues at the same op.
+    Pause,
+}
+
+impl From<RuntimeError> for Stop {
+    fn from(err: RuntimeError) -> Stop {
+        Stop::Error(err)
+    }
+}
+
+/// Where a run stands between two calls of the dispatch loop.
+#[derive(Clone, Debug)]
+pub(crate) struct Run {
+    /// The op to execute next.
+    pub(crate) ip: usize,
+    pub(crate) fuel: Fuel,
+    /// Whether the loop stops with [`Stop::Io`] instead of using the
+    /// [`InputOutput`].
+    pub(crate) suspend_io: bool,
+    pub(crate) calls: Calls,
+}
+
+/// The procedures a pbrain program defined so far and the calls that did not
+/// return yet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Calls {
+    /// The ip of the `ProcDefStart` of every procedure, by number.
+    pub(crate) procedures: BTreeMap<u32, usize>,
+    /// The ip of every `Call` that did not return yet, the innermost last.
+    pub(crate) stack: Vec<usize>,
+}
+
+impl Run {
+    pub(crate) fn new(config: &ExecutionConfig, suspend_io: bool) -> Run {
+        Run {
+            ip: 0,
+            fuel: Fuel::new(config),
+            suspend_io,
+            calls: Calls::default(),
+        }
+    }
+}
+
+/// Counts executed ops and enforces `max_ops` and `timeout`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Fuel {
+    executed: u64,
+    /// The next count at which the limits have to be looked at.
+    next_check: u64,
+    max_ops: u64,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+    /// The count at which the loop stops with [`Stop::Pause`].
+    pause_at: u64,
+}
+
+impl Fuel {
+    fn new(config: &ExecutionConfig) -> Fuel {
+        let mut fuel = Fuel {
+            executed: 0,
+            next_check: 0,
+            max_ops: config.max_ops.unwrap_or(u64::MAX),
+            #[cfg(feature = "std")]
+            deadline: config.timeout.map(|timeout| Instant::now() + timeout),
+            pause_at: u64::MAX,
+        };
+        fuel.schedule();
+        fuel
+    }
+
+    /// Whether the ops have to be counted at all.
+    fn is_limited(&self) -> bool {
+        self.max_ops != u64::MAX || self.has_deadline() || self.pause_at != u64::MAX
+    }
+
+    fn has_deadline(&self) -> bool {
+        #[cfg(feature = "std")]
+        return self.deadline.is_some();
+        #[cfg(not(feature = "std"))]
+        false
+    }
+
+    /// Stops the loop before the op `ops` ops from now. A scan is not
+    /// interrupted, the pause waits for the op after it.
+    pub(crate) fn pause_after(&mut self, ops: u64) {
+        self.pause_at = self.executed.saturating_add(ops);
+        self.schedule();
+    }
+
+    pub(crate) fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Continues counting at `executed`, as if that many ops had run.
+    pub(crate) fn set_executed(&mut self, executed: u64) {
+        self.executed = executed.min(self.max_ops);
+        self.schedule();
+    }
+
+    /// Counts one op, `at_op` is false for the steps of a scan.
+    #[inline(always)]
+    fn tick(&mut self, at_op: bool) -> Result<(), Stop> {
+        if self.executed == self.next_check {
+            self.check(at_op)?;
+        }
+        self.executed += 1;
+        Ok(())
+    }
+
+    #[cold]
+    fn check(&mut self, at_op: bool) -> Result<(), Stop> {
+        let executed = self.executed;
+        if executed == self.max_ops {
+            return Err(RuntimeError::FuelExhausted { executed }.into());
+        }
+        #[cfg(feature = "std")]
+        if matches!(self.deadline, Some(deadline) if Instant::now() >= deadline) {
+            return Err(RuntimeError::Timeout { executed }.into());
+        }
+        if at_op && executed >= self.pause_at {
+            self.pause_at = u64::MAX;
+            self.schedule();
+            return Err(Stop::Pause);
+        }
+        self.schedule();
+        Ok(())
+    }
+
+    fn schedule(&mut self) {
+        let next_check = if self.has_deadline() {
+            self.max_ops.min(self.executed + TIMEOUT_CHECK_INTERVAL)
+        } else {
+            self.max_ops
+        };
+        // a pause that is due during a scan is looked at on every step after it
+        self.next_check = next_check.min(self.pause_at.max(self.executed + 1));
+    }
+}
+
+/// Called whenever the data pointer ended up outside of `memory`, returns the
+/// position execution continues at.
+#[cold]
+fn outside_tape<C: Cell>(
+    memory: &mut Vec<C>,
+    pos: usize,
+    ip: usize,
+    tape: TapeMode,
+) -> Result<usize, RuntimeError> {
+    match tape {
+        TapeMode::Grow | TapeMode::Infinite if (pos as isize) >= 0 => {
+            memory.resize(core::cmp::max(pos + 1, memory.len() * 2), C::default());
+            Ok(pos)
+        }
+        TapeMode::Infinite => {
+            // prepend zeroed cells, which shifts every existing cell to the right
+            let extra = core::cmp::max(pos.wrapping_neg(), memory.len());
+            memory.splice(0..0, core::iter::repeat_n(C::default(), extra));
+            Ok(pos.wrapping_add(extra))
+        }
+        TapeMode::Wrap => Ok((pos as isize).rem_euclid(memory.len() as isize) as usize),
+        _ => Err(RuntimeError::PointerOutOfBounds {
+            ip,
+            pos: pos as isize,
+        }),
+    }
+}
+
+/// Index of the cell `offset` cells away from the data pointer, growing or
+/// wrapping the tape if necessary.
+#[inline(always)]
+fn cell_at<C: Cell>(
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    offset: isize,
+    ip: usize,
+    tape: TapeMode,
+) -> Result<usize, RuntimeError> {
+    let target = pos.wrapping_add(offset as usize);
+    if target < memory.len() {
+        return Ok(target);
+    }
+    let len = memory.len();
+    let moved = outside_tape(memory, target, ip, tape)?;
+    if (target as isize) < 0 {
+        // an infinite tape grew to the left and shifted all cells
+        *pos += memory.len() - len;
+    }
+    Ok(moved)
+}
+
+fn execute_cells<C: Cell, const LIMITED: bool, P: Probe, IO: InputOutput + ?Sized>(
+    ops: &[Ops],
+    in_out: &mut IO,
+    config: &ExecutionConfig,
+    probe: &mut P,
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    run: &mut Run,
+) -> Result<(), Stop> {
+    // Stores to the cells may alias the length and pointer of a vec behind a
+    // reference, so the loop works on local copies that stay in registers.
+    let mut cells = core::mem::take(memory);
+    let mut current = *pos;
+    let mut ip = run.ip;
+    let mut fuel = run.fuel;
+    let result = execute_loop::<C, LIMITED, P, IO>(
+        ops,
+        in_out,
+        config,
+        probe,
+        &mut cells,
+        &mut current,
+        &mut ip,
+        &mut fuel,
+        run.suspend_io,
+        &mut run.calls,
+    );
+    *memory = cells;
+    *pos = current;
+    run.ip = ip;
+    run.fuel = fuel;
+    result
+}
+
+/// Does what `SearchZeroCell` does for a `step` of 1 or -1, but searches all
+/// cells up to the end of the tape at once instead of one after the other.
+#[inline(always)]
+fn scan_by_one<C: Cell>(
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    step: isize,
+    ip: usize,
+    tape: TapeMode,
+) -> Result<(), RuntimeError> {
+    loop {
+        let found = match step {
+            1 => C::find_zero(&memory[*pos..]).map(|i| *pos + i),
+            _ => C::rfind_zero(&memory[..=*pos]),
+        };
+        if let Some(found) = found {
+            *pos = found;
+            return Ok(());
+        }
+        // a wrapping tape goes on at the other end, a growing one with a zero
+        *pos = match step {
+            1 => memory.len(),
+            _ => usize::MAX,
+        };
+        *pos = outside_tape(memory, *pos, ip, tape)?;
+    }
+}
+
+/// Fails if the loop closed at `ip` has to stop instead of jumping back.
+#[inline(always)]
+fn check_interrupt(interrupt: &AtomicBool, ip: usize) -> Result<(), RuntimeError> {
+    match interrupt.load(Ordering::Relaxed) {
+        true => Err(RuntimeError::Interrupted { ip }),
+        false => Ok(()),
+    }
+}
+
+/// Does what `,` does with `read`.
+#[inline(always)]
+fn store_read<C: Cell>(
+    cell: &mut C,
+    read: Result<Option<i64>, NotANumber>,
+    eof: EofBehavior,
+    ip: usize,
+) -> Result<(), RuntimeError> {
+    match (read, eof) {
+        (Ok(Some(value)), _) if (0..=i64::from(C::MAX)).contains(&value) => {
+            *cell = C::from_u32(value as u32)
+        }
+        (Ok(Some(_)), _) => return Err(RuntimeError::NumberOutOfRange { ip }),
+        (Err(NotANumber), _) => return Err(RuntimeError::InvalidNumber { ip }),
+        (Ok(None), EofBehavior::Zero) => *cell = C::default(),
+        (Ok(None), EofBehavior::MinusOne) => *cell = C::default().wrapping_add_i32(-1),
+        (Ok(None), EofBehavior::Unchanged) => {}
+        (Ok(None), EofBehavior::Error) => return Err(RuntimeError::UnexpectedEof { ip }),
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+fn execute_loop<C: Cell, const LIMITED: bool, P: Probe, IO: InputOutput + ?Sized>(
+    ops: &[Ops],
+    in_out: &mut IO,
+    config: &ExecutionConfig,
+    probe: &mut P,
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    next: &mut usize,
+    fuel: &mut Fuel,
+    suspend_io: bool,
+    calls: &mut Calls,
+) -> Result<(), Stop> {
+    let zero = C::default();
+    let interrupt = config.interrupt;
+    // a local counter is faster than one behind the reference, which is only
+    // updated when the loop returns
+    let mut ip = *next;
+    macro_rules! stop_on_err {
+        ($result:expr) => {
+            match $result {
+                Ok(value) => value,
+                Err(err) => {
+                    *next = ip;
+                    return Err(err.into());
+                }
+            }
+        };
+    }
+
+    macro_rules! move_by {
+        ($offset:expr) => {{
+            // a move below 0 wraps around to a huge value, so a single
+            // comparison catches both ends of the tape
+            *pos = pos.wrapping_add($offset as usize);
+            if *pos >= memory.len() {
+                *pos = stop_on_err!(outside_tape(memory, *pos, ip, config.tape));
+            }
+        }};
+    }
+
+    // running past the last op ends the program as well, so slices without a
+    // trailing `End` are fine
+    while let Some(&op) = ops.get(ip) {
+        if LIMITED {
+            stop_on_err!(fuel.tick(true));
+        }
+        if P::ENABLED {
+            stop_on_err!(probe.on_op(ip, op, *pos, memory));
+        }
+        match op {
+            Ops::Move(val) => move_by!(val),
+            Ops::Mod(val) => memory[*pos] = memory[*pos].wrapping_add_i32(val),
+            Ops::ModAt(offset, val) => {
+                let target = stop_on_err!(cell_at(memory, pos, offset, ip, config.tape));
+                memory[target] = memory[target].wrapping_add_i32(val);
+            }
+            Ops::AddTo(offset, factor) => {
+                let value = memory[*pos];
+                // the loop this replaces never touches other cells if it is skipped
+                if value != zero {
+                    let target = stop_on_err!(cell_at(memory, pos, offset, ip, config.tape));
+                    memory[target] = memory[target].wrapping_add_mul(value, factor);
+                }
+            }
+            Ops::LoopOpen(end) => {
+                if memory[*pos] == zero {
+                    ip = end;
+                }
+            }
+            Ops::BalancedLoop(end, _, _) if memory[*pos] == zero => ip = end,
+            // only without counting every step, as there are no checks
+            // for the fuel either
+            Ops::BalancedLoop(_, min, max)
+                if !LIMITED
+                    && !P::ENABLED
+                    && !suspend_io
+                    && *pos >= min.unsigned_abs() as usize
+                    && (max as usize) < memory.len() - *pos =>
+            {
+                stop_on_err!(balanced_loop(ops, &mut ip, memory, pos, in_out, config))
+            }
+            Ops::BalancedLoop(..) => {}
+            Ops::LoopClose(start) => {
+                if memory[*pos] != zero {
+                    stop_on_err!(check_interrupt(interrupt, ip));
+                    ip = start;
+                }
+            }
+            Ops::MoveClose(val, start) => {
+                move_by!(val);
+                if memory[*pos] != zero {
+                    stop_on_err!(check_interrupt(interrupt, ip));
+                    ip = start;
+                }
+            }
+            Ops::ModMove(val, offset) => {
+                memory[*pos] = memory[*pos].wrapping_add_i32(val);
+                move_by!(offset);
+            }
+            Ops::If(end) => {
+                if memory[*pos] == zero {
+                    ip = end;
+                }
+            }
+            Ops::SetCell(value) => memory[*pos] = C::from_u32(value),
+            // only without counting every step
+            Ops::SearchZeroCell(step @ (1 | -1)) if !LIMITED => {
+                stop_on_err!(scan_by_one(memory, pos, step, ip, config.tape))
+            }
+            Ops::SearchZeroCell(step) => {
+                while memory[*pos] != zero {
+                    // every step counts, a scan that never finds a zero must run out of fuel
+                    if LIMITED {
+                        stop_on_err!(fuel.tick(false));
+                    }
+                    move_by!(step);
+                }
+            }
+            Ops::Print | Ops::Read if suspend_io => {
+                *next = ip;
+                return Err(Stop::Io);
+            }
+            Ops::Print => in_out.write_value(memory[*pos].to_u32()),
+            Ops::Read => stop_on_err!(store_read(
+                &mut memory[*pos],
+                in_out.read_value(),
+                config.eof,
+                ip
+            )),
+            Ops::Break => {}
+            Ops::Dump => dump_cells(in_out, ip, *pos, memory),
+            Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call => {
+                let cell = memory[*pos].to_u32();
+                ip = stop_on_err!(procedure_op(op, ip, cell, calls, config.call_depth));
+            }
+            Ops::End => break,
+        };
+        ip += 1;
+    }
+    *next = ip;
+    Ok(())
+}
+
+/// Runs the `BalancedLoop` at `ip` until it ends, which leaves `ip` at its
+/// close, with a current cell that is not zero and every cell the loop
+/// reaches on the tape. `Program::new` checked that its body moves by fixed
+/// offsets only and reaches no cells outside of its range, so unlike
+/// `execute_loop` this does not check the position.
+#[inline(never)]
+fn balanced_loop<C: Cell, IO: InputOutput + ?Sized>(
+    ops: &[Ops],
+    ip: &mut usize,
+    memory: &mut [C],
+    pos: &mut usize,
+    in_out: &mut IO,
+    config: &ExecutionConfig,
+) -> Result<(), RuntimeError> {
+    let zero = C::default();
+    let open = *ip;
+    let mut at = *pos;
+    let mut i = open + 1;
+    macro_rules! cell {
+        ($offset:expr) => {
+            // SAFETY: the offsets of the body are in the range of the loop,
+            // which is on the tape around the cell the loop started at, and
+            // every run of the body ends at that cell again
+            *unsafe { memory.get_unchecked_mut(at.wrapping_add($offset as usize)) }
+        };
+    }
+    loop {
+        match ops[i] {
+            Ops::Move(offset) => at = at.wrapping_add(offset as usize),
+            Ops::Mod(value) => cell!(0) = cell!(0).wrapping_add_i32(value),
+            Ops::ModAt(offset, value) => cell!(offset) = cell!(offset).wrapping_add_i32(value),
+            Ops::AddTo(offset, factor) => {
+                let value = cell!(0);
+                if value != zero {
+                    cell!(offset) = cell!(offset).wrapping_add_mul(value, factor);
+                }
+            }
+            Ops::LoopOpen(end) | Ops::BalancedLoop(end, _, _) => {
+                if cell!(0) == zero {
+                    i = end;
+                }
+            }
+            Ops::LoopClose(start) | Ops::MoveClose(_, start) => {
+                if let Ops::MoveClose(offset, _) = ops[i] {
+                    at = at.wrapping_add(offset as usize);
+                }
+                if cell!(0) != zero {
+                    if let Err(err) = check_interrupt(config.interrupt, i) {
+                        *ip = i;
+                        *pos = at;
+                        return Err(err);
+                    }
+                    i = start;
+                } else if start == open {
+                    break;
+                }
+            }
+            Ops::ModMove(value, offset) => {
+                cell!(0) = cell!(0).wrapping_add_i32(value);
+                at = at.wrapping_add(offset as usize);
+            }
+            Ops::SetCell(value) => cell!(0) = C::from_u32(value),
+            Ops::Print => in_out.write_value(cell!(0).to_u32()),
+            Ops::Read => {
+                if let Err(err) = store_read(&mut cell!(0), in_out.read_value(), config.eof, i) {
+                    *ip = i;
+                    *pos = at;
+                    return Err(err);
+                }
+            }
+            Ops::Break => {}
+            Ops::Dump => dump_cells(in_out, i, at, memory),
+            op => unreachable!("{:?} is not in the body of a balanced loop", op),
+        }
+        i += 1;
+    }
+    *ip = i;
+    *pos = at;
+    Ok(())
+}
+
+#[cold]
+#[inline(never)]
+fn dump_cells<C: Cell, IO: InputOutput + ?Sized>(
+    in_out: &mut IO,
+    ip: usize,
+    pos: usize,
+    memory: &[C],
+) {
+    in_out.dump(&CellDump::new(ip, pos, memory));
+}
+
+/// Runs a pbrain op on the current `cell` and returns the ip of the op that
+/// comes before the next one. Kept out of `execute_loop`, which is faster
+/// without these rare ops.
+#[cold]
+#[inline(never)]
+fn procedure_op(
+    op: Ops,
+    ip: usize,
+    cell: u32,
+    calls: &mut Calls,
+    call_depth: usize,
+) -> Result<usize, RuntimeError> {
+    match op {
+        Ops::ProcDefStart(end) => {
+            calls.procedures.insert(cell, ip);
+            Ok(end)
+        }
+        // a procedure is only entered by a call
+        Ops::ProcDefEnd => Ok(calls.stack.pop().unwrap_or(ip)),
+        Ops::Call => {
+            let start = *calls
+                .procedures
+                .get(&cell)
+                .ok_or(RuntimeError::UndefinedProcedure { ip, id: cell })?;
+            if calls.stack.len() >= call_depth {
+                let depth = call_depth;
+                return Err(RuntimeError::CallDepthExceeded { ip, depth });
+            }
+            calls.stack.push(ip);
+            Ok(start)
+        }
+        _ => unreachable!("{:?} is no pbrain op", op),
+    }
+}
+
+/// Reads the program in `filename` and compiles it with `pipeline`, as Ook!
+/// if the file ends in `.ook`.
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub fn compile_file(filename: &str, pipeline: &Pipeline) -> Result<Program, Error> {
+    let extensions = Extensions {
+        language: Language::from_path(filename),
+        ..Extensions::default()
+    };
+    Ok(compile_extended(
+        &read_source(filename)?,
+        &extensions,
+        pipeline,
+    )?)
+}
+
+/// Splits `source` at the first `!` into the code and the input of the
+/// program, a convention of dbfi and many golfed programs. The first `!`
+/// splits, even inside a comment loop, but not the one of a `#!` line. The
+/// input is everything after it, including a final newline.
+///
+/// ```
+/// assert_eq!(brainfuck::split_input(",[.,]!hi\n"), (",[.,]", Some("hi\n")));
+/// assert_eq!(brainfuck::split_input("+."), ("+.", None));
+/// ```
+pub fn split_input(source: &str) -> (&str, Option<&str>) {
+    let shebang = shebang_len(source);
+    match source[shebang..].find('!') {
+        Some(bang) => (
+            &source[..shebang + bang],
+            Some(&source[shebang + bang + 1..]),
+        ),
+        None => (source, None),
+    }
+}
+
+/// Reads the program in `filename`.
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub fn read_source(filename: &str) -> Result<String, Error> {
+    std::fs::read_to_string(filename).map_err(|source| Error::Io {
+        path: filename.into(),
+        source,
+    })
+}
+
+/// Reads a program from `reader` up to its end, like stdin. `name` stands in
+/// for the path in errors.
+#[cfg(feature = "std")]
+pub fn read_source_from(mut reader: impl Read, name: &str) -> Result<String, Error> {
+    let mut source = String::new();
+    match reader.read_to_string(&mut source) {
+        Ok(_) => Ok(source),
+        Err(source) => Err(Error::Io {
+            path: name.into(),
+            source,
+        }),
+    }
+}
+
+/// Reads the program in `filename` and runs it with [`run_source`].
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub fn run<IO: InputOutput + ?Sized>(
+    filename: &str,
+    in_out: &mut IO,
+    pipeline: &Pipeline,
+    config: &ExecutionConfig,
+) -> Result<(), Error> {
+    run_source(&read_source(filename)?, in_out, pipeline, config)
+}
+
+/// Compiles `source` with `pipeline` and runs it, a program that does not
+/// compile is [`Error::Compile`] and one that fails is [`Error::Runtime`].
+///
+/// ```
+/// use brainfuck::{optimizer::Pipeline, run_source, Error, ExecutionConfig, StringInputOutput};
+///
+/// let (pipeline, config) = (Pipeline::default(), ExecutionConfig::default());
+/// let mut in_out = StringInputOutput::new();
+/// run_source("++++++++[>++++++++<-]>+.", &mut in_out, &pipeline, &config).unwrap();
+/// assert_eq!(in_out.output(), "A");
+/// let err = run_source("[", &mut in_out, &pipeline, &config).unwrap_err();
+/// assert!(matches!(err, Error::Compile(_)));
+/// ```
+pub fn run_source<IO: InputOutput + ?Sized>(
+    source: &str,
+    in_out: &mut IO,
+    pipeline: &Pipeline,
+    config: &ExecutionConfig,
+) -> Result<(), Error> {
+    let program = compile_with(source, pipeline)?;
+    Ok(config.run(&program, in_out)?)
+}
+
+/// Runs `source` without input and returns its output, one char per byte
+/// like [`StringInputOutput`].
+pub fn run_to_string(source: &str) -> Result<String, Error> {
+    run_to_string_with_input(source, "")
+}
+
+/// Like [`run_to_string`], `,` reads the chars of `input`.
+///
+/// ```
+/// assert_eq!(brainfuck::run_to_string_with_input(",[.,]", "cat").unwrap(), "cat");
+/// ```
+pub fn run_to_string_with_input(source: &str, input: &str) -> Result<String, Error> {
+    let mut in_out = StringInputOutput::with_input(input);
+    run_source(
+        source,
+        &mut in_out,
+        &Pipeline::default(),
+        &ExecutionConfig::default(),
+    )?;
+    Ok(in_out.into_output())
+}
+
+/// Like [`run_to_string_with_input`], but for bytes.
+#[cfg(feature = "std")]
+pub fn run_to_bytes(source: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut in_out = IoAdapter::new(input, Vec::new());
+    run_source(
+        source,
+        &mut in_out,
+        &Pipeline::default(),
+        &ExecutionConfig::default(),
+    )?;
+    Ok(in_out.into_output())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use optimizer::OptLevel;
+
+    /// Runs `X.bf` with every dispatch and compares the output with `X.out`,
+    /// `,` reads `X.in` if there is one. Returns whether there is an `X.out`.
+    fn check_program_output(path: &std::path::Path) -> bool {
+        // twice what mandelbrot needs, a program that hangs fails instead
+        let limited = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .max_ops(4_000_000_000)
+            .build();
+        // the other dispatches count no ops, but the limited run shows that the
+        // program ends
+        let unlimited = ExecutionConfig::builder().eof(EofBehavior::MinusOne);
+        let threaded = unlimited.dispatch(Dispatch::Threaded).build();
+        let packed = unlimited.dispatch(Dispatch::Packed).build();
+        let name = path.display();
+        let expected = match std::fs::read(path.with_extension("out")) {
+            Ok(expected) => expected,
+            Err(_) => return false,
+        };
+        let input = std::fs::read(path.with_extension("in")).unwrap_or_default();
+        let program = compile_file(path.to_str().unwrap(), &Pipeline::default())
+            .unwrap_or_else(|err| panic!("{}: {}", name, err));
+        for config in [limited, threaded, packed] {
+            let mut in_out = IoAdapter::new(&input[..], Vec::new());
+            if let Err(err) = config.run(&program, &mut in_out) {
+                panic!("{} with {:?}: {}", name, config.dispatch, err);
+            }
+            let output = in_out.into_output();
+            if output != expected {
+                let offset = output
+                    .iter()
+                    .zip(&expected)
+                    .position(|(byte, expected)| byte != expected)
+                    .unwrap_or_else(|| output.len().min(expected.len()));
+                panic!(
+                    "{} with {:?}: the output differs from the .out at byte {}, {} bytes instead of {}",
+                    name,
+                    config.dispatch,
+                    offset,
+                    output.len(),
+                    expected.len()
+                );
+            }
+        }
+        true
+    }
+
+    /// Checks every program in `programs` that has an `X.out`, adding a
+    /// program with its output is all it takes to test it. Mandelbrot is
+    /// left to its own test.
+    #[test]
+    fn program_outputs() {
+        let mut paths: Vec<_> = std::fs::read_dir("programs")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some("bf".as_ref()))
+            .filter(|path| !path.ends_with("mandelbrot.bf"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            if !check_program_output(&path) {
+                eprintln!("skipping {}, there is no .out", path.display());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        ignore = "slow in a debug build, run with cargo test --release"
+    )]
+    fn mandelbrot() {
+        assert!(check_program_output("programs/mandelbrot.bf".as_ref()));
+    }
+
+    #[test]
+    fn small_programs() {
+        let source = |name| std::fs::read_to_string(format!("programs/{}.bf", name)).unwrap();
+        let config = ExecutionConfig::default();
+
+        // `[<]` is a scan
+        let hello = source("hello");
+        let program = compile(&hello).unwrap();
+        assert!(program.contains(&Ops::SearchZeroCell(-1)));
+        assert_eq!(run_str(&hello, "", &config), Ok("Hello World!\n".into()));
+
+        // `[-]` clears the cell, and the store after it is folded in
+        let counter = source("counter");
+        let program = compile(&counter).unwrap();
+        assert!(program.contains(&Ops::SetCell(10)) && program.contains(&Ops::SetCell(0)));
+        let unoptimized = compile_with(&counter, &Pipeline::new(OptLevel::O0)).unwrap();
+        assert!(!unoptimized.iter().any(|op| matches!(op, Ops::SetCell(_))));
+        assert_eq!(run_str(&counter, "", &config), Ok("123\n123\n123\n".into()));
+
+        // the read path, the cat in programs stops at an EOF of minus one and
+        // passes zeros, the classic one stops at an EOF of zero
+        let cat = source("cat");
+        let input = "Hello, cat!\n\tand a \u{0} byte";
+        assert_eq!(run_with_eof(&cat, input, EofBehavior::MinusOne), input);
+        assert_eq!(
+            run_str(",[.,]", "Hello, cat!\n", &config),
+            Ok("Hello, cat!\n".into())
+        );
+        assert_eq!(
+            run_with_eof(&source("rot13"), "Cat!\n", EofBehavior::MinusOne),
+            "Png!\n"
+        );
+    }
+
+    fn run_str(
+        source: &str,
+        input: &str,
+        config: &ExecutionConfig,
+    ) -> Result<String, RuntimeError> {
+        let mut in_out = StringInputOutput::with_input(input);
+        match run_source(source, &mut in_out, &Pipeline::default(), config) {
+            Ok(()) => Ok(in_out.output),
+            Err(Error::Runtime(err)) => Err(err),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn run_with_eof(source: &str, input: &str, eof: EofBehavior) -> String {
+        let config = ExecutionConfig::builder().eof(eof).build();
+        run_str(source, input, &config).unwrap()
+    }
+
+    #[test]
+    fn eof_behavior() {
+        assert_eq!(run_with_eof("+,.", "", EofBehavior::Zero), "\0");
+        assert_eq!(run_with_eof("+,.", "", EofBehavior::MinusOne), "\u{ff}");
+        assert_eq!(run_with_eof("+,.", "", EofBehavior::Unchanged), "\u{1}");
+    }
+
+    fn suspended(execution: Execution) -> ExecState {
+        match execution {
+            Execution::NeedsInput(state) => state,
+            Execution::Finished => panic!("the program did not wait for input"),
+        }
+    }
+
+    #[test]
+    fn suspending() {
+        let program = compile(",+.").unwrap();
+        let mut in_out = StringInputOutput::new();
+        let state = suspended(
+            ExecutionConfig::default()
+                .run_suspending(&program, &mut in_out)
+                .unwrap(),
+        );
+        assert_eq!(state.ip(), 0);
+        let finished = state.resume(&program, Some(64), &mut in_out).unwrap();
+        assert!(matches!(finished, Execution::Finished));
+        assert_eq!(in_out.output(), "A");
+
+        // the output before a read is written, and the end of the input follows
+        // the eof behavior
+        let program = compile("+++.>,.<,.").unwrap();
+        for (eof, expected) in [
+            (EofBehavior::Zero, "\u{3}a\0"),
+            (EofBehavior::MinusOne, "\u{3}a\u{ff}"),
+            (EofBehavior::Unchanged, "\u{3}a\u{3}"),
+        ] {
+            let config = ExecutionConfig::builder().eof(eof).build();
+            let mut in_out = StringInputOutput::new();
+            let state = suspended(config.run_suspending(&program, &mut in_out).unwrap());
+            assert_eq!(in_out.output(), "\u{3}");
+            // `+++` is a single op
+            assert_eq!((state.ip(), state.memory().pos()), (3, 1));
+            let state = suspended(state.resume(&program, Some(b'a'), &mut in_out).unwrap());
+            assert!(matches!(
+                state.resume(&program, None, &mut in_out).unwrap(),
+                Execution::Finished
+            ));
+            assert_eq!(in_out.output(), expected);
+        }
+        let config = ExecutionConfig::builder().eof(EofBehavior::Error).build();
+        let state = suspended(config.run_suspending(&program, &mut in_out).unwrap());
+        assert_eq!(
+            state.resume(&program, None, &mut in_out).unwrap_err(),
+            RuntimeError::UnexpectedEof { ip: 3 }
+        );
+        // a program without input never suspends
+        assert!(matches!(
+            config
+                .run_suspending(&compile("+.").unwrap(), &mut in_out)
+                .unwrap(),
+            Execution::Finished
+        ));
+    }
+
+    /// Records the bytes as they are, without going through `char`.
+    struct Bytes {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    impl InputOutput for Bytes {
+        fn read_byte(&mut self) -> Option<u8> {
+            if self.input.is_empty() {
+                None
+            } else {
+                Some(self.input.remove(0))
+            }
+        }
+        fn write_byte(&mut self, byte: u8) {
+            self.output.push(byte);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let mut in_out = Bytes {
+            input: vec![0x80, 0xff],
+            output: Vec::new(),
+        };
+        execute(&compile("-.,.,.").unwrap(), &mut in_out).unwrap();
+        assert_eq!(in_out.output, vec![0xff, 0x80, 0xff]);
+
+        let config = ExecutionConfig::builder().cell_size(CellSize::U16).build();
+        let mut in_out = Bytes {
+            input: vec![0xff],
+            output: Vec::new(),
+        };
+        // only the lowest 8 bits of a wider cell are printed, input is not sign extended
+        config.run(&compile("-.,+.").unwrap(), &mut in_out).unwrap();
+        assert_eq!(in_out.output, vec![0xff, 0x00]);
+    }
+
+    #[test]
+    fn io_adapter() {
+        // cat stops at the first 0, so the payload has every other byte
+        let payload: Vec<u8> = (1..=255).chain(b"\nend".iter().copied()).collect();
+        let mut in_out = IoAdapter::new(std::io::Cursor::new(payload.clone()), Vec::new());
+        execute(&compile(",[.,]").unwrap(), &mut in_out).unwrap();
+        assert!(in_out.error().is_none());
+        assert_eq!(in_out.into_output(), payload);
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+        let mut in_out = IoAdapter::new(Broken, Vec::new());
+        execute(&compile("+,.").unwrap(), &mut in_out).unwrap();
+        // the failed read is the end of the input
+        assert_eq!(in_out.output(), &vec![0]);
+        assert_eq!(
+            in_out.error().unwrap().kind(),
+            std::io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn input_files() {
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        let input = std::fs::File::open("programs/rot13.in").unwrap();
+        let mut in_out = IoAdapter::new(input, Vec::new());
+        run(
+            "programs/rot13.bf",
+            &mut in_out,
+            &Pipeline::default(),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            in_out.into_output(),
+            std::fs::read("programs/rot13.out").unwrap()
+        );
+
+        // newlines and NUL bytes pass through, a cat that stops at EOF instead of 0
+        let path = std::env::temp_dir().join("brainfuck-input-files.in");
+        std::fs::write(&path, b"a\0b\r\nc\0").unwrap();
+        let input = std::fs::File::open(&path).unwrap();
+        let mut in_out = IoAdapter::new(input, Vec::new());
+        config
+            .run(&compile(",+[-.,+]").unwrap(), &mut in_out)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(in_out.into_output(), b"a\0b\r\nc\0");
+    }
+
+    #[test]
+    fn bang_input() {
+        let (code, input) = split_input(",[.,]!hello");
+        assert_eq!(
+            run_str(code, input.unwrap(), &ExecutionConfig::default()),
+            Ok("hello".into())
+        );
+        // a second ! is part of the input
+        assert_eq!(split_input("+[-]!a!b"), ("+[-]", Some("a!b")));
+        assert_eq!(
+            split_input("#!/bin/bf --bang-input\n,.!x"),
+            ("#!/bin/bf --bang-input\n,.", Some("x"))
+        );
+    }
+
+    #[test]
+    fn newlines() {
+        let translate = |source: &str, input: &str, newline| {
+            let mut in_out = Newlines::new(StringInputOutput::with_input(input), newline);
+            execute(&compile(source).unwrap(), &mut in_out).unwrap();
+            in_out.into_inner().output().to_string()
+        };
+        let echo = ",[.,]";
+        assert_eq!(
+            translate(echo, "a\r\nb\nc\rd\r\r\ne\n\r", Newline::Lf),
+            "a\nb\nc\nd\n\ne\n\n"
+        );
+        assert_eq!(
+            translate(echo, "x\ny\r\n\nz", Newline::CrLf),
+            "x\r\ny\r\n\r\nz"
+        );
+        // a \r\n written by the program is not doubled
+        let written = "+++++++++++++.---.>++++++++++.";
+        assert_eq!(translate(written, "", Newline::CrLf), "\r\n\r\n");
+        assert_eq!(translate(written, "", Newline::Lf), "\r\n\n");
+        assert!("native".parse::<Newline>().is_ok());
+        assert!("cr".parse::<Newline>().is_err());
+    }
+
+    #[test]
+    fn numbers() {
+        let run = |source: &str, input: &str, config: &ExecutionConfig| {
+            let mut in_out = Numbers::new(StringInputOutput::with_input(input));
+            config
+                .run(&compile(source).unwrap(), &mut in_out)
+                .map(|_| in_out.into_inner().output().to_string())
+        };
+        let config = ExecutionConfig::default();
+        let adder = ",>,[-<+>]<.";
+        assert_eq!(run(adder, "3 4", &config), Ok("7\n".into()));
+        assert_eq!(run(adder, "\n 100\t\t55\n", &config), Ok("155\n".into()));
+        assert_eq!(
+            run(adder, "3 four", &config),
+            Err(RuntimeError::InvalidNumber { ip: 2 })
+        );
+        // values are checked against the width of the cell, not truncated
+        assert_eq!(
+            run(adder, "300 1", &config),
+            Err(RuntimeError::NumberOutOfRange { ip: 0 })
+        );
+        assert_eq!(
+            run(adder, "1 -1", &config),
+            Err(RuntimeError::NumberOutOfRange { ip: 2 })
+        );
+        assert_eq!(
+            run(adder, "1 99999999999999999999", &config),
+            Err(RuntimeError::NumberOutOfRange { ip: 2 })
+        );
+        let wide = ExecutionConfig::builder().cell_size(CellSize::U16).build();
+        assert_eq!(run(adder, "300 1", &wide), Ok("301\n".into()));
+        // the eof behavior applies once the numbers run out
+        assert_eq!(run(",.,.", "5", &config), Ok("5\n0\n".into()));
+
+        // newlines translates the text
+        let run = |input: &str| {
+            let in_out = Newlines::new(StringInputOutput::with_input(input), Newline::CrLf);
+            let mut in_out = Numbers::new(in_out);
+            wide.run(&compile(adder).unwrap(), &mut in_out)
+                .map(|_| in_out.into_inner().into_inner().output().to_string())
+        };
+        assert_eq!(run("300\r\n1"), Ok("301\r\n".into()));
+        assert_eq!(run("3\r\nfour"), Err(RuntimeError::InvalidNumber { ip: 2 }));
+    }
+
+    #[test]
+    fn tee() {
+        struct Flushes(Vec<u8>, usize);
+        impl Write for Flushes {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.write(bytes)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.1 += 1;
+                Ok(())
+            }
+        }
+        let mut in_out = TeeIo::new(
+            StringInputOutput::with_input("a\0b\n"),
+            Flushes(Vec::new(), 0),
+        );
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        config
+            .run(&compile(",+[-.,+]").unwrap(), &mut in_out)
+            .unwrap();
+        in_out.flush().unwrap();
+        let (in_out, copy) = in_out.into_parts();
+        assert_eq!(in_out.output().as_bytes(), &copy.0[..]);
+        assert_eq!(copy.0, b"a\0b\n");
+        // before each of the five reads and at the end
+        assert_eq!(copy.1, 6);
+
+        // below numbers, both get the text
+        let mut in_out = Numbers::new(TeeIo::new(StringInputOutput::with_input("7"), Vec::new()));
+        let config = ExecutionConfig::builder().cell_size(CellSize::U16).build();
+        let program = compile("+++[>++++++++++<-]>[<++++++++++>-]<.,.").unwrap();
+        config.run(&program, &mut in_out).unwrap();
+        let (in_out, copy) = in_out.into_inner().into_parts();
+        assert_eq!(in_out.output(), "300\n7\n");
+        assert_eq!(copy, b"300\n7\n");
+    }
+
+    #[test]
+    fn wrapped_dumps() {
+        struct Dumps(usize);
+        impl InputOutput for Dumps {
+            fn read_byte(&mut self) -> Option<u8> {
+                None
+            }
+            fn write_byte(&mut self, _: u8) {}
+            fn dump(&mut self, _: &CellDump) {
+                self.0 += 1;
+            }
+        }
+        let extensions = Extensions {
+            dumps: true,
+            ..Extensions::default()
+        };
+        let program = compile_extended("+#>#", &extensions, &Pipeline::default()).unwrap();
+        // the stack main builds, every layer passes the dumps on
+        let mut in_out = Numbers::new(Newlines::new(
+            record::RecordingIo::new(TeeIo::new(Dumps(0), Vec::new()), Vec::new()),
+            Newline::CrLf,
+        ));
+        execute(&program, &mut in_out).unwrap();
+        let (tee, _) = in_out.into_inner().into_inner().into_parts();
+        assert_eq!(tee.into_parts().0 .0, 2);
+    }
+
+    #[test]
+    fn closures() {
+        let mut input: std::collections::VecDeque<u8> = b"abc".iter().copied().collect();
+        let mut written = 0;
+        let program = compile(",[.,]>+++.").unwrap();
+        execute(
+            &program,
+            &mut ClosureIo::new(|| input.pop_front(), |_| written += 1),
+        )
+        .unwrap();
+        assert_eq!(written, 4);
+        assert!(input.is_empty());
+
+        let mut output = Vec::new();
+        let mut in_out = match written {
+            4 => ClosureIo::boxed(|| Some(b'x'), |byte| output.push(byte)),
+            _ => ClosureIo::boxed(|| None, |_| {}),
+        };
+        execute(&compile(",.,.").unwrap(), &mut in_out).unwrap();
+        drop(in_out);
+        assert_eq!(output, b"xx");
+    }
+
+    #[test]
+    fn channels() {
+        let (mut in_out, input, output) = ChannelIo::new();
+        let worker = std::thread::spawn(move || execute(&compile(",[.,]").unwrap(), &mut in_out));
+        for &byte in b"echo" {
+            input.send(byte).unwrap();
+        }
+        // the program only ends once the input does
+        drop(input);
+        assert_eq!(worker.join().unwrap(), Ok(()));
+        assert_eq!(output.iter().collect::<Vec<u8>>(), b"echo");
+
+        let (in_out, input, output) = ChannelIo::new();
+        let mut in_out = in_out.read_timeout(Duration::from_millis(10));
+        input.send(b'x').unwrap();
+        let worker = std::thread::spawn(move || execute(&compile(",[.,]+.").unwrap(), &mut in_out));
+        // the sender is kept, the timeout ends the input
+        assert_eq!(worker.join().unwrap(), Ok(()));
+        assert_eq!(output.iter().collect::<Vec<u8>>(), b"x\x01");
+        drop(input);
+    }
+
+    #[test]
+    fn source_from_reader() {
+        let stdin = std::io::Cursor::new(b"++++++++[>++++++++<-]>+.".to_vec());
+        let source = read_source_from(stdin, "<stdin>").unwrap();
+        assert_eq!(
+            run_str(&source, "", &ExecutionConfig::default()),
+            Ok("A".into())
+        );
+
+        let invalid = std::io::Cursor::new(vec![b'+', 0xff]);
+        let err = read_source_from(invalid, "<stdin>").unwrap_err();
+        assert!(err.to_string().starts_with("cannot open '<stdin>': "));
+    }
+
+    #[test]
+    fn shebang() {
+        let source = "#!/usr/bin/env -S brainfuck --tape=wrap -O2 [x]\n+.";
+        assert_eq!(parse(source), Ok(vec![Ops::Mod(1), Ops::Print]));
+        let program = compile(source).unwrap();
+        let plus = source.len() - 2;
+        assert_eq!(program.source_span(0), Some(plus..plus + 1));
+        // the breakpoint extension does not turn the shebang into one
+        let extensions = Extensions {
+            breakpoints: true,
+            ..Extensions::default()
+        };
+        assert_eq!(parse_extended("#!.\n#", &extensions), Ok(vec![Ops::Break]));
+        assert_eq!(parse("#!-"), Ok(vec![]));
+        // only the very start of the source counts
+        assert_eq!(parse(" #!-\n"), Ok(vec![Ops::Mod(-1)]));
+        assert_eq!(
+            parse("#!\n]"),
+            Err(vec![CompileError::UnmatchedClose {
+                pos: Position { line: 2, column: 1 }
+            }])
+        );
+
+        let mut in_out = StringInputOutput::new();
+        reference::run(source, &mut in_out, &ExecutionConfig::default()).unwrap();
+        assert_eq!(in_out.output(), "\u{1}");
+    }
+
+    #[test]
+    fn cat() {
+        assert_eq!(run_with_eof(",[.,]", "hello", EofBehavior::Zero), "hello");
+    }
+
+    #[test]
+    fn rot13() {
+        let source = std::fs::read_to_string("programs/rot13.bf").unwrap();
+        assert_eq!(
+            run_with_eof(&source, "Hello, World!\n", EofBehavior::MinusOne),
+            "Uryyb, Jbeyq!\n"
+        );
+    }
+
+    #[test]
+    fn pointer_out_of_bounds() {
+        assert_eq!(
+            run_str("<", "", &ExecutionConfig::default()),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: -1 })
+        );
+    }
+
+    #[test]
+    fn unexpected_eof() {
+        let config = ExecutionConfig::builder().eof(EofBehavior::Error).build();
+        assert_eq!(
+            run_str("+.,", "", &config),
+            Err(RuntimeError::UnexpectedEof { ip: 2 })
+        );
+    }
+
+    #[test]
+    fn pointer_underflow() {
+        let config = ExecutionConfig::default();
+        assert_eq!(
+            run_str("<+", "", &config),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: -1 })
+        );
+        // the scan is never entered on a zeroed tape
+        assert_eq!(run_str("[<]", "", &config), Ok(String::new()));
+        assert_eq!(
+            run_str("+[<]", "", &config),
+            Err(RuntimeError::PointerOutOfBounds { ip: 1, pos: -1 })
+        );
+        assert_eq!(run_str(">+[<]+.", "", &config), Ok("\u{1}".into()));
+    }
+
+    #[test]
+    fn growing_tape() {
+        let grow = ExecutionConfig::builder().tape(TapeMode::Grow).build();
+        let far_right = format!("{}+.", ">".repeat(40000));
+        assert_eq!(
+            run_str(&far_right, "", &ExecutionConfig::default()),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: 40000 })
+        );
+        assert_eq!(run_str(&far_right, "", &grow), Ok("\u{1}".into()));
+
+        let scan_past_end = format!("{}+[>]++.", ">".repeat(29999));
+        assert_eq!(run_str(&scan_past_end, "", &grow), Ok("\u{2}".into()));
+
+        assert_eq!(
+            run_str("<", "", &grow),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: -1 })
+        );
+    }
+
+    #[test]
+    fn tape_size() {
+        let source = format!("{}+.", ">".repeat(100_000));
+        assert_eq!(
+            run_str(&source, "", &ExecutionConfig::default()),
+            Err(RuntimeError::PointerOutOfBounds {
+                ip: 0,
+                pos: 100_000
+            })
+        );
+        let config = ExecutionConfig::builder().tape_size(200_000).build();
+        assert_eq!(run_str(&source, "", &config), Ok("\u{1}".into()));
+        let config = ExecutionConfig::builder().tape_size(MAX_TAPE_SIZE).build();
+        assert_eq!(config.tape_size(), MAX_TAPE_SIZE);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid tape size 0, expected a number between 1 and 1073741824")]
+    fn empty_tape() {
+        ExecutionConfig::builder().tape_size(0).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid tape size 1073741825")]
+    fn huge_tape() {
+        ExecutionConfig::builder()
+            .tape_size(MAX_TAPE_SIZE + 1)
+            .build();
+    }
+
+    #[test]
+    fn wrapping_tape() {
+        let wrap = ExecutionConfig::builder()
+            .tape(TapeMode::Wrap)
+            .tape_size(10)
+            .build();
+        assert_eq!(run_str("<+.", "", &wrap), Ok("\u{1}".into()));
+        assert_eq!(
+            run_str("<+.", "", &ExecutionConfig::default()),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: -1 })
+        );
+        // Move(-5) from cell 2 lands on cell 7, Move(3) from there on cell 0
+        assert_eq!(
+            run_str(">>+<<<<<+++>>>+<<<.", "", &wrap),
+            Ok("\u{3}".into())
+        );
+        // scanning left from cell 1 passes cells 0 and 9 and stops at cell 8
+        assert_eq!(run_str("+<+>>+[<]>.", "", &wrap), Ok("\u{1}".into()));
+    }
+
+    #[test]
+    fn scans_by_one() {
+        let tape = |tape, cell_size| {
+            ExecutionConfig::builder()
+                .tape(tape)
+                .tape_size(6)
+                .cell_size(cell_size)
+        };
+        // scans over bytes and wider cells, off both ends of every kind of
+        // tape, with and without a zero
+        let sources = [
+            "+>+>+[<]>.",
+            "+>+>+>+>+>+<<<[>]",
+            "+>+>+[<]",
+            "+>+>>>++>+<[>]<.",
+        ];
+        for source in sources {
+            for mode in [
+                TapeMode::Fixed,
+                TapeMode::Grow,
+                TapeMode::Wrap,
+                TapeMode::Infinite,
+            ] {
+                // a full wrapping tape has no zero to stop at
+                if mode == TapeMode::Wrap && source == sources[1] {
+                    continue;
+                }
+                for cell_size in [CellSize::U8, CellSize::U16] {
+                    let fast = tape(mode, cell_size).build();
+                    // counting ops takes every step on its own
+                    let stepped = tape(mode, cell_size).max_ops(1000).build();
+                    let run = |config: &ExecutionConfig| {
+                        let mut memory = Memory::new(config);
+                        let mut in_out = StringInputOutput::new();
+                        let program = compile(source).unwrap();
+                        let result = config.run_on(&program, &mut in_out, &mut memory);
+                        (result, in_out.into_output(), memory)
+                    };
+                    let context = format!("{:?} on {:?}", source, fast);
+                    assert_eq!(run(&fast), run(&stepped), "{}", context);
+                }
+            }
+        }
+        assert_eq!(
+            run_str(
+                "+>+>+>+>+>+<<<[>]",
+                "",
+                &ExecutionConfig::builder().tape_size(6).build()
+            ),
+            Err(RuntimeError::PointerOutOfBounds { ip: 7, pos: 6 })
+        );
+        assert_eq!(
+            run_str("+>+>+[<]", "", &ExecutionConfig::default()),
+            Err(RuntimeError::PointerOutOfBounds { ip: 4, pos: -1 })
+        );
+    }
+
+    #[test]
+    fn infinite_tape() {
+        let infinite = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .build();
+        let left = "<".repeat(10);
+        let right = ">".repeat(10);
+        let source = format!("+{0}+++{1}.{0}.", left, right);
+        assert_eq!(run_str(&source, "", &infinite), Ok("\u{1}\u{3}".into()));
+        assert_eq!(run_str("+<+<+[<]>.", "", &infinite), Ok("\u{1}".into()));
+        let far_right = format!("{}+.", ">".repeat(100));
+        assert_eq!(run_str(&far_right, "", &infinite), Ok("\u{1}".into()));
+    }
+
+    #[test]
+    fn cell_size() {
+        let source = std::fs::read_to_string("programs/bitwidth.bf").unwrap();
+        for (cell_size, expected) in [
+            (CellSize::U8, "8 bit cells"),
+            (CellSize::U16, "16 bit cells"),
+            (CellSize::U32, "32 bit cells"),
+        ] {
+            let config = ExecutionConfig::builder().cell_size(cell_size).build();
+            assert_eq!(run_str(&source, "", &config), Ok(expected.into()));
+        }
+    }
+
+    #[test]
+    fn unsigned_cells() {
+        let config = ExecutionConfig::default();
+        assert_eq!(
+            run_str(",[.,]", "\u{7f}\u{80}\u{c8}\u{ff}", &config),
+            Ok("\u{7f}\u{80}\u{c8}\u{ff}".into())
+        );
+        let source = format!("{}.[-]-.", "+".repeat(200));
+        assert_eq!(run_str(&source, "", &config), Ok("\u{c8}\u{ff}".into()));
+        // chars above U+00FF keep their lowest byte, the bytes go in unchanged
+        assert_eq!(run_str(",.", "€", &config), Ok("\u{ac}".into()));
+        let mut in_out = StringInputOutput::with_bytes(&[0, 0x80, 0xff]);
+        config
+            .run(&compile(",.,.,.").unwrap(), &mut in_out)
+            .unwrap();
+        assert_eq!(in_out.output(), "\0\u{80}\u{ff}");
+    }
+
+    #[test]
+    fn multiply_loops() {
+        assert_eq!(
+            compile(",[->+>+++<<]").unwrap(),
+            vec![
+                Ops::Read,
+                Ops::AddTo(1, 1),
+                Ops::AddTo(2, 3),
+                Ops::SetCell(0),
+                Ops::End
+            ]
+        );
+        assert_eq!(
+            compile(",[>--<-]").unwrap(),
+            vec![Ops::Read, Ops::AddTo(1, -2), Ops::SetCell(0), Ops::End]
+        );
+        // decrementing by two or moving the pointer is not a multiplication
+        assert!(!compile(",[-->+<]").unwrap().contains(&Ops::SetCell(0)));
+        assert!(!compile(",[->+<<]").unwrap().contains(&Ops::SetCell(0)));
+
+        let config = ExecutionConfig::default();
+        assert_eq!(run_str("++[->+++<]>.", "", &config), Ok("\u{6}".into()));
+        assert_eq!(run_str("++++[-->+<]>.", "", &config), Ok("\u{2}".into()));
+        // 100 * 3 wraps around in an 8 bit cell
+        let source = format!("{}[->+++<]>.", "+".repeat(100));
+        assert_eq!(run_str(&source, "", &config), Ok("\u{2c}".into()));
+        // the targets are only touched when the loop would run
+        assert_eq!(run_str(",[-<+>]", "", &config), Ok("".into()));
+        assert_eq!(
+            run_str("+[-<+>]", "", &config),
+            Err(RuntimeError::PointerOutOfBounds { ip: 1, pos: -1 })
+        );
+        let infinite = ExecutionConfig::builder().tape(TapeMode::Infinite).build();
+        assert_eq!(run_str("+++[-<+>]<.", "", &infinite), Ok("\u{3}".into()));
+    }
+
+    #[test]
+    fn offset_fusion() {
+        use Ops::*;
+        assert_eq!(
+            compile(">+++>--<<").unwrap(),
+            vec![ModAt(1, 3), ModAt(2, -2), End]
+        );
+        assert_eq!(compile("+>+<+").unwrap(), vec![Mod(2), ModAt(1, 1), End]);
+        // the change of the current cell goes along with the move
+        assert_eq!(
+            compile(">+<-<").unwrap(),
+            vec![ModAt(1, 1), ModMove(-1, -1), End]
+        );
+        // loops and I/O end a run
+        assert_eq!(
+            compile(">+.>,").unwrap(),
+            vec![ModAt(1, 1), Move(1), Print, Move(1), Read, End]
+        );
+        assert_eq!(
+            compile(",[>+>]<+").unwrap(),
+            vec![
+                Read,
+                LoopOpen(3),
+                ModAt(1, 1),
+                MoveClose(2, 1),
+                ModAt(-1, 1),
+                Move(-1),
+                End
+            ]
+        );
+
+        let config = ExecutionConfig::default();
+        assert_eq!(
+            run_str(">+++>--<<[-]>.>.", "", &config),
+            Ok("\u{3}\u{fe}".into())
+        );
+        assert_eq!(
+            run_str("<+>", "", &config),
+            Err(RuntimeError::PointerOutOfBounds { ip: 0, pos: -1 })
+        );
+        let infinite = ExecutionConfig::builder().tape(TapeMode::Infinite).build();
+        assert_eq!(run_str("+<++>.<.", "", &infinite), Ok("\u{1}\u{2}".into()));
+    }
+
+    #[test]
+    fn dead_stores() {
+        use Ops::*;
+        assert_eq!(compile("+++[-]").unwrap(), vec![SetCell(0), End]);
+        assert_eq!(compile("[-]+[-]").unwrap(), vec![SetCell(0), End]);
+        assert_eq!(
+            compile("+>+<[-]").unwrap(),
+            vec![ModAt(1, 1), SetCell(0), End]
+        );
+        assert_eq!(
+            compile(">+<[-]>[-]").unwrap(),
+            vec![SetCell(0), Move(1), SetCell(0), End]
+        );
+        assert_eq!(
+            compile(">+<[->+<]>[-]").unwrap(),
+            vec![SetCell(0), Move(1), SetCell(0), End]
+        );
+        // reads keep the store alive
+        assert_eq!(
+            compile("+.[-]").unwrap(),
+            vec![Mod(1), Print, SetCell(0), End]
+        );
+        assert_eq!(
+            compile("+[->+<]").unwrap(),
+            vec![Mod(1), AddTo(1, 1), SetCell(0), End]
+        );
+    }
+
+    #[test]
+    fn dead_loops() {
+        assert_eq!(
+            compile("[this is a comment with + - < > inside]++.").unwrap(),
+            compile("++.").unwrap()
+        );
+        assert_eq!(
+            compile("[[+]>[-]]+[.-][+[>]]").unwrap(),
+            compile("+[.-]").unwrap()
+        );
+        assert_eq!(
+            compile("+[>]<[-][,]").unwrap(),
+            compile("+[>]<[-]").unwrap()
+        );
+    }
+
+    #[test]
+    fn if_loops() {
+        use Ops::*;
+        assert_eq!(
+            compile(",[>+<[-]]").unwrap(),
+            vec![Read, If(3), ModAt(1, 1), SetCell(0), End]
+        );
+        assert_eq!(
+            compile(",[.[<]]").unwrap(),
+            vec![Read, If(3), Print, SearchZeroCell(-1), End]
+        );
+        // the outer loop is an if because the inner loop ends with a zero cell
+        assert_eq!(
+            compile(",[>,[.,]]").unwrap(),
+            vec![
+                Read,
+                If(7),
+                Move(1),
+                Read,
+                BalancedLoop(7, 0, 0),
+                Print,
+                Read,
+                LoopClose(4),
+                End
+            ]
+        );
+        // the cell checked at the end is not the cleared one
+        assert_eq!(
+            compile(",[>[-]<-]").unwrap(),
+            vec![
+                Read,
+                BalancedLoop(5, 0, 1),
+                Move(1),
+                SetCell(0),
+                ModAt(-1, -1),
+                MoveClose(-1, 1),
+                End
+            ]
+        );
+
+        let config = ExecutionConfig::default();
+        let source = ",[>++<[-]]>.,[>+<,[-]]>.";
+        assert_eq!(run_str(source, "\0\u{5}\0", &config), Ok("\0\u{1}".into()));
+        assert_eq!(run_str(source, "\u{1}\0", &config), Ok("\u{2}\0".into()));
+        assert_eq!(
+            run_str(",[>,[.,]]+.", "\u{1}abc", &config),
+            Ok("abc\u{1}".into())
+        );
+    }
+
+    #[test]
+    fn fused_pairs() {
+        use Ops::*;
+        // the targets of the outer loop stay right around a fused inner one
+        assert_eq!(
+            compile("++[>+>,[<]<-]").unwrap(),
+            vec![
+                Mod(2),
+                LoopOpen(7),
+                ModAt(1, 1),
+                Move(2),
+                Read,
+                SearchZeroCell(-1),
+                ModAt(-1, -1),
+                MoveClose(-1, 1),
+                End
+            ]
+        );
+        // an if whose body ends with a fused close, and a pair after it
+        let ops = compile(",[>,[.>]]+>.").unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                Read,
+                If(6),
+                Move(1),
+                Read,
+                LoopOpen(6),
+                Print,
+                MoveClose(1, 4),
+                ModMove(1, 1),
+                Print,
+                End
+            ]
+        );
+        assert_eq!(Program::new(ops.to_vec()), Ok(ops));
+
+        let config = ExecutionConfig::default();
+        for (source, input) in [
+            ("++[>+>,[<]<-]>>.>>.", "ab"),
+            (",[>,[.>]]+>.", "\u{1}ab\0"),
+            (",[>,[.>]]+>.", "\0"),
+            (",[+>.[-]]+>.", "\u{4}"),
+        ] {
+            assert_same_as_reference(source, input, &config);
+        }
+    }
+
+    #[test]
+    fn balanced_loops() {
+        use Ops::*;
+        // the range of the outer loop covers the inner one, which reads at -1
+        let ops = compile(",[>,[<.>-]<<+>-]").unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                Read,
+                BalancedLoop(11, -1, 1),
+                Move(1),
+                Read,
+                BalancedLoop(8, -1, 0),
+                Move(-1),
+                Print,
+                ModAt(1, -1),
+                MoveClose(1, 4),
+                ModAt(-2, 1),
+                ModAt(-1, -1),
+                MoveClose(-1, 1),
+                End
+            ]
+        );
+        assert_eq!(Program::new(ops.to_vec()), Ok(ops));
+        // a loop that moves on, one with a scan and one inside of an if
+        assert_eq!(
+            compile(",[>,]").unwrap(),
+            vec![Read, LoopOpen(4), Move(1), Read, LoopClose(1), End]
+        );
+        assert!(!compile(",[>[>]<-]")
+            .unwrap()
+            .iter()
+            .any(|op| matches!(op, BalancedLoop(..))));
+
+        // the range has to contain every cell the body reaches
+        assert!(Program::new(vec![BalancedLoop(2, 0, 1), ModAt(1, 1), LoopClose(0)]).is_ok());
+        assert!(Program::new(vec![BalancedLoop(2, -3, 3), ModAt(1, 1), LoopClose(0)]).is_ok());
+        let invalid = [
+            vec![BalancedLoop(2, 0, 0), ModAt(1, 1), LoopClose(0)],
+            vec![BalancedLoop(2, 0, 9), SearchZeroCell(1), LoopClose(0)],
+            vec![BalancedLoop(2, 0, 9), Move(1), LoopClose(0)],
+            // the loops cross
+            vec![
+                BalancedLoop(3, 0, 0),
+                LoopOpen(4),
+                Print,
+                LoopClose(0),
+                LoopClose(1),
+            ],
+        ];
+        for ops in invalid {
+            assert_eq!(Program::new(ops), Err(CompileError::InvalidJump { ip: 0 }));
+        }
+
+        // where the range is not on the tape the loop runs checked and has to
+        // stop where the plain loop does
+        let plain = Pipeline::default().disable("balanced_loops").unwrap();
+        let fixed = ExecutionConfig::builder().tape_size(4);
+        let configs = [
+            fixed.build(),
+            fixed.tape(TapeMode::Wrap).build(),
+            fixed.tape(TapeMode::Grow).build(),
+            fixed.tape(TapeMode::Infinite).build(),
+            fixed.eof(EofBehavior::Error).build(),
+        ];
+        let sources = [
+            "+[<+>-]<.",
+            ">+[<+>-]<.",
+            ">>>+[>+<-]>.",
+            ">>+[>+<-]>.",
+            ",[>,.<,]",
+            "+++[>++[>+<-]<-]>>.",
+        ];
+        for config in &configs {
+            for source in sources {
+                let outcome = |pipeline| {
+                    let program = compile_with(source, pipeline).unwrap();
+                    let mut in_out = StringInputOutput::with_input("ab");
+                    let mut memory = Memory::new(config);
+                    let result = config.run_on(&program, &mut in_out, &mut memory);
+                    (result, in_out.into_output(), memory)
+                };
+                let context = format!("{:?} with {:?}", source, config);
+                assert_eq!(
+                    outcome(&Pipeline::default()),
+                    outcome(&plain),
+                    "{}",
+                    context
+                );
+                assert_same_as_reference(source, "ab", config);
+            }
+        }
+    }
+
+    #[test]
+    fn dispatches() {
+        let builder = ExecutionConfig::builder().eof(EofBehavior::Error);
+        let matched = builder.build();
+        let run = |source, input, config: &ExecutionConfig| {
+            let mut in_out = StringInputOutput::with_input(input);
+            let result = config.run(&compile(source).unwrap(), &mut in_out);
+            (result, in_out.into_output())
+        };
+        for dispatch in [Dispatch::Threaded, Dispatch::Packed] {
+            let config = builder.dispatch(dispatch).build();
+            // the same output and the same error at the same op
+            for (source, input) in [
+                (",[.,]", "hi"),
+                ("+.>>[<]<", ""),
+                (",.,", "a"),
+                ("++[>+<-]>[>+++<-]>.", ""),
+            ] {
+                let expected = run(source, input, &matched);
+                let context = format!("{:?} with {:?}", source, dispatch);
+                assert_eq!(run(source, input, &config), expected, "{}", context);
+            }
+            assert_eq!(
+                run(",.,", "a", &config).0,
+                Err(RuntimeError::UnexpectedEof { ip: 2 })
+            );
+
+            let mut memory = Memory::new(&config);
+            let mut in_out = StringInputOutput::new();
+            let program = compile("+++>++").unwrap();
+            config.run_on(&program, &mut in_out, &mut memory).unwrap();
+            assert_eq!((memory.pos(), memory.cell(0)), (1, Some(3)));
+        }
+
+        assert_eq!("threaded".parse(), Ok(Dispatch::Threaded));
+        assert_eq!("packed".parse(), Ok(Dispatch::Packed));
+        assert_eq!(
+            "jump".parse::<Dispatch>(),
+            Err("invalid dispatch 'jump', expected match, threaded or packed".into())
+        );
+    }
+
+    #[test]
+    fn disassembly() {
+        let program = compile(",[>+<[-]]>[.,[>]<]>[->++<]").unwrap();
+        assert_eq!(
+            program.disassemble().lines().collect::<Vec<_>>(),
+            vec![
+                "0000  read",
+                "0001  if -> 0003",
+                "0002    add_at 1, 1",
+                "0003    set 0",
+                "0004  move 1",
+                "0005  loop_open -> 0009",
+                "0006    print",
+                "0007    read",
+                "0008    scan 1",
+                "0009  move_close -1 -> 0005",
+                "0010  move 1",
+                "0011  add_to 1, 2",
+                "0012  set 0",
+                "0013  end",
+            ]
+        );
+    }
+
+    #[test]
+    fn hand_built_programs() {
+        use Ops::*;
+        assert!(Program::new(vec![LoopOpen(2), Print, LoopClose(0)]).is_ok());
+        assert!(Program::new(vec![If(2), Print, SetCell(0), End]).is_ok());
+        assert_eq!(
+            Program::new(vec![LoopOpen(1), Print, LoopClose(0)]),
+            Err(CompileError::InvalidJump { ip: 0 })
+        );
+        assert_eq!(
+            Program::new(vec![Print, LoopClose(7)]),
+            Err(CompileError::InvalidJump { ip: 1 })
+        );
+        assert_eq!(
+            Program::new(vec![If(1)]),
+            Err(CompileError::InvalidJump { ip: 0 })
+        );
+    }
+
+    #[test]
+    fn unmatched_brackets() {
+        let errors = |source| compile(source).unwrap_err();
+        let open = |line, column| CompileError::UnmatchedOpen {
+            pos: Position { line, column },
+        };
+        let close = |line, column| CompileError::UnmatchedClose {
+            pos: Position { line, column },
+        };
+        assert_eq!(errors("[]]"), vec![close(1, 3)]);
+        assert_eq!(
+            errors("a comment\n\n  +[-]\n  more text ]."),
+            vec![close(4, 13)]
+        );
+        assert_eq!(errors("["), vec![open(1, 1)]);
+        assert_eq!(errors("+[-]\nloop: [>[<]\n[]"), vec![open(2, 7)]);
+        assert_eq!(
+            errors("]+[\n-]]\n[ >"),
+            vec![close(1, 1), close(2, 3), open(3, 1)]
+        );
+        assert_eq!(
+            close(4, 13).to_string(),
+            "unmatched ']' at line 4, column 13"
+        );
+        assert_eq!(
+            Error::from(errors("[[")).to_string(),
+            "unmatched '[' at line 1, column 1\nunmatched '[' at line 1, column 2"
+        );
+    }
+
+    #[test]
+    fn pbrain() {
+        let extensions = Extensions {
+            language: Language::Pbrain,
+            ..Extensions::default()
+        };
+        let compile_pbrain = |source| compile_extended(source, &extensions, &Pipeline::default());
+        let config = ExecutionConfig::default();
+        let run = |source, config: &ExecutionConfig| {
+            let mut in_out = StringInputOutput::new();
+            let result = config.run(&compile_pbrain(source).unwrap(), &mut in_out);
+            result.map(|()| in_out.into_output())
+        };
+        // procedure 1 prints the next cell, procedure 2 the char after it
+        let source = "+(>.<)+(>+.-<)>>++++++++[<++++++++>-]<+<-:+:-:";
+        assert_eq!(run(source, &config), Ok("ABA".into()));
+        // programs with procedures are matched whatever the dispatch
+        let threaded = ExecutionConfig::builder()
+            .dispatch(Dispatch::Threaded)
+            .build();
+        assert_eq!(run(source, &threaded), Ok("ABA".into()));
+        // a procedure may call itself
+        assert_eq!(
+            run("+(>-.[<:>]<)>+++<:", &config),
+            Ok("\u{2}\u{1}\u{0}".into())
+        );
+        assert!(matches!(
+            run("+:", &config),
+            Err(RuntimeError::UndefinedProcedure { id: 1, .. })
+        ));
+        let shallow = ExecutionConfig::builder().call_depth(10).build();
+        assert!(matches!(
+            run("+(:):", &shallow),
+            Err(RuntimeError::CallDepthExceeded { depth: 10, .. })
+        ));
+        assert_eq!(
+            RuntimeError::UndefinedProcedure { ip: 3, id: 7 }.to_string(),
+            "call of the undefined procedure 7 at instruction 3"
+        );
+
+        let pos = |line, column| Position { line, column };
+        assert_eq!(
+            compile_pbrain("(\n)) ([)]"),
+            Err(vec![
+                CompileError::UnmatchedProcedureClose { pos: pos(2, 2) },
+                CompileError::UnmatchedProcedureOpen { pos: pos(2, 4) },
+                CompileError::UnmatchedProcedureClose { pos: pos(2, 6) },
+            ])
+        );
+        // plain brainfuck reads the parens as a comment
+        assert_eq!(compile("+(:)."), compile("+."));
+        let program = compile_pbrain("(.)").unwrap();
+        assert_eq!(
+            translate::translate(&program, &config, translate::Target::C),
+            Err(translate::TranslateError::Unsupported(
+                "call pbrain procedures"
+            ))
+        );
+    }
+
+    #[test]
+    fn dumps() {
+        #[derive(Default)]
+        struct Dumps(Vec<CellDump>);
+        impl InputOutput for Dumps {
+            fn read_byte(&mut self) -> Option<u8> {
+                None
+            }
+            fn write_byte(&mut self, _: u8) {}
+            fn dump(&mut self, dump: &CellDump) {
+                self.0.push(dump.clone());
+            }
+        }
+        let extensions = Extensions {
+            dumps: true,
+            ..Extensions::default()
+        };
+        let run = |source, config: &ExecutionConfig| {
+            let program = compile_extended(source, &extensions, &Pipeline::default()).unwrap();
+            let mut dumps = Dumps::default();
+            config.run(&program, &mut dumps).unwrap();
+            dumps.0
+        };
+        let dumps = run("+++>#", &ExecutionConfig::default());
+        let mut cells = vec![0; CellDump::CELLS];
+        cells[0] = 3;
+        let expected = CellDump {
+            ip: 1,
+            pointer: 1,
+            first: 0,
+            cells,
+        };
+        assert_eq!(
+            expected.to_string(),
+            "ip 1, pointer 1, cells 0..16\n3 [0] 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n. [.] . . . . . . . . . . . . . .\n"
+        );
+        assert_eq!(dumps, [expected]);
+
+        // the pointer is in the middle unless an end of the tape is closer
+        let source = "++++++++++[>++++++++++<-]>>>>>>>>>#>>>>>>>>>>>>>>>#";
+        let config = ExecutionConfig::builder().tape_size(25).build();
+        let dumps = run(source, &config);
+        let windows: Vec<_> = dumps
+            .iter()
+            .map(|dump| (dump.first, dump.cells.len()))
+            .collect();
+        assert_eq!(windows, [(1, 16), (9, 16)]);
+        assert_eq!(dumps[0].cells[..2], [100, 0]);
+        let tiny = ExecutionConfig::builder().tape_size(3).build();
+        assert_eq!(run(">#", &tiny)[0].cells, [0; 3]);
+        assert!(dumps[1]
+            .to_string()
+            .ends_with("0 0 [0]\n. . . . . . . . . . . . . . . [.]\n"));
+
+        // without the extension `#` is a comment
+        assert_eq!(compile("+#"), compile("+"));
+        assert_eq!(
+            parse_extended(
+                "#",
+                &Extensions {
+                    breakpoints: true,
+                    ..extensions.clone()
+                }
+            ),
+            Ok(vec![Ops::Break])
+        );
+    }
+
+    #[test]
+    fn run_errors() {
+        let mut in_out = StringInputOutput::new();
+        let pipeline = Pipeline::default();
+        let config = ExecutionConfig::default();
+
+        let err = run("programs/missing.bf", &mut in_out, &pipeline, &config).unwrap_err();
+        assert!(
+            matches!(&err, Error::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound)
+        );
+        assert_eq!(
+            err.to_string(),
+            "cannot open 'programs/missing.bf': No such file or directory"
+        );
+
+        let path = std::env::temp_dir().join("brainfuck-run-errors.bf");
+        std::fs::write(&path, "+[.").unwrap();
+        let err = run(path.to_str().unwrap(), &mut in_out, &pipeline, &config).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            err,
+            Error::Compile(errors) if errors == vec![CompileError::UnmatchedOpen { pos: Position { line: 1, column: 2 } }]
+        ));
+        assert_eq!(in_out.output(), "");
+    }
+
+    #[test]
+    fn run_to_string() {
+        let hello_world = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        assert_eq!(super::run_to_string(hello_world).unwrap(), "Hello World!\n");
+        assert_eq!(run_to_string_with_input(",[.,]", "abc").unwrap(), "abc");
+        assert_eq!(run_to_bytes(",[+.,]", &[0x7f, 0xfe]).unwrap(), [0x80, 0xff]);
+        assert!(matches!(
+            super::run_to_string("+[").unwrap_err(),
+            Error::Compile(_)
+        ));
+        assert!(matches!(
+            super::run_to_string("<").unwrap_err(),
+            Error::Runtime(RuntimeError::PointerOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn run_source_errors() {
+        let mut in_out = StringInputOutput::new();
+        let pipeline = Pipeline::default();
+        let config = ExecutionConfig::default();
+        // nothing runs if the program does not compile
+        let err = run_source(".]", &mut in_out, &pipeline, &config).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Compile(errors) if errors == vec![CompileError::UnmatchedClose { pos: Position { line: 1, column: 2 } }]
+        ));
+        assert_eq!(in_out.output(), "");
+        let err = run_source("+.<", &mut in_out, &pipeline, &config).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Runtime(RuntimeError::PointerOutOfBounds { ip: 2, pos: -1 })
+        ));
+        assert_eq!(in_out.output(), "\u{1}");
+    }
+
+    #[test]
+    fn empty_program() {
+        use Ops::*;
+        assert_eq!(compile("").unwrap(), vec![End]);
+        assert_eq!(compile("only a comment").unwrap(), vec![End]);
+        assert_eq!(
+            run_str("", "", &ExecutionConfig::default()),
+            Ok(String::new())
+        );
+
+        let mut in_out = StringInputOutput::new();
+        assert_eq!(execute(&Program::default(), &mut in_out), Ok(()));
+        assert_eq!(in_out.output(), "");
+    }
+
+    #[test]
+    fn ops_without_end() {
+        use Ops::*;
+        // prints 3, 2 and 1 and runs off the end once the loop is done
+        let program = Program::new(vec![SetCell(3), LoopOpen(4), Print, Mod(-1), LoopClose(1)]);
+        let mut in_out = StringInputOutput::new();
+        assert_eq!(execute(&program.unwrap(), &mut in_out), Ok(()));
+        assert_eq!(in_out.output(), "\u{3}\u{2}\u{1}");
+    }
+
+    #[test]
+    fn dyn_io() {
+        let program = compile(",[.,]").unwrap();
+        let mut in_out: Box<dyn InputOutput> = Box::new(StringInputOutput::with_input("ab"));
+        assert_eq!(execute_dyn(&program, in_out.as_mut()), Ok(()));
+        // a box is an `InputOutput` of its own
+        assert_eq!(execute(&program, &mut in_out), Ok(()));
+        let mut in_out = StringInputOutput::with_input("cd");
+        assert_eq!(execute(&program, &mut &mut in_out), Ok(()));
+        assert_eq!(in_out.output(), "cd");
+    }
+
+    #[test]
+    fn source_spans() {
+        let spans = |source: &str, level| {
+            let program = compile_with(source, &Pipeline::new(level)).unwrap();
+            (0..program.len())
+                .map(|ip| program.source_span(ip).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // every token is its own op, comments are skipped
+        assert_eq!(spans("+ >.", OptLevel::O0), vec![0..1, 2..3, 3..4, 4..4]);
+        // runs fold into one op
+        assert_eq!(spans("+++++.", OptLevel::O1), vec![0..5, 5..6, 6..6]);
+        // the clear and the + after it become a single SetCell
+        assert_eq!(spans("a[-]b+", OptLevel::O1), vec![1..6, 6..6]);
+        assert_eq!(spans(",[>>]", OptLevel::O1), vec![0..1, 1..5, 5..5]);
+        // the AddTo and the SetCell both stand for the loop
+        assert_eq!(spans(",[->+<]", OptLevel::O2), vec![0..1, 1..7, 1..7, 7..7]);
+        // each ModAt spans the mods at its offset, the net move is dropped
+        assert_eq!(spans(",+>+<+", OptLevel::O2), vec![0..1, 1..6, 3..4, 6..6]);
+        // offsets are bytes, not characters
+        assert_eq!(spans("ä+", OptLevel::O2), vec![2..3, 3..3]);
+
+        let program = Program::new(vec![Ops::Print]).unwrap();
+        assert_eq!(program.source_span(0), None);
+        assert_eq!(compile("+").unwrap().source_span(2), None);
+    }
+
+    #[test]
+    fn positions() {
+        let source = "+\n ä[\n";
+        assert_eq!(
+            Position::in_source(source, 0),
+            Position { line: 1, column: 1 }
+        );
+        assert_eq!(
+            Position::in_source(source, 5),
+            Position { line: 2, column: 3 }
+        );
+        assert_eq!(
+            Position::in_source(source, source.len()),
+            Position { line: 3, column: 1 }
+        );
+    }
+
+    #[test]
+    fn stages() {
+        use Ops::*;
+        assert_eq!(
+            parse("a+[b>]").unwrap(),
+            vec![Mod(1), LoopOpen(0), Move(1), LoopClose(0)]
+        );
+        assert_eq!(
+            optimize(parse("[-]").unwrap()).unwrap(),
+            vec![SetCell(0), End]
+        );
+        assert_eq!(
+            optimize(parse(",[>]").unwrap()).unwrap(),
+            vec![Read, SearchZeroCell(1), End]
+        );
+        // a scan is dead on a zero cell, just like any other loop
+        assert_eq!(optimize(parse("[>]").unwrap()).unwrap(), vec![End]);
+        assert_eq!(
+            optimize(parse(",[<<]").unwrap()).unwrap(),
+            vec![Read, SearchZeroCell(-2), End]
+        );
+        // the first + is overwritten by the clear, the second one becomes part of it
+        assert_eq!(
+            optimize(parse("+[-]+").unwrap()).unwrap(),
+            vec![SetCell(1), End]
+        );
+        assert_eq!(
+            optimize(parse("[-]-").unwrap()).unwrap(),
+            vec![SetCell(u32::MAX), End]
+        );
+        // only a single - is a clear loop
+        assert_eq!(
+            optimize(parse(",[--]").unwrap()).unwrap(),
+            vec![Read, BalancedLoop(3, 0, 0), Mod(-2), LoopClose(1), End]
+        );
+        assert_eq!(
+            optimize(parse(",[->+<][>]").unwrap()).unwrap(),
+            vec![Read, AddTo(1, 1), SetCell(0), End]
+        );
+        assert_eq!(
+            optimize(parse("+>[-]<[>+<-]").unwrap()).unwrap(),
+            vec![
+                ModMove(1, 1),
+                SetCell(0),
+                Move(-1),
+                AddTo(1, 1),
+                SetCell(0),
+                End
+            ]
+        );
+        assert_eq!(optimize(parse("").unwrap()).unwrap(), vec![End]);
+
+        // hand-built ops need not be balanced
+        let invalid = |ips: &[usize]| {
+            let errors = ips.iter().map(|&ip| CompileError::InvalidJump { ip });
+            Err(errors.collect::<Vec<_>>())
+        };
+        assert_eq!(optimize(vec![LoopClose(0), Mod(1)]), invalid(&[0]));
+        assert_eq!(
+            optimize(vec![LoopOpen(0), LoopOpen(0), LoopClose(0)]),
+            invalid(&[0])
+        );
+        assert_eq!(
+            optimize(vec![ProcDefStart(0), LoopClose(0), LoopOpen(0), ProcDefEnd]),
+            invalid(&[0, 1, 2, 3])
+        );
+        assert_eq!(
+            optimize(vec![
+                ProcDefStart(0),
+                LoopOpen(0),
+                Mod(-1),
+                LoopClose(0),
+                ProcDefEnd
+            ]),
+            Ok(vec![ProcDefStart(2), SetCell(0), ProcDefEnd, End])
+        );
+    }
+
+    /// Runs `source` through the reference interpreter and through every
+    /// optimization level, all of them have to agree.
+    fn assert_same_as_reference(source: &str, input: &str, config: &ExecutionConfig) {
+        let mut expected = StringInputOutput::with_input(input);
+        let expected_ok = reference::run(source, &mut expected, config).is_ok();
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let program = compile_with(source, &Pipeline::new(level)).unwrap();
+            for dispatch in [Dispatch::Match, Dispatch::Threaded, Dispatch::Packed] {
+                let config = ExecutionConfig {
+                    dispatch,
+                    ..*config
+                };
+                let mut in_out = StringInputOutput::with_input(input);
+                let ok = config.run(&program, &mut in_out).is_ok();
+                let context = format!("{:?} at {:?} with {:?}", source, level, config);
+                assert_agrees(
+                    (expected.output(), expected_ok),
+                    (in_out.output(), ok),
+                    &context,
+                );
+            }
+        }
+    }
+
+    /// The reference checks every single move while compiled programs only
+    /// check where folded moves end up, so where the reference fails the
+    /// program may get further, but has to print the same up to that point.
+    fn assert_agrees(expected: (&str, bool), actual: (&str, bool), context: &str) {
+        if expected.1 {
+            assert_eq!(actual, expected, "{}", context);
+        } else {
+            assert!(actual.0.starts_with(expected.0), "{}", context);
+        }
+    }
+
+    #[test]
+    fn differential_programs() {
+        let minus_one = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        for entry in std::fs::read_dir("programs").unwrap() {
+            let path = entry.unwrap().path();
+            // far too slow for the reference interpreter in a debug build, see
+            // differential_mandelbrot
+            if path.extension() != Some("bf".as_ref()) || path.ends_with("mandelbrot.bf") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let input = std::fs::read_to_string(path.with_extension("in")).ok();
+            for cell_size in [CellSize::U8, CellSize::U16, CellSize::U32] {
+                let config = ExecutionConfig::builder()
+                    .eof(EofBehavior::MinusOne)
+                    .cell_size(cell_size)
+                    .build();
+                let input = input.as_deref().unwrap_or("Hello, World!\n");
+                assert_same_as_reference(&source, input, &config);
+            }
+            // dbfi keeps reading until a `!` ends the program it interprets
+            if !path.ends_with("dbfi.bf") {
+                assert_same_as_reference(&source, "", &minus_one);
+            }
+        }
+    }
+
+    /// Decompiles the programs at every level and checks that the source
+    /// compiles to a program with the same output.
+    #[test]
+    fn decompiled_programs() {
+        // cat needs the end of the input to be -1
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        let run = |program: &Program, input: &str| {
+            let mut in_out = StringInputOutput::with_input(input);
+            let result = config.run(program, &mut in_out);
+            (result, in_out.into_output())
+        };
+        let mut sources = vec![
+            // a dead store leaves out the clear after the multiplication
+            (",[->+>++<<]>[-]<[-]>>.<.".to_string(), "x".to_string()),
+            (",[-]-.[-]+.".to_string(), "x".to_string()),
+            (",[.[-]],[>+<[-]]>.".to_string(), "xy".to_string()),
+        ];
+        for entry in std::fs::read_dir("programs").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("bf".as_ref()) || path.ends_with("mandelbrot.bf") {
+                continue;
+            }
+            let input = std::fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+            sources.push((std::fs::read_to_string(&path).unwrap(), input));
+        }
+        for (source, input) in &sources {
+            for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+                let pipeline = Pipeline::new(level);
+                let program = compile_with(source, &pipeline).unwrap();
+                let decompiled = program.to_brainfuck();
+                let recompiled = compile_with(&decompiled, &pipeline).unwrap();
+                assert_eq!(
+                    run(&recompiled, input),
+                    run(&program, input),
+                    "{} at {:?}",
+                    decompiled,
+                    level
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        ignore = "slow in a debug build, run with cargo test --release"
+    )]
+    fn differential_mandelbrot() {
+        let source = std::fs::read_to_string("programs/mandelbrot.bf").unwrap();
+        assert_same_as_reference(&source, "", &ExecutionConfig::default());
+    }
+
+    #[test]
+    fn differential_snippets() {
+        let snippets = [
+            "+[-]+.",
+            "+++[-]--.",
+            "[-]-.>[-]+++++[<+>-]<.",
+            "++[>+++<-]>.",
+            "-[>+<-]>.",
+            "+>+<[->+<]>.",
+            "++>+++<[->[->+>+<<]>[-<+>]<<]>>>.",
+            ",[.,]",
+            ",[>,]<[.<]",
+            ",[>+<[-]]>.",
+            "+[>]",
+            "<",
+            ">>+<<<",
+            "+[<]>.",
+            "+>+>+<<[>]+.",
+            "+++++[>+++++<-]>[>++<-]>[-]<<.",
+            "[comment with + and -]++.",
+        ];
+        let configs = [
+            ExecutionConfig::default(),
+            ExecutionConfig::builder()
+                .eof(EofBehavior::MinusOne)
+                .build(),
+            ExecutionConfig::builder()
+                .eof(EofBehavior::Unchanged)
+                .build(),
+            ExecutionConfig::builder().cell_size(CellSize::U16).build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Wrap)
+                .tape_size(4)
+                .build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Grow)
+                .tape_size(2)
+                .build(),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Infinite)
+                .tape_size(2)
+                .build(),
+        ];
+        for source in snippets {
+            for config in &configs {
+                // the trailing zero ends the read loops whatever the EOF behavior is
+                assert_same_as_reference(source, "ab\u{ff}\0", config);
+            }
+        }
+    }
+
+    /// What the fuzz target does with one input: compile it and, if that
+    /// works, run it under a couple of configurations with a small budget.
+    /// Whenever the program finishes, the reference has to agree.
+    fn fuzz_one(data: &[u8]) {
+        let source = String::from_utf8_lossy(data);
+        let program = match compile(&source) {
+            Ok(program) => program,
+            Err(_) => return,
+        };
+        let configs = [
+            ExecutionConfig::builder(),
+            ExecutionConfig::builder().eof(EofBehavior::Error),
+            ExecutionConfig::builder().cell_size(CellSize::U16),
+            ExecutionConfig::builder().tape(TapeMode::Wrap).tape_size(3),
+            ExecutionConfig::builder().tape(TapeMode::Grow).tape_size(1),
+            ExecutionConfig::builder()
+                .tape(TapeMode::Infinite)
+                .tape_size(1),
+        ];
+        for config in &configs {
+            let mut in_out = StringInputOutput::with_input("fuzz");
+            let result = config.max_ops(10_000).build().run(&program, &mut in_out);
+            if let Err(RuntimeError::FuelExhausted { .. }) = result {
+                continue;
+            }
+            let mut expected = StringInputOutput::with_input("fuzz");
+            let reference_config = config.max_ops(1_000_000).build();
+            match reference::run(&source, &mut expected, &reference_config) {
+                Err(RuntimeError::FuelExhausted { .. }) => {}
+                reference_result => assert_agrees(
+                    (expected.output(), reference_result.is_ok()),
+                    (in_out.output(), result.is_ok()),
+                    &format!("{:?} with {:?}", source, config.build()),
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn fuel() {
+        let config = ExecutionConfig::builder().max_ops(1000).build();
+        assert_eq!(
+            run_str("+[]", "", &config),
+            Err(RuntimeError::FuelExhausted { executed: 1000 })
+        );
+        // Mod, Print and End
+        let config = ExecutionConfig::builder().max_ops(3).build();
+        assert_eq!(run_str("+.", "", &config), Ok("\u{1}".into()));
+        let config = ExecutionConfig::builder().max_ops(2).build();
+        assert_eq!(
+            run_str("+.", "", &config),
+            Err(RuntimeError::FuelExhausted { executed: 2 })
+        );
+        // found by fuzzing: scans that never find a zero used to run forever
+        let config = ExecutionConfig::builder()
+            .max_ops(1000)
+            .tape(TapeMode::Wrap)
+            .tape_size(3)
+            .build();
+        for source in &["+[<>]", "+[>>>]"] {
+            assert!(matches!(
+                run_str(source, "", &config),
+                Err(RuntimeError::FuelExhausted { .. })
+            ));
+        }
+        // a generous budget changes nothing
+        let source = std::fs::read_to_string("programs/rot13.bf").unwrap();
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .max_ops(1_000_000)
+            .build();
+        assert_eq!(
+            run_str(&source, "Hello, World!\n", &config),
+            Ok("Uryyb, Jbeyq!\n".into())
+        );
+        fuzz_one(b"<.-x-,--x><\n\n[[x<>]+>--\n],,,>");
+    }
+
+    #[test]
+    fn interrupt() {
+        static SET: AtomicBool = AtomicBool::new(true);
+        for dispatch in [Dispatch::Match, Dispatch::Threaded, Dispatch::Packed] {
+            let config = ExecutionConfig::builder()
+                .dispatch(dispatch)
+                .interrupt(&SET)
+                .build();
+            // a balanced loop, one that moves and a nested one
+            for source in ["+.[]", "+.[>>+<+<]", "+.[>+[-<]>]", "+.[[>]<]"] {
+                let program = compile(source).unwrap();
+                let mut in_out = StringInputOutput::new();
+                let ip = match config.run(&program, &mut in_out) {
+                    Err(RuntimeError::Interrupted { ip }) => ip,
+                    result => panic!("{} with {:?}: {:?}", source, dispatch, result),
+                };
+                assert!(matches!(
+                    program[ip],
+                    Ops::LoopClose(_) | Ops::MoveClose(..)
+                ));
+                assert_eq!(in_out.output(), "\u{1}");
+            }
+            // loops that do not jump back never look at the flag
+            assert_eq!(run_str("+[-.]++.", "", &config), Ok("\0\u{2}".into()));
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let config = ExecutionConfig::builder()
+            .timeout(Duration::from_millis(10))
+            .build();
+        let mut in_out = StringInputOutput::new();
+        let start = Instant::now();
+        let result = config.run(&compile("+.[]").unwrap(), &mut in_out);
+        assert!(matches!(result, Err(RuntimeError::Timeout { executed }) if executed > 2));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // the output up to then is kept
+        assert_eq!(in_out.output(), "\u{1}");
+
+        let config = ExecutionConfig::builder()
+            .timeout(Duration::from_secs(60))
+            .max_ops(100_000)
+            .build();
+        assert_eq!(
+            run_str("+[]", "", &config),
+            Err(RuntimeError::FuelExhausted { executed: 100_000 })
+        );
+        assert_eq!(run_str("+.", "", &config), Ok("\u{1}".into()));
+    }
+
+    #[test]
+    fn random_programs() {
+        // a fixed xorshift sequence, so that failures can be reproduced
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let tokens = b"+-<>[].,x\n";
+        for _ in 0..500 {
+            let len = next() % 32;
+            let source: Vec<u8> = (0..len)
+                .map(|_| tokens[(next() % tokens.len() as u64) as usize])
+                .collect();
+            fuzz_one(&source);
+        }
+    }
+}
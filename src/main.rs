@@ -1,195 +1,1581 @@
-use std::fs::File;
-use std::io::prelude::*;
+mod format;
+mod interrupt;
+mod serve;
+mod terminal;
 
-pub trait InputOutput {
-    fn read(&mut self) -> Option<char>;
-    fn write(&mut self, ch: char);
+use brainfuck::bytecode;
+use brainfuck::cache::CompileCache;
+use brainfuck::debugger::Debugger;
+use brainfuck::dialect::Dialect;
+use brainfuck::optimizer::{OptLevel, Pipeline, PASSES};
+use brainfuck::profile::Profile;
+use brainfuck::record::{RecordingIo, ReplayIo};
+use brainfuck::stats::{RunStats, TapeStats};
+use brainfuck::trace::Trace;
+use brainfuck::translate::{translate, Target};
+use brainfuck::{
+    compile_extended, split_input, CellDump, CompileError, ConsoleInputOutput, DummyInputOutput,
+    Error, ExecutionConfig, Extensions, InputOutput, IoAdapter, Language, Memory, Newline,
+    Newlines, Numbers, Ops, Position, Program, RuntimeError, TeeIo, MAX_TAPE_SIZE,
+};
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: brainfuck [--lang=bf|ook|pbrain] [--dialect FILE] [--eof=zero|minus-one|unchanged|error] [--tape=fixed|grow|wrap|infinite] [--tape-size N] [--cell-size=8|16|32] [--max-ops N] [--timeout DURATION] [--call-depth N] [--dispatch=match|threaded|packed] [-O0|-O1|-O2] [--no-pass=PASS,...] [--cache[=DIR]] [--dump-ops[=FILE]] [--no-run] [--jit] [--profile[=ops|loops]] [--trace[=FILE]] [--trace-limit N] [--trace-span START..END] [--debug] [--debug-ops] [--dump-memory[=N]] [--tape-stats] [--stats-json FILE|-] [--bench N] [--time] [--input FILE|--input-string TEXT|--bang-input] [--output FILE|--expect FILE|--generate-expected [--force]] [--tee] [--raw] [--newline=lf|crlf|native] [--io=chars|numbers] [--record FILE|--replay FILE] [[run] FILE|-|repl]
+       brainfuck compile [--lang=bf|ook|pbrain] [--dialect FILE] [--debug-ops] [-O0|-O1|-O2] [--no-pass=PASS,...] [--cache[=DIR]] [--dump-ops[=FILE]] FILE|- -o FILE
+       brainfuck translate --to c|rust|js|wasm|wat [--lang=bf|ook|pbrain] [--dialect FILE] [--eof=...] [--tape=...] [--tape-size N] [--cell-size=...] [-O0|-O1|-O2] [--no-pass=PASS,...] [--cache[=DIR]] FILE|- [-o FILE]
+       brainfuck serve --listen ADDR [--lang=bf|ook|pbrain] [--dialect FILE] [--eof=...] [--tape=...] [--cell-size=...] [--max-ops N] [--timeout DURATION] [--call-depth N] [-O0|-O1|-O2] [--newline=...] [--io=...] FILE
+       brainfuck minify [--lang=bf|ook|pbrain] [--dialect FILE] [--debug-ops] [--strip-dead] [--wrap N] FILE|-
+       brainfuck fmt [--lang=bf|ook|pbrain] [--dialect FILE] [--debug-ops] [--width N] [--in-place] FILE|-";
+
+struct Options {
+    /// The program to run, `None` starts the REPL.
+    filename: Option<String>,
+    /// What the program is written in, by default guessed from the name of
+    /// the file.
+    language: Language,
+    pipeline: Pipeline,
+    config: ExecutionConfig,
+    /// Where to print the compiled ops, `Some(None)` is stderr.
+    dump_ops: Option<Option<String>>,
+    /// Where compiled programs are kept between runs, `Some(None)` is the
+    /// default directory.
+    cache: Option<Option<String>>,
+    no_run: bool,
+    /// Run the program as machine code, see `ExecutionConfig::run_jit`.
+    jit: bool,
+    /// Which profile to print to stderr after the run.
+    profile: Option<Report>,
+    /// Where to log every executed op, `Some(None)` is stderr.
+    trace: Option<Option<String>>,
+    trace_limit: Option<u64>,
+    trace_span: Option<Range<usize>>,
+    /// Compile `#` to breakpoints and stop at them.
+    debug: bool,
+    /// Compile `#` to dumps of the cells around the pointer.
+    debug_ops: bool,
+    /// How many cells to print to stderr after the run.
+    dump_memory: Option<usize>,
+    /// Print how much of the tape the program used.
+    tape_stats: bool,
+    /// Where to write the statistics of the run as JSON, "-" is stderr.
+    stats_json: Option<String>,
+    /// How often to time the program after a warm-up run, with the output
+    /// dropped.
+    bench: Option<usize>,
+    /// Print how long compiling and running took.
+    time: bool,
+    /// What `,` reads instead of stdin.
+    input: Option<Input>,
+    /// Everything after the first `!` of the program is its input.
+    bang_input: bool,
+    /// The file `.` writes to instead of stdout.
+    output: Option<String>,
+    /// A file the output has to match, it is not written to stdout then.
+    expect: Option<String>,
+    /// Write the output to the program with the extension `.out`.
+    generate_expected: bool,
+    /// Let `generate_expected` overwrite the file.
+    force: bool,
+    /// Write to stdout as well as to `output`, `expect` or the generated file.
+    tee: bool,
+    /// Hand every key to `,` as it is pressed, if stdin is a terminal.
+    raw: bool,
+    /// How line endings are translated, untouched by default.
+    newline: Option<Newline>,
+    /// Whether `,` and `.` use bytes or decimal numbers.
+    io: IoMode,
+    /// Where to log every byte the program read and wrote.
+    record: Option<String>,
+    /// A log to take the input from and to compare the output with.
+    replay: Option<String>,
+    /// Where `serve` accepts connections.
+    listen: Option<String>,
+    /// Where `compile` and `translate` write the program instead of running
+    /// it, `translate` writes to stdout without it.
+    write_to: Option<String>,
+    /// The language `translate` writes.
+    translate: Option<Target>,
+    /// Print the commands of the program instead of running it.
+    minify: bool,
+    /// Leave out the loops that `minify` finds can never run.
+    strip_dead: bool,
+    /// How many commands `minify` prints per line.
+    wrap: Option<usize>,
+    /// Print the program laid out by `fmt` instead of running it.
+    format: bool,
+    /// How many characters of commands `fmt` puts on a line.
+    width: Option<usize>,
+    /// Let `fmt` overwrite the program instead of printing it.
+    in_place: bool,
 }
 
-// Used for Benchmarks
-pub struct DummyInputOutput;
-impl InputOutput for DummyInputOutput {
-    fn read(&mut self) -> Option<char> {
-        None
-    }
-    fn write(&mut self, _: char) {}
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Input {
+    File(String),
+    Bytes(Vec<u8>),
 }
 
-// Used for tests
-pub struct StringInputOutput {
-    output: String,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum IoMode {
+    /// Every byte stands for itself.
+    #[default]
+    Chars,
+    /// Decimal numbers separated by whitespace, see [`Numbers`].
+    Numbers,
 }
-impl StringInputOutput {
-    fn new() -> StringInputOutput {
-        StringInputOutput {
-            output: String::new(),
+
+impl std::str::FromStr for IoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IoMode, String> {
+        match s {
+            "chars" => Ok(IoMode::Chars),
+            "numbers" => Ok(IoMode::Numbers),
+            _ => Err(format!("invalid io '{}', expected chars or numbers", s)),
         }
     }
 }
-impl InputOutput for StringInputOutput {
-    fn read(&mut self) -> Option<char> {
-        None
-    }
-    fn write(&mut self, ch: char) {
-        self.output.push(ch);
-    }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Report {
+    /// Executions per kind of op.
+    Ops,
+    /// The loops with the most iterations.
+    Loops,
 }
 
-pub struct ConsoleInputOutput;
-impl InputOutput for ConsoleInputOutput {
-    fn read(&mut self) -> Option<char> {
-        None
-    }
-    fn write(&mut self, ch: char) {
-        print!("{}", ch);
+impl std::str::FromStr for Report {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Report, String> {
+        match s {
+            "ops" => Ok(Report::Ops),
+            "loops" => Ok(Report::Loops),
+            _ => Err(format!("invalid profile '{}', expected ops or loops", s)),
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-enum Ops {
-    Move(isize),
-    Mod(i8),
-    LoopOpen(usize),
-    LoopClose(usize),
-    SetCell(i8),
-    SearchZeroCell(isize), // stores the step with
-    Print,
-    Read,
-    End,
-}
+/// How many loops --profile=loops lists.
+const HOT_LOOPS: usize = 10;
 
-fn compile(source: &str) -> Result<Vec<Ops>, String> {
-    let converted = source.chars().filter_map(|token| match token {
-        '<' => Some(Ops::Move(-1)),
-        '>' => Some(Ops::Move(1)),
-        '-' => Some(Ops::Mod(-1)),
-        '+' => Some(Ops::Mod(1)),
-        '.' => Some(Ops::Print),
-        ',' => Some(Ops::Read),
-        '[' => Some(Ops::LoopOpen(0)),
-        ']' => Some(Ops::LoopClose(0)),
-        _ => None,
-    });
+/// How many cells --dump-memory prints without a count.
+const DUMPED_CELLS: usize = 32;
 
-    // Optimize
-    let mut compiled = Vec::new();
-    {
-        let mut prepre = None;
-        let mut pre = None;
-        for cur in converted {
-            match (prepre, pre, cur) {
-                (_, Some(Ops::Move(v1)), Ops::Move(v2)) => {
-                    pre = Some(Ops::Move(v1 + v2));
-                }
-                (_, Some(Ops::Mod(v1)), Ops::Mod(v2)) => {
-                    pre = Some(Ops::Mod(v1 + v2));
-                }
-                (Some(Ops::LoopOpen(_)), Some(Ops::Mod(-1)), Ops::LoopClose(_)) => {
-                    prepre = None;
-                    pre = Some(Ops::SetCell(0));
-                }
-                (Some(Ops::LoopOpen(_)), Some(Ops::Move(n)), Ops::LoopClose(_)) => {
-                    prepre = None;
-                    pre = Some(Ops::SearchZeroCell(n));
-                }
-                (_, Some(Ops::SetCell(0)), Ops::Mod(v)) => {
-                    pre = Some(Ops::SetCell(v));
+/// How many bytes before and after the first difference --expect shows.
+const CONTEXT_BYTES: usize = 8;
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut filename = None;
+    let mut language = None;
+    let mut dialect = None;
+    let mut config = ExecutionConfig::builder();
+    let mut level = OptLevel::default();
+    let mut disabled = Vec::new();
+    let mut dump_ops = None;
+    let mut cache = None;
+    let mut no_run = false;
+    let mut jit = false;
+    let mut profile = None;
+    let mut trace = None;
+    let mut trace_limit = None;
+    let mut trace_span = None;
+    let mut debug = false;
+    let mut debug_ops = false;
+    let mut dump_memory = None;
+    let mut tape_stats = false;
+    let mut stats_json = None;
+    let mut bench = None;
+    let mut time = false;
+    let mut input = None;
+    let mut output = None;
+    let mut bang_input = false;
+    let mut raw = false;
+    let mut tee = false;
+    let mut expect = None;
+    let mut generate_expected = false;
+    let mut force = false;
+    let mut newline = None;
+    let mut io = IoMode::default();
+    let mut record = None;
+    let mut replay = None;
+    let mut command = None;
+    let mut listen = None;
+    let mut write_to = None;
+    let mut to = None;
+    let mut strip_dead = false;
+    let mut wrap = None;
+    let mut width = None;
+    let mut in_place = false;
+
+    while let Some(arg) = args.next() {
+        // options accept both "--flag=value" and "--flag value"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.into())),
+            _ => (arg.clone(), None),
+        };
+        let value = |args: &mut dyn Iterator<Item = String>| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(format!("missing value for {}", flag))
+        };
+
+        match flag.as_str() {
+            "--lang" => language = Some(value(&mut args)?.parse()?),
+            "--dialect" => dialect = Some(value(&mut args)?),
+            "--eof" => config = config.eof(value(&mut args)?.parse()?),
+            "--tape" => config = config.tape(value(&mut args)?.parse()?),
+            "--cell-size" => config = config.cell_size(value(&mut args)?.parse()?),
+            "--tape-size" => config = config.tape_size(parse_tape_size(&value(&mut args)?)?),
+            "--max-ops" => config = config.max_ops(parse_max_ops(&value(&mut args)?)?),
+            "--timeout" => config = config.timeout(parse_duration(&value(&mut args)?)?),
+            "--call-depth" => config = config.call_depth(parse_call_depth(&value(&mut args)?)?),
+            "--dispatch" => config = config.dispatch(value(&mut args)?.parse()?),
+            "--no-pass" => disabled.extend(value(&mut args)?.split(',').map(String::from)),
+            // the path is optional, so it has to be given inline
+            "--dump-ops" => dump_ops = Some(inline_value.clone()),
+            // the directory is optional, so it has to be given inline
+            "--cache" => cache = Some(inline_value.clone()),
+            "--no-run" => no_run = true,
+            "--jit" => jit = true,
+            // the report is optional, so it has to be given inline
+            "--profile" => profile = Some(inline_value.as_deref().unwrap_or("ops").parse()?),
+            "--trace" => trace = Some(inline_value.clone()),
+            "--trace-limit" => trace_limit = Some(parse_max_ops(&value(&mut args)?)?),
+            "--trace-span" => trace_span = Some(parse_span(&value(&mut args)?)?),
+            "--debug" => debug = true,
+            "--debug-ops" => debug_ops = true,
+            // the count is optional, so it has to be given inline
+            "--dump-memory" => {
+                dump_memory = Some(match inline_value.as_deref() {
+                    Some(count) => parse_cell_count(count)?,
+                    None => DUMPED_CELLS,
+                })
+            }
+            "--tape-stats" => tape_stats = true,
+            "--stats-json" => stats_json = Some(value(&mut args)?),
+            "--bench" => bench = Some(parse_run_count(&value(&mut args)?)?),
+            "--time" => time = true,
+            "--input" | "--input-string" => {
+                let value = value(&mut args)?;
+                let given = if flag == "--input" {
+                    Input::File(value)
+                } else {
+                    Input::Bytes(unescape(&value)?)
+                };
+                if input.replace(given).is_some() {
+                    return Err("only one of --input and --input-string can be given".into());
                 }
-                _ => {
-                    if let Some(o) = prepre {
-                        compiled.push(o);
-                    }
-                    prepre = pre;
-                    pre = Some(cur);
+            }
+            "--bang-input" => bang_input = true,
+            "--output" => output = Some(value(&mut args)?),
+            "--raw" => raw = true,
+            "--tee" => tee = true,
+            "--expect" => expect = Some(value(&mut args)?),
+            "--generate-expected" => generate_expected = true,
+            "--force" => force = true,
+            "--newline" => newline = Some(value(&mut args)?.parse()?),
+            "--io" => io = value(&mut args)?.parse()?,
+            "--record" => record = Some(value(&mut args)?),
+            "--replay" => replay = Some(value(&mut args)?),
+            "--listen" => listen = Some(value(&mut args)?),
+            "-o" => write_to = Some(value(&mut args)?),
+            "--to" => to = Some(value(&mut args)?.parse()?),
+            "--strip-dead" => strip_dead = true,
+            "--wrap" => wrap = Some(parse_width(&value(&mut args)?)?),
+            "--width" => width = Some(parse_width(&value(&mut args)?)?),
+            "--in-place" => in_place = true,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if flag.starts_with("-O") => level = flag[2..].parse()?,
+            // a lone - is stdin
+            _ if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option '{}'", arg))
+            }
+            "serve" | "compile" | "translate" | "minify" | "fmt" | "run"
+                if filename.is_none() && command.is_none() =>
+            {
+                command = Some(arg)
+            }
+            _ => {
+                if filename.replace(arg).is_some() {
+                    return Err("only one program can be given".into());
                 }
-            };
+            }
         }
-        if let Some(o) = prepre {
-            compiled.push(o);
+    }
+
+    if [profile.is_some(), trace.is_some(), debug]
+        .iter()
+        .filter(|&&on| on)
+        .count()
+        > 1
+    {
+        return Err("only one of --profile, --trace and --debug can be given".into());
+    }
+    // the commands would be read from the same stdin as the program
+    if debug && filename.as_deref() == Some("-") {
+        return Err("--debug cannot read the program from stdin".into());
+    }
+    // both of them compile `#`
+    if debug && debug_ops {
+        return Err("--debug-ops cannot be combined with --debug".into());
+    }
+    // the debugger reads whole lines from the terminal
+    if raw && debug {
+        return Err("--raw cannot be combined with --debug".into());
+    }
+    // the recording is the input and what the output is compared with
+    if replay.is_some()
+        && (input.is_some()
+            || bang_input
+            || output.is_some()
+            || raw
+            || record.is_some()
+            || expect.is_some()
+            || generate_expected)
+    {
+        return Err("--replay cannot be combined with --input, --input-string, --bang-input, --output, --raw, --record, --expect or --generate-expected".into());
+    }
+    if [output.is_some(), expect.is_some(), generate_expected]
+        .iter()
+        .filter(|&&on| on)
+        .count()
+        > 1
+    {
+        return Err("only one of --output, --expect and --generate-expected can be given".into());
+    }
+    if force && !generate_expected {
+        return Err("--force needs --generate-expected".into());
+    }
+    // the file is written next to the program
+    if generate_expected && filename.as_deref() == Some("-") {
+        return Err("--generate-expected cannot read the program from stdin".into());
+    }
+    let serve = command.as_deref() == Some("serve");
+    let compile = command.as_deref() == Some("compile");
+    let translate = command.as_deref() == Some("translate");
+    let minify = command.as_deref() == Some("minify");
+    let format = command.as_deref() == Some("fmt");
+    if serve != listen.is_some() {
+        return Err("serve needs --listen ADDR, and --listen needs serve".into());
+    }
+    // the clients are the input and the output, there is nobody to report to
+    let per_run = [
+        ("--jit", jit),
+        ("--profile", profile.is_some()),
+        ("--trace", trace.is_some()),
+        ("--debug", debug),
+        ("--dump-memory", dump_memory.is_some()),
+        ("--tape-stats", tape_stats),
+        ("--stats-json", stats_json.is_some()),
+        ("--bench", bench.is_some()),
+        ("--time", time),
+        ("--input", matches!(input, Some(Input::File(_)))),
+        ("--input-string", matches!(input, Some(Input::Bytes(_)))),
+        ("--bang-input", bang_input),
+        ("--output", output.is_some()),
+        ("--raw", raw),
+        ("--record", record.is_some()),
+        ("--replay", replay.is_some()),
+        ("--expect", expect.is_some()),
+        ("--generate-expected", generate_expected),
+    ];
+    if serve {
+        if matches!(filename.as_deref(), None | Some("-" | "repl")) {
+            return Err("serve needs a FILE".into());
         }
-        if let Some(o) = pre {
-            compiled.push(o);
+        if let Some(flags) = given(&per_run) {
+            return Err(format!("serve cannot be combined with {}", flags));
         }
     }
+    if compile && write_to.is_none() || write_to.is_some() && !compile && !translate {
+        return Err("compile needs -o FILE, and -o needs compile or translate".into());
+    }
+    if translate != to.is_some() {
+        return Err("translate needs --to LANGUAGE, and --to needs translate".into());
+    }
+    if (strip_dead || wrap.is_some()) && !minify {
+        return Err("--strip-dead and --wrap need minify".into());
+    }
+    if (width.is_some() || in_place) && !format {
+        return Err("--width and --in-place need fmt".into());
+    }
+    if in_place && filename.as_deref() == Some("-") {
+        return Err("--in-place cannot read the program from stdin".into());
+    }
+    if compile || translate || minify || format {
+        let command = command.unwrap_or_default();
+        if matches!(filename.as_deref(), None | Some("repl")) {
+            return Err(format!("{} needs a FILE", command));
+        }
+        let mut flags = vec![("--no-run", no_run)];
+        flags.extend(per_run);
+        if let Some(flags) = given(&flags) {
+            return Err(format!("{} cannot be combined with {}", command, flags));
+        }
+    }
+    // the translation reads and writes bytes on its own
+    if translate && (newline.is_some() || io == IoMode::Numbers) {
+        return Err("translate cannot be combined with --newline or --io=numbers".into());
+    }
+    if tee && output.is_none() && expect.is_none() && !generate_expected {
+        return Err("--tee needs --output, --expect or --generate-expected".into());
+    }
+    let language = match (language, dialect) {
+        (Some(_), Some(_)) => return Err("--dialect cannot be combined with --lang".into()),
+        (None, Some(path)) => Language::Dialect(read_dialect(&path)?),
+        (Some(language), None) => language,
+        (None, None) => filename
+            .as_deref()
+            .map_or(Language::Brainfuck, Language::from_path),
+    };
+    // every Ook! token would split the program
+    if bang_input && language == Language::Ook {
+        return Err("--bang-input cannot be combined with Ook!".into());
+    }
+    if bang_input && input.is_some() {
+        return Err("--bang-input cannot be combined with --input or --input-string".into());
+    }
+    if stats_json.is_some() && (trace.is_some() || debug) {
+        return Err("--stats-json cannot be combined with --trace or --debug".into());
+    }
+    // the runs read nothing, drop the output and are compared with each other
+    // and --stats-json reports the runs
+    if bench.is_some() {
+        let mut flags = vec![("--no-run", no_run)];
+        flags.extend(
+            per_run
+                .iter()
+                .filter(|(name, _)| !matches!(*name, "--bench" | "--stats-json")),
+        );
+        if let Some(flags) = given(&flags) {
+            return Err(format!("--bench cannot be combined with {}", flags));
+        }
+    }
+    // only the interpreter looks at every op
+    if jit && (profile.is_some() || trace.is_some() || debug || tape_stats || stats_json.is_some())
+    {
+        return Err("--jit cannot be combined with --profile, --trace, --debug, --tape-stats or --stats-json".into());
+    }
+    // --stats-json collects the profile along with everything else
+    let profiled_alone = profile.is_some() && stats_json.is_none();
+    if (dump_memory.is_some() || tape_stats) && (profiled_alone || trace.is_some() || debug) {
+        return Err(
+            "--dump-memory and --tape-stats cannot be combined with --profile, --trace or --debug"
+                .into(),
+        );
+    }
 
-    // calculate all loop jump destinations
-    let mut stack: Vec<usize> = vec![];
-    for i in 0..compiled.len() {
-        match compiled[i] {
-            Ops::LoopOpen(_) => stack.push(i),
-            Ops::LoopClose(_) => {
-                if let Some(start_pos) = stack.pop() {
-                    compiled[start_pos] = Ops::LoopOpen(i);
-                    compiled[i] = Ops::LoopClose(start_pos);
-                } else {
-                    return Err("missing [ for ]".into());
+    // without a program there is nothing to dump, profile, trace or debug
+    if matches!(filename.as_deref(), None | Some("repl")) {
+        filename = None;
+        let mut file_only = vec![
+            ("--dump-ops", dump_ops.is_some()),
+            ("--cache", cache.is_some()),
+            ("--no-run", no_run),
+            // the REPL tracks the bytes written to end them with a newline
+            ("--io=numbers", io == IoMode::Numbers),
+        ];
+        file_only.extend(per_run);
+        if let Some(flags) = given(&file_only) {
+            return Err(format!("{} cannot be used without a FILE", flags));
+        }
+    }
+
+    let mut pipeline = Pipeline::new(level);
+    for name in disabled {
+        pipeline = pipeline.disable(&name)?;
+    }
+
+    Ok(Options {
+        filename,
+        language,
+        pipeline,
+        config: config.interrupt(&interrupt::INTERRUPTED).build(),
+        dump_ops,
+        cache,
+        no_run,
+        jit,
+        profile,
+        trace,
+        trace_limit,
+        trace_span,
+        debug,
+        debug_ops,
+        dump_memory,
+        tape_stats,
+        stats_json,
+        bench,
+        time,
+        input,
+        bang_input,
+        output,
+        expect,
+        generate_expected,
+        force,
+        tee,
+        raw,
+        newline,
+        io,
+        record,
+        replay,
+        listen,
+        write_to,
+        translate: to,
+        minify,
+        strip_dead,
+        wrap,
+        format,
+        width,
+        in_place,
+    })
+}
+
+/// The names of the flags that are set, for an error about them, `None` if
+/// there are none.
+fn given(flags: &[(&str, bool)]) -> Option<String> {
+    let names: Vec<_> = flags
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| *name)
+        .collect();
+    match names.split_last()? {
+        (last, []) => Some(last.to_string()),
+        (last, names) => Some(format!("{} and {}", names.join(", "), last)),
+    }
+}
+
+/// "cannot open 'PATH': REASON", the same as for the program.
+fn cannot_open(path: &str, source: std::io::Error) -> String {
+    let path = path.to_string();
+    Error::Io { path, source }.to_string()
+}
+
+fn read_dialect(path: &str) -> Result<Dialect, String> {
+    let mapping = std::fs::read_to_string(path).map_err(|err| cannot_open(path, err))?;
+    mapping
+        .parse()
+        .map_err(|err| format!("invalid dialect '{}': {}", path, err))
+}
+
+fn parse_tape_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 && size <= MAX_TAPE_SIZE => Ok(size),
+        _ => Err(format!(
+            "invalid tape size '{}', expected a number between 1 and {}",
+            value, MAX_TAPE_SIZE
+        )),
+    }
+}
+
+fn parse_cell_count(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!(
+            "invalid cell count '{}', expected a positive number",
+            value
+        )),
+    }
+}
+
+/// The bytes of `value` with the escapes `\n`, `\r`, `\t`, `\0`, `\\` and
+/// `\xNN` replaced.
+fn unescape(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    // from_str_radix would accept a sign as well
+                    Ok(byte) if hex.len() == 2 && !hex.starts_with('+') => byte,
+                    _ => {
+                        return Err(format!(
+                            "invalid escape '\\x{}', expected two hex digits",
+                            hex
+                        ))
+                    }
                 }
             }
-            _ => {
-                // not relevant for this optimization
+            Some(other) => return Err(format!("unknown escape '\\{}'", other)),
+            None => return Err("unfinished escape at the end of the input".into()),
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+fn parse_max_ops(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid op limit '{}', expected a number", value))
+}
+
+fn parse_run_count(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(runs) if runs > 0 => Ok(runs),
+        _ => Err(format!(
+            "invalid run count '{}', expected a positive number",
+            value
+        )),
+    }
+}
+
+fn parse_width(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(width) if width > 0 => Ok(width),
+        _ => Err(format!(
+            "invalid line width '{}', expected a positive number",
+            value
+        )),
+    }
+}
+
+fn parse_call_depth(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(depth) if depth > 0 => Ok(depth),
+        _ => Err(format!(
+            "invalid call depth '{}', expected a positive number",
+            value
+        )),
+    }
+}
+
+/// Parses durations like "5s", "250ms" or "2m", a plain number is in seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let scale = match unit {
+        "ms" => Some(0.001),
+        "" | "s" => Some(1.0),
+        "m" => Some(60.0),
+        _ => None,
+    };
+    let parsed = number.parse::<f64>().ok().zip(scale);
+    match parsed.and_then(|(number, scale)| Duration::try_from_secs_f64(number * scale).ok()) {
+        Some(duration) => Ok(duration),
+        None => Err(format!(
+            "invalid timeout '{}', expected a duration like 5s, 250ms or 2m",
+            value
+        )),
+    }
+}
+
+/// Parses a range of source bytes like "10..20".
+fn parse_span(value: &str) -> Result<Range<usize>, String> {
+    let span = value
+        .split_once("..")
+        .and_then(|(start, end)| Some(start.parse().ok()?..end.parse().ok()?));
+    match span {
+        Some(span) if span.start < span.end => Ok(span),
+        _ => Err(format!(
+            "invalid span '{}', expected a range of source bytes like 10..20",
+            value
+        )),
+    }
+}
+
+/// The first `count` cells of `memory` in decimal and hex, 16 per row, the
+/// current cell marked with a `*`.
+fn dump_memory(memory: &Memory, count: usize) -> String {
+    const ROW: usize = 16;
+    let cells = &memory.cells()[..count.min(memory.len())];
+    let mut dump = String::new();
+    for (row, chunk) in cells.chunks(ROW).enumerate() {
+        let marked = |i: usize| {
+            if row * ROW + i == memory.pos() {
+                "*"
+            } else {
+                ""
             }
         };
+        let decimal: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{}{}", cell, marked(i)))
+            .collect();
+        let hex: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:02x}{}", cell, marked(i)))
+            .collect();
+        dump += &format!(
+            "{:>6}: {} | {}\n",
+            row * ROW,
+            decimal.join(" "),
+            hex.join(" ")
+        );
     }
+    dump + &format!("pointer at cell {} of {}\n", memory.pos(), memory.len())
+}
 
-    if stack.is_empty() {
-        compiled.push(Ops::End);
-        Ok(compiled)
-    } else {
-        Err("missing ] for [".into())
+/// Runs `program` while logging every op as requested by `options`.
+fn run_traced(
+    options: &Options,
+    program: &Program,
+    in_out: &mut dyn InputOutput,
+    path: Option<&str>,
+) -> Result<(), RuntimeError> {
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("cannot write {}: {}", path, err);
+                std::process::exit(2);
+            }
+        },
+        None => Box::new(BufWriter::new(std::io::stderr())),
+    };
+    let mut trace = Trace::new(&mut out);
+    if let Some(limit) = options.trace_limit {
+        trace = trace.limit(limit);
+    }
+    if let Some(span) = options.trace_span.clone() {
+        trace = trace.span(span);
     }
+    let result = options.config.run_traced(program, in_out, &mut trace);
+    if let Some(err) = trace.error() {
+        eprintln!("\ntrace stopped: {}", err);
+    }
+    let _ = out.flush();
+    result
 }
 
-fn execute(ops: &[Ops], in_out: &mut dyn InputOutput) {
-    let mut memory = vec![0i8; 30000]; // a vec is much faster than a normal array
-    let mut pos: usize = 0;
-    let mut ip: usize = 0;
+/// The wall times of the runs of --bench.
+struct Bench {
+    /// Sorted, the shortest first.
+    times: Vec<Duration>,
+    /// How many ops a run executes.
+    instructions: u64,
+}
 
-    'main: loop {
-        match ops[ip] {
-            Ops::Move(val) => pos = ((pos as isize) + val) as usize,
-            Ops::Mod(val) => memory[pos] = memory[pos].wrapping_add(val),
-            Ops::LoopOpen(end) => {
-                if memory[pos] == 0 {
-                    ip = end;
-                }
+impl Bench {
+    /// Times `runs` runs of `program` that read EOF and drop the output. The
+    /// warm-up run before them counted the `instructions`.
+    fn run(
+        config: &ExecutionConfig,
+        program: &Program,
+        runs: usize,
+        instructions: u64,
+    ) -> Result<Bench, RuntimeError> {
+        let mut times = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let started = Instant::now();
+            config.run(program, &mut DummyInputOutput)?;
+            times.push(started.elapsed());
+        }
+        times.sort();
+        Ok(Bench {
+            times,
+            instructions,
+        })
+    }
+
+    fn median(&self) -> Duration {
+        let count = self.times.len();
+        (self.times[(count - 1) / 2] + self.times[count / 2]) / 2
+    }
+}
+
+/// A single summary line.
+impl std::fmt::Display for Bench {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let median = self.median();
+        write!(
+            f,
+            "bench: {} runs, min {:?}, median {:?}, max {:?}, {} instructions, {:.0} instructions/s",
+            self.times.len(),
+            self.times[0],
+            median,
+            self.times[self.times.len() - 1],
+            self.instructions,
+            self.instructions as f64 / median.as_secs_f64()
+        )
+    }
+}
+
+/// The two lines of --time, with the executed ops if they were counted.
+fn timing(compile_time: Duration, run_time: Duration, instructions: Option<u64>) -> String {
+    let ops = match instructions {
+        Some(count) => format!(", {} instructions", count),
+        None => String::new(),
+    };
+    format!(
+        "compile: {:.1?}\nexecute: {:.1?}{}\n",
+        compile_time, run_time, ops
+    )
+}
+
+const REPL_HELP: &str = "enter brainfuck code or one of :reset, :dump START..END, :help, :quit";
+
+/// The interactive mode, every entry runs on the tape the previous one left.
+struct Repl {
+    config: ExecutionConfig,
+    extensions: Extensions,
+    pipeline: Pipeline,
+    memory: Memory,
+    /// The lines of an entry whose brackets are not balanced yet.
+    pending: String,
+    /// --newline and --io, which go around the [`LastWritten`].
+    newline: Option<Newline>,
+    io: IoMode,
+}
+
+impl Repl {
+    fn new(config: ExecutionConfig, extensions: Extensions, pipeline: Pipeline) -> Repl {
+        Repl {
+            config,
+            extensions,
+            // entries do not start on a cleared tape, so a leading loop is not dead
+            pipeline: pipeline
+                .disable("dead_loops")
+                .expect("dead_loops is a pass"),
+            memory: Memory::new(&config),
+            pending: String::new(),
+            newline: None,
+            io: IoMode::Chars,
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        if self.pending.is_empty() {
+            "bf> "
+        } else {
+            "... "
+        }
+    }
+
+    /// Handles one line and returns what to print after the output of the
+    /// program, `None` ends the session.
+    fn eval(&mut self, line: &str, in_out: &mut dyn InputOutput) -> Option<String> {
+        if let Some(command) = line.trim().strip_prefix(':') {
+            return self.command(command);
+        }
+        self.pending.push_str(line);
+        if !line.ends_with('\n') {
+            self.pending.push('\n');
+        }
+        let program = match compile_extended(&self.pending, &self.extensions, &self.pipeline) {
+            Ok(program) => program,
+            // keep reading until the brackets are balanced and the pairs complete
+            Err(errors)
+                if errors.iter().all(|err| {
+                    matches!(
+                        err,
+                        CompileError::UnmatchedOpen { .. }
+                            | CompileError::UnmatchedProcedureOpen { .. }
+                            | CompileError::UnpairedOokToken { .. }
+                    )
+                }) =>
+            {
+                return Some(String::new())
             }
-            Ops::LoopClose(start) => {
-                if memory[pos] != 0 {
-                    ip = start;
-                }
+            Err(errors) => {
+                self.pending.clear();
+                return Some(format!("{}\n", Error::Compile(errors)));
             }
-            Ops::SetCell(value) => memory[pos] = value,
-            Ops::SearchZeroCell(step) => {
-                while memory[pos] != 0 {
-                    pos = ((pos as isize) + step) as usize;
-                }
+        };
+        self.pending.clear();
+
+        let mut tracked = LastWritten { in_out, last: None };
+        let mut translated = translated(Box::new(&mut tracked), self.newline, self.io);
+        let result = self
+            .config
+            .run_on(&program, translated.as_mut(), &mut self.memory);
+        drop(translated);
+        let mut answer = match tracked.last {
+            Some(byte) if byte != b'\n' => "\n".to_string(),
+            _ => String::new(),
+        };
+        if let Err(err) = result {
+            answer += &format!("Runtime error: {}\n", err);
+        }
+        answer += &format!("pos={} cell={}\n", self.memory.pos(), self.current_cell());
+        Some(answer)
+    }
+
+    fn command(&mut self, command: &str) -> Option<String> {
+        let mut words = command.split_whitespace();
+        let answer = match (words.next(), words.next()) {
+            (Some("reset"), None) => {
+                self.memory = Memory::new(&self.config);
+                self.pending.clear();
+                "tape cleared\n".to_string()
             }
-            Ops::Print => in_out.write(memory[pos] as u8 as char),
-            Ops::Read => memory[pos] = in_out.read().unwrap() as i8,
-            Ops::End => break 'main,
+            (Some("dump"), Some(range)) => match parse_span(range) {
+                Ok(range) if range.start < self.memory.len() => {
+                    let end = range.end.min(self.memory.len());
+                    let cells: Vec<String> = (range.start..end)
+                        .map(|i| self.memory.cell(i).unwrap_or(0).to_string())
+                        .collect();
+                    format!("{}..{}: {}\n", range.start, end, cells.join(" "))
+                }
+                Ok(_) => format!("the tape has {} cells\n", self.memory.len()),
+                Err(msg) => format!("{}\n", msg),
+            },
+            (Some("quit"), None) => return None,
+            _ => format!("{}\n", REPL_HELP),
         };
-        ip += 1;
+        Some(answer)
+    }
+
+    fn current_cell(&self) -> u32 {
+        self.memory.cell(self.memory.pos()).unwrap_or(0)
     }
 }
 
-pub fn run(filename: &str, in_out: &mut dyn InputOutput) {
-    let mut file = File::open(filename).unwrap();
-    let mut source =
-        String::with_capacity(file.metadata().map(|m| m.len() as usize + 1).unwrap_or(0));
-    file.read_to_string(&mut source).unwrap();
+/// Remembers the last byte a program printed, so that the REPL knows
+/// whether its summary needs a line of its own.
+struct LastWritten<'a> {
+    in_out: &'a mut dyn InputOutput,
+    last: Option<u8>,
+}
 
-    match compile(&source) {
-        Ok(ops) => {
-            // println!("{:?}", ops);
-            execute(&ops, in_out)
+impl InputOutput for LastWritten<'_> {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.in_out.read_byte()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.last = Some(byte);
+        self.in_out.write_byte(byte);
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.in_out.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.in_out.dump(dump)
+    }
+}
+
+/// Writes everything before `,` waits for stdin, where Ctrl+C ends the
+/// process right away, see [`interrupt::while_reading`].
+struct Interruptible<'a>(Box<dyn InputOutput + 'a>);
+
+impl InputOutput for Interruptible<'_> {
+    fn read_byte(&mut self) -> Option<u8> {
+        let _ = self.0.flush();
+        interrupt::while_reading(|| self.0.read_byte())
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.0.write_byte(byte)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.0.dump(dump)
+    }
+}
+
+/// Writes every byte right away, so that the output of a program being
+/// debugged shows up before the next prompt.
+struct Unbuffered<'a>(Box<dyn InputOutput + 'a>);
+
+impl InputOutput for Unbuffered<'_> {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.0.read_byte()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.0.write_byte(byte);
+        let _ = self.0.flush();
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.0.dump(dump)
+    }
+}
+
+/// Reads entries from stdin until it is closed or `:quit` is entered. `,`
+/// reads from stdin as well, so the input of a program follows its line.
+fn repl(options: &Options) {
+    let extensions = Extensions {
+        language: options.language.clone(),
+        dumps: options.debug_ops,
+        ..Extensions::default()
+    };
+    let mut repl = Repl {
+        newline: options.newline,
+        io: options.io,
+        ..Repl::new(options.config, extensions, options.pipeline.clone())
+    };
+    let mut in_out = Interruptible(Box::new(ConsoleInputOutput::new()));
+    // Ctrl+C stops the entry that runs, and ends the REPL at the prompt
+    interrupt::install();
+    println!("{}", REPL_HELP);
+    loop {
+        print!("{}", repl.prompt());
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match interrupt::while_reading(|| std::io::stdin().read_line(&mut line)) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let answer = repl.eval(&line, &mut in_out);
+        let _ = in_out.flush();
+        interrupt::INTERRUPTED.store(false, Ordering::SeqCst);
+        match answer {
+            Some(answer) => print!("{}", answer),
+            None => break,
         }
-        Err(msg) => println!("Compilation error {}", msg),
     }
 }
 
+/// Where the program reads from and writes to, the files are opened right
+/// away. With --expect and --generate-expected the output is collected in
+/// `expected`.
+fn program_io<'a>(
+    options: &Options,
+    replay: Option<&'a mut ReplayIo>,
+    expected: Option<&'a mut Vec<u8>>,
+) -> Result<Box<dyn InputOutput + 'a>, String> {
+    let open = |path: &str| std::fs::File::open(path).map_err(|err| cannot_open(path, err));
+    let create = |path: &str| {
+        std::fs::File::create(path).map_err(|err| format!("cannot write {}: {}", path, err))
+    };
+    if let Some(replay) = replay {
+        return Ok(translated(Box::new(replay), options.newline, options.io));
+    }
+    let input: Option<Box<dyn Read>> = match &options.input {
+        Some(Input::File(path)) => Some(Box::new(open(path)?)),
+        Some(Input::Bytes(bytes)) => Some(Box::new(std::io::Cursor::new(bytes.clone()))),
+        None => None,
+    };
+    let mut output: Option<Box<dyn Write + 'a>> = match (&options.output, expected) {
+        (Some(path), _) => Some(Box::new(BufWriter::new(create(path)?))),
+        (None, Some(expected)) => Some(Box::new(expected)),
+        (None, None) => None,
+    };
+    // with --tee the program writes to the console, and the file gets a copy
+    let copy = if options.tee { output.take() } else { None };
+    let in_out: Box<dyn InputOutput + 'a> = match (input, output) {
+        (None, None) => Box::new(ConsoleInputOutput::new()),
+        (input, output) => {
+            let input = input.unwrap_or_else(|| Box::new(std::io::stdin()));
+            let output = output.unwrap_or_else(|| Box::new(std::io::stdout()));
+            Box::new(IoAdapter::new(input, output))
+        }
+    };
+    let in_out = match copy {
+        Some(copy) => Box::new(TeeIo::new(in_out, copy)),
+        None => in_out,
+    };
+    let mut in_out = recorded(in_out, options, create)?;
+    if options.input.is_none() {
+        in_out = Box::new(Interruptible(in_out));
+    }
+    if options.debug {
+        in_out = Box::new(Unbuffered(in_out));
+    }
+    Ok(translated(in_out, options.newline, options.io))
+}
+
+/// Where --generate-expected writes the output of `filename`, an error if
+/// the file exists and may not be overwritten.
+fn expected_file(filename: &str, force: bool) -> Result<PathBuf, String> {
+    let path = Path::new(filename).with_extension("out");
+    if path.exists() && !force {
+        return Err(format!(
+            "{} exists already, --force overwrites it",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
+
+/// Compares the output of --expect with the file byte by byte. The report
+/// shows where they differ, the bytes around that and both lengths.
+fn compare_output(expected: &[u8], output: &[u8]) -> Result<(), String> {
+    let offset = match output.iter().zip(expected).position(|(a, b)| a != b) {
+        Some(offset) => offset,
+        None if output.len() == expected.len() => return Ok(()),
+        None => output.len().min(expected.len()),
+    };
+    let around = |bytes: &[u8]| {
+        let start = offset.saturating_sub(CONTEXT_BYTES).min(bytes.len());
+        let end = bytes.len().min(offset + CONTEXT_BYTES);
+        format!("\"{}\"", bytes[start..end].escape_ascii())
+    };
+    Err(format!(
+        "the output differs from the expected one at byte {}\nexpected {}\nfound    {}\nthe output has {} bytes, the expected one {}",
+        offset,
+        around(expected),
+        around(output),
+        output.len(),
+        expected.len()
+    ))
+}
+
+/// Applies --record to `in_out`. The bytes are logged before --newline and
+/// --io see them, so that a replay with the same options reproduces the run.
+fn recorded<'a>(
+    in_out: Box<dyn InputOutput + 'a>,
+    options: &Options,
+    create: impl Fn(&str) -> Result<std::fs::File, String>,
+) -> Result<Box<dyn InputOutput + 'a>, String> {
+    Ok(match &options.record {
+        Some(path) => Box::new(RecordingIo::new(in_out, BufWriter::new(create(path)?))),
+        None => in_out,
+    })
+}
+
+/// Applies --newline and --io to `in_out`, the numbers are made of
+/// translated bytes.
+fn translated<'a>(
+    in_out: Box<dyn InputOutput + 'a>,
+    newline: Option<Newline>,
+    io: IoMode,
+) -> Box<dyn InputOutput + 'a> {
+    let in_out: Box<dyn InputOutput + 'a> = match newline {
+        Some(newline) => Box::new(Newlines::new(in_out, newline)),
+        None => in_out,
+    };
+    match io {
+        IoMode::Chars => in_out,
+        IoMode::Numbers => Box::new(Numbers::new(in_out)),
+    }
+}
+
+/// The commands of `source` as brainfuck and nothing else, in lines of
+/// `wrap` commands. Loops that can never run, like a comment at the start,
+/// are left out if `strip_dead` is set.
+fn minify(
+    source: &str,
+    extensions: &Extensions,
+    strip_dead: bool,
+    wrap: Option<usize>,
+) -> Result<String, Vec<CompileError>> {
+    let mut pipeline = Pipeline::new(OptLevel::O0);
+    if strip_dead {
+        // any other pass would change the commands
+        let others = PASSES.iter().filter(|pass| pass.name != "dead_loops");
+        pipeline = others.fold(Pipeline::new(OptLevel::O2), |pipeline, pass| {
+            pipeline.disable(pass.name).unwrap()
+        });
+    }
+    // every command is an op of its own at O0, which reads back as itself
+    let commands = compile_extended(source, extensions, &pipeline)?.to_brainfuck();
+    let mut minified = String::with_capacity(commands.len() + 1);
+    for line in commands.as_bytes().chunks(wrap.unwrap_or(usize::MAX)) {
+        minified.push_str(std::str::from_utf8(line).unwrap());
+        minified.push('\n');
+    }
+    Ok(minified)
+}
+
 fn main() {
-    let mut in_out = ConsoleInputOutput {};
-    run(&std::env::args().nth(1).unwrap(), &mut in_out);
+    let mut options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+    let filename = match options.filename.clone() {
+        Some(filename) => filename,
+        None => return repl(&options),
+    };
+    let (name, bytes) = if filename == "-" {
+        let mut bytes = Vec::new();
+        let read = std::io::stdin().read_to_end(&mut bytes).map(|_| bytes);
+        ("<stdin>", read)
+    } else {
+        (filename.as_str(), std::fs::read(&filename))
+    };
+    let unreadable = |source| Error::Io {
+        path: name.into(),
+        source,
+    };
+    let compiled = bytes.map_err(unreadable).and_then(|bytes| {
+        if bytes.starts_with(bytecode::MAGIC) {
+            let needs = if options.minify {
+                Some("minify")
+            } else if options.format {
+                Some("fmt")
+            } else if options.bang_input {
+                Some("--bang-input")
+            } else {
+                None
+            };
+            if let Some(needs) = needs {
+                eprintln!(
+                    "{} needs the source of the program, not a compiled one",
+                    needs
+                );
+                std::process::exit(2);
+            }
+            let started = Instant::now();
+            let program = Program::load(&bytes[..]).map_err(unreadable)?;
+            // without a source, errors and reports cannot point into it
+            return Ok((String::new(), program, started.elapsed()));
+        }
+        let source = String::from_utf8(bytes)
+            .map_err(|err| unreadable(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))?;
+        let extensions = Extensions {
+            language: options.language.clone(),
+            breakpoints: options.debug,
+            dumps: options.debug_ops,
+        };
+        let code = if options.bang_input {
+            let (code, input) = split_input(&source);
+            // without a ! the program gets no input at all
+            options.input = Some(Input::Bytes(input.unwrap_or("").into()));
+            code
+        } else {
+            &source
+        };
+        let started = Instant::now();
+        let program = match &options.cache {
+            Some(dir) => {
+                let dir = dir
+                    .as_ref()
+                    .map_or_else(CompileCache::default_dir, PathBuf::from);
+                CompileCache::new(dir)
+                    .compile(code, &extensions, &options.pipeline)?
+                    .0
+            }
+            None => compile_extended(code, &extensions, &options.pipeline)?,
+        };
+        Ok((source, program, started.elapsed()))
+    });
+    let (source, program, compile_time) = match compiled {
+        Ok(compiled) => compiled,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if filename == "-"
+        && options.input.is_none()
+        && options.replay.is_none()
+        && !options.no_run
+        && options.write_to.is_none()
+        && options.translate.is_none()
+        && !options.minify
+        && !options.format
+        && program.contains(&Ops::Read)
+    {
+        eprintln!("the program reads input, but stdin held the program, use --input, --input-string or --bang-input");
+        std::process::exit(2);
+    }
+
+    match &options.dump_ops {
+        Some(Some(path)) => {
+            if let Err(err) = std::fs::write(path, program.disassemble()) {
+                eprintln!("cannot write {}: {}", path, err);
+                std::process::exit(2);
+            }
+        }
+        Some(None) => eprint!("{}", program),
+        None => {}
+    }
+    // both keep the commands as they are, a `#` only counts with --debug-ops
+    let extensions = Extensions {
+        language: options.language.clone(),
+        breakpoints: false,
+        dumps: options.debug_ops,
+    };
+    if options.minify {
+        let minified = match minify(&source, &extensions, options.strip_dead, options.wrap) {
+            Ok(minified) => minified,
+            Err(errors) => {
+                eprintln!("{}", Error::Compile(errors));
+                std::process::exit(1);
+            }
+        };
+        if let Err(err) = std::io::stdout().write_all(minified.as_bytes()) {
+            eprintln!("cannot write stdout: {}", err);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if options.format {
+        let width = options.width.unwrap_or(format::DEFAULT_WIDTH);
+        let formatted = match format::format(&source, &extensions, width) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                eprintln!("{}", Error::Compile(errors));
+                std::process::exit(1);
+            }
+        };
+        let written = if options.in_place {
+            std::fs::write(&filename, formatted).map_err(|err| (filename.as_str(), err))
+        } else {
+            std::io::stdout()
+                .write_all(formatted.as_bytes())
+                .map_err(|err| ("stdout", err))
+        };
+        if let Err((path, err)) = written {
+            eprintln!("cannot write {}: {}", path, err);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if let Some(target) = options.translate {
+        let written = match translate(&program, &options.config, target) {
+            Ok(code) => match &options.write_to {
+                Some(path) => std::fs::write(path, code).map_err(|err| (path.as_str(), err)),
+                None => std::io::stdout()
+                    .write_all(&code)
+                    .map_err(|err| ("stdout", err)),
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        if let Err((path, err)) = written {
+            eprintln!("cannot write {}: {}", path, err);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if let Some(path) = &options.write_to {
+        let saved = std::fs::File::create(path).and_then(|file| program.save(file));
+        if let Err(err) = saved {
+            eprintln!("cannot write {}: {}", path, err);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if options.no_run {
+        if options.time {
+            eprintln!("compile: {:.1?}", compile_time);
+        }
+        return;
+    }
+    if let Some(address) = &options.listen {
+        match std::net::TcpListener::bind(address) {
+            Ok(listener) => {
+                eprintln!("listening on {}", address);
+                return serve::serve(&options, &program, listener);
+            }
+            Err(err) => {
+                eprintln!("cannot listen on {}: {}", address, err);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let replay = options.replay.as_ref().map(|path| {
+        std::fs::read_to_string(path)
+            .map_err(|err| cannot_open(path, err))
+            .and_then(|log| ReplayIo::parse(&log))
+    });
+    let mut replay = match replay.transpose() {
+        Ok(replay) => replay,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+    // checked before the run, which may take a while
+    let generated = match options.generate_expected {
+        true => match expected_file(&filename, options.force) {
+            Ok(path) => Some(path),
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(2);
+            }
+        },
+        false => None,
+    };
+    let mut output = (options.expect.is_some() || generated.is_some()).then(Vec::new);
+    let mut in_out = match options.bench {
+        None => match program_io(&options, replay.as_mut(), output.as_mut()) {
+            Ok(in_out) => in_out,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(2);
+            }
+        },
+        Some(runs) => {
+            eprintln!(
+                "running {} {} times after a warm-up run, the output is dropped",
+                filename, runs
+            );
+            Box::new(DummyInputOutput)
+        }
+    };
+    interrupt::install();
+    let raw_mode = match options.raw {
+        true => terminal::raw_mode(),
+        false => Ok(None),
+    };
+    let raw_mode = match raw_mode {
+        Ok(raw_mode) => raw_mode,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+    let mut profile = Profile::new();
+    let mut memory = Memory::new(&options.config);
+    let mut tape_stats = TapeStats::new();
+    let mut run_stats = None;
+    let mut bench = None;
+    let started = Instant::now();
+    let result = match (&options.profile, &options.trace) {
+        // the warm-up run counts the instructions, and --stats-json reports it
+        _ if options.bench.is_some() => {
+            let stats = run_stats.insert(RunStats::measure(
+                &options.config,
+                &program,
+                in_out.as_mut(),
+                &mut memory,
+            ));
+            stats.compile_time = compile_time;
+            let instructions = stats.profile.total();
+            stats.result.and_then(|()| {
+                let runs = options.bench.unwrap_or_default();
+                let timed = Bench::run(&options.config, &program, runs, instructions)?;
+                bench = Some(timed);
+                Ok(())
+            })
+        }
+        // the statistics include the profile and the tape statistics
+        _ if options.stats_json.is_some() => {
+            let mut stats =
+                RunStats::measure(&options.config, &program, in_out.as_mut(), &mut memory);
+            stats.compile_time = compile_time;
+            profile = stats.profile.clone();
+            tape_stats = stats.tape.clone();
+            run_stats.insert(stats).result
+        }
+        (Some(_), _) => options
+            .config
+            .run_profiled(&program, in_out.as_mut(), &mut profile),
+        (None, Some(path)) => run_traced(&options, &program, in_out.as_mut(), path.as_deref()),
+        (None, None) if options.debug => {
+            // commands are whole lines on stdin, separate from the bytes `,` reads
+            let mut commands = std::iter::from_fn(|| {
+                let mut line = String::new();
+                match interrupt::while_reading(|| std::io::stdin().read_line(&mut line)) {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) => Some(line),
+                }
+            });
+            let mut stderr = std::io::stderr();
+            let mut debugger = Debugger::new(&mut commands, &mut stderr);
+            options
+                .config
+                .run_debugged(&program, in_out.as_mut(), &mut debugger)
+        }
+        (None, None) if options.tape_stats => {
+            options
+                .config
+                .run_tape_stats(&program, in_out.as_mut(), &mut memory, &mut tape_stats)
+        }
+        (None, None) if options.jit => {
+            options
+                .config
+                .run_jit_on(&program, in_out.as_mut(), &mut memory)
+        }
+        (None, None) => options
+            .config
+            .run_on(&program, in_out.as_mut(), &mut memory),
+    };
+    let run_time = started.elapsed();
+    // exit skips destructors, and the reports should not be written in raw mode
+    drop(raw_mode);
+    // exit does not flush the output, what was printed so far shows how far the program got
+    if let Err(err) = in_out.flush() {
+        eprintln!("\ncannot write the output: {}", err);
+    }
+    drop(in_out);
+    if let Some(count) = options.dump_memory {
+        eprint!("\n{}", dump_memory(&memory, count));
+    }
+    if options.tape_stats {
+        eprintln!("\n{}", tape_stats);
+    }
+    match options.profile {
+        Some(Report::Ops) => eprint!("\n{}", profile),
+        Some(Report::Loops) => eprint!("\n{}", profile.loop_report(&source, HOT_LOOPS)),
+        None => {}
+    }
+    if let (Some(path), Some(stats)) = (&options.stats_json, &run_stats) {
+        let json = stats.to_json() + "\n";
+        if path == "-" {
+            eprint!("{}", json);
+        } else if let Err(err) = std::fs::write(path, json) {
+            eprintln!("cannot write {}: {}", path, err);
+            std::process::exit(2);
+        }
+    }
+    if let Some(bench) = &bench {
+        eprintln!("\n{}", bench);
+    }
+    if options.time {
+        // the profile is only counted for these
+        let counted = options.profile.is_some() || options.stats_json.is_some();
+        let instructions = counted.then(|| profile.total());
+        eprint!("\n{}", timing(compile_time, run_time, instructions));
+    }
+    if let Err(err) = result {
+        let span = err.ip().and_then(|ip| program.source_span(ip));
+        match span {
+            Some(span) => eprintln!(
+                "\nRuntime error: {} ({})",
+                err,
+                Position::in_source(&source, span.start)
+            ),
+            None => eprintln!("\nRuntime error: {}", err),
+        }
+        match err {
+            RuntimeError::Interrupted { .. } => std::process::exit(interrupt::INTERRUPTED_STATUS),
+            _ => std::process::exit(1),
+        }
+    }
+    if let Some(Err(divergence)) = replay.map(|replay| replay.finish()) {
+        eprintln!("\n{}", divergence);
+        std::process::exit(1);
+    }
+    if let (Some(path), Some(output)) = (&options.expect, &output) {
+        let expected = match std::fs::read(path) {
+            Ok(expected) => expected,
+            Err(err) => {
+                eprintln!("{}", cannot_open(path, err));
+                std::process::exit(2);
+            }
+        };
+        if let Err(report) = compare_output(&expected, output) {
+            eprintln!("\n{}", report);
+            std::process::exit(1);
+        }
+    }
+    if let (Some(path), Some(output)) = (&generated, &output) {
+        if let Err(err) = std::fs::write(path, output) {
+            eprintln!("cannot write {}: {}", path.display(), err);
+            std::process::exit(2);
+        }
+        eprintln!(
+            "\n{}: wrote {} bytes to {}",
+            filename,
+            output.len(),
+            path.display()
+        );
+    }
     println!("\nDone");
 }
 
@@ -197,14 +1583,756 @@ fn main() {
 mod tests {
     use super::*;
 
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn tape_size() {
+        assert_eq!(
+            parse(&["--tape-size", "200000", "a.bf"])
+                .unwrap()
+                .config
+                .tape_size(),
+            200_000
+        );
+        assert_eq!(
+            parse(&["a.bf", "--tape-size=5"])
+                .unwrap()
+                .config
+                .tape_size(),
+            5
+        );
+        assert!(parse(&["--tape-size", "0", "a.bf"]).is_err());
+        assert!(parse(&["--tape-size", "99999999999999", "a.bf"]).is_err());
+        assert!(parse(&["a.bf", "--tape-size"]).is_err());
+    }
+
+    #[test]
+    fn max_ops() {
+        assert_eq!(parse(&["a.bf"]).unwrap().config.max_ops(), None);
+        assert_eq!(
+            parse(&["--max-ops", "1000", "a.bf"])
+                .unwrap()
+                .config
+                .max_ops(),
+            Some(1000)
+        );
+        assert!(parse(&["--max-ops=-1", "a.bf"]).is_err());
+        assert!(parse(&["--max-ops=lots", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn call_depth() {
+        let call_depth = |args: &[&str]| parse(args).map(|o| o.config.call_depth());
+        assert_eq!(call_depth(&["a.bf"]), Ok(1 << 16));
+        assert_eq!(call_depth(&["--call-depth=100", "a.bf"]), Ok(100));
+        assert!(call_depth(&["--call-depth=0", "a.bf"]).is_err());
+        assert!(call_depth(&["--call-depth=deep", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn dispatch() {
+        use brainfuck::Dispatch;
+        let dispatch = |args: &[&str]| parse(args).map(|o| o.config.dispatch());
+        assert_eq!(dispatch(&["a.bf"]), Ok(Dispatch::Match));
+        assert_eq!(
+            dispatch(&["--dispatch=threaded", "a.bf"]),
+            Ok(Dispatch::Threaded)
+        );
+        assert_eq!(
+            dispatch(&["--dispatch", "packed", "a.bf"]),
+            Ok(Dispatch::Packed)
+        );
+        assert!(dispatch(&["--dispatch=goto", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn timeout() {
+        let timeout = |value| parse(&["--timeout", value, "a.bf"]).map(|o| o.config.timeout());
+        assert_eq!(parse(&["a.bf"]).unwrap().config.timeout(), None);
+        assert_eq!(timeout("5s"), Ok(Some(Duration::from_secs(5))));
+        assert_eq!(timeout("2"), Ok(Some(Duration::from_secs(2))));
+        assert_eq!(timeout("250ms"), Ok(Some(Duration::from_millis(250))));
+        assert_eq!(timeout("1.5m"), Ok(Some(Duration::from_secs(90))));
+        assert!(timeout("5h").is_err());
+        assert!(timeout("s").is_err());
+        assert!(timeout("-1s").is_err());
+        assert!(timeout("1e999").is_err());
+        assert!(timeout("99999999999999999999999s").is_err());
+    }
+
+    #[test]
+    fn optimization_level() {
+        assert_eq!(parse(&["a.bf"]).unwrap().pipeline.level(), OptLevel::O2);
+        assert_eq!(
+            parse(&["-O0", "a.bf"]).unwrap().pipeline.level(),
+            OptLevel::O0
+        );
+        assert!(parse(&["-O7", "a.bf"]).is_err());
+
+        let passes: Vec<_> = parse(&["-O1", "--no-pass=fold_runs,scan_loops", "a.bf"])
+            .unwrap()
+            .pipeline
+            .passes()
+            .collect();
+        assert_eq!(passes, vec!["clear_loops"]);
+        assert!(parse(&["--no-pass", "no_such_pass", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn dump_ops() {
+        let options = parse(&["--dump-ops", "a.bf", "--no-run"]).unwrap();
+        assert_eq!(options.filename.as_deref(), Some("a.bf"));
+        assert_eq!(options.dump_ops, Some(None));
+        assert!(options.no_run);
+
+        let options = parse(&["--dump-ops=ops.txt", "a.bf"]).unwrap();
+        assert_eq!(options.dump_ops, Some(Some("ops.txt".into())));
+        assert!(!options.no_run);
+    }
+
+    #[test]
+    fn cache() {
+        assert_eq!(parse(&["a.bf"]).unwrap().cache, None);
+        assert_eq!(parse(&["--cache", "a.bf"]).unwrap().cache, Some(None));
+        let options = parse(&["--cache=/tmp/bf", "a.bf"]).unwrap();
+        assert_eq!(options.cache, Some(Some("/tmp/bf".into())));
+        assert_eq!(options.filename.as_deref(), Some("a.bf"));
+        assert!(parse(&["--cache"]).is_err());
+    }
+
+    #[test]
+    fn profile() {
+        assert_eq!(parse(&["a.bf"]).unwrap().profile, None);
+        assert_eq!(
+            parse(&["--profile", "a.bf"]).unwrap().profile,
+            Some(Report::Ops)
+        );
+        assert_eq!(
+            parse(&["--profile=loops", "a.bf"]).unwrap().profile,
+            Some(Report::Loops)
+        );
+        assert!(parse(&["--profile=time", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn trace() {
+        let options = parse(&["a.bf"]).unwrap();
+        assert_eq!(options.trace, None);
+        let options = parse(&["--trace", "--trace-limit", "5", "a.bf"]).unwrap();
+        assert_eq!(options.trace, Some(None));
+        assert_eq!(options.trace_limit, Some(5));
+        let options = parse(&["--trace=log.txt", "--trace-span=3..10", "a.bf"]).unwrap();
+        assert_eq!(options.trace, Some(Some("log.txt".into())));
+        assert_eq!(options.trace_span, Some(3..10));
+
+        assert!(parse(&["--trace-span=10..3", "a.bf"]).is_err());
+        assert!(parse(&["--trace-span=3", "a.bf"]).is_err());
+        assert!(parse(&["--trace", "--profile", "a.bf"]).is_err());
+        assert!(parse(&["--trace", "--debug", "a.bf"]).is_err());
+        assert!(parse(&["--debug", "a.bf"]).unwrap().debug);
+        assert!(parse(&["--debug-ops", "a.bf"]).unwrap().debug_ops);
+        assert!(parse(&["--debug-ops", "repl"]).unwrap().debug_ops);
+        assert!(parse(&["--debug-ops", "--debug", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn repl_mode() {
+        assert_eq!(parse(&[]).unwrap().filename, None);
+        assert_eq!(parse(&["--tape-size=5", "repl"]).unwrap().filename, None);
+        assert!(parse(&["--profile"]).is_err());
+        assert!(parse(&["repl", "--debug"]).is_err());
+        assert!(parse(&["--help", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn repl_entries() {
+        let config = ExecutionConfig::builder().tape_size(4).build();
+        let mut repl = Repl::new(config, Extensions::default(), Pipeline::default());
+        let mut in_out = brainfuck::StringInputOutput::new();
+        let mut eval = |line: &str| repl.eval(line, &mut in_out).unwrap();
+
+        // the tape and the pointer survive between entries
+        assert_eq!(eval("+++++>++\n"), "pos=1 cell=2\n");
+        assert_eq!(eval("<."), "\npos=0 cell=5\n");
+        assert_eq!(eval(":dump 0..3"), "0..3: 5 2 0\n");
+        assert_eq!(eval(":dump 2..10"), "2..4: 0 0\n");
+        assert!(eval(":dump 9..10").contains("4 cells"));
+
+        // an open loop continues on the next line
+        assert_eq!(eval("[->"), "");
+        assert_eq!(repl.prompt(), "... ");
+        let mut eval = |line: &str| repl.eval(line, &mut in_out).unwrap();
+        assert_eq!(eval("+<]"), "pos=0 cell=0\n");
+        assert_eq!(eval(":dump 0..2"), "0..2: 0 7\n");
+
+        // errors end the entry, not the session
+        assert!(eval("]").starts_with("unmatched ']'"));
+        assert!(eval(">>>>").starts_with("Runtime error: "));
+        assert_eq!(eval(":reset"), "tape cleared\n");
+        assert_eq!(eval("+"), "pos=0 cell=1\n");
+        assert_eq!(repl.prompt(), "bf> ");
+        assert_eq!(repl.eval(":quit", &mut in_out), None);
+
+        // the numbers go through the translation and end their line
+        let config = ExecutionConfig::builder()
+            .cell_size(brainfuck::CellSize::U16)
+            .build();
+        let mut repl = Repl {
+            newline: Some(Newline::CrLf),
+            io: IoMode::Numbers,
+            ..Repl::new(config, Extensions::default(), Pipeline::default())
+        };
+        let mut in_out = brainfuck::StringInputOutput::with_input("300");
+        assert_eq!(repl.eval(",.", &mut in_out).unwrap(), "pos=0 cell=300\n");
+        assert_eq!(in_out.output(), "300\r\n");
+    }
+
+    #[test]
+    fn memory_dump() {
+        assert_eq!(parse(&["a.bf"]).unwrap().dump_memory, None);
+        assert_eq!(
+            parse(&["--dump-memory", "a.bf"]).unwrap().dump_memory,
+            Some(DUMPED_CELLS)
+        );
+        assert_eq!(
+            parse(&["--dump-memory=100", "a.bf"]).unwrap().dump_memory,
+            Some(100)
+        );
+        assert!(parse(&["--dump-memory=0", "a.bf"]).is_err());
+        assert!(parse(&["--dump-memory", "--profile", "a.bf"]).is_err());
+        assert!(
+            parse(&["--dump-memory", "--tape-stats", "a.bf"])
+                .unwrap()
+                .tape_stats
+        );
+        assert!(parse(&["--tape-stats", "--trace", "a.bf"]).is_err());
+        assert!(parse(&["--tape-stats"]).is_err());
+    }
+
+    #[test]
+    fn input() {
+        assert_eq!(parse(&["a.bf"]).unwrap().input, None);
+        let options = parse(&["--input", "in.txt", "--debug", "a.bf"]).unwrap();
+        assert_eq!(options.input, Some(Input::File("in.txt".into())));
+        assert!(parse(&["a.bf", "--input"]).is_err());
+        assert!(parse(&["--input=in.txt"]).is_err());
+        assert!(parse(&["--input=in.txt", "--input-string=x", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn bang_input() {
+        assert!(!parse(&["a.bf"]).unwrap().bang_input);
+        assert!(parse(&["--bang-input", "a.bf"]).unwrap().bang_input);
+        assert!(parse(&["--bang-input", "--input-string=x", "a.bf"]).is_err());
+        assert!(parse(&["--bang-input"]).is_err());
+    }
+
+    #[test]
+    fn jit() {
+        assert!(!parse(&["a.bf"]).unwrap().jit);
+        assert!(parse(&["--jit", "--dump-memory", "a.bf"]).unwrap().jit);
+        assert!(parse(&["--jit", "--profile", "a.bf"]).is_err());
+        assert!(parse(&["--jit", "--tape-stats", "a.bf"]).is_err());
+        assert!(parse(&["--jit", "serve", "--listen", ":1", "a.bf"]).is_err());
+        assert!(parse(&["--jit"]).is_err());
+    }
+
+    #[test]
+    fn lang() {
+        assert_eq!(parse(&["a.bf"]).unwrap().language, Language::Brainfuck);
+        assert_eq!(parse(&["hello.ook"]).unwrap().language, Language::Ook);
+        assert_eq!(
+            parse(&["--lang=bf", "hello.ook"]).unwrap().language,
+            Language::Brainfuck
+        );
+        assert_eq!(
+            parse(&["--lang", "ook", "-"]).unwrap().language,
+            Language::Ook
+        );
+        assert_eq!(
+            parse(&["--lang=pbrain", "a.bf"]).unwrap().language,
+            Language::Pbrain
+        );
+        assert!(parse(&["--lang=cow", "a.bf"]).is_err());
+        assert!(parse(&["--bang-input", "hello.ook"]).is_err());
+
+        let path = std::env::temp_dir().join("brainfuck-main-dialect.txt");
+        let tokens = ["r", "l", "u", "d", "o", "i", "(", ")"];
+        let mapping: String = brainfuck::dialect::COMMANDS
+            .iter()
+            .zip(tokens)
+            .map(|(command, token)| format!("{}={}\n", command, token))
+            .collect();
+        std::fs::write(&path, &mapping).unwrap();
+        let dialect = ["--dialect", path.to_str().unwrap(), "a.bf"];
+        assert_eq!(
+            parse(&dialect).unwrap().language,
+            Language::Dialect(Dialect::new(tokens).unwrap())
+        );
+        std::fs::write(&path, ">=r\n").unwrap();
+        let err = parse(&dialect).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.ends_with("there is no token for '<'"), "{}", err);
+        assert!(parse(&["--dialect", "missing.txt", "a.bf"]).is_err());
+        assert!(parse(&["--dialect", "missing.txt", "--lang=bf", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn raw() {
+        assert!(!parse(&["a.bf"]).unwrap().raw);
+        assert!(parse(&["--raw", "a.bf"]).unwrap().raw);
+        assert!(parse(&["--raw", "--debug", "a.bf"]).is_err());
+        assert!(parse(&["--raw"]).is_err());
+    }
+
+    #[test]
+    fn newline() {
+        assert_eq!(parse(&["a.bf"]).unwrap().newline, None);
+        assert_eq!(
+            parse(&["--newline=crlf", "a.bf"]).unwrap().newline,
+            Some(Newline::CrLf)
+        );
+        assert_eq!(
+            parse(&["--newline", "lf", "repl"]).unwrap().newline,
+            Some(Newline::Lf)
+        );
+        assert!(parse(&["--newline=cr", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn io_mode() {
+        assert_eq!(parse(&["a.bf"]).unwrap().io, IoMode::Chars);
+        assert_eq!(
+            parse(&["--io=numbers", "a.bf"]).unwrap().io,
+            IoMode::Numbers
+        );
+        assert!(parse(&["--io=hex", "a.bf"]).is_err());
+        assert!(parse(&["--io=numbers"]).is_err());
+        assert!(parse(&["--io=chars"]).is_ok());
+    }
+
+    #[test]
+    fn stdin_program() {
+        assert_eq!(parse(&["-"]).unwrap().filename.as_deref(), Some("-"));
+        assert!(parse(&["--debug", "-"]).is_err());
+    }
+
+    #[test]
+    fn input_string() {
+        assert_eq!(unescape("plain ä"), Ok("plain ä".into()));
+        assert_eq!(
+            unescape(r"a\n\t\r\0\\\x41\xff\x80"),
+            Ok(b"a\n\t\r\0\\A\xff\x80".to_vec())
+        );
+        assert!(unescape(r"\x4").is_err());
+        assert!(unescape(r"\xg0").is_err());
+        assert!(unescape(r"\x+4").is_err());
+        assert!(unescape(r"\q").is_err());
+        assert!(unescape("\\").is_err());
+
+        // cat echoes the bytes unchanged
+        let path = std::env::temp_dir().join("brainfuck-main-input-string.out");
+        let options = parse(&[
+            r"--input-string=hi\x80\xfe\t\n",
+            "--output",
+            path.to_str().unwrap(),
+            "cat.bf",
+        ])
+        .unwrap();
+        let mut in_out = program_io(&options, None, None).unwrap();
+        let program = brainfuck::compile(",[.,]").unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        in_out.flush().unwrap();
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, b"hi\x80\xfe\t\n");
+    }
+
+    #[test]
+    fn output() {
+        let path = std::env::temp_dir().join("brainfuck-main-output.out");
+        let options = parse(&[
+            "--eof=minus-one",
+            "--input",
+            "programs/rot13.in",
+            "--output",
+            path.to_str().unwrap(),
+            "programs/rot13.bf",
+        ])
+        .unwrap();
+        let program = brainfuck::compile_file("programs/rot13.bf", &options.pipeline).unwrap();
+        let mut in_out = program_io(&options, None, None).unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        in_out.flush().unwrap();
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, std::fs::read("programs/rot13.out").unwrap());
+
+        let options = parse(&["--input=programs/missing.in", "a.bf"]).unwrap();
+        assert_eq!(
+            program_io(&options, None, None).err().unwrap(),
+            "cannot open 'programs/missing.in': No such file or directory"
+        );
+    }
+
+    #[test]
+    fn record_replay() {
+        let log = std::env::temp_dir().join("brainfuck-main-record.log");
+        let out = std::env::temp_dir().join("brainfuck-main-record.out");
+        let options = parse(&[
+            "--eof=minus-one",
+            "--input=programs/rot13.in",
+            "--output",
+            out.to_str().unwrap(),
+            "--record",
+            log.to_str().unwrap(),
+            "programs/rot13.bf",
+        ])
+        .unwrap();
+        let program = brainfuck::compile_file("programs/rot13.bf", &options.pipeline).unwrap();
+        let mut in_out = program_io(&options, None, None).unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        in_out.flush().unwrap();
+        drop(in_out);
+        std::fs::remove_file(&out).unwrap();
+
+        let options = parse(&["--eof=minus-one", "--replay=recorded.log", "rot13.bf"]).unwrap();
+        let mut replay = ReplayIo::parse(&std::fs::read_to_string(&log).unwrap()).unwrap();
+        std::fs::remove_file(&log).unwrap();
+        let mut in_out = program_io(&options, Some(&mut replay), None).unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        drop(in_out);
+        assert_eq!(replay.finish(), Ok(()));
+
+        assert!(parse(&["--replay=a.log", "--input=a.in", "a.bf"]).is_err());
+        assert!(parse(&["--replay=a.log", "--record=b.log", "a.bf"]).is_err());
+        assert!(parse(&["--record=a.log"]).is_err());
+    }
+
+    #[test]
+    fn expect() {
+        let path = std::env::temp_dir().join("brainfuck-main-expect.out");
+        std::fs::write(&path, std::fs::read("programs/rot13.out").unwrap()).unwrap();
+        let options = parse(&[
+            "--eof=minus-one",
+            "--input=programs/rot13.in",
+            "--expect",
+            path.to_str().unwrap(),
+            "programs/rot13.bf",
+        ])
+        .unwrap();
+        let program = brainfuck::compile_file("programs/rot13.bf", &options.pipeline).unwrap();
+        let mut output = Vec::new();
+        let mut in_out = program_io(&options, None, Some(&mut output)).unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        drop(in_out);
+        let expected = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(compare_output(&expected, &output), Ok(()));
+
+        // the bytes are compared, not the lines
+        let output = b"binary\0\xff output with more than a few bytes".to_vec();
+        let mut expected = output.clone();
+        expected[7] = b'\n';
+        assert_eq!(
+            compare_output(&expected, &output),
+            Err("the output differs from the expected one at byte 7\nexpected \"binary\\x00\\n output\"\nfound    \"binary\\x00\\xff output\"\nthe output has 42 bytes, the expected one 42".into())
+        );
+        assert_eq!(
+            compare_output(b"abc", b"ab"),
+            Err("the output differs from the expected one at byte 2\nexpected \"abc\"\nfound    \"ab\"\nthe output has 2 bytes, the expected one 3".into())
+        );
+        assert!(parse(&["--expect=a.out", "--tee", "a.bf"]).unwrap().tee);
+        assert!(parse(&["--expect=a.out", "--output=b.out", "a.bf"]).is_err());
+        assert!(parse(&["--expect=a.out", "--replay=a.log", "a.bf"]).is_err());
+        assert!(parse(&["--expect=a.out", "repl"]).is_err());
+    }
+
+    #[test]
+    fn generate_expected() {
+        let dir = std::env::temp_dir().join("brainfuck-main-generate-expected");
+        std::fs::create_dir_all(&dir).unwrap();
+        let program_path = dir.join("rot13.bf");
+        std::fs::copy("programs/rot13.bf", &program_path).unwrap();
+        let filename = program_path.to_str().unwrap();
+        let options = parse(&[
+            "--eof=minus-one",
+            "--input=programs/rot13.in",
+            "--generate-expected",
+            filename,
+        ])
+        .unwrap();
+        let expected = dir.join("rot13.out");
+        let _ = std::fs::remove_file(&expected);
+        assert_eq!(expected_file(filename, options.force), Ok(expected.clone()));
+
+        let program = brainfuck::compile_file(filename, &options.pipeline).unwrap();
+        let mut output = Vec::new();
+        let mut in_out = program_io(&options, None, Some(&mut output)).unwrap();
+        options.config.run(&program, in_out.as_mut()).unwrap();
+        drop(in_out);
+        std::fs::write(&expected, &output).unwrap();
+        assert_eq!(
+            std::fs::read(&expected).unwrap(),
+            std::fs::read("programs/rot13.out").unwrap()
+        );
+
+        // an existing file is only overwritten with --force
+        assert!(expected_file(filename, false)
+            .unwrap_err()
+            .ends_with("rot13.out exists already, --force overwrites it"));
+        assert_eq!(expected_file(filename, true), Ok(expected));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            parse(&["--generate-expected", "--force", "a.bf"])
+                .unwrap()
+                .force
+        );
+        assert!(parse(&["--force", "a.bf"]).is_err());
+        assert!(parse(&["--generate-expected", "-"]).is_err());
+        assert!(parse(&["--generate-expected", "--expect=a.out", "a.bf"]).is_err());
+        assert!(parse(&["--generate-expected", "--output=a.out", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn tee() {
+        assert!(!parse(&["--output=a.out", "a.bf"]).unwrap().tee);
+        assert!(parse(&["--output=a.out", "--tee", "a.bf"]).unwrap().tee);
+        assert!(parse(&["--tee", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn serve() {
+        let options = parse(&["serve", "--listen", "127.0.0.1:4000", "-O0", "a.bf"]).unwrap();
+        assert_eq!(options.listen.as_deref(), Some("127.0.0.1:4000"));
+        assert_eq!(options.filename.as_deref(), Some("a.bf"));
+        // a program that is called serve
+        assert_eq!(
+            parse(&["./serve"]).unwrap().filename.as_deref(),
+            Some("./serve")
+        );
+        assert!(parse(&["serve", "a.bf"]).is_err());
+        assert!(parse(&["--listen=127.0.0.1:4000", "a.bf"]).is_err());
+        assert!(parse(&["serve", "--listen=127.0.0.1:4000"]).is_err());
+        assert!(parse(&["serve", "--listen=127.0.0.1:4000", "repl"]).is_err());
+        assert!(parse(&["serve", "--listen=127.0.0.1:4000", "--profile", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn compile() {
+        let options = parse(&["compile", "-O1", "a.bf", "-o", "a.bfc"]).unwrap();
+        assert_eq!(options.write_to.as_deref(), Some("a.bfc"));
+        assert_eq!(options.filename.as_deref(), Some("a.bf"));
+        assert!(parse(&["compile", "--dump-ops", "-", "-o", "a.bfc"]).is_ok());
+        assert_eq!(
+            parse(&["run", "a.bfc"]).unwrap().filename.as_deref(),
+            Some("a.bfc")
+        );
+        assert_eq!(parse(&["a.bfc"]).unwrap().write_to, None);
+        assert!(parse(&["compile", "a.bf"]).is_err());
+        assert!(parse(&["-o", "a.bfc", "a.bf"]).is_err());
+        assert!(parse(&["compile", "-o", "a.bfc"]).is_err());
+        assert!(parse(&["compile", "--no-run", "a.bf", "-o", "a.bfc"]).is_err());
+        assert!(parse(&["compile", "--input-string=x", "a.bf", "-o", "a.bfc"]).is_err());
+        assert!(parse(&["compile", "run", "a.bf", "-o", "a.bfc"]).is_err());
+    }
+
     #[test]
-    fn mandelbrot() {
-        let filename = "programs/mandelbrot";
-        let mut in_out = StringInputOutput::new();
-        run(&format!("{}.bf", filename), &mut in_out);
-        let mut f = File::open(&format!("{}.out", filename)).unwrap();
-        let mut source = String::new();
-        f.read_to_string(&mut source).unwrap();
-        assert_eq!(source, in_out.output);
+    fn translate() {
+        let options = parse(&["translate", "--to", "c", "--eof=minus-one", "a.bf"]).unwrap();
+        assert_eq!(options.translate, Some(Target::C));
+        assert_eq!(options.write_to, None);
+        let options = parse(&["translate", "--to=rust", "a.bf", "-o", "a.rs"]).unwrap();
+        assert_eq!(options.translate, Some(Target::Rust));
+        assert_eq!(options.write_to.as_deref(), Some("a.rs"));
+        let options = parse(&["translate", "--to=js", "a.bf"]).unwrap();
+        assert_eq!(options.translate, Some(Target::Js));
+        let options = parse(&["translate", "--to=wasm", "a.bf", "-o", "a.wasm"]).unwrap();
+        assert_eq!(options.translate, Some(Target::Wasm));
+        assert!(parse(&["translate", "a.bf"]).is_err());
+        assert!(parse(&["--to=c", "a.bf"]).is_err());
+        assert!(parse(&["translate", "--to=cobol", "a.bf"]).is_err());
+        assert!(parse(&["translate", "--to=c", "repl"]).is_err());
+        assert!(parse(&["translate", "--to=c", "--trace", "a.bf"]).is_err());
+        assert!(parse(&["translate", "--to=c", "--io=numbers", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn minify() {
+        let options = parse(&["minify", "--strip-dead", "--wrap=8", "a.bf"]).unwrap();
+        assert!(options.minify && options.strip_dead);
+        assert_eq!(options.wrap, Some(8));
+        assert!(!parse(&["minify", "-"]).unwrap().strip_dead);
+        assert!(parse(&["--strip-dead", "a.bf"]).is_err());
+        assert!(parse(&["minify", "--wrap=0", "a.bf"]).is_err());
+        assert!(parse(&["minify", "--input-string=x", "a.bf"]).is_err());
+        assert!(parse(&["minify", "repl"]).is_err());
+
+        let brainfuck = Extensions::default();
+        let source = "[a comment, with - and +]\n+++ add three\n[-> move <]\n\n>. done\n";
+        let minify =
+            |strip_dead, wrap| super::minify(source, &brainfuck, strip_dead, wrap).unwrap();
+        assert_eq!(minify(false, None), "[,-+]+++[-><]>.\n");
+        assert_eq!(minify(true, None), "+++[-><]>.\n");
+        assert_eq!(minify(true, Some(4)), "+++[\n-><]\n>.\n");
+        assert_eq!(minify(false, Some(5)), "[,-+]\n+++[-\n><]>.\n");
+        // a loop right after another one never runs either
+        assert_eq!(
+            super::minify("+[-][.]#", &brainfuck, true, None).unwrap(),
+            "+[-]\n"
+        );
+        assert_eq!(
+            super::minify("no commands", &brainfuck, false, None).unwrap(),
+            ""
+        );
+        let dumps = Extensions {
+            dumps: true,
+            ..Extensions::default()
+        };
+        assert_eq!(super::minify("+#.", &dumps, false, None).unwrap(), "+#.\n");
+        assert_eq!(
+            super::minify("+[.", &brainfuck, false, None),
+            Err(vec![CompileError::UnmatchedOpen {
+                pos: Position { line: 1, column: 2 }
+            }])
+        );
+    }
+
+    #[test]
+    fn stats_json() {
+        assert_eq!(parse(&["a.bf"]).unwrap().stats_json, None);
+        let options = parse(&["--stats-json", "-", "--profile", "--tape-stats", "a.bf"]).unwrap();
+        assert_eq!(options.stats_json.as_deref(), Some("-"));
+        assert_eq!(
+            parse(&["--stats-json=run.json", "a.bf"])
+                .unwrap()
+                .stats_json
+                .as_deref(),
+            Some("run.json")
+        );
+        assert!(parse(&["a.bf", "--stats-json"]).is_err());
+        assert!(parse(&["--stats-json=-", "--debug", "a.bf"]).is_err());
+
+        let config = ExecutionConfig::builder().tape_size(20).build();
+        let mut memory = Memory::new(&config);
+        let program = brainfuck::compile("+++>++").unwrap();
+        config
+            .run_on(
+                &program,
+                &mut brainfuck::StringInputOutput::new(),
+                &mut memory,
+            )
+            .unwrap();
+        let dump = dump_memory(&memory, 32);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "     0: 3 2* 0 0 0 0 0 0 0 0 0 0 0 0 0 0 | 03 02* 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+                "    16: 0 0 0 0 | 00 00 00 00",
+                "pointer at cell 1 of 20",
+            ]
+        );
+        assert_eq!(
+            dump_memory(&memory, 2).lines().next(),
+            Some("     0: 3 2* | 03 02*")
+        );
+    }
+
+    #[test]
+    fn fmt() {
+        let options = parse(&["fmt", "--width=40", "--in-place", "a.bf"]).unwrap();
+        assert!(options.format && options.in_place);
+        assert_eq!(options.width, Some(40));
+        assert!(!parse(&["fmt", "-"]).unwrap().in_place);
+        assert!(parse(&["fmt", "--in-place", "-"]).is_err());
+        assert!(parse(&["--width=40", "a.bf"]).is_err());
+        assert!(parse(&["minify", "--in-place", "a.bf"]).is_err());
+        assert!(parse(&["fmt", "--width=0", "a.bf"]).is_err());
+        assert!(parse(&["fmt", "--bench=2", "a.bf"]).is_err());
+    }
+
+    #[test]
+    fn conflicts() {
+        let error = |args: &[&str]| parse(args).err().unwrap_or_default();
+        // the errors name the flags that were given
+        assert_eq!(
+            error(&[
+                "--bench=3",
+                "--no-run",
+                "--time",
+                "--input-string=x",
+                "a.bf"
+            ]),
+            "--bench cannot be combined with --no-run, --time and --input-string"
+        );
+        assert_eq!(
+            error(&["compile", "--jit", "a.bf", "-o", "a.bfc"]),
+            "compile cannot be combined with --jit"
+        );
+        assert_eq!(
+            error(&[
+                "serve",
+                "--listen=:0",
+                "--input=in.txt",
+                "--stats-json=-",
+                "a.bf"
+            ]),
+            "serve cannot be combined with --stats-json and --input"
+        );
+        assert_eq!(error(&["serve", "--listen=:0"]), "serve needs a FILE");
+        assert_eq!(
+            error(&["--cache", "--io=numbers", "--raw"]),
+            "--cache, --io=numbers and --raw cannot be used without a FILE"
+        );
+    }
+
+    #[test]
+    fn bench() {
+        let options = parse(&["--bench", "3", "--stats-json=-", "a.bf"]).unwrap();
+        assert_eq!(options.bench, Some(3));
+        assert!(parse(&["--bench=0", "a.bf"]).is_err());
+        assert!(parse(&["--bench", "a.bf"]).is_err());
+        assert!(parse(&["--bench=3", "--input-string=x", "a.bf"]).is_err());
+        assert!(parse(&["--bench=3", "--jit", "a.bf"]).is_err());
+        assert!(parse(&["--bench=3", "--no-run", "a.bf"]).is_err());
+        assert!(parse(&["--bench=3"]).is_err());
+
+        let program = brainfuck::compile("++[>+++<-]>.").unwrap();
+        let bench = Bench::run(&options.config, &program, 3, 21).unwrap();
+        assert_eq!(bench.times.len(), 3);
+        assert!(bench.times[0] <= bench.median() && bench.median() <= bench.times[2]);
+        let summary = bench.to_string();
+        assert!(
+            summary.starts_with("bench: 3 runs, min ") && summary.contains(", 21 instructions, "),
+            "{}",
+            summary
+        );
+        assert!(summary.ends_with(" instructions/s"));
+
+        let program = brainfuck::compile("<").unwrap();
+        assert!(Bench::run(&options.config, &program, 3, 1).is_err());
+    }
+
+    #[test]
+    fn time() {
+        assert!(!parse(&["a.bf"]).unwrap().time);
+        assert!(parse(&["--time", "a.bf"]).unwrap().time);
+        assert!(parse(&["--time"]).is_err());
+        assert!(parse(&["--time", "--bench=2", "a.bf"]).is_err());
+        assert!(parse(&["compile", "--time", "a.bf", "-o", "a.bfc"]).is_err());
+
+        let millis = Duration::from_millis;
+        assert_eq!(
+            timing(Duration::from_micros(1200), millis(843), None),
+            "compile: 1.2ms\nexecute: 843.0ms\n"
+        );
+        assert_eq!(
+            timing(millis(2), Duration::from_secs(2), Some(1_000_000)),
+            "compile: 2.0ms\nexecute: 2.0s, 1000000 instructions\n"
+        );
     }
 }
@@ -0,0 +1,134 @@
+//! Callbacks into a run of [`ExecutionConfig::run_observed`], to build
+//! tracing, coverage or watchpoints outside of the interpreter.
+//!
+//! ```
+//! use brainfuck::observer::Observer;
+//! use brainfuck::{compile, execute_with_observer, StringInputOutput};
+//!
+//! #[derive(Default)]
+//! struct Coverage(Vec<usize>);
+//! impl Observer for Coverage {
+//!     fn on_op(&mut self, ip: usize, _: &brainfuck::Ops, _: usize) {
+//!         self.0.push(ip);
+//!     }
+//! }
+//!
+//! let mut coverage = Coverage::default();
+//! let program = compile("+[-]>.").unwrap();
+//! execute_with_observer(&program, &mut StringInputOutput::new(), &mut coverage).unwrap();
+//! assert_eq!(coverage.0, [0, 1, 2, 3]);
+//! ```
+//!
+//! [`ExecutionConfig::run_observed`]: crate::ExecutionConfig::run_observed
+
+use crate::{Ops, Probe, RuntimeError, Tape};
+
+/// Called right before the ops run, every method does nothing by default.
+pub trait Observer {
+    /// Every op, `pos` is the index of the current cell.
+    fn on_op(&mut self, _ip: usize, _op: &Ops, _pos: usize) {}
+
+    /// The byte a `.` is about to write.
+    fn on_output(&mut self, _byte: u8) {}
+
+    /// A `[` at `ip` that enters its loop instead of skipping it. Loops the
+    /// optimizer replaced, like `[-]`, are not reported.
+    fn on_loop_enter(&mut self, _ip: usize) {}
+}
+
+/// Hands the ops to an [`Observer`].
+pub(crate) struct Observed<'a>(pub(crate) &'a mut dyn Observer);
+
+impl Probe for Observed<'_> {
+    fn on_op(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        self.0.on_op(ip, &op, pos);
+        let cell = tape.cell(pos).unwrap_or(0);
+        match op {
+            Ops::Print => self.0.on_output(cell as u8),
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) if cell != 0 => self.0.on_loop_enter(ip),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::StringInputOutput;
+    use crate::{compile, compile_with, execute_with_observer, ExecutionConfig};
+
+    #[derive(Default)]
+    struct Counting {
+        ops: Vec<(usize, Ops, usize)>,
+        output: Vec<u8>,
+        loops: Vec<usize>,
+    }
+
+    impl Observer for Counting {
+        fn on_op(&mut self, ip: usize, op: &Ops, pos: usize) {
+            self.ops.push((ip, *op, pos));
+        }
+        fn on_output(&mut self, byte: u8) {
+            self.output.push(byte);
+        }
+        fn on_loop_enter(&mut self, ip: usize) {
+            self.loops.push(ip);
+        }
+    }
+
+    #[test]
+    fn op_sequence() {
+        let program = compile_with("++[-].", &Pipeline::new(OptLevel::O0)).unwrap();
+        let mut counting = Counting::default();
+        let mut in_out = StringInputOutput::new();
+        execute_with_observer(&program, &mut in_out, &mut counting).unwrap();
+        let ip = |ip| (ip, program[ip], 0);
+        assert_eq!(
+            counting.ops,
+            [
+                ip(0),
+                ip(1),
+                ip(2),
+                ip(3),
+                ip(4),
+                ip(3),
+                ip(4),
+                ip(5),
+                ip(6)
+            ]
+        );
+        assert_eq!(program[6], Ops::End);
+        assert_eq!(counting.output, [0]);
+        assert_eq!(counting.loops, [2]);
+        assert_eq!(in_out.output(), "\0");
+
+        // the optimized program has no loop left
+        let program = compile("++[-].").unwrap();
+        let mut counting = Counting::default();
+        execute_with_observer(&program, &mut in_out, &mut counting).unwrap();
+        assert_eq!(counting.ops.len(), program.len());
+        assert!(counting.loops.is_empty());
+    }
+
+    #[test]
+    fn configured_runs() {
+        let program = compile("+[>+<+]").unwrap();
+        let config = ExecutionConfig::builder().max_ops(7).build();
+        let mut counting = Counting::default();
+        assert_eq!(
+            config.run_observed(&program, &mut StringInputOutput::new(), &mut counting),
+            Err(RuntimeError::FuelExhausted { executed: 7 })
+        );
+        // a failing op is not reported
+        assert_eq!(counting.ops.len(), 7);
+        assert_eq!(counting.loops, [1]);
+    }
+}
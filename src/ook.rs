@@ -0,0 +1,163 @@
+//! The front end of [`Language::Ook`], which reads the pairs of Ook! tokens
+//! as the brainfuck commands they stand for.
+//!
+//! [`Language::Ook`]: crate::Language::Ook
+
+use crate::{shebang_len, CompileError, Positions, Span};
+use alloc::vec::Vec;
+
+/// The command of every pair of tokens in `source`, spanning both tokens.
+/// Every token that is not an Ook! token is reported, and then whether the
+/// pairs are commands.
+pub(crate) fn tokens(source: &str) -> Result<Vec<(char, Span)>, Vec<CompileError>> {
+    let mut positions = Positions::new(source);
+    let mut errors = Vec::new();
+    let mut marks = Vec::new();
+    for (index, span) in words(source).enumerate() {
+        match &source[span.start..span.end] {
+            "Ook." => marks.push(('.', span)),
+            "Ook?" => marks.push(('?', span)),
+            "Ook!" => marks.push(('!', span)),
+            _ => errors.push(CompileError::InvalidOokToken {
+                index,
+                pos: positions.at(span.start),
+            }),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut commands = Vec::with_capacity(marks.len() / 2);
+    for (pair, marks) in marks.chunks(2).enumerate() {
+        let index = pair * 2;
+        let (first, second) = match *marks {
+            [first, second] => (first, second),
+            _ => {
+                let pos = positions.at(marks[0].1.start);
+                errors.push(CompileError::UnpairedOokToken { index, pos });
+                break;
+            }
+        };
+        let command = match (first.0, second.0) {
+            ('.', '?') => '>',
+            ('?', '.') => '<',
+            ('.', '.') => '+',
+            ('!', '!') => '-',
+            ('!', '.') => '.',
+            ('.', '!') => ',',
+            ('!', '?') => '[',
+            ('?', '!') => ']',
+            _ => {
+                let pos = positions.at(first.1.start);
+                errors.push(CompileError::UnknownOokPair { index, pos });
+                continue;
+            }
+        };
+        commands.push((command, first.1.to(second.1)));
+    }
+    if errors.is_empty() {
+        Ok(commands)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The spans of the whitespace separated words of `source` after a `#!` line.
+fn words(source: &str) -> impl Iterator<Item = Span> + '_ {
+    let mut rest = shebang_len(source);
+    core::iter::from_fn(move || {
+        let start = rest + source[rest..].find(|c: char| !c.is_whitespace())?;
+        let end = source[start..]
+            .find(char::is_whitespace)
+            .map_or(source.len(), |len| start + len);
+        rest = end;
+        Some(Span { start, end })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compile, compile_extended, compile_file, optimizer::Pipeline, CompileError, Error,
+        Extensions, Language, Position, StringInputOutput,
+    };
+
+    fn compile_ook(source: &str) -> Result<crate::Program, Vec<CompileError>> {
+        let extensions = Extensions {
+            language: Language::Ook,
+            ..Extensions::default()
+        };
+        compile_extended(source, &extensions, &Pipeline::default())
+    }
+
+    #[test]
+    fn hello_world() {
+        let bf = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let run = |program| {
+            let mut in_out = StringInputOutput::new();
+            crate::execute(&program, &mut in_out).unwrap();
+            in_out.into_output()
+        };
+        let ook = compile_file("programs/hello.ook", &Pipeline::default()).unwrap();
+        assert_eq!(ook, compile(bf).unwrap());
+        assert_eq!(run(ook), "Hello World!\n");
+    }
+
+    #[test]
+    fn commands() {
+        let program = compile_ook("#!/usr/bin/env brainfuck\nOok. Ook.\n\tOok! Ook.").unwrap();
+        assert_eq!(program, compile("+.").unwrap());
+        // a pair spans both of its tokens, whatever is between them
+        assert_eq!(program.source_span(0), Some(25..34));
+        let program = compile_ook("Ook. Ook! Ook! Ook? Ook? Ook! Ook. Ook? Ook? Ook. Ook! Ook!");
+        assert_eq!(program, compile(",[]><-"));
+        assert_eq!(Language::from_path("programs/hello.ook"), Language::Ook);
+        assert_eq!(Language::from_path("rot13.bf"), Language::Brainfuck);
+        assert_eq!("ook".parse(), Ok(Language::Ook));
+    }
+
+    #[test]
+    fn errors() {
+        let pos = |line, column| Position { line, column };
+        assert_eq!(
+            compile_ook("Ook. Ook? ook. Ook. Ook,\nOok"),
+            Err(vec![
+                CompileError::InvalidOokToken {
+                    index: 2,
+                    pos: pos(1, 11)
+                },
+                CompileError::InvalidOokToken {
+                    index: 4,
+                    pos: pos(1, 21)
+                },
+                CompileError::InvalidOokToken {
+                    index: 5,
+                    pos: pos(2, 1)
+                },
+            ])
+        );
+        assert_eq!(
+            compile_ook("Ook. Ook.\nOok? Ook? Ook."),
+            Err(vec![
+                CompileError::UnknownOokPair {
+                    index: 2,
+                    pos: pos(2, 1)
+                },
+                CompileError::UnpairedOokToken {
+                    index: 4,
+                    pos: pos(2, 11)
+                },
+            ])
+        );
+        // the brackets are checked at the position of their pairs
+        assert_eq!(
+            compile_ook("Ook. Ook. Ook! Ook?"),
+            Err(vec![CompileError::UnmatchedOpen { pos: pos(1, 11) }])
+        );
+        assert_eq!(
+            Error::Compile(compile_ook("Ook? Ook? Ook.").unwrap_err()).to_string(),
+            "'Ook? Ook?' at token 0, line 1, column 1, is not a command\nunpaired Ook! token 2 at line 1, column 11"
+        );
+    }
+}
@@ -0,0 +1,854 @@
+//! The optimizer turns the naive translation of a program into a tree in
+//! which every loop holds its body, rewrites the tree in a sequence of
+//! independent passes, see [`PASSES`], and lowers it to ops again.
+//!
+//! ```
+//! use brainfuck::optimizer::{OptLevel, Pipeline};
+//! use brainfuck::{compile_with, Ops};
+//!
+//! let pipeline = Pipeline::new(OptLevel::O1).disable("clear_loops").unwrap();
+//! let ops = compile_with("++[-]", &pipeline).unwrap();
+//! assert_eq!(
+//!     ops,
+//!     vec![Ops::Mod(2), Ops::LoopOpen(3), Ops::Mod(-1), Ops::LoopClose(1), Ops::End]
+//! );
+//! ```
+
+use crate::{Ops, Span};
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::convert::TryFrom;
+
+/// Selects which of the [`PASSES`] run, mirrors the `-O` flag of the binary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// Executes every token as it is.
+    O0,
+    /// Folds runs and replaces clear and scan loops.
+    O1,
+    /// Everything, including the loop and offset analyses.
+    #[default]
+    O2,
+}
+
+impl core::str::FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OptLevel, String> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            _ => Err(format!(
+                "invalid optimization level '{}', expected 0, 1 or 2",
+                s
+            )),
+        }
+    }
+}
+
+/// A single rewrite of the tree of a program.
+pub struct Pass {
+    pub name: &'static str,
+    /// The lowest level the pass is enabled at.
+    pub level: OptLevel,
+    run: fn(Vec<Node>) -> Vec<Node>,
+}
+
+/// Ops with the spans of the source they were compiled from.
+type Code = Vec<(Ops, Span)>;
+
+/// A program as a tree, in which every loop holds its body. The passes
+/// rewrite it and [`lower`] turns it into ops again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    /// Any op but the ones of a loop, and the brackets that do not match.
+    Op(Ops, Span),
+    /// A loop with the ops that open and close it, a `LoopOpen` or
+    /// `BalancedLoop` and a `LoopClose` or `MoveClose`.
+    Loop {
+        open: (Ops, Span),
+        body: Vec<Node>,
+        close: (Ops, Span),
+    },
+    /// A loop that runs its body at most once, spanning the whole loop.
+    If(Vec<Node>, Span),
+}
+
+/// The tree of `code`, an open that is never closed stays an op and is
+/// followed by what it would have held.
+fn tree(code: Code) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    // the open of every loop the current op is in and the nodes before it
+    let mut outer: Vec<((Ops, Span), Vec<Node>)> = Vec::new();
+    for (op, span) in code {
+        match op {
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) => {
+                outer.push(((op, span), core::mem::take(&mut nodes)));
+            }
+            Ops::LoopClose(_) | Ops::MoveClose(..) => match outer.pop() {
+                Some((open, before)) => {
+                    let body = core::mem::replace(&mut nodes, before);
+                    let close = (op, span);
+                    nodes.push(Node::Loop { open, body, close });
+                }
+                None => nodes.push(Node::Op(op, span)),
+            },
+            _ => nodes.push(Node::Op(op, span)),
+        }
+    }
+    while let Some(((op, span), before)) = outer.pop() {
+        let body = core::mem::replace(&mut nodes, before);
+        nodes.push(Node::Op(op, span));
+        nodes.extend(body);
+    }
+    nodes
+}
+
+/// Appends the ops of `nodes` to `code`. An `If` stores the length of its
+/// body until the jump targets are calculated.
+fn lower(nodes: &[Node], code: &mut Code) {
+    for node in nodes {
+        match node {
+            Node::Op(op, span) => code.push((*op, *span)),
+            Node::Loop { open, body, close } => {
+                code.push(*open);
+                lower(body, code);
+                code.push(*close);
+            }
+            Node::If(body, span) => {
+                let start = code.len();
+                code.push((Ops::If(0), *span));
+                lower(body, code);
+                code[start].0 = Ops::If(code.len() - 1 - start);
+            }
+        }
+    }
+}
+
+/// Rewrites the bodies in `nodes` and then `nodes` itself with `rewrite`,
+/// which so sees every list of nodes after the loops in it.
+fn bottom_up(nodes: Vec<Node>, rewrite: fn(Vec<Node>) -> Vec<Node>) -> Vec<Node> {
+    let nodes = nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Loop { open, body, close } => Node::Loop {
+                open,
+                body: bottom_up(body, rewrite),
+                close,
+            },
+            Node::If(body, span) => Node::If(bottom_up(body, rewrite), span),
+            node => node,
+        })
+        .collect();
+    rewrite(nodes)
+}
+
+/// All passes in the order they run, later passes rely on the patterns the
+/// earlier ones produce but stay correct if those are disabled.
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "fold_runs",
+        level: OptLevel::O1,
+        run: fold_runs,
+    },
+    Pass {
+        name: "clear_loops",
+        level: OptLevel::O1,
+        run: clear_loops,
+    },
+    Pass {
+        name: "scan_loops",
+        level: OptLevel::O1,
+        run: scan_loops,
+    },
+    Pass {
+        name: "dead_loops",
+        level: OptLevel::O2,
+        run: dead_loops,
+    },
+    Pass {
+        name: "multiply_loops",
+        level: OptLevel::O2,
+        run: multiply_loops,
+    },
+    Pass {
+        name: "fuse_offsets",
+        level: OptLevel::O2,
+        run: fuse_offsets,
+    },
+    Pass {
+        name: "dead_stores",
+        level: OptLevel::O2,
+        run: dead_stores,
+    },
+    Pass {
+        name: "if_loops",
+        level: OptLevel::O2,
+        run: if_loops,
+    },
+    Pass {
+        name: "fuse_pairs",
+        level: OptLevel::O2,
+        run: fuse_pairs,
+    },
+    Pass {
+        name: "balanced_loops",
+        level: OptLevel::O2,
+        run: balanced_loops,
+    },
+];
+
+/// The passes selected by an [`OptLevel`], minus the ones that were disabled
+/// by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    level: OptLevel,
+    disabled: Vec<&'static str>,
+}
+
+impl Pipeline {
+    pub fn new(level: OptLevel) -> Pipeline {
+        Pipeline {
+            level,
+            disabled: Vec::new(),
+        }
+    }
+
+    pub fn level(&self) -> OptLevel {
+        self.level
+    }
+
+    /// Skips the pass called `name`, which is an error if there is no such pass.
+    pub fn disable(mut self, name: &str) -> Result<Pipeline, String> {
+        match PASSES.iter().find(|pass| pass.name == name) {
+            Some(pass) => {
+                self.disabled.push(pass.name);
+                Ok(self)
+            }
+            None => Err(format!(
+                "unknown pass '{}', expected one of {}",
+                name,
+                PASSES
+                    .iter()
+                    .map(|pass| pass.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// The names of the passes that run, in order.
+    pub fn passes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.enabled().map(|pass| pass.name)
+    }
+
+    /// Runs the enabled passes over the naive translation of a program, the
+    /// jump targets are calculated afterwards.
+    pub fn run(&self, ops: Vec<Ops>) -> Vec<Ops> {
+        let code = ops.into_iter().map(|op| (op, Span::default())).collect();
+        self.run_spanned(code)
+            .into_iter()
+            .map(|(op, _)| op)
+            .collect()
+    }
+
+    pub(crate) fn run_spanned(&self, code: Code) -> Code {
+        let mut lowered = Vec::with_capacity(code.len());
+        let nodes = self
+            .enabled()
+            .fold(tree(code), |nodes, pass| (pass.run)(nodes));
+        lower(&nodes, &mut lowered);
+        lowered
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = &'static Pass> + '_ {
+        PASSES
+            .iter()
+            .filter(move |pass| pass.level <= self.level && !self.disabled.contains(&pass.name))
+    }
+}
+
+/// Merges consecutive `Move`s and consecutive `Mod`s. Deltas wrap around,
+/// which is exact as long as the cell width divides 2^32, true for all cell
+/// sizes, and keeps debug and release builds identical. Runs that cancel out are
+/// dropped.
+fn fold_runs(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let mut result = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match (result.last_mut(), node) {
+                (Some(Node::Op(Ops::Move(v1), last)), Node::Op(Ops::Move(v2), span)) => {
+                    *v1 = v1.wrapping_add(v2);
+                    *last = last.to(span);
+                }
+                (Some(Node::Op(Ops::Mod(v1), last)), Node::Op(Ops::Mod(v2), span)) => {
+                    *v1 = v1.wrapping_add(v2);
+                    *last = last.to(span);
+                }
+                (_, node) => result.push(node),
+            }
+            // a run that cancels out does nothing, `[<>]` has to stay a loop
+            if let Some(Node::Op(Ops::Move(0), _)) | Some(Node::Op(Ops::Mod(0), _)) = result.last()
+            {
+                result.pop();
+            }
+        }
+        result
+    })
+}
+
+/// Replaces `[-]` with `SetCell(0)` and merges it with a following `Mod`.
+fn clear_loops(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let mut result = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match (result.last_mut(), node) {
+                (_, Node::Loop { open, body, close })
+                    if matches!(body[..], [Node::Op(Ops::Mod(-1), _)]) =>
+                {
+                    result.push(Node::Op(Ops::SetCell(0), open.1.to(close.1)));
+                }
+                (
+                    Some(Node::Op(last @ Ops::SetCell(0), last_span)),
+                    Node::Op(Ops::Mod(v), span),
+                ) => {
+                    // a negative delta from zero wraps around to the top of the cell range
+                    *last = Ops::SetCell(v as u32);
+                    *last_span = last_span.to(span);
+                }
+                (_, node) => result.push(node),
+            }
+        }
+        result
+    })
+}
+
+/// Replaces loops that only move the pointer, like `[>]` or `[<<]`, with a
+/// `SearchZeroCell`.
+fn scan_loops(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let scan = |node| match node {
+            Node::Loop { open, body, close } => match body[..] {
+                [Node::Op(Ops::Move(n), _)] => Node::Op(Ops::SearchZeroCell(n), open.1.to(close.1)),
+                _ => Node::Loop { open, body, close },
+            },
+            node => node,
+        };
+        nodes.into_iter().map(scan).collect()
+    })
+}
+
+/// Drops loops that start while the current cell is known to be zero: at the
+/// start of the program (a common way to write comments), right after another
+/// loop, and after a cell was cleared. Scans that were already replaced by
+/// `SearchZeroCell` are dropped the same way.
+fn dead_loops(nodes: Vec<Node>) -> Vec<Node> {
+    fn drop_dead(nodes: Vec<Node>, mut zero: bool) -> Vec<Node> {
+        let mut result = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match node {
+                Node::Op(Ops::SearchZeroCell(_), _) | Node::Loop { .. } if zero => {}
+                // an unmatched open is left alone
+                Node::Op(Ops::LoopOpen(_), _) if zero => result.push(node),
+                Node::Op(op, _) => {
+                    zero = matches!(
+                        op,
+                        Ops::LoopClose(_) | Ops::SetCell(0) | Ops::SearchZeroCell(_)
+                    );
+                    result.push(node);
+                }
+                Node::Loop { open, body, close } => {
+                    let body = drop_dead(body, false);
+                    result.push(Node::Loop { open, body, close });
+                    zero = true;
+                }
+                Node::If(body, span) => {
+                    result.push(Node::If(drop_dead(body, false), span));
+                    zero = false;
+                }
+            }
+        }
+        result
+    }
+    drop_dead(nodes, true)
+}
+
+/// Replaces loops like `[->+>+++<<]`, which add multiples of the loop cell to
+/// other cells, with one `AddTo` per target cell and a `SetCell(0)`.
+fn multiply_loops(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let mut result = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let (open, body, close) = match node {
+                Node::Loop { open, body, close } => (open, body, close),
+                node => {
+                    result.push(node);
+                    continue;
+                }
+            };
+            // a body is only a candidate if it does not contain other loops
+            let ops: Option<Vec<Ops>> = body
+                .iter()
+                .map(|node| match *node {
+                    Node::Op(op, _) => Some(op),
+                    _ => None,
+                })
+                .collect();
+            match ops.as_deref().and_then(multiply_loop) {
+                Some(replacement) => {
+                    // every op of the replacement stands for the whole loop
+                    let span = open.1.to(close.1);
+                    result.extend(replacement.into_iter().map(|op| Node::Op(op, span)));
+                }
+                None => result.push(Node::Loop { open, body, close }),
+            }
+        }
+        result
+    })
+}
+
+fn multiply_loop(body: &[Ops]) -> Option<Vec<Ops>> {
+    let mut offset = 0;
+    let mut deltas: Vec<(isize, i32)> = Vec::new();
+    for op in body {
+        match *op {
+            Ops::Move(n) => offset += n,
+            Ops::Mod(v) => match deltas.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, delta)) => *delta = delta.wrapping_add(v),
+                None => deltas.push((offset, v)),
+            },
+            _ => return None,
+        }
+    }
+
+    // the loop cell has to count down by exactly one, otherwise the number of
+    // iterations is not the value of the cell
+    if offset != 0 || !deltas.contains(&(0, -1)) {
+        return None;
+    }
+
+    let mut ops: Vec<Ops> = deltas
+        .into_iter()
+        .filter(|&(offset, factor)| offset != 0 && factor != 0)
+        .map(|(offset, factor)| Ops::AddTo(offset, factor))
+        .collect();
+    ops.push(Ops::SetCell(0));
+    Some(ops)
+}
+
+/// Rewrites runs of `Move` and `Mod` into `ModAt`s relative to the pointer at
+/// the start of the run, followed by a single `Move` for the net displacement.
+/// All other ops and loops end a run. Each `ModAt` spans the `Mod`s it
+/// combines, the `Move` all moves of the run.
+fn fuse_offsets(nodes: Vec<Node>) -> Vec<Node> {
+    /// The deltas of a run, by offset, and the span of its moves.
+    type Run = (Vec<(isize, i32, Span)>, Option<Span>);
+
+    fn flush(result: &mut Vec<Node>, offset: &mut isize, (deltas, moves): &mut Run) {
+        for (at, delta, span) in deltas.drain(..) {
+            match (at, delta) {
+                (_, 0) => {}
+                (0, _) => result.push(Node::Op(Ops::Mod(delta), span)),
+                _ => result.push(Node::Op(Ops::ModAt(at, delta), span)),
+            }
+        }
+        if let Some(span) = moves.take() {
+            if *offset != 0 {
+                result.push(Node::Op(Ops::Move(*offset), span));
+            }
+        }
+        *offset = 0;
+    }
+
+    bottom_up(nodes, |nodes| {
+        let mut result = Vec::with_capacity(nodes.len());
+        let mut offset = 0;
+        let mut run: Run = (Vec::new(), None);
+        for node in nodes {
+            match node {
+                Node::Op(Ops::Move(n), span) => {
+                    offset += n;
+                    run.1 = Some(run.1.map_or(span, |moves| moves.to(span)));
+                }
+                Node::Op(Ops::Mod(v), span) => {
+                    match run.0.iter_mut().find(|(at, ..)| *at == offset) {
+                        Some((_, delta, mods)) => {
+                            *delta = delta.wrapping_add(v);
+                            *mods = mods.to(span);
+                        }
+                        None => run.0.push((offset, v, span)),
+                    }
+                }
+                node => {
+                    flush(&mut result, &mut offset, &mut run);
+                    result.push(node);
+                }
+            }
+        }
+        flush(&mut result, &mut offset, &mut run);
+        result
+    })
+}
+
+/// Removes writes to cells that are overwritten by a `SetCell` before anything
+/// reads them. Only straight-line code is analysed, loops and I/O end the
+/// analysis.
+fn dead_stores(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let mut dead = vec![false; nodes.len()];
+        // offsets relative to the current pointer that are overwritten later on
+        let mut overwritten: Vec<isize> = Vec::new();
+        for (i, node) in nodes.iter().enumerate().rev() {
+            let op = match *node {
+                Node::Op(op, _) => op,
+                _ => {
+                    overwritten.clear();
+                    continue;
+                }
+            };
+            match op {
+                Ops::SetCell(_) if overwritten.contains(&0) => dead[i] = true,
+                Ops::SetCell(_) => overwritten.push(0),
+                Ops::Mod(_) => dead[i] = overwritten.contains(&0),
+                Ops::ModAt(offset, _) => dead[i] = overwritten.contains(&offset),
+                Ops::AddTo(offset, _) => {
+                    dead[i] = overwritten.contains(&offset);
+                    if !dead[i] {
+                        overwritten.retain(|&at| at != 0);
+                    }
+                }
+                Ops::Move(n) => overwritten.iter_mut().for_each(|at| *at += n),
+                _ => overwritten.clear(),
+            }
+        }
+        nodes
+            .into_iter()
+            .zip(dead)
+            .filter_map(|(node, dead)| if dead { None } else { Some(node) })
+            .collect()
+    })
+}
+
+/// The op of `nodes` that runs last when they run to the end.
+fn last_op(nodes: &[Node]) -> Option<Ops> {
+    match nodes.last()? {
+        Node::Op(op, _) => Some(*op),
+        Node::Loop { close, .. } => Some(close.0),
+        Node::If(body, _) => last_op(body),
+    }
+}
+
+/// Turns loops that always end with the current cell cleared into an `If`,
+/// which runs the body at most once and has no closing op. The `If` spans
+/// the whole loop.
+fn if_loops(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let convert = |node| match node {
+            Node::Loop {
+                open,
+                body,
+                close: close @ (Ops::LoopClose(_), _),
+            } => {
+                // after the last op of the body the cell is zero on every path,
+                // if the body ends with an if that skipped its body the cell
+                // was zero as well
+                let clears = matches!(
+                    last_op(&body),
+                    Some(Ops::SetCell(0)) | Some(Ops::SearchZeroCell(_)) | Some(Ops::LoopClose(_))
+                );
+                if clears {
+                    Node::If(body, open.1.to(close.1))
+                } else {
+                    Node::Loop { open, body, close }
+                }
+            }
+            node => node,
+        };
+        nodes.into_iter().map(convert).collect()
+    })
+}
+
+/// Fuses a `Move` with the `LoopClose` right after it into a `MoveClose`, and
+/// a `Mod` with the `Move` right after it into a `ModMove`, which saves the
+/// interpreter a dispatch in the hot loops. The other passes do not know the
+/// fused ops, so this one runs late. The last op of an `If` body is never
+/// fused with the op after the body, which runs whether the body is skipped
+/// or not.
+fn fuse_pairs(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let mut result: Vec<Node> = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let node = match node {
+                Node::Loop {
+                    open,
+                    mut body,
+                    close: (Ops::LoopClose(target), span),
+                } => {
+                    let close = match body.last() {
+                        Some(&Node::Op(Ops::Move(n), last)) => {
+                            body.pop();
+                            (Ops::MoveClose(n, target), last.to(span))
+                        }
+                        _ => (Ops::LoopClose(target), span),
+                    };
+                    Node::Loop { open, body, close }
+                }
+                node => node,
+            };
+            match (result.last_mut(), node) {
+                (Some(Node::Op(last, last_span)), Node::Op(op, span)) => {
+                    let fused = match (*last, op) {
+                        (Ops::Move(n), Ops::LoopClose(target)) => Some(Ops::MoveClose(n, target)),
+                        (Ops::Mod(v), Ops::Move(n)) => Some(Ops::ModMove(v, n)),
+                        _ => None,
+                    };
+                    match fused {
+                        Some(fused) => {
+                            *last = fused;
+                            *last_span = last_span.to(span);
+                        }
+                        None => result.push(Node::Op(op, span)),
+                    }
+                }
+                (_, node) => result.push(node),
+            }
+        }
+        result
+    })
+}
+
+/// Turns loops that end at the cell they started at and reach only a fixed
+/// range of cells around it into a `BalancedLoop` with that range, which the
+/// interpreter runs without checking the tape if the range is on it. The
+/// ranges of the loops in the body are part of it, these stay plain loops.
+fn balanced_loops(nodes: Vec<Node>) -> Vec<Node> {
+    bottom_up(nodes, |nodes| {
+        let balance = |node| match node {
+            Node::Loop {
+                open: (Ops::LoopOpen(target), span),
+                body,
+                close,
+            } => {
+                let mut code = Vec::new();
+                lower(&body, &mut code);
+                code.push(close);
+                let ops: Vec<Ops> = code.into_iter().map(|(op, _)| op).collect();
+                let open = match excursion(&ops) {
+                    Some((min, max)) => Ops::BalancedLoop(target, min, max),
+                    None => Ops::LoopOpen(target),
+                };
+                Node::Loop {
+                    open: (open, span),
+                    body,
+                    close,
+                }
+            }
+            node => node,
+        };
+        nodes.into_iter().map(balance).collect()
+    })
+}
+
+/// The lowest and the highest offset from the cell a loop starts at that a
+/// run of its `body` reaches, the ops after its open up to and including its
+/// close. `None` unless every run of the body ends at the cell it started
+/// at, as every run of the loops in it has to, and the body has nothing but
+/// loops and ops that move by a fixed offset. A scan, for one, moves as far
+/// as the next zero.
+pub(crate) fn excursion(body: &[Ops]) -> Option<(i32, i32)> {
+    let (mut at, mut min, mut max) = (0isize, 0isize, 0isize);
+    // where the loops the current op is in started
+    let mut starts = Vec::new();
+    for (i, &op) in body.iter().enumerate() {
+        let reached = match op {
+            Ops::Move(offset) | Ops::ModMove(_, offset) => {
+                at = at.checked_add(offset)?;
+                at
+            }
+            Ops::ModAt(offset, _) | Ops::AddTo(offset, _) => at.checked_add(offset)?,
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) => {
+                starts.push(at);
+                at
+            }
+            Ops::LoopClose(_) | Ops::MoveClose(..) => {
+                if let Ops::MoveClose(offset, _) = op {
+                    at = at.checked_add(offset)?;
+                }
+                match starts.pop() {
+                    Some(start) if start == at => at,
+                    // the close of the loop itself
+                    None if i + 1 == body.len() && at == 0 => {
+                        return Some((i32::try_from(min).ok()?, i32::try_from(max).ok()?));
+                    }
+                    _ => return None,
+                }
+            }
+            Ops::Mod(_) | Ops::SetCell(_) | Ops::Print | Ops::Read | Ops::Break | Ops::Dump => at,
+            _ => return None,
+        };
+        min = min.min(reached);
+        max = max.max(reached);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_with;
+    use crate::Ops::*;
+
+    #[test]
+    fn unoptimized() {
+        let pipeline = Pipeline::new(OptLevel::O0);
+        assert_eq!(pipeline.passes().count(), 0);
+        assert_eq!(
+            compile_with("++[->+<]x.,", &pipeline).unwrap(),
+            vec![
+                Mod(1),
+                Mod(1),
+                LoopOpen(7),
+                Mod(-1),
+                Move(1),
+                Mod(1),
+                Move(-1),
+                LoopClose(2),
+                Print,
+                Read,
+                End
+            ]
+        );
+        assert_eq!(
+            compile_with("[-][>]", &pipeline).unwrap(),
+            vec![
+                LoopOpen(2),
+                Mod(-1),
+                LoopClose(0),
+                LoopOpen(5),
+                Move(1),
+                LoopClose(3),
+                End
+            ]
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let o1 = Pipeline::new(OptLevel::O1);
+        assert_eq!(
+            compile_with("[-]+++>>[<]", &o1).unwrap(),
+            vec![SetCell(3), Move(2), SearchZeroCell(-1), End]
+        );
+        assert_eq!(
+            compile_with(",[->+<]", &o1).unwrap(),
+            vec![
+                Read,
+                LoopOpen(6),
+                Mod(-1),
+                Move(1),
+                Mod(1),
+                Move(-1),
+                LoopClose(1),
+                End
+            ]
+        );
+        assert_eq!(
+            compile_with(",[->+<]", &Pipeline::default()).unwrap(),
+            vec![Read, AddTo(1, 1), SetCell(0), End]
+        );
+    }
+
+    #[test]
+    fn disabled_passes() {
+        let pipeline = Pipeline::default().disable("multiply_loops").unwrap();
+        assert!(!pipeline.passes().any(|name| name == "multiply_loops"));
+        assert_eq!(
+            compile_with(",[->+<]", &pipeline).unwrap(),
+            vec![
+                Read,
+                BalancedLoop(4, 0, 1),
+                Mod(-1),
+                ModAt(1, 1),
+                LoopClose(1),
+                End
+            ]
+        );
+        assert!(Pipeline::default().disable("no_such_pass").is_err());
+    }
+
+    #[test]
+    fn trees() {
+        let span = |start| Span {
+            start,
+            end: start + 1,
+        };
+        let code: Code = [
+            Read,
+            LoopOpen(0),
+            Print,
+            LoopClose(0),
+            LoopClose(0),
+            LoopOpen(0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &op)| (op, span(i)))
+        .collect();
+        let nodes = tree(code.clone());
+        assert_eq!(
+            nodes,
+            [
+                Node::Op(Read, span(0)),
+                Node::Loop {
+                    open: (LoopOpen(0), span(1)),
+                    body: vec![Node::Op(Print, span(2))],
+                    close: (LoopClose(0), span(3)),
+                },
+                Node::Op(LoopClose(0), span(4)),
+                Node::Op(LoopOpen(0), span(5)),
+            ]
+        );
+        let mut lowered = Vec::new();
+        lower(&nodes, &mut lowered);
+        assert_eq!(lowered, code);
+
+        // brackets that do not match are left alone by the passes
+        let pipeline = Pipeline::default();
+        assert_eq!(
+            pipeline.run(vec![LoopOpen(0), Mod(-1)]),
+            [LoopOpen(0), Mod(-1)]
+        );
+        assert_eq!(
+            pipeline.run(vec![Read, Move(1), LoopClose(0), LoopOpen(0), Move(1)]),
+            [Read, MoveClose(1, 0), LoopOpen(0), Move(1)]
+        );
+    }
+
+    #[test]
+    fn long_runs() {
+        let plus = "+".repeat(300);
+        let ops = compile_with(&format!("{}.", plus), &Pipeline::default()).unwrap();
+        assert_eq!(ops, vec![Mod(300), Print, End]);
+        let ops = compile_with(&format!("[-]{}.", "-".repeat(300)), &Pipeline::default());
+        assert_eq!(
+            ops.unwrap(),
+            vec![SetCell(300u32.wrapping_neg()), Print, End]
+        );
+
+        // the same output as one op per token, 300 = 44 and -300 = 212 modulo 256
+        let cases = [
+            (format!("{}.", plus), '\u{2c}'),
+            (format!(",[-]{}.", "-".repeat(300)), '\u{d4}'),
+        ];
+        for level in [OptLevel::O0, OptLevel::O2] {
+            for (source, expected) in &cases {
+                let ops = compile_with(source, &Pipeline::new(level)).unwrap();
+                let mut in_out = crate::StringInputOutput::with_input("x");
+                crate::execute(&ops, &mut in_out).unwrap();
+                assert_eq!(in_out.output(), expected.to_string());
+            }
+        }
+    }
+}
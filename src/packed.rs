@@ -0,0 +1,318 @@
+//! The ops packed into 4 bytes each for [`Dispatch::Packed`], an [`Ops`]
+//! takes 24 on 64-bit targets. The low 5 bits of a word are the kind and
+//! the 26 bits above the flag bit the operands: a single one takes all of
+//! them, a pair 10 bits for the offset and 16 for the value, factor or
+//! target. Operands that do not fit go to a table, the flag marks a word
+//! whose payload is their index there.
+//!
+//! [`Dispatch::Packed`]: crate::Dispatch::Packed
+
+use crate::{
+    cell_at, check_interrupt, dump_cells, outside_tape, scan_by_one, store_read, Cell,
+    ExecutionConfig, InputOutput, Ops, RuntimeError,
+};
+use alloc::vec::Vec;
+
+const MOVE: u32 = 0;
+const MOD: u32 = 1;
+const MOD_AT: u32 = 2;
+const ADD_TO: u32 = 3;
+const LOOP_OPEN: u32 = 4;
+const LOOP_CLOSE: u32 = 5;
+const IF: u32 = 6;
+const SET_CELL: u32 = 7;
+const SEARCH_ZERO_CELL: u32 = 8;
+const PRINT: u32 = 9;
+const READ: u32 = 10;
+const BREAK: u32 = 11;
+const DUMP: u32 = 12;
+const MOVE_CLOSE: u32 = 13;
+const MOD_MOVE: u32 = 14;
+const END: u32 = 15;
+
+const KIND: u32 = 0x1f;
+/// Set if the operands are in the table.
+const WIDE: u32 = 1 << 5;
+const PAYLOAD_SHIFT: u32 = 6;
+/// The bits of the offset of a pair, the other operand takes the rest.
+const OFFSET_BITS: u32 = 10;
+const SECOND_SHIFT: u32 = PAYLOAD_SHIFT + OFFSET_BITS;
+
+/// Packed ops, see the [module documentation](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Packed {
+    code: Vec<u32>,
+    wide: Vec<(isize, isize)>,
+}
+
+/// Whether `value` survives the round trip through the top `bits` of a word.
+fn fits(value: isize, bits: u32) -> bool {
+    let shift = isize::BITS - bits;
+    (value << shift) >> shift == value
+}
+
+impl Packed {
+    /// Packs `ops`, which have no pbrain procedures.
+    pub(crate) fn new(ops: &[Ops]) -> Packed {
+        let mut packed = Packed::default();
+        for &op in ops {
+            let word = match op {
+                Ops::Move(offset) => packed.single(MOVE, offset),
+                Ops::Mod(value) => packed.single(MOD, value as isize),
+                Ops::ModAt(offset, value) => packed.pair(MOD_AT, offset, value as isize),
+                Ops::AddTo(offset, factor) => packed.pair(ADD_TO, offset, factor as isize),
+                // the range is only needed by the match dispatch
+                Ops::LoopOpen(end) | Ops::BalancedLoop(end, ..) => {
+                    packed.single(LOOP_OPEN, end as isize)
+                }
+                Ops::LoopClose(start) => packed.single(LOOP_CLOSE, start as isize),
+                Ops::If(end) => packed.single(IF, end as isize),
+                // cells are at most 32 bits wide, so the value is one as well
+                Ops::SetCell(value) => packed.single(SET_CELL, value as i32 as isize),
+                Ops::SearchZeroCell(step) => packed.single(SEARCH_ZERO_CELL, step),
+                Ops::Print => PRINT,
+                Ops::Read => READ,
+                Ops::Break => BREAK,
+                Ops::Dump => DUMP,
+                Ops::MoveClose(offset, start) => packed.pair(MOVE_CLOSE, offset, start as isize),
+                Ops::ModMove(value, offset) => packed.pair(MOD_MOVE, offset, value as isize),
+                Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call => {
+                    unreachable!("programs with procedures are matched")
+                }
+                Ops::End => END,
+            };
+            packed.code.push(word);
+        }
+        packed
+    }
+
+    fn single(&mut self, kind: u32, operand: isize) -> u32 {
+        if fits(operand, 32 - PAYLOAD_SHIFT) {
+            kind | (operand as u32) << PAYLOAD_SHIFT
+        } else {
+            self.escape(kind, operand, 0)
+        }
+    }
+
+    fn pair(&mut self, kind: u32, offset: isize, second: isize) -> u32 {
+        if fits(offset, OFFSET_BITS) && fits(second, 32 - SECOND_SHIFT) {
+            let offset = (offset as u32) << PAYLOAD_SHIFT & ((1 << SECOND_SHIFT) - 1);
+            kind | offset | (second as u32) << SECOND_SHIFT
+        } else {
+            self.escape(kind, offset, second)
+        }
+    }
+
+    fn escape(&mut self, kind: u32, first: isize, second: isize) -> u32 {
+        self.wide.push((first, second));
+        kind | WIDE | ((self.wide.len() - 1) as u32) << PAYLOAD_SHIFT
+    }
+
+    #[inline(always)]
+    fn single_of(&self, word: u32) -> isize {
+        if word & WIDE != 0 {
+            return self.wide[(word >> PAYLOAD_SHIFT) as usize].0;
+        }
+        (word as i32 >> PAYLOAD_SHIFT) as isize
+    }
+
+    #[inline(always)]
+    fn pair_of(&self, word: u32) -> (isize, isize) {
+        if word & WIDE != 0 {
+            return self.wide[(word >> PAYLOAD_SHIFT) as usize];
+        }
+        let offset = ((word << (32 - SECOND_SHIFT)) as i32 >> (32 - OFFSET_BITS)) as isize;
+        (offset, (word as i32 >> SECOND_SHIFT) as isize)
+    }
+
+    /// The ops again, to check that packing loses nothing.
+    #[cfg(test)]
+    fn ops(&self) -> Vec<Ops> {
+        let ops = self.code.iter().map(|&word| {
+            let single = || self.single_of(word);
+            let pair = || self.pair_of(word);
+            match word & KIND {
+                MOVE => Ops::Move(single()),
+                MOD => Ops::Mod(single() as i32),
+                MOD_AT => Ops::ModAt(pair().0, pair().1 as i32),
+                ADD_TO => Ops::AddTo(pair().0, pair().1 as i32),
+                LOOP_OPEN => Ops::LoopOpen(single() as usize),
+                LOOP_CLOSE => Ops::LoopClose(single() as usize),
+                IF => Ops::If(single() as usize),
+                SET_CELL => Ops::SetCell(single() as u32),
+                SEARCH_ZERO_CELL => Ops::SearchZeroCell(single()),
+                PRINT => Ops::Print,
+                READ => Ops::Read,
+                BREAK => Ops::Break,
+                DUMP => Ops::Dump,
+                MOVE_CLOSE => Ops::MoveClose(pair().0, pair().1 as usize),
+                MOD_MOVE => Ops::ModMove(pair().1 as i32, pair().0),
+                _ => Ops::End,
+            }
+        });
+        ops.collect()
+    }
+}
+
+/// Runs the packed ops from `ip` to the end, which leaves `ip` at the op that
+/// ended the program or stopped it with an error, like `execute_loop`.
+pub(crate) fn run<C: Cell, IO: InputOutput + ?Sized>(
+    packed: &Packed,
+    config: &ExecutionConfig,
+    in_out: &mut IO,
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    next: &mut usize,
+) -> Result<(), RuntimeError> {
+    // local copies stay in registers, see execute_cells
+    let mut cells = core::mem::take(memory);
+    let mut current = *pos;
+    let mut ip = *next;
+    let result = execute(packed, config, in_out, &mut cells, &mut current, &mut ip);
+    *memory = cells;
+    *pos = current;
+    *next = ip;
+    result
+}
+
+#[inline(always)]
+fn execute<C: Cell, IO: InputOutput + ?Sized>(
+    packed: &Packed,
+    config: &ExecutionConfig,
+    in_out: &mut IO,
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    next: &mut usize,
+) -> Result<(), RuntimeError> {
+    let zero = C::default();
+    let interrupt = config.interrupt;
+    let mut ip = *next;
+    macro_rules! stop_on_err {
+        ($result:expr) => {
+            match $result {
+                Ok(value) => value,
+                Err(err) => {
+                    *next = ip;
+                    return Err(err);
+                }
+            }
+        };
+    }
+
+    macro_rules! move_by {
+        ($offset:expr) => {{
+            *pos = pos.wrapping_add($offset as usize);
+            if *pos >= memory.len() {
+                *pos = stop_on_err!(outside_tape(memory, *pos, ip, config.tape));
+            }
+        }};
+    }
+
+    while let Some(&word) = packed.code.get(ip) {
+        match word & KIND {
+            MOVE => move_by!(packed.single_of(word)),
+            MOD => {
+                let value = packed.single_of(word) as i32;
+                memory[*pos] = memory[*pos].wrapping_add_i32(value)
+            }
+            MOD_AT => {
+                let (offset, value) = packed.pair_of(word);
+                let target = stop_on_err!(cell_at(memory, pos, offset, ip, config.tape));
+                memory[target] = memory[target].wrapping_add_i32(value as i32);
+            }
+            ADD_TO => {
+                let value = memory[*pos];
+                if value != zero {
+                    let (offset, factor) = packed.pair_of(word);
+                    let target = stop_on_err!(cell_at(memory, pos, offset, ip, config.tape));
+                    memory[target] = memory[target].wrapping_add_mul(value, factor as i32);
+                }
+            }
+            LOOP_OPEN | IF => {
+                if memory[*pos] == zero {
+                    ip = packed.single_of(word) as usize;
+                }
+            }
+            LOOP_CLOSE => {
+                if memory[*pos] != zero {
+                    stop_on_err!(check_interrupt(interrupt, ip));
+                    ip = packed.single_of(word) as usize;
+                }
+            }
+            MOVE_CLOSE => {
+                let (offset, start) = packed.pair_of(word);
+                move_by!(offset);
+                if memory[*pos] != zero {
+                    stop_on_err!(check_interrupt(interrupt, ip));
+                    ip = start as usize;
+                }
+            }
+            MOD_MOVE => {
+                let (offset, value) = packed.pair_of(word);
+                memory[*pos] = memory[*pos].wrapping_add_i32(value as i32);
+                move_by!(offset);
+            }
+            SET_CELL => memory[*pos] = C::from_u32(packed.single_of(word) as u32),
+            SEARCH_ZERO_CELL => {
+                let step = packed.single_of(word);
+                if step == 1 || step == -1 {
+                    stop_on_err!(scan_by_one(memory, pos, step, ip, config.tape));
+                }
+                while memory[*pos] != zero {
+                    move_by!(step);
+                }
+            }
+            PRINT => in_out.write_value(memory[*pos].to_u32()),
+            READ => stop_on_err!(store_read(
+                &mut memory[*pos],
+                in_out.read_value(),
+                config.eof,
+                ip
+            )),
+            BREAK => {}
+            DUMP => dump_cells(in_out, ip, *pos, memory),
+            _ => break,
+        }
+        ip += 1;
+    }
+    *next = ip;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ops::*;
+
+    #[test]
+    fn round_trip() {
+        let ops = [
+            Move(-1),
+            Move(1 << 25),
+            Mod(-(1 << 25)),
+            ModAt(-512, -32768),
+            ModAt(512, 1),
+            AddTo(511, 32767),
+            AddTo(1, 32768),
+            LoopOpen(1 << 24),
+            LoopClose(usize::MAX >> 1),
+            If(3),
+            SetCell(u32::MAX),
+            SetCell(1 << 31),
+            SearchZeroCell(isize::MIN),
+            Print,
+            Read,
+            Break,
+            Dump,
+            MoveClose(-3, 9),
+            MoveClose(1, 40000),
+            ModMove(i32::MIN, 2),
+            ModMove(-5, -7),
+            End,
+        ];
+        let packed = Packed::new(&ops);
+        assert_eq!(packed.ops(), ops);
+        // only the operands that do not fit go to the table
+        assert_eq!(packed.wide.len(), 8);
+    }
+}
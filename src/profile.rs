@@ -0,0 +1,295 @@
+//! Execution counters collected by [`ExecutionConfig::run_profiled`].
+//!
+//! ```
+//! use brainfuck::profile::Profile;
+//! use brainfuck::{compile, ExecutionConfig, StringInputOutput};
+//!
+//! let program = compile("+++[>+<-]").unwrap();
+//! let mut profile = Profile::new();
+//! ExecutionConfig::default()
+//!     .run_profiled(&program, &mut StringInputOutput::new(), &mut profile)
+//!     .unwrap();
+//! assert_eq!(profile.count("Mod"), 1);
+//! print!("{}", profile);
+//! ```
+//!
+//! [`ExecutionConfig::run_profiled`]: crate::ExecutionConfig::run_profiled
+
+use crate::{Ops, Position, Probe, Program, RuntimeError, Span, Tape};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::ops::Range;
+
+/// How often every op of a program was executed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    ops: Vec<Ops>,
+    spans: Vec<Span>,
+    counts: Vec<u64>,
+}
+
+/// A loop that survived optimization, see [`Profile::hot_loops`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotLoop {
+    /// The bytes of the source from `[` to `]`.
+    pub span: Range<usize>,
+    /// How often the body ran.
+    pub iterations: u64,
+    /// Ops executed in the body, including the ones of nested loops.
+    pub executed: u64,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    /// Clears all counts and sizes them for `program`.
+    pub(crate) fn reset(&mut self, program: &Program) {
+        self.ops = program.to_vec();
+        self.spans = program.spans().to_vec();
+        self.counts = vec![0; program.len()];
+    }
+
+    /// The ops of the profiled program.
+    pub fn ops(&self) -> &[Ops] {
+        &self.ops
+    }
+
+    /// Executions per op, indexed like [`Profile::ops`]. A `SearchZeroCell`
+    /// counts once per execution, not once per cell it visits.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Executions of all ops of a kind, named like the variant of [`Ops`].
+    pub fn count(&self, kind: &str) -> u64 {
+        self.by_kind()
+            .into_iter()
+            .find(|&(name, _)| name == kind)
+            .map_or(0, |(_, count)| count)
+    }
+
+    /// Executions per kind of op, the most frequent first. Kinds that never
+    /// ran are left out.
+    pub fn by_kind(&self) -> Vec<(&'static str, u64)> {
+        let mut kinds: Vec<(&'static str, u64)> = Vec::new();
+        for (op, &count) in self.ops.iter().zip(&self.counts) {
+            let name = kind(op);
+            match kinds.iter_mut().find(|(kind, _)| *kind == name) {
+                Some((_, total)) => *total += count,
+                None => kinds.push((name, count)),
+            }
+        }
+        kinds.retain(|&(_, count)| count > 0);
+        // the sort is stable, equal counts keep the order of the program
+        kinds.sort_by_key(|&(_, count)| core::cmp::Reverse(count));
+        kinds
+    }
+
+    /// The loops of the program, the most iterations first. Loops the
+    /// optimizer replaced, like `[-]` or `[->+<]`, are single ops and not
+    /// listed. Empty for programs that were not compiled from source.
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        if self.spans.len() != self.ops.len() {
+            return Vec::new();
+        }
+        let mut loops: Vec<HotLoop> = self
+            .ops
+            .iter()
+            .enumerate()
+            .filter_map(|(open, op)| match *op {
+                Ops::LoopOpen(close) | Ops::BalancedLoop(close, ..) => Some(HotLoop {
+                    span: self.spans[open].start..self.spans[close].end,
+                    // the close runs once at the end of every iteration
+                    iterations: self.counts[close],
+                    executed: self.counts[open + 1..close].iter().sum(),
+                }),
+                _ => None,
+            })
+            .collect();
+        loops.sort_by_key(|hot| core::cmp::Reverse(hot.iterations));
+        loops
+    }
+
+    /// A table of the `top` [`Profile::hot_loops`], located in `source`, which
+    /// has to be the source the program was compiled from.
+    pub fn loop_report(&self, source: &str, top: usize) -> String {
+        let total = self.total();
+        let mut report = format!(
+            "{:<22}{:>14}{:>16}{:>9}  {}\n",
+            "loop", "iterations", "executed", "share", "source"
+        );
+        for hot in self.hot_loops().into_iter().take(top) {
+            let share = 100.0 * hot.executed as f64 / total as f64;
+            report += &format!(
+                "{:<22}{:>14}{:>16}{:>8.2}%  {}\n",
+                Position::in_source(source, hot.span.start).to_string(),
+                hot.iterations,
+                hot.executed,
+                share,
+                excerpt(&source[hot.span])
+            );
+        }
+        report
+    }
+}
+
+/// The commands of `code` without comments, shortened to fit into a table.
+fn excerpt(code: &str) -> String {
+    const MAX_LEN: usize = 40;
+    let commands: String = code.chars().filter(|c| "+-<>[].,".contains(*c)).collect();
+    if commands.len() > MAX_LEN {
+        format!("{}...", &commands[..MAX_LEN - 3])
+    } else {
+        commands
+    }
+}
+
+impl Probe for Profile {
+    #[inline(always)]
+    fn on_op(&mut self, ip: usize, _: Ops, _: usize, _: &dyn Tape) -> Result<(), RuntimeError> {
+        self.counts[ip] += 1;
+        Ok(())
+    }
+}
+
+/// Prints a table of the executions per kind and their share of the total.
+impl core::fmt::Display for Profile {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let total = self.total();
+        writeln!(f, "{:<16}{:>16}{:>9}", "op", "executed", "share")?;
+        for (name, count) in self.by_kind() {
+            let share = 100.0 * count as f64 / total as f64;
+            writeln!(f, "{:<16}{:>16}{:>8.2}%", name, count, share)?;
+        }
+        writeln!(f, "{:<16}{:>16}", "total", total)
+    }
+}
+
+fn kind(op: &Ops) -> &'static str {
+    match op {
+        Ops::Move(_) => "Move",
+        Ops::Mod(_) => "Mod",
+        Ops::ModAt(..) => "ModAt",
+        Ops::AddTo(..) => "AddTo",
+        Ops::LoopOpen(_) => "LoopOpen",
+        Ops::LoopClose(_) => "LoopClose",
+        Ops::If(_) => "If",
+        Ops::SetCell(_) => "SetCell",
+        Ops::SearchZeroCell(_) => "SearchZeroCell",
+        Ops::Print => "Print",
+        Ops::Read => "Read",
+        Ops::Break => "Break",
+        Ops::ProcDefStart(_) => "ProcDefStart",
+        Ops::ProcDefEnd => "ProcDefEnd",
+        Ops::Call => "Call",
+        Ops::Dump => "Dump",
+        Ops::MoveClose(..) => "MoveClose",
+        Ops::ModMove(..) => "ModMove",
+        Ops::BalancedLoop(..) => "BalancedLoop",
+        Ops::End => "End",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{compile_with, ExecutionConfig, StringInputOutput};
+
+    fn run(source: &str, level: OptLevel) -> Profile {
+        let program = compile_with(source, &Pipeline::new(level)).unwrap();
+        let mut profile = Profile::new();
+        ExecutionConfig::default()
+            .run_profiled(&program, &mut StringInputOutput::new(), &mut profile)
+            .unwrap();
+        profile
+    }
+
+    #[test]
+    fn counts() {
+        // Mod(3), LoopOpen, Move, Mod, Move, Mod, LoopClose, End
+        let profile = run("+++[>+<-]", OptLevel::O1);
+        assert_eq!(profile.counts(), &[1, 1, 3, 3, 3, 3, 3, 1]);
+        assert_eq!(profile.count("Mod"), 7);
+        assert_eq!(profile.count("LoopOpen"), 1);
+        assert_eq!(profile.count("LoopClose"), 3);
+        assert_eq!(profile.count("Read"), 0);
+        assert_eq!(profile.total(), 18);
+        assert_eq!(
+            profile.by_kind(),
+            vec![
+                ("Mod", 7),
+                ("Move", 6),
+                ("LoopClose", 3),
+                ("LoopOpen", 1),
+                ("End", 1)
+            ]
+        );
+
+        // the whole loop becomes an AddTo
+        let profile = run("+++[>+<-]", OptLevel::O2);
+        assert_eq!(profile.count("AddTo"), 1);
+        assert_eq!(profile.count("LoopClose"), 0);
+    }
+
+    #[test]
+    fn stopped_programs() {
+        let program = compile_with("+[>+]", &Pipeline::new(OptLevel::O1)).unwrap();
+        let mut profile = Profile::new();
+        let config = ExecutionConfig::builder().tape_size(4).build();
+        assert!(config
+            .run_profiled(&program, &mut StringInputOutput::new(), &mut profile)
+            .is_err());
+        // the loop body ran three times before the fourth move left the tape
+        assert_eq!(profile.count("Move"), 4);
+    }
+
+    #[test]
+    fn hot_loops() {
+        // the inner loop runs three times per iteration of the outer one, the
+        // print keeps the optimizer from replacing it
+        let source = "++[>+++[.-]<-]\n+[-.]";
+        let program = compile_with(source, &Pipeline::default()).unwrap();
+        let mut profile = Profile::new();
+        ExecutionConfig::default()
+            .run_profiled(&program, &mut StringInputOutput::new(), &mut profile)
+            .unwrap();
+
+        let loops = profile.hot_loops();
+        assert_eq!(loops.len(), 3);
+        assert_eq!((loops[0].span.clone(), loops[0].iterations), (7..11, 6));
+        assert_eq!((loops[1].span.clone(), loops[1].iterations), (2..14, 2));
+        assert_eq!((loops[2].span.clone(), loops[2].iterations), (16..20, 1));
+        // the body of the outer loop contains the inner one
+        assert!(loops[1].executed > loops[0].executed);
+
+        let report = profile.loop_report(source, 2);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("line 1, column 8") && lines[1].ends_with("  [.-]"));
+        assert!(lines[2].starts_with("line 1, column 3") && lines[2].ends_with("  [>+++[.-]<-]"));
+        assert!(profile.loop_report(source, 10).contains("line 2, column 2"));
+    }
+
+    #[test]
+    fn excerpts() {
+        assert_eq!(excerpt("[- comment\n>+<]"), "[->+<]");
+        assert_eq!(excerpt(&"+".repeat(50)), format!("{}...", "+".repeat(37)));
+    }
+
+    #[test]
+    fn table() {
+        let table = run("+.", OptLevel::O2).to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("Mod") && lines[1].ends_with("33.33%"));
+        assert!(lines[4].starts_with("total") && lines[4].ends_with(" 3"));
+    }
+}
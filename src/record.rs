@@ -0,0 +1,290 @@
+//! Recording the input and output of a run with [`RecordingIo`] and checking
+//! a later run against the recording with [`ReplayIo`], which turns an
+//! interactive session into a regression test.
+//!
+//! A log has one line per byte: its sequence number, `r` for a byte `,` read
+//! or `w` for one `.` wrote, and the byte in hex. `r eof` is a read that found
+//! the end of the input.
+//!
+//! ```
+//! use brainfuck::record::{RecordingIo, ReplayIo};
+//! use brainfuck::{compile, execute, StringInputOutput};
+//!
+//! let cat = compile(",[.,]").unwrap();
+//! let mut recording = RecordingIo::new(StringInputOutput::with_input("hi"), Vec::new());
+//! execute(&cat, &mut recording).unwrap();
+//! let (_, log) = recording.into_parts();
+//! let log = String::from_utf8(log).unwrap();
+//! assert_eq!(log, "0 r 68\n1 w 68\n2 r 69\n3 w 69\n4 r eof\n");
+//!
+//! let mut replay = ReplayIo::parse(&log).unwrap();
+//! execute(&cat, &mut replay).unwrap();
+//! assert_eq!(replay.finish(), Ok(()));
+//! ```
+
+use crate::{CellDump, InputOutput};
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// Passes everything on to `in_out` and logs it to `log`, which should be
+/// buffered. The log has bytes, so values are read and written as bytes.
+pub struct RecordingIo<T: InputOutput, W: Write> {
+    in_out: T,
+    log: W,
+    sequence: u64,
+    error: Option<std::io::Error>,
+}
+
+impl<T: InputOutput, W: Write> RecordingIo<T, W> {
+    pub fn new(in_out: T, log: W) -> RecordingIo<T, W> {
+        RecordingIo {
+            in_out,
+            log,
+            sequence: 0,
+            error: None,
+        }
+    }
+
+    /// The error that stopped the log, if writing to it failed. The program
+    /// keeps running.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_parts(self) -> (T, W) {
+        (self.in_out, self.log)
+    }
+
+    fn record(&mut self, kind: char, byte: Option<u8>) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = match byte {
+            Some(byte) => writeln!(self.log, "{} {} {:02x}", self.sequence, kind, byte),
+            None => writeln!(self.log, "{} {} eof", self.sequence, kind),
+        };
+        match line {
+            Ok(()) => self.sequence += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+impl<T: InputOutput, W: Write> InputOutput for RecordingIo<T, W> {
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = self.in_out.read_byte();
+        self.record('r', byte);
+        byte
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.record('w', Some(byte));
+        self.in_out.write_byte(byte);
+    }
+    /// Fails if writing the log failed at any point.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.in_out.flush()?;
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.log.flush()
+    }
+    fn dump(&mut self, dump: &CellDump) {
+        self.in_out.dump(dump)
+    }
+}
+
+/// The first byte of the output that differs from the recording, `None`
+/// where one of them ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The offset in the output.
+    pub offset: usize,
+    pub expected: Option<u8>,
+    pub found: Option<u8>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let byte = |byte: Option<u8>| match byte {
+            Some(byte) => format!("{:#04x}", byte),
+            None => "the end of the output".to_string(),
+        };
+        write!(
+            f,
+            "the output differs from the recording at byte {}: expected {}, found {}",
+            self.offset,
+            byte(self.expected),
+            byte(self.found)
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Feeds the recorded input to `,` and compares what `.` writes with the
+/// recorded output.
+pub struct ReplayIo {
+    input: VecDeque<Option<u8>>,
+    expected: Vec<u8>,
+    written: usize,
+    divergence: Option<Divergence>,
+}
+
+impl ReplayIo {
+    /// Reads a log written by [`RecordingIo`].
+    pub fn parse(log: &str) -> Result<ReplayIo, String> {
+        let mut replay = ReplayIo {
+            input: VecDeque::new(),
+            expected: Vec::new(),
+            written: 0,
+            divergence: None,
+        };
+        for (number, line) in log.lines().enumerate() {
+            let invalid = || format!("invalid line {} in the recording: '{}'", number + 1, line);
+            let mut fields = line.split(' ');
+            let (sequence, kind, byte) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(sequence), Some(kind), Some(byte)) if fields.next().is_none() => {
+                    (sequence, kind, byte)
+                }
+                _ => return Err(invalid()),
+            };
+            // a missing or repeated line means the log was cut or edited
+            if sequence.parse() != Ok(number) {
+                return Err(invalid());
+            }
+            let byte = match byte {
+                "eof" => None,
+                hex if hex.len() == 2 && hex.bytes().all(|digit| digit.is_ascii_hexdigit()) => {
+                    Some(u8::from_str_radix(hex, 16).map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            };
+            match (kind, byte) {
+                ("r", byte) => replay.input.push_back(byte),
+                ("w", Some(byte)) => replay.expected.push(byte),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// The first difference between the output and the recording, including
+    /// output that is missing at the end.
+    pub fn finish(&self) -> Result<(), Divergence> {
+        match self.divergence {
+            Some(divergence) => Err(divergence),
+            None if self.written < self.expected.len() => Err(Divergence {
+                offset: self.written,
+                expected: Some(self.expected[self.written]),
+                found: None,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl InputOutput for ReplayIo {
+    /// The recorded input, the end of the input once it ran out.
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.pop_front().flatten()
+    }
+    fn write_byte(&mut self, byte: u8) {
+        let expected = self.expected.get(self.written).copied();
+        if self.divergence.is_none() && expected != Some(byte) {
+            self.divergence = Some(Divergence {
+                offset: self.written,
+                expected,
+                found: Some(byte),
+            });
+        }
+        self.written += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, EofBehavior, ExecutionConfig, StringInputOutput};
+
+    /// A cat that also copies NUL bytes, though not 0xff.
+    const CAT: &str = ",+[-.,+]";
+
+    fn config() -> ExecutionConfig {
+        ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build()
+    }
+
+    fn record(source: &str, input: &str) -> String {
+        let mut recording = RecordingIo::new(StringInputOutput::with_input(input), Vec::new());
+        config()
+            .run(&compile(source).unwrap(), &mut recording)
+            .unwrap();
+        assert!(recording.error().is_none());
+        String::from_utf8(recording.into_parts().1).unwrap()
+    }
+
+    fn replay(source: &str, log: &str) -> Result<(), Divergence> {
+        let mut replay = ReplayIo::parse(log).unwrap();
+        config()
+            .run(&compile(source).unwrap(), &mut replay)
+            .unwrap();
+        replay.finish()
+    }
+
+    #[test]
+    fn cat() {
+        // newlines, NUL and bytes above 0x7f survive the log
+        let log = record(CAT, "a\n\0\u{fe}b");
+        assert_eq!(log.lines().count(), 11);
+        assert!(log.starts_with("0 r 61\n1 w 61\n2 r 0a\n3 w 0a\n4 r 00\n5 w 00\n6 r fe\n"));
+        assert!(log.ends_with("9 w 62\n10 r eof\n"));
+        assert_eq!(replay(CAT, &log), Ok(()));
+    }
+
+    #[test]
+    fn mismatch() {
+        let log = record(CAT, "abc");
+        // upper case from the second byte on
+        assert_eq!(
+            replay(",.,+[-------------------------------- -.,+]", &log),
+            Err(Divergence {
+                offset: 1,
+                expected: Some(b'b'),
+                found: Some(b'B'),
+            })
+        );
+        // stops after the first byte
+        assert_eq!(
+            replay(",.", &log),
+            Err(Divergence {
+                offset: 1,
+                expected: Some(b'b'),
+                found: None,
+            })
+        );
+        // one byte too many
+        assert_eq!(
+            replay(",+[-.,+]+.", &log),
+            Err(Divergence {
+                offset: 3,
+                expected: None,
+                found: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_logs() {
+        assert!(ReplayIo::parse("").is_ok());
+        assert!(ReplayIo::parse("0 r 6").is_err());
+        assert!(ReplayIo::parse("0 w eof").is_err());
+        assert!(ReplayIo::parse("0 x 61").is_err());
+        assert!(ReplayIo::parse("0 r 61\n2 w 61").is_err());
+        assert!(ReplayIo::parse("0 r +1").is_err());
+        assert_eq!(
+            ReplayIo::parse("0 r 61 62").err().unwrap(),
+            "invalid line 1 in the recording: '0 r 61 62'"
+        );
+    }
+}
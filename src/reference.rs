@@ -0,0 +1,103 @@
+//! A deliberately simple interpreter that walks the source token by token,
+//! without compiling or optimizing anything. It is slow, but easy to check by
+//! reading, which makes it the oracle the optimized interpreter is compared
+//! against.
+
+use crate::{CellSize, EofBehavior, ExecutionConfig, InputOutput, RuntimeError, TapeMode};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Runs `source` with the same semantics as [`ExecutionConfig::run`], except
+/// that the `ip` of a [`RuntimeError`] is the index of the token in `source`
+/// and that `max_ops` counts every character of the source that is passed.
+/// Every single `<` and `>` is checked against the tape, so this may stop with
+/// [`RuntimeError::PointerOutOfBounds`] where the compiled program does not,
+/// but never the other way around.
+///
+/// Panics if the brackets do not match, use [`crate::parse`] to check them.
+pub fn run(
+    source: &str,
+    in_out: &mut dyn InputOutput,
+    config: &ExecutionConfig,
+) -> Result<(), RuntimeError> {
+    let tokens: Vec<char> = source.chars().collect();
+    let mask = match config.cell_size() {
+        CellSize::U8 => 0xff,
+        CellSize::U16 => 0xffff,
+        CellSize::U32 => u32::MAX,
+    };
+    let mut memory = vec![0u32; config.tape_size()];
+    let mut pos: usize = 0;
+    // the shebang is skipped like by the compiler
+    let mut ip = source[..crate::shebang_len(source)].chars().count();
+    let mut executed = 0;
+
+    while ip < tokens.len() {
+        if Some(executed) == config.max_ops() {
+            return Err(RuntimeError::FuelExhausted { executed });
+        }
+        executed += 1;
+        match tokens[ip] {
+            '>' => {
+                pos += 1;
+                if pos == memory.len() {
+                    match config.tape() {
+                        TapeMode::Fixed => {
+                            return Err(RuntimeError::PointerOutOfBounds {
+                                ip,
+                                pos: pos as isize,
+                            })
+                        }
+                        TapeMode::Grow | TapeMode::Infinite => memory.push(0),
+                        TapeMode::Wrap => pos = 0,
+                    }
+                }
+            }
+            '<' => {
+                if pos == 0 {
+                    match config.tape() {
+                        TapeMode::Fixed | TapeMode::Grow => {
+                            return Err(RuntimeError::PointerOutOfBounds { ip, pos: -1 })
+                        }
+                        TapeMode::Infinite => memory.insert(0, 0),
+                        TapeMode::Wrap => pos = memory.len() - 1,
+                    }
+                } else {
+                    pos -= 1;
+                }
+            }
+            '+' => memory[pos] = memory[pos].wrapping_add(1) & mask,
+            '-' => memory[pos] = memory[pos].wrapping_sub(1) & mask,
+            '.' => in_out.write_byte(memory[pos] as u8),
+            ',' => match (in_out.read_byte(), config.eof()) {
+                (Some(byte), _) => memory[pos] = byte as u32,
+                (None, EofBehavior::Zero) => memory[pos] = 0,
+                (None, EofBehavior::MinusOne) => memory[pos] = mask,
+                (None, EofBehavior::Unchanged) => {}
+                (None, EofBehavior::Error) => return Err(RuntimeError::UnexpectedEof { ip }),
+            },
+            '[' if memory[pos] == 0 => ip = matching(&tokens, ip, 1),
+            ']' if memory[pos] != 0 => ip = matching(&tokens, ip, -1),
+            _ => {}
+        }
+        ip += 1;
+    }
+    Ok(())
+}
+
+/// The index of the bracket matching the one at `ip`, searching in the
+/// direction of `step`.
+fn matching(tokens: &[char], mut ip: usize, step: isize) -> usize {
+    let mut depth = 0;
+    loop {
+        match tokens[ip] {
+            '[' => depth += step,
+            ']' => depth -= step,
+            _ => {}
+        }
+        if depth == 0 {
+            return ip;
+        }
+        ip = (ip as isize + step) as usize;
+    }
+}
@@ -0,0 +1,93 @@
+//! `brainfuck serve --listen ADDR FILE`: runs the program for every client
+//! that connects, with the socket as its input and output, so that it can be
+//! played with netcat.
+//!
+//! The clients are served one after another, each on a fresh tape. A client
+//! that disconnects ends the input of the program, which then sees the
+//! configured eof behavior. As one client blocks all others, --timeout should
+//! be given for servers that are not just a demo.
+
+use crate::{translated, Options};
+use brainfuck::{InputOutput, IoAdapter, Program};
+use std::io::BufWriter;
+use std::net::{Shutdown, TcpListener, TcpStream};
+
+/// Serves `program` to the connections on `listener` until accepting fails
+/// for good.
+pub fn serve(options: &Options, program: &Program, listener: TcpListener) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("cannot accept a connection: {}", err);
+                continue;
+            }
+        };
+        let peer = stream
+            .peer_addr()
+            .map_or_else(|_| "unknown client".to_string(), |peer| peer.to_string());
+        eprintln!("{} connected", peer);
+        match run(options, program, stream) {
+            Ok(()) => eprintln!("{} done", peer),
+            Err(msg) => eprintln!("{}: {}", peer, msg),
+        }
+    }
+}
+
+/// Runs `program` with `stream` as its input and output.
+fn run(options: &Options, program: &Program, stream: TcpStream) -> Result<(), String> {
+    let input = stream.try_clone().map_err(|err| err.to_string())?;
+    // IoAdapter flushes the output before it waits for input, so prompts
+    // reach the client
+    let output = BufWriter::new(&stream);
+    let mut in_out = translated(
+        Box::new(IoAdapter::new(input, output)),
+        options.newline,
+        options.io,
+    );
+    let result = options.config.run(program, &mut in_out);
+    let flushed = in_out.flush();
+    drop(in_out);
+    let _ = stream.shutdown(Shutdown::Both);
+    result.map_err(|err| format!("runtime error: {}", err))?;
+    flushed.map_err(|err| format!("cannot write to the client: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_args;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn connection() {
+        let options = parse_args(
+            [
+                "serve",
+                "--listen=127.0.0.1:0",
+                "--eof=minus-one",
+                "rot13.bf",
+            ]
+            .iter()
+            .map(|arg| arg.to_string()),
+        )
+        .unwrap();
+        assert_eq!(options.listen.as_deref(), Some("127.0.0.1:0"));
+        let program = brainfuck::compile_file("programs/rot13.bf", &options.pipeline).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            run(&options, &program, stream)
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"Hello\n").unwrap();
+        // disconnecting ends the input
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).unwrap();
+        assert_eq!(answer, "Uryyb\n");
+        assert_eq!(server.join().unwrap(), Ok(()));
+    }
+}
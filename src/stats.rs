@@ -0,0 +1,387 @@
+//! How much of the tape a program used, collected by
+//! [`ExecutionConfig::run_tape_stats`], and everything about a run at once in
+//! [`RunStats`].
+//!
+//! ```
+//! use brainfuck::stats::TapeStats;
+//! use brainfuck::{compile, ExecutionConfig, Memory, StringInputOutput};
+//!
+//! let config = ExecutionConfig::default();
+//! let mut stats = TapeStats::new();
+//! config
+//!     .run_tape_stats(
+//!         &compile("+>>+<").unwrap(),
+//!         &mut StringInputOutput::new(),
+//!         &mut Memory::new(&config),
+//!         &mut stats,
+//!     )
+//!     .unwrap();
+//! assert_eq!((stats.min_pos(), stats.max_pos()), (0, 2));
+//! assert_eq!(stats.written(), 2);
+//! ```
+//!
+//! [`ExecutionConfig::run_tape_stats`]: crate::ExecutionConfig::run_tape_stats
+
+use crate::profile::Profile;
+use crate::{ExecutionConfig, InputOutput, Memory, Ops, Probe, Program, RuntimeError, Tape};
+use std::time::{Duration, Instant};
+
+/// The cells a program reached and wrote. Positions are indices into the tape
+/// as it was when the run started, cells an infinite tape grew to the left
+/// have negative positions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TapeStats {
+    min_pos: isize,
+    max_pos: isize,
+    /// Written cells at positions 0, 1, ...
+    right: Vec<bool>,
+    /// Written cells at positions -1, -2, ...
+    left: Vec<bool>,
+    nonzero: usize,
+    /// Cells in front of the original cell 0, only an infinite tape has any.
+    origin: usize,
+    /// The op, pointer and tape length seen last, to tell which way the tape
+    /// grew.
+    last: Option<(Ops, usize, usize)>,
+}
+
+impl TapeStats {
+    pub fn new() -> TapeStats {
+        TapeStats::default()
+    }
+
+    pub(crate) fn reset(&mut self, memory: &Memory) {
+        *self = TapeStats::new();
+        self.min_pos = memory.pos() as isize;
+        self.max_pos = memory.pos() as isize;
+    }
+
+    /// Counts the nonzero cells once the program stopped.
+    pub(crate) fn finish(&mut self, memory: &Memory) {
+        self.nonzero = memory.cells().iter().filter(|&&cell| cell != 0).count();
+    }
+
+    /// The leftmost cell the program moved to or touched. Moves that cancel
+    /// each other out, like `<>`, are folded away and do not count.
+    pub fn min_pos(&self) -> isize {
+        self.min_pos
+    }
+
+    /// The rightmost cell the program moved to or touched.
+    pub fn max_pos(&self) -> isize {
+        self.max_pos
+    }
+
+    /// The number of distinct cells that were modified or read into.
+    pub fn written(&self) -> usize {
+        self.right.iter().chain(&self.left).filter(|&&w| w).count()
+    }
+
+    /// The number of cells that are not zero after the run.
+    pub fn nonzero(&self) -> usize {
+        self.nonzero
+    }
+
+    /// Adds the cells an infinite tape grew to the left by since the last op.
+    fn follow_growth(&mut self, len: usize) {
+        if let Some((op, pos, last_len)) = self.last {
+            let left = match op {
+                Ops::Move(offset)
+                | Ops::ModAt(offset, _)
+                | Ops::AddTo(offset, _)
+                | Ops::MoveClose(offset, _)
+                | Ops::ModMove(_, offset) => (pos as isize) + offset < 0,
+                Ops::SearchZeroCell(step) => step < 0,
+                _ => false,
+            };
+            if len > last_len && left {
+                self.origin += len - last_len;
+            }
+        }
+    }
+
+    /// Registers a visit of the cell at `index` of the current tape.
+    fn reach(&mut self, index: isize) -> isize {
+        let pos = index - self.origin as isize;
+        self.min_pos = self.min_pos.min(pos);
+        self.max_pos = self.max_pos.max(pos);
+        pos
+    }
+
+    fn write(&mut self, index: isize) {
+        let pos = self.reach(index);
+        let (cells, i) = if pos >= 0 {
+            (&mut self.right, pos as usize)
+        } else {
+            (&mut self.left, (-pos - 1) as usize)
+        };
+        if i >= cells.len() {
+            cells.resize(i + 1, false);
+        }
+        cells[i] = true;
+    }
+}
+
+impl Probe for TapeStats {
+    fn on_op(
+        &mut self,
+        _: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        let len = tape.len();
+        self.follow_growth(len);
+        self.last = Some((op, pos, len));
+        let here = pos as isize;
+        match op {
+            Ops::Mod(_) | Ops::ModMove(..) | Ops::SetCell(_) | Ops::Read => self.write(here),
+            Ops::ModAt(offset, _) => self.write(here + offset),
+            // a skipped AddTo stands for a loop that never ran
+            Ops::AddTo(offset, _) if tape.cell(pos) != Some(0) => self.write(here + offset),
+            _ => {
+                self.reach(here);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One line like "pointer reached cells 0..100, 12 written, 3 nonzero".
+impl std::fmt::Display for TapeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "pointer reached cells {}..{}, {} written, {} nonzero",
+            self.min_pos,
+            self.max_pos,
+            self.written(),
+            self.nonzero
+        )
+    }
+}
+
+/// The statistics of a single run, written as JSON by [`RunStats::to_json`].
+#[derive(Clone, Debug)]
+pub struct RunStats {
+    /// Not measured by [`RunStats::measure`], the caller compiled the program.
+    pub compile_time: Duration,
+    pub run_time: Duration,
+    pub profile: Profile,
+    pub tape: TapeStats,
+    pub result: Result<(), RuntimeError>,
+}
+
+impl RunStats {
+    /// Runs `program` on `memory` like [`ExecutionConfig::run_on`], profiled
+    /// and with tape statistics.
+    pub fn measure<IO: InputOutput + ?Sized>(
+        config: &ExecutionConfig,
+        program: &Program,
+        in_out: &mut IO,
+        memory: &mut Memory,
+    ) -> RunStats {
+        let mut probes = (Profile::new(), TapeStats::new());
+        probes.0.reset(program);
+        probes.1.reset(memory);
+        let started = Instant::now();
+        let result = config.run_probed(program, in_out, memory, &mut probes);
+        let run_time = started.elapsed();
+        probes.1.finish(memory);
+        RunStats {
+            compile_time: Duration::ZERO,
+            run_time,
+            profile: probes.0,
+            tape: probes.1,
+            result,
+        }
+    }
+
+    /// Why the program stopped: "completed", "fuel_exhausted", "timeout",
+    /// "interrupted" or "runtime_error".
+    pub fn exit(&self) -> &'static str {
+        match self.result {
+            Ok(()) => "completed",
+            Err(RuntimeError::FuelExhausted { .. }) => "fuel_exhausted",
+            Err(RuntimeError::Timeout { .. }) => "timeout",
+            Err(RuntimeError::Interrupted { .. }) => "interrupted",
+            Err(_) => "runtime_error",
+        }
+    }
+
+    /// A single JSON object with the fields `compile_seconds`,
+    /// `run_seconds`, `instructions` (executed ops, a `SearchZeroCell` counts
+    /// once), `ops` (executions per kind), `output_bytes`, `min_pos`,
+    /// `max_pos`, `cells_written`, `nonzero_cells`, `exit` (see
+    /// [`RunStats::exit`]) and `error` (the message or null). The fields only
+    /// ever get added to.
+    pub fn to_json(&self) -> String {
+        let ops: Vec<String> = self
+            .profile
+            .by_kind()
+            .iter()
+            .map(|(kind, count)| format!("\"{}\":{}", kind, count))
+            .collect();
+        let error = match &self.result {
+            Ok(()) => "null".to_string(),
+            Err(err) => json_string(&err.to_string()),
+        };
+        format!(
+            "{{\"compile_seconds\":{},\"run_seconds\":{},\"instructions\":{},\"ops\":{{{}}},\"output_bytes\":{},\"min_pos\":{},\"max_pos\":{},\"cells_written\":{},\"nonzero_cells\":{},\"exit\":\"{}\",\"error\":{}}}",
+            self.compile_time.as_secs_f64(),
+            self.run_time.as_secs_f64(),
+            self.profile.total(),
+            ops.join(","),
+            self.profile.count("Print"),
+            self.tape.min_pos(),
+            self.tape.max_pos(),
+            self.tape.written(),
+            self.tape.nonzero(),
+            self.exit(),
+            error
+        )
+    }
+}
+
+/// `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if (c as u32) < 0x20 => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted + "\""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{OptLevel, Pipeline};
+    use crate::{compile_with, ExecutionConfig, StringInputOutput, TapeMode};
+
+    fn run(source: &str, level: OptLevel, config: ExecutionConfig) -> TapeStats {
+        let program = compile_with(source, &Pipeline::new(level)).unwrap();
+        let mut stats = TapeStats::new();
+        let _ = config.run_tape_stats(
+            &program,
+            &mut StringInputOutput::new(),
+            &mut Memory::new(&config),
+            &mut stats,
+        );
+        stats
+    }
+
+    #[test]
+    fn walk() {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let stats = run(&"+>".repeat(100), level, ExecutionConfig::default());
+            assert_eq!((stats.min_pos(), stats.max_pos()), (0, 100));
+            assert_eq!(stats.written(), 100);
+            assert_eq!(stats.nonzero(), 100);
+        }
+        // back to cell 0, the scan stops at cell 100, the first one that was never written
+        let stats = run(
+            &format!("{}{}[>]", "+>".repeat(100), "<".repeat(100)),
+            OptLevel::O2,
+            ExecutionConfig::default(),
+        );
+        assert_eq!((stats.max_pos(), stats.written()), (100, 100));
+    }
+
+    #[test]
+    fn clears() {
+        let stats = run("+>+>+[-]<<[-]", OptLevel::O2, ExecutionConfig::default());
+        assert_eq!(
+            (stats.max_pos(), stats.written(), stats.nonzero()),
+            (2, 3, 1)
+        );
+        // moves that are never executed do not count
+        let stats = run("[>>>+]>", OptLevel::O0, ExecutionConfig::default());
+        assert_eq!((stats.max_pos(), stats.written()), (1, 0));
+    }
+
+    #[test]
+    fn infinite_tape() {
+        let config = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .build();
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let stats = run("<+<<+>>>>>+", level, config);
+            assert_eq!((stats.min_pos(), stats.max_pos()), (-3, 2));
+            assert_eq!(stats.written(), 3);
+            assert_eq!(stats.nonzero(), 3);
+        }
+        let limited = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .max_ops(100)
+            .build();
+        let stats = run("+[<+]", OptLevel::O0, limited);
+        assert!(stats.min_pos() < -10);
+    }
+
+    #[test]
+    fn errors() {
+        // the move left of cell 0 stops the program before it reaches anything
+        let stats = run("+>+<<", OptLevel::O0, ExecutionConfig::default());
+        assert_eq!((stats.min_pos(), stats.max_pos()), (0, 1));
+        assert_eq!(stats.nonzero(), 2);
+    }
+
+    /// The value of `key` in a flat JSON object, nested objects are returned
+    /// as a whole.
+    fn field<'a>(json: &'a str, key: &str) -> &'a str {
+        let start = json.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+        let mut depth = 0;
+        for (i, c) in json[start..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                ',' | '}' if depth == 0 => return &json[start..start + i],
+                _ => {}
+            }
+        }
+        panic!("unterminated object")
+    }
+
+    #[test]
+    fn json() {
+        let config = ExecutionConfig::default();
+        let program = compile_with("+++[>+<-]>.", &Pipeline::new(OptLevel::O1)).unwrap();
+        let mut in_out = StringInputOutput::new();
+        let stats = RunStats::measure(&config, &program, &mut in_out, &mut Memory::new(&config));
+        let json = stats.to_json();
+        assert!(json.starts_with('{') && json.ends_with('}'));
+        assert!(field(&json, "compile_seconds").parse::<f64>().is_ok());
+        assert!(field(&json, "run_seconds").parse::<f64>().is_ok());
+        // 18 ops for the loop as in the profile tests, plus Move and Print
+        assert_eq!(field(&json, "instructions"), "20");
+        assert_eq!(
+            field(&json, "ops"),
+            r#"{"Mod":7,"Move":7,"LoopClose":3,"LoopOpen":1,"Print":1,"End":1}"#
+        );
+        assert_eq!(field(&json, "output_bytes"), "1");
+        assert_eq!(field(&json, "max_pos"), "1");
+        assert_eq!(field(&json, "cells_written"), "2");
+        assert_eq!(field(&json, "exit"), r#""completed""#);
+        assert_eq!(field(&json, "error"), "null");
+
+        let config = ExecutionConfig::builder().max_ops(5).build();
+        let stats = RunStats::measure(&config, &program, &mut in_out, &mut Memory::new(&config));
+        assert_eq!(field(&stats.to_json(), "instructions"), "5");
+        assert_eq!(field(&stats.to_json(), "exit"), r#""fuel_exhausted""#);
+        assert_eq!(
+            field(&stats.to_json(), "error"),
+            r#""stopped after executing 5 instructions""#
+        );
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("a\"b\\c\n"), r#""a\"b\\c\u000a""#);
+    }
+}
@@ -0,0 +1,155 @@
+//! The raw terminal mode of `--raw`: `,` gets every key as soon as it is
+//! pressed, and the keys are not echoed. Ctrl+C still interrupts the program,
+//! Ctrl+D is read as the byte 4 instead of ending the input.
+
+use std::io::IsTerminal;
+
+/// Puts the terminal on stdin into raw mode until the returned guard is
+/// dropped or [`restore`] is called. Does nothing if stdin is not a
+/// terminal.
+pub fn raw_mode() -> Result<Option<RawMode>, String> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    RawMode::enable().map(Some)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub use termios::{restore, RawMode};
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub struct RawMode;
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn restore() {}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+impl RawMode {
+    fn enable() -> Result<RawMode, String> {
+        Err("--raw is not supported on this platform".into())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod termios {
+    use std::os::raw::c_int;
+    use std::ptr;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    #[cfg(target_os = "linux")]
+    mod sys {
+        pub type Flag = u32;
+        pub const NCCS: usize = 32;
+        pub const ICANON: Flag = 0o2;
+        pub const ECHO: Flag = 0o10;
+        pub const VTIME: usize = 5;
+        pub const VMIN: usize = 6;
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct Termios {
+            pub iflag: Flag,
+            pub oflag: Flag,
+            pub cflag: Flag,
+            pub lflag: Flag,
+            line: u8,
+            pub cc: [u8; NCCS],
+            ispeed: u32,
+            ospeed: u32,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    mod sys {
+        pub type Flag = std::os::raw::c_ulong;
+        pub const NCCS: usize = 20;
+        pub const ICANON: Flag = 0x100;
+        pub const ECHO: Flag = 0x8;
+        pub const VMIN: usize = 16;
+        pub const VTIME: usize = 17;
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct Termios {
+            pub iflag: Flag,
+            pub oflag: Flag,
+            pub cflag: Flag,
+            pub lflag: Flag,
+            pub cc: [u8; NCCS],
+            ispeed: Flag,
+            ospeed: Flag,
+        }
+    }
+
+    use sys::*;
+
+    const STDIN: c_int = 0;
+    const TCSANOW: c_int = 0;
+
+    extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, actions: c_int, termios: *const Termios) -> c_int;
+    }
+
+    /// The settings [`restore`] restores, those of the first raw mode that is
+    /// alive.
+    static SAVED: AtomicPtr<Termios> = AtomicPtr::new(ptr::null_mut());
+
+    /// Restores the settings the terminal had before when dropped.
+    pub struct RawMode {
+        saved: Box<Termios>,
+    }
+
+    impl RawMode {
+        pub(super) fn enable() -> Result<RawMode, String> {
+            let error = |what| {
+                format!(
+                    "cannot {} the terminal: {}",
+                    what,
+                    std::io::Error::last_os_error()
+                )
+            };
+            // zeroed is fine, tcgetattr overwrites every field
+            let mut saved = Box::new(unsafe { std::mem::zeroed::<Termios>() });
+            if unsafe { tcgetattr(STDIN, &mut *saved) } != 0 {
+                return Err(error("read the settings of"));
+            }
+            let mut raw = *saved;
+            raw.lflag &= !(ICANON | ECHO);
+            raw.cc[VMIN] = 1;
+            raw.cc[VTIME] = 0;
+            let mode = RawMode { saved };
+            let _ = SAVED.compare_exchange(
+                ptr::null_mut(),
+                &*mode.saved as *const Termios as *mut Termios,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+            if unsafe { tcsetattr(STDIN, TCSANOW, &raw) } != 0 {
+                return Err(error("switch to raw mode"));
+            }
+            Ok(mode)
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe { tcsetattr(STDIN, TCSANOW, &*self.saved) };
+            let _ = SAVED.compare_exchange(
+                &*self.saved as *const Termios as *mut Termios,
+                ptr::null_mut(),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// Restores the settings the terminal had before the raw mode, for an
+    /// exit that skips the destructors. Safe in a signal handler.
+    pub fn restore() {
+        let saved = SAVED.load(Ordering::Acquire);
+        if !saved.is_null() {
+            unsafe { tcsetattr(STDIN, TCSANOW, saved) };
+        }
+    }
+}
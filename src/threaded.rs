@@ -0,0 +1,287 @@
+//! Direct-threaded execution for [`Dispatch::Threaded`]. Before the run,
+//! every op is translated to the function that executes it and its
+//! operands, so the loop makes one indirect call per op instead of matching
+//! on it.
+//!
+//! [`Dispatch::Threaded`]: crate::Dispatch::Threaded
+
+use crate::{
+    cell_at, check_interrupt, dump_cells, outside_tape, scan_by_one, store_read, Cell, EofBehavior,
+    ExecutionConfig, InputOutput, Ops, RuntimeError, TapeMode,
+};
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+
+/// Whether the loop goes on after a handler.
+enum Control {
+    Next,
+    Stop,
+}
+
+/// Everything the handlers work on.
+struct Vm<'a, C, IO: ?Sized> {
+    cells: Vec<C>,
+    pos: usize,
+    /// The op that runs, after a jump the one before the next.
+    ip: usize,
+    in_out: &'a mut IO,
+    tape: TapeMode,
+    eof: EofBehavior,
+    interrupt: &'static AtomicBool,
+    error: Option<RuntimeError>,
+}
+
+type Handler<'a, C, IO> = fn(&mut Vm<'a, C, IO>, isize, isize) -> Control;
+
+/// An op as the handler that executes it, what the operands mean depends on
+/// the handler.
+struct Entry<'a, C, IO: ?Sized> {
+    run: Handler<'a, C, IO>,
+    a: isize,
+    b: isize,
+}
+
+impl<C: Cell, IO: InputOutput + ?Sized> Vm<'_, C, IO> {
+    fn fail(&mut self, err: RuntimeError) -> Control {
+        self.error = Some(err);
+        Control::Stop
+    }
+
+    fn current(&self) -> C {
+        self.cells[self.pos]
+    }
+
+    #[inline(always)]
+    fn move_by(&mut self, offset: isize) -> Control {
+        // a move below 0 wraps around to a huge value, so a single
+        // comparison catches both ends of the tape
+        self.pos = self.pos.wrapping_add(offset as usize);
+        if self.pos >= self.cells.len() {
+            match outside_tape(&mut self.cells, self.pos, self.ip, self.tape) {
+                Ok(pos) => self.pos = pos,
+                Err(err) => return self.fail(err),
+            }
+        }
+        Control::Next
+    }
+
+    #[inline(always)]
+    fn jump_if(&mut self, jump: bool, target: isize) -> Control {
+        if jump {
+            self.ip = target as usize;
+        }
+        Control::Next
+    }
+}
+
+fn move_pointer<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    offset: isize,
+    _: isize,
+) -> Control {
+    vm.move_by(offset)
+}
+
+fn modify<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    value: isize,
+    _: isize,
+) -> Control {
+    vm.cells[vm.pos] = vm.current().wrapping_add_i32(value as i32);
+    Control::Next
+}
+
+fn modify_at<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    offset: isize,
+    value: isize,
+) -> Control {
+    match cell_at(&mut vm.cells, &mut vm.pos, offset, vm.ip, vm.tape) {
+        Ok(target) => {
+            vm.cells[target] = vm.cells[target].wrapping_add_i32(value as i32);
+            Control::Next
+        }
+        Err(err) => vm.fail(err),
+    }
+}
+
+fn add_to<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    offset: isize,
+    factor: isize,
+) -> Control {
+    let value = vm.current();
+    // the loop this replaces never touches other cells if it is skipped
+    if value == C::default() {
+        return Control::Next;
+    }
+    match cell_at(&mut vm.cells, &mut vm.pos, offset, vm.ip, vm.tape) {
+        Ok(target) => {
+            vm.cells[target] = vm.cells[target].wrapping_add_mul(value, factor as i32);
+            Control::Next
+        }
+        Err(err) => vm.fail(err),
+    }
+}
+
+/// `LoopOpen` and `If`.
+fn jump_if_zero<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    end: isize,
+    _: isize,
+) -> Control {
+    vm.jump_if(vm.current() == C::default(), end)
+}
+
+fn loop_close<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    start: isize,
+    _: isize,
+) -> Control {
+    let jump = vm.current() != C::default();
+    if jump {
+        if let Err(err) = check_interrupt(vm.interrupt, vm.ip) {
+            return vm.fail(err);
+        }
+    }
+    vm.jump_if(jump, start)
+}
+
+fn move_close<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    offset: isize,
+    start: isize,
+) -> Control {
+    match vm.move_by(offset) {
+        Control::Next => loop_close(vm, start, 0),
+        Control::Stop => Control::Stop,
+    }
+}
+
+fn modify_move<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    value: isize,
+    offset: isize,
+) -> Control {
+    modify(vm, value, 0);
+    vm.move_by(offset)
+}
+
+fn set_cell<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    value: isize,
+    _: isize,
+) -> Control {
+    vm.cells[vm.pos] = C::from_u32(value as u32);
+    Control::Next
+}
+
+fn search_zero_cell<C: Cell, IO: InputOutput + ?Sized>(
+    vm: &mut Vm<C, IO>,
+    step: isize,
+    _: isize,
+) -> Control {
+    if step == 1 || step == -1 {
+        return match scan_by_one(&mut vm.cells, &mut vm.pos, step, vm.ip, vm.tape) {
+            Ok(()) => Control::Next,
+            Err(err) => vm.fail(err),
+        };
+    }
+    while vm.current() != C::default() {
+        if let Control::Stop = vm.move_by(step) {
+            return Control::Stop;
+        }
+    }
+    Control::Next
+}
+
+fn print<C: Cell, IO: InputOutput + ?Sized>(vm: &mut Vm<C, IO>, _: isize, _: isize) -> Control {
+    let value = vm.current().to_u32();
+    vm.in_out.write_value(value);
+    Control::Next
+}
+
+fn read<C: Cell, IO: InputOutput + ?Sized>(vm: &mut Vm<C, IO>, _: isize, _: isize) -> Control {
+    let read = vm.in_out.read_value();
+    match store_read(&mut vm.cells[vm.pos], read, vm.eof, vm.ip) {
+        Ok(()) => Control::Next,
+        Err(err) => vm.fail(err),
+    }
+}
+
+fn nothing<C: Cell, IO: InputOutput + ?Sized>(_: &mut Vm<C, IO>, _: isize, _: isize) -> Control {
+    Control::Next
+}
+
+fn dump<C: Cell, IO: InputOutput + ?Sized>(vm: &mut Vm<C, IO>, _: isize, _: isize) -> Control {
+    dump_cells(vm.in_out, vm.ip, vm.pos, &vm.cells);
+    Control::Next
+}
+
+fn end<C: Cell, IO: InputOutput + ?Sized>(_: &mut Vm<C, IO>, _: isize, _: isize) -> Control {
+    Control::Stop
+}
+
+/// The handlers of `ops`, which have no pbrain procedures.
+fn entries<'a, C: Cell, IO: InputOutput + ?Sized>(ops: &[Ops]) -> Vec<Entry<'a, C, IO>> {
+    let entry = |run: Handler<'a, C, IO>, a: isize, b: isize| Entry { run, a, b };
+    ops.iter()
+        .map(|&op| match op {
+            Ops::Move(offset) => entry(move_pointer, offset, 0),
+            Ops::Mod(value) => entry(modify, value as isize, 0),
+            Ops::ModAt(offset, value) => entry(modify_at, offset, value as isize),
+            Ops::AddTo(offset, factor) => entry(add_to, offset, factor as isize),
+            // without the checks of the tape a balanced loop saves nothing here
+            Ops::LoopOpen(end) | Ops::BalancedLoop(end, ..) | Ops::If(end) => {
+                entry(jump_if_zero, end as isize, 0)
+            }
+            Ops::LoopClose(start) => entry(loop_close, start as isize, 0),
+            Ops::MoveClose(offset, start) => entry(move_close, offset, start as isize),
+            Ops::ModMove(value, offset) => entry(modify_move, value as isize, offset),
+            Ops::SetCell(value) => entry(set_cell, value as isize, 0),
+            Ops::SearchZeroCell(step) => entry(search_zero_cell, step, 0),
+            Ops::Print => entry(print, 0, 0),
+            Ops::Read => entry(read, 0, 0),
+            Ops::Break => entry(nothing, 0, 0),
+            Ops::Dump => entry(dump, 0, 0),
+            Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call => {
+                unreachable!("programs with procedures are matched")
+            }
+            Ops::End => entry(end, 0, 0),
+        })
+        .collect()
+}
+
+/// Runs `ops` from `ip` to the end, which leaves `ip` at the op that ended
+/// the program or stopped it with an error.
+pub(crate) fn run<C: Cell, IO: InputOutput + ?Sized>(
+    ops: &[Ops],
+    config: &ExecutionConfig,
+    in_out: &mut IO,
+    memory: &mut Vec<C>,
+    pos: &mut usize,
+    ip: &mut usize,
+) -> Result<(), RuntimeError> {
+    let entries = entries::<C, IO>(ops);
+    let mut vm = Vm {
+        cells: core::mem::take(memory),
+        pos: *pos,
+        ip: *ip,
+        in_out,
+        tape: config.tape,
+        eof: config.eof,
+        interrupt: config.interrupt,
+        error: None,
+    };
+    // running past the last op ends the program as well
+    while let Some(entry) = entries.get(vm.ip) {
+        if let Control::Stop = (entry.run)(&mut vm, entry.a, entry.b) {
+            break;
+        }
+        vm.ip += 1;
+    }
+    *memory = vm.cells;
+    *pos = vm.pos;
+    *ip = vm.ip;
+    vm.error.map_or(Ok(()), Err)
+}
@@ -0,0 +1,191 @@
+//! Execution traces written by [`ExecutionConfig::run_traced`], one line per
+//! executed op with the state right before it ran.
+//!
+//! ```
+//! use brainfuck::trace::Trace;
+//! use brainfuck::{compile, ExecutionConfig, StringInputOutput};
+//!
+//! let mut log = Vec::new();
+//! let mut trace = Trace::new(&mut log).limit(1);
+//! let program = compile("++.").unwrap();
+//! ExecutionConfig::default()
+//!     .run_traced(&program, &mut StringInputOutput::new(), &mut trace)
+//!     .unwrap();
+//! assert_eq!(String::from_utf8(log).unwrap(), "ip=0000 pos=0 cell=0 op=Mod(2)\n");
+//! ```
+//!
+//! [`ExecutionConfig::run_traced`]: crate::ExecutionConfig::run_traced
+
+use crate::{Ops, Probe, Program, RuntimeError, Span, Tape};
+use std::io::Write;
+use std::ops::Range;
+
+/// Where and how much to trace.
+pub struct Trace<'a> {
+    out: &'a mut dyn Write,
+    limit: Option<u64>,
+    filter: Option<Range<usize>>,
+    spans: Vec<Span>,
+    written: u64,
+    error: Option<std::io::Error>,
+}
+
+impl<'a> Trace<'a> {
+    /// Traces every op to `out`, which should be buffered.
+    pub fn new(out: &'a mut dyn Write) -> Trace<'a> {
+        Trace {
+            out,
+            limit: None,
+            filter: None,
+            spans: Vec::new(),
+            written: 0,
+            error: None,
+        }
+    }
+
+    /// Stops tracing after `limit` lines, the program keeps running.
+    pub fn limit(mut self, limit: u64) -> Trace<'a> {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Only traces ops compiled from bytes of the source in `span`, see
+    /// [`Program::source_span`]. Programs without spans are not traced at all.
+    pub fn span(mut self, span: Range<usize>) -> Trace<'a> {
+        self.filter = Some(span);
+        self
+    }
+
+    /// The number of lines written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// The error that stopped the trace, if writing to it failed.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    pub(crate) fn reset(&mut self, program: &Program) {
+        self.spans = program.spans().to_vec();
+        self.written = 0;
+        self.error = None;
+    }
+
+    fn selected(&self, ip: usize) -> bool {
+        match (&self.filter, self.spans.get(ip)) {
+            (None, _) => true,
+            // an empty span, like the one of End, is selected by the byte it is in front of
+            (Some(filter), Some(span)) => {
+                span.start < filter.end && filter.start < span.end.max(span.start + 1)
+            }
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl Probe for Trace<'_> {
+    fn on_op(
+        &mut self,
+        ip: usize,
+        op: Ops,
+        pos: usize,
+        tape: &dyn Tape,
+    ) -> Result<(), RuntimeError> {
+        if self.error.is_some() || Some(self.written) == self.limit || !self.selected(ip) {
+            return Ok(());
+        }
+        let cell = tape.cell(pos).unwrap_or(0);
+        let line = writeln!(
+            self.out,
+            "ip={:04} pos={} cell={} op={:?}",
+            ip, pos, cell, op
+        );
+        match line {
+            Ok(()) => self.written += 1,
+            Err(err) => self.error = Some(err),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, ExecutionConfig, StringInputOutput};
+
+    fn run(source: &str, trace: impl FnOnce(Trace) -> Trace) -> Vec<String> {
+        let mut log = Vec::new();
+        let mut trace = trace(Trace::new(&mut log));
+        ExecutionConfig::default()
+            .run_traced(
+                &compile(source).unwrap(),
+                &mut StringInputOutput::new(),
+                &mut trace,
+            )
+            .unwrap();
+        let written = trace.written() as usize;
+        let lines: Vec<String> = String::from_utf8(log)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines.len(), written);
+        lines
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            run("++.", |trace| trace),
+            vec![
+                "ip=0000 pos=0 cell=0 op=Mod(2)",
+                "ip=0001 pos=0 cell=2 op=Print",
+                "ip=0002 pos=0 cell=2 op=End",
+            ]
+        );
+    }
+
+    #[test]
+    fn limit() {
+        assert_eq!(run("+[-.]", |trace| trace.limit(2)).len(), 2);
+        assert_eq!(run("+", |trace| trace.limit(0)).len(), 0);
+    }
+
+    #[test]
+    fn span() {
+        // only the ops of the second line: Move(1) and Print
+        assert_eq!(
+            run("+.\n>.\n", |trace| trace.span(3..5)),
+            vec![
+                "ip=0002 pos=0 cell=1 op=Move(1)",
+                "ip=0003 pos=1 cell=0 op=Print"
+            ]
+        );
+        assert_eq!(
+            run("+.", |trace| trace.span(2..3)),
+            vec!["ip=0002 pos=0 cell=1 op=End"]
+        );
+    }
+
+    #[test]
+    fn write_errors() {
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut full = Full;
+        let mut trace = Trace::new(&mut full);
+        let program = compile("+++.").unwrap();
+        ExecutionConfig::default()
+            .run_traced(&program, &mut StringInputOutput::new(), &mut trace)
+            .unwrap();
+        assert_eq!(trace.written(), 0);
+        assert!(trace.error().is_some());
+    }
+}
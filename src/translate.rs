@@ -0,0 +1,271 @@
+//! Translating compiled programs to other languages.
+//!
+//! The translations start from the optimized ops rather than the source, so
+//! runs are already folded and the loops the optimizer recognized are already
+//! replaced. They follow the [`ExecutionConfig`] they are given: the cell
+//! size, the tape and what `,` does at the end of the input.
+//!
+//! ```
+//! use brainfuck::translate::{translate, Target};
+//! use brainfuck::{compile, ExecutionConfig};
+//!
+//! let program = compile("+++[-].").unwrap();
+//! let c = translate(&program, &ExecutionConfig::default(), Target::C).unwrap();
+//! assert!(String::from_utf8(c).unwrap().contains("    tape[p] = 0;\n"));
+//! ```
+
+mod c;
+mod js;
+mod rust;
+mod wasm;
+
+use crate::{CellSize, EofBehavior, ExecutionConfig, Ops, Program, TapeMode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A language [`translate`] can write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// A standalone C99 program.
+    C,
+    /// A `main.rs` without dependencies.
+    Rust,
+    /// A JavaScript function for web pages and node.
+    Js,
+    /// A WebAssembly module in the binary format.
+    Wasm,
+    /// The same module as text.
+    Wat,
+}
+
+impl core::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Target, String> {
+        match s {
+            "c" => Ok(Target::C),
+            "rust" => Ok(Target::Rust),
+            "js" => Ok(Target::Js),
+            "wasm" => Ok(Target::Wasm),
+            "wat" => Ok(Target::Wat),
+            _ => Err(format!(
+                "invalid target '{}', expected c, rust, js, wasm or wat",
+                s
+            )),
+        }
+    }
+}
+
+/// The translation of `program` to `target`.
+pub fn translate(
+    program: &Program,
+    config: &ExecutionConfig,
+    target: Target,
+) -> Result<Vec<u8>, TranslateError> {
+    if config.max_ops.is_some() {
+        return Err(TranslateError::Unsupported("limit the executed ops"));
+    }
+    if config.timeout.is_some() {
+        return Err(TranslateError::Unsupported("time out"));
+    }
+    if program.has_procedures() {
+        return Err(TranslateError::Unsupported("call pbrain procedures"));
+    }
+    if program.has_dumps() {
+        return Err(TranslateError::Unsupported("dump cells"));
+    }
+    match target {
+        Target::C => c::translate(program, config).map(String::into_bytes),
+        Target::Rust => rust::translate(program, config).map(String::into_bytes),
+        Target::Js => js::translate(program, config).map(String::into_bytes),
+        Target::Wasm => wasm::binary(program, config),
+        Target::Wat => wasm::text(program, config).map(String::into_bytes),
+    }
+}
+
+/// Why a program could not be translated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranslateError {
+    /// The op at `ip` ends a loop or an `If` that encloses another unfinished
+    /// one, which only programs built by [`Program::new`] can contain.
+    Unstructured { ip: usize },
+    /// Translated programs cannot do what the config asks for.
+    Unsupported(&'static str),
+}
+
+impl core::fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            TranslateError::Unstructured { ip } => {
+                write!(f, "the jumps cross each other at op {}", ip)
+            }
+            TranslateError::Unsupported(what) => write!(f, "translated programs cannot {}", what),
+        }
+    }
+}
+
+impl core::error::Error for TranslateError {}
+
+/// An op of a program whose loops were nested again by [`structure`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// Any op but the jumps and the pairs the optimizer fused, which are
+    /// split again.
+    Op(Ops),
+    /// The body of a loop.
+    Loop(Vec<Node>),
+    /// The body of an [`Ops::If`], which runs at most once.
+    If(Vec<Node>),
+}
+
+/// The ops of `program` as a tree, with the bodies of the loops and `If`s
+/// inside of them instead of jump targets. Languages with structured control
+/// flow need this to translate the loops.
+///
+/// ```
+/// use brainfuck::translate::{structure, Node};
+/// use brainfuck::{compile, Ops};
+///
+/// let program = compile(",[.,]").unwrap();
+/// assert_eq!(
+///     structure(&program).unwrap(),
+///     [
+///         Node::Op(Ops::Read),
+///         Node::Loop(vec![Node::Op(Ops::Print), Node::Op(Ops::Read)]),
+///         Node::Op(Ops::End),
+///     ]
+/// );
+/// ```
+pub fn structure(program: &Program) -> Result<Vec<Node>, TranslateError> {
+    // the nodes before each open loop or `If`, with the ip it opened at or
+    // the ip of the last op of its body
+    let mut outer: Vec<(Ops, usize, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    for (ip, &op) in program.iter().enumerate() {
+        match op {
+            Ops::LoopOpen(_) | Ops::BalancedLoop(..) => {
+                outer.push((op, ip, core::mem::take(&mut nodes)))
+            }
+            Ops::If(end) => outer.push((op, end, core::mem::take(&mut nodes))),
+            Ops::ModMove(value, offset) => {
+                nodes.push(Node::Op(Ops::Mod(value)));
+                nodes.push(Node::Op(Ops::Move(offset)));
+            }
+            Ops::LoopClose(start) | Ops::MoveClose(_, start) => match outer.pop() {
+                Some((Ops::LoopOpen(_) | Ops::BalancedLoop(..), open, before)) if open == start => {
+                    if let Ops::MoveClose(offset, _) = op {
+                        nodes.push(Node::Op(Ops::Move(offset)));
+                    }
+                    let body = core::mem::replace(&mut nodes, before);
+                    nodes.push(Node::Loop(body));
+                }
+                _ => return Err(TranslateError::Unstructured { ip }),
+            },
+            _ => nodes.push(Node::Op(op)),
+        }
+        while let Some(&(Ops::If(_), end, _)) = outer.last() {
+            if end > ip {
+                break;
+            }
+            if end < ip {
+                // the body ended inside a loop that only closed now
+                return Err(TranslateError::Unstructured { ip: end });
+            }
+            let (_, _, before) = outer.pop().unwrap();
+            let body = core::mem::replace(&mut nodes, before);
+            nodes.push(Node::If(body));
+        }
+    }
+    Ok(nodes)
+}
+
+/// Source code, indented by four spaces per level.
+#[derive(Default)]
+struct Code {
+    text: String,
+}
+
+impl Code {
+    fn line(&mut self, depth: usize, line: &str) {
+        for _ in 0..depth {
+            self.text += "    ";
+        }
+        self.text += line;
+        self.text += "\n";
+    }
+}
+
+/// The arithmetic of cells of the given size.
+#[derive(Clone, Copy)]
+struct Cells(CellSize);
+
+impl Cells {
+    fn bits(self) -> u32 {
+        match self.0 {
+            CellSize::U8 => 8,
+            CellSize::U16 => 16,
+            CellSize::U32 => 32,
+        }
+    }
+
+    /// `value` wrapped around to the cell size.
+    fn truncate(self, value: i64) -> u32 {
+        value.rem_euclid(1 << self.bits()) as u32
+    }
+
+    /// Whether adding `value` is shorter written as a subtraction, and the
+    /// amount that is added or subtracted.
+    fn wrap(self, value: i32) -> (bool, u32) {
+        let value = self.truncate(i64::from(value));
+        let negated = self.truncate(-i64::from(value));
+        match value > 1 << (self.bits() - 1) {
+            true => (true, negated),
+            false => (false, value),
+        }
+    }
+}
+
+/// One line per setting of `config`, for the comment at the top of a
+/// translation.
+fn describe(config: &ExecutionConfig) -> [String; 3] {
+    let bits = Cells(config.cell_size).bits();
+    let tape = match config.tape {
+        TapeMode::Fixed => "a fixed tape",
+        TapeMode::Grow => "a tape that grows to the right",
+        TapeMode::Wrap => "a tape that wraps around",
+        TapeMode::Infinite => "a tape that grows in both directions",
+    };
+    let eof = match config.eof {
+        EofBehavior::Zero => "stores 0",
+        EofBehavior::MinusOne => "stores -1",
+        EofBehavior::Unchanged => "leaves the cell unchanged",
+        EofBehavior::Error => "stops with an error",
+    };
+    [
+        format!("{} bit cells", bits),
+        format!("{} of {} cells", tape, config.tape_size),
+        format!("`,` at the end of the input {}", eof),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unstructured() {
+        // the body of the If ends inside the loop
+        let ops = vec![Ops::If(2), Ops::LoopOpen(3), Ops::Print, Ops::LoopClose(1)];
+        let program = Program::new(ops).unwrap();
+        assert_eq!(
+            structure(&program),
+            Err(TranslateError::Unstructured { ip: 2 })
+        );
+        let program = Program::new(vec![Ops::If(2), Ops::Print, Ops::Read]).unwrap();
+        assert_eq!(
+            structure(&program).unwrap(),
+            [Node::If(vec![Node::Op(Ops::Print), Node::Op(Ops::Read)])]
+        );
+    }
+}
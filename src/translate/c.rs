@@ -0,0 +1,354 @@
+//! The C backend. The data pointer is an index into a heap allocated tape, so
+//! that the helpers can check it against the end of the tape and grow the
+//! tape the same way [`crate::ExecutionConfig::run`] does.
+
+use super::{describe, structure, Cells, Code, Node, TranslateError};
+use crate::{EofBehavior, ExecutionConfig, Ops, Program, TapeMode};
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::convert::TryFrom;
+
+pub(super) fn translate(
+    program: &Program,
+    config: &ExecutionConfig,
+) -> Result<String, TranslateError> {
+    let nodes = structure(program)?;
+    let moves = program.iter().any(|op| {
+        matches!(
+            op,
+            Ops::Move(_) | Ops::SearchZeroCell(_) | Ops::MoveClose(..) | Ops::ModMove(..)
+        )
+    });
+    let offsets = program
+        .iter()
+        .any(|op| matches!(op, Ops::ModAt(..) | Ops::AddTo(..)));
+    let reads = program.contains(&Ops::Read);
+
+    let mut code = Code::default();
+    code.text += "/*\n * Translated by brainfuck:\n";
+    for setting in describe(config).iter() {
+        code.text += &format!(" * - {}\n", setting);
+    }
+    code.text += " */\n";
+    code.text +=
+        "#include <stddef.h>\n#include <stdint.h>\n#include <stdio.h>\n#include <stdlib.h>\n";
+    if moves || offsets {
+        if let TapeMode::Grow | TapeMode::Infinite = config.tape {
+            code.text += "#include <string.h>\n";
+        }
+    }
+    let cells = Cells(config.cell_size);
+    code.text += &format!("\ntypedef uint{}_t cell;\n\n", cells.bits());
+    code.text += &format!(
+        "static cell *tape;\nstatic size_t len = {};\nstatic size_t p;\n",
+        config.tape_size
+    );
+    code.text += FAIL;
+    if moves || offsets {
+        code.text += match config.tape {
+            TapeMode::Fixed => FIXED,
+            TapeMode::Grow => GROW,
+            TapeMode::Wrap => WRAP,
+            TapeMode::Infinite => INFINITE,
+        };
+    }
+    if moves {
+        code.text += MOVE;
+    }
+    if offsets {
+        code.text += AT;
+    }
+    if reads {
+        code.text += READ;
+        code.text += match config.eof {
+            EofBehavior::Zero => "    } else {\n        tape[p] = 0;\n    }\n",
+            EofBehavior::MinusOne => "    } else {\n        tape[p] = (cell)-1;\n    }\n",
+            EofBehavior::Unchanged => "    }\n",
+            EofBehavior::Error => {
+                "    } else {\n        bf_fail(\"unexpected end of input\");\n    }\n"
+            }
+        };
+        code.text += "}\n";
+    }
+    code.text += "\nint main(void) {\n";
+    if offsets {
+        code.line(1, "size_t t;");
+    }
+    code.line(1, "tape = calloc(len, sizeof(cell));");
+    code.line(1, "if (!tape) {");
+    code.line(2, "bf_fail(\"out of memory\");");
+    code.line(1, "}");
+    block(&mut code, cells, &nodes, 1);
+    if program.last() != Some(&Ops::End) {
+        code.line(1, "return 0;");
+    }
+    code.text += "}\n";
+    Ok(code.text)
+}
+
+fn block(code: &mut Code, cells: Cells, nodes: &[Node], depth: usize) {
+    for node in nodes {
+        let op = match node {
+            Node::Op(op) => *op,
+            Node::Loop(body) | Node::If(body) => {
+                let keyword = match node {
+                    Node::Loop(_) => "while",
+                    _ => "if",
+                };
+                code.line(depth, &format!("{} (tape[p]) {{", keyword));
+                block(code, cells, body, depth + 1);
+                code.line(depth, "}");
+                continue;
+            }
+        };
+        match op {
+            Ops::Move(offset) => code.line(depth, &format!("bf_move({});", offset)),
+            Ops::Mod(value) => code.line(depth, &format!("tape[p] {};", add(cells, value))),
+            Ops::ModAt(offset, value) => {
+                code.line(depth, &format!("t = bf_at({});", offset));
+                code.line(depth, &format!("tape[t] {};", add(cells, value)));
+            }
+            Ops::AddTo(offset, factor) => {
+                // the loop this replaces never touches other cells if it is skipped
+                code.line(depth, "if (tape[p]) {");
+                code.line(depth + 1, &format!("t = bf_at({});", offset));
+                code.line(depth + 1, &format!("tape[t] {};", add_times(cells, factor)));
+                code.line(depth, "}");
+            }
+            Ops::SetCell(value) => code.line(
+                depth,
+                &format!("tape[p] = {};", literal(cells.truncate(i64::from(value)))),
+            ),
+            Ops::SearchZeroCell(step) => {
+                code.line(depth, "while (tape[p]) {");
+                code.line(depth + 1, &format!("bf_move({});", step));
+                code.line(depth, "}");
+            }
+            Ops::Print => code.line(depth, "putchar(tape[p]);"),
+            Ops::Read => code.line(depth, "bf_read();"),
+            Ops::Break => code.line(depth, "/* breakpoint */"),
+            Ops::End => code.line(depth, "return 0;"),
+            // structure nests the loops and splits the fused pairs
+            Ops::LoopOpen(_)
+            | Ops::BalancedLoop(..)
+            | Ops::LoopClose(_)
+            | Ops::If(_)
+            | Ops::MoveClose(..)
+            | Ops::ModMove(..) => unreachable!(),
+            // translate rejects programs with procedures and dumps
+            Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call | Ops::Dump => unreachable!(),
+        }
+    }
+}
+
+/// `+= value` or `-= value`, whichever is shorter. Smaller cells are promoted
+/// to int, the value wraps around to the cell size so that the sum cannot
+/// overflow.
+fn add(cells: Cells, value: i32) -> String {
+    match cells.wrap(value) {
+        (false, value) => format!("+= {}", literal(value)),
+        (true, value) => format!("-= {}", literal(value)),
+    }
+}
+
+/// Like [`add`], but adds `factor` times the current cell. The product of a
+/// promoted cell and a wrapped factor fits into an int.
+fn add_times(cells: Cells, factor: i32) -> String {
+    let (subtract, factor) = cells.wrap(factor);
+    let sign = if subtract { '-' } else { '+' };
+    match factor {
+        1 => format!("{}= tape[p]", sign),
+        _ => format!("{}= tape[p] * {}", sign, literal(factor)),
+    }
+}
+
+/// Unsigned if `value` does not fit into an int.
+fn literal(value: u32) -> String {
+    match i32::try_from(value) {
+        Ok(_) => value.to_string(),
+        Err(_) => format!("{}u", value),
+    }
+}
+
+const FAIL: &str = "
+static void bf_fail(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, \"runtime error: %s\\n\", msg);
+    exit(1);
+}
+";
+
+/// `bf_outside(target)` is the index of the cell at `target`, which is past
+/// one of the ends of the tape.
+const FIXED: &str = "
+static size_t bf_outside(size_t target) {
+    (void)target;
+    bf_fail(\"pointer moved out of bounds\");
+    return 0;
+}
+";
+
+const GROW: &str = "
+static size_t bf_outside(size_t target) {
+    size_t grown = target + 1 > len * 2 ? target + 1 : len * 2;
+    if ((ptrdiff_t)target < 0) {
+        bf_fail(\"pointer moved out of bounds\");
+    }
+    tape = realloc(tape, grown * sizeof(cell));
+    if (!tape) {
+        bf_fail(\"out of memory\");
+    }
+    memset(tape + len, 0, (grown - len) * sizeof(cell));
+    len = grown;
+    return target;
+}
+";
+
+const WRAP: &str = "
+static size_t bf_outside(size_t target) {
+    ptrdiff_t wrapped = (ptrdiff_t)target % (ptrdiff_t)len;
+    return (size_t)(wrapped < 0 ? wrapped + (ptrdiff_t)len : wrapped);
+}
+";
+
+const INFINITE: &str = "
+static size_t bf_outside(size_t target) {
+    size_t extra = -target > len ? -target : len;
+    size_t grown = target + 1 > len * 2 ? target + 1 : len * 2;
+    cell *shifted;
+    if ((ptrdiff_t)target >= 0) {
+        tape = realloc(tape, grown * sizeof(cell));
+        if (!tape) {
+            bf_fail(\"out of memory\");
+        }
+        memset(tape + len, 0, (grown - len) * sizeof(cell));
+        len = grown;
+        return target;
+    }
+    /* prepend zeroed cells, which shifts every existing cell to the right */
+    shifted = calloc(len + extra, sizeof(cell));
+    if (!shifted) {
+        bf_fail(\"out of memory\");
+    }
+    memcpy(shifted + extra, tape, len * sizeof(cell));
+    free(tape);
+    tape = shifted;
+    len += extra;
+    p += extra;
+    return target + extra;
+}
+";
+
+/// Offsets are passed as size_t, so negative ones wrap around and a single
+/// comparison catches both ends of the tape.
+const MOVE: &str = "
+static void bf_move(size_t offset) {
+    p += offset;
+    if (p >= len) {
+        p = bf_outside(p);
+    }
+}
+";
+
+const AT: &str = "
+static size_t bf_at(size_t offset) {
+    size_t target = p + offset;
+    return target < len ? target : bf_outside(target);
+}
+";
+
+/// Flushes first, so that prompts are shown before the program waits. The
+/// branch for the end of the input follows.
+const READ: &str = "
+static void bf_read(void) {
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c != EOF) {
+        tape[p] = (cell)c;
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile, compile_file, CellSize};
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Compares with the file in programs/translated, `BLESS=1` writes it.
+    fn golden(name: &str, code: &str) {
+        let path = Path::new("programs/translated").join(name);
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(&path, code).unwrap();
+        }
+        assert_eq!(code, std::fs::read_to_string(&path).unwrap(), "{:?}", path);
+    }
+
+    fn rot13() -> Program {
+        compile_file("programs/rot13.bf", &Pipeline::default()).unwrap()
+    }
+
+    #[test]
+    fn golden_files() {
+        let config = ExecutionConfig::default();
+        golden(
+            "cat.c",
+            &translate(&compile(",[.,]").unwrap(), &config).unwrap(),
+        );
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        golden("rot13.c", &translate(&rot13(), &config).unwrap());
+        // every kind of op on a tape that has to grow to the left
+        let config = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .cell_size(CellSize::U16)
+            .eof(EofBehavior::Error)
+            .build();
+        let source = "+[-<]>>+++[>+>--<<-]<[-]+[>]<<[->>+<<]>>---.,+[-]";
+        golden(
+            "infinite.c",
+            &translate(&compile(source).unwrap(), &config).unwrap(),
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let cells = Cells(CellSize::U8);
+        assert_eq!(add(cells, -1), "-= 1");
+        assert_eq!(add(cells, 300), "+= 44");
+        assert_eq!(add_times(cells, -1), "-= tape[p]");
+        assert_eq!(add_times(cells, 255 * 3), "-= tape[p] * 3");
+        let cells = Cells(CellSize::U32);
+        assert_eq!(add(cells, i32::MIN), "+= 2147483648u");
+        assert_eq!(add(cells, i32::MIN + 1), "-= 2147483647");
+        assert_eq!(literal(u32::MAX), "4294967295u");
+    }
+
+    /// Builds the translation of rot13 with the C compiler, if there is one.
+    #[test]
+    fn compiles() {
+        let dir = std::env::temp_dir();
+        let source = dir.join("brainfuck-translate-rot13.c");
+        let binary = dir.join("brainfuck-translate-rot13");
+        let config = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        std::fs::write(&source, translate(&rot13(), &config).unwrap()).unwrap();
+        let built = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Werror", "-O1", "-o"])
+            .args([&binary, &source])
+            .status();
+        match built {
+            Ok(status) => assert!(status.success()),
+            Err(_) => return eprintln!("no C compiler, skipping"),
+        }
+        let output = Command::new(&binary)
+            .stdin(std::fs::File::open("programs/rot13.in").unwrap())
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, std::fs::read("programs/rot13.out").unwrap());
+    }
+}
@@ -0,0 +1,468 @@
+//! The JavaScript backend. It writes a single function `run(input)`, which
+//! keeps the tape in a typed array of the cell size, so stores wrap around
+//! by themselves. Runtime errors are thrown.
+
+use super::{describe, structure, Cells, Code, Node, TranslateError};
+use crate::{CellSize, EofBehavior, ExecutionConfig, Ops, Program, TapeMode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub(super) fn translate(
+    program: &Program,
+    config: &ExecutionConfig,
+) -> Result<String, TranslateError> {
+    let nodes = structure(program)?;
+    let moves = program.iter().any(|op| {
+        matches!(
+            op,
+            Ops::Move(_) | Ops::SearchZeroCell(_) | Ops::MoveClose(..) | Ops::ModMove(..)
+        )
+    });
+    let offsets = program
+        .iter()
+        .any(|op| matches!(op, Ops::ModAt(..) | Ops::AddTo(..)));
+    let searches = |step| program.contains(&Ops::SearchZeroCell(step));
+    let reads = program.contains(&Ops::Read);
+
+    let mut code = Code::default();
+    code.text += "// Translated by brainfuck:\n";
+    for setting in describe(config).iter() {
+        code.text += &format!("// - {}\n", setting);
+    }
+    code.text += "//\n// run(input) takes the input as a string or an array of bytes and returns\n";
+    code.text += "// the output as a string.\nfunction run(input) {\n";
+    let cells = Cells(config.cell_size);
+    let array = match config.cell_size {
+        CellSize::U8 => "Uint8Array",
+        CellSize::U16 => "Uint16Array",
+        CellSize::U32 => "Uint32Array",
+    };
+    if reads {
+        code.line(
+            1,
+            "const bytes = typeof input === \"string\" ? new TextEncoder().encode(input) : input || [];",
+        );
+        code.line(1, "let i = 0;");
+    }
+    code.line(
+        1,
+        &format!("let tape = new {}({});", array, config.tape_size),
+    );
+    code.line(1, "let p = 0;");
+    if offsets {
+        code.line(1, "let t;");
+    }
+    code.line(1, "const output = [];");
+    if moves || offsets {
+        let outside = match config.tape {
+            TapeMode::Fixed => FIXED,
+            TapeMode::Grow => GROW,
+            TapeMode::Wrap => WRAP,
+            TapeMode::Infinite => INFINITE,
+        };
+        code.text += &outside.replace("{array}", array);
+    }
+    if moves {
+        code.text += MOVE;
+    }
+    if searches(1) {
+        code.text += SEARCH_RIGHT;
+    }
+    if searches(-1) {
+        code.text += SEARCH_LEFT;
+    }
+    if offsets {
+        code.text += AT;
+    }
+    if reads {
+        code.text += READ;
+        code.text += match config.eof {
+            EofBehavior::Zero => "        } else {\n            tape[p] = 0;\n        }\n",
+            EofBehavior::MinusOne => "        } else {\n            tape[p] = -1;\n        }\n",
+            EofBehavior::Unchanged => "        }\n",
+            EofBehavior::Error => {
+                "        } else {\n            throw new Error(\"unexpected end of input\");\n        }\n"
+            }
+        };
+        code.line(1, "}");
+    }
+    // the end of the program is the end of the function
+    let nodes = match nodes.split_last() {
+        Some((Node::Op(Ops::End), nodes)) => nodes,
+        _ => &nodes[..],
+    };
+    let mut body = Body {
+        cells,
+        code: Code::default(),
+        functions: Vec::new(),
+    };
+    body.block(nodes, 1);
+    for function in &body.functions {
+        code.text += "\n";
+        code.text += function;
+    }
+    code.text += "\n";
+    code.text += &body.code.text;
+    code.line(1, OUTPUT);
+    code.text += "}\n";
+    Ok(code.text)
+}
+
+/// Loops with more ops than this become functions of their own. V8 compiles
+/// each function on its own, while a function with many nested loops is
+/// optimized badly or, once it is large, not at all.
+const INLINE_OPS: usize = 2;
+
+/// The statements of `run`, and of the functions for its large loops.
+struct Body {
+    cells: Cells,
+    code: Code,
+    /// Numbered in the order they are called for the first time.
+    functions: Vec<String>,
+}
+
+impl Body {
+    fn block(&mut self, nodes: &[Node], depth: usize) {
+        for node in nodes {
+            let op = match node {
+                Node::Op(op) => *op,
+                Node::Loop(body) if ops(body) > INLINE_OPS && !ends(body) => {
+                    let n = self.functions.len();
+                    self.functions.push(String::new());
+                    let outer = core::mem::take(&mut self.code);
+                    self.code.line(1, &format!("function loop{}() {{", n));
+                    self.code.line(2, "while (tape[p]) {");
+                    self.block(body, 3);
+                    self.code.line(2, "}");
+                    self.code.line(1, "}");
+                    self.functions[n] = core::mem::replace(&mut self.code, outer).text;
+                    self.code.line(depth, &format!("loop{}();", n));
+                    continue;
+                }
+                Node::Loop(body) | Node::If(body) => {
+                    let keyword = match node {
+                        Node::Loop(_) => "while",
+                        _ => "if",
+                    };
+                    self.code.line(depth, &format!("{} (tape[p]) {{", keyword));
+                    self.block(body, depth + 1);
+                    self.code.line(depth, "}");
+                    continue;
+                }
+            };
+            self.op(op, depth);
+        }
+    }
+
+    fn op(&mut self, op: Ops, depth: usize) {
+        let (code, cells) = (&mut self.code, self.cells);
+        match op {
+            Ops::Move(offset) => code.line(depth, &format!("move({});", offset)),
+            Ops::Mod(value) => code.line(depth, &format!("tape[p] {};", add(cells, value))),
+            Ops::ModAt(offset, value) => {
+                // `at` can replace the tape, so it has to be called before the
+                // tape is looked up
+                code.line(depth, &format!("t = at({});", offset));
+                code.line(depth, &format!("tape[t] {};", add(cells, value)));
+            }
+            Ops::AddTo(offset, factor) => {
+                // the loop this replaces never touches other cells if it is skipped
+                code.line(depth, "if (tape[p]) {");
+                code.line(depth + 1, &format!("t = at({});", offset));
+                code.line(depth + 1, &format!("tape[t] {};", add_times(cells, factor)));
+                code.line(depth, "}");
+            }
+            Ops::SetCell(value) => code.line(
+                depth,
+                &format!("tape[p] = {};", cells.truncate(i64::from(value))),
+            ),
+            Ops::SearchZeroCell(1) => code.line(depth, "searchRight();"),
+            Ops::SearchZeroCell(-1) => code.line(depth, "searchLeft();"),
+            Ops::SearchZeroCell(step) => {
+                code.line(depth, "while (tape[p]) {");
+                code.line(depth + 1, &format!("move({});", step));
+                code.line(depth, "}");
+            }
+            Ops::Print => code.line(depth, "output.push(tape[p]);"),
+            Ops::Read => code.line(depth, "read();"),
+            Ops::Break => code.line(depth, "// breakpoint"),
+            Ops::End => code.line(depth, OUTPUT),
+            // structure nests the loops and splits the fused pairs
+            Ops::LoopOpen(_)
+            | Ops::BalancedLoop(..)
+            | Ops::LoopClose(_)
+            | Ops::If(_)
+            | Ops::MoveClose(..)
+            | Ops::ModMove(..) => unreachable!(),
+            // translate rejects programs with procedures and dumps
+            Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call | Ops::Dump => unreachable!(),
+        }
+    }
+}
+
+/// The number of ops in `nodes`, including the nested ones.
+fn ops(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Op(_) => 1,
+            Node::Loop(body) | Node::If(body) => 1 + ops(body),
+        })
+        .sum()
+}
+
+/// Whether `nodes` end the program somewhere, which a function of its own
+/// could not do.
+fn ends(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Op(op) => *op == Ops::End,
+        Node::Loop(body) | Node::If(body) => ends(body),
+    })
+}
+
+/// `+= value` or `-= value`, whichever is shorter.
+fn add(cells: Cells, value: i32) -> String {
+    match cells.wrap(value) {
+        (false, value) => format!("+= {}", value),
+        (true, value) => format!("-= {}", value),
+    }
+}
+
+/// Like [`add`], but adds `factor` times the current cell. Products of 32 bit
+/// cells can be too large for a double, `Math.imul` keeps their low bits.
+fn add_times(cells: Cells, factor: i32) -> String {
+    let (subtract, factor) = cells.wrap(factor);
+    let sign = if subtract { '-' } else { '+' };
+    match (factor, cells.0) {
+        (1, _) => format!("{}= tape[p]", sign),
+        (_, CellSize::U32) => format!("{}= Math.imul(tape[p], {})", sign, factor),
+        _ => format!("{}= tape[p] * {}", sign, factor),
+    }
+}
+
+/// Turns the bytes that were written into a string.
+const OUTPUT: &str = "return new TextDecoder().decode(new Uint8Array(output));";
+
+/// `outside(target)` is the index of the cell at `target`, which is past one
+/// of the ends of the tape. Typed arrays cannot grow, a larger tape is a copy.
+const FIXED: &str = "
+    function outside(target) {
+        throw new Error(\"pointer moved out of bounds\");
+    }
+";
+
+const GROW: &str = "
+    function outside(target) {
+        if (target < 0) {
+            throw new Error(\"pointer moved out of bounds\");
+        }
+        const grown = new {array}(Math.max(target + 1, tape.length * 2));
+        grown.set(tape);
+        tape = grown;
+        return target;
+    }
+";
+
+const WRAP: &str = "
+    function outside(target) {
+        return ((target % tape.length) + tape.length) % tape.length;
+    }
+";
+
+const INFINITE: &str = "
+    function outside(target) {
+        if (target >= 0) {
+            const grown = new {array}(Math.max(target + 1, tape.length * 2));
+            grown.set(tape);
+            tape = grown;
+            return target;
+        }
+        // prepend zeroed cells, which shifts every existing cell to the right
+        const extra = Math.max(-target, tape.length);
+        const shifted = new {array}(tape.length + extra);
+        shifted.set(tape, extra);
+        tape = shifted;
+        p += extra;
+        return target + extra;
+    }
+";
+
+const MOVE: &str = "
+    function move(offset) {
+        p += offset;
+        if (p < 0 || p >= tape.length) {
+            p = outside(p);
+        }
+    }
+";
+
+/// The searches with a step of one let the typed array find the zero, and
+/// only step past the end of the tape if there is none.
+const SEARCH_RIGHT: &str = "
+    function searchRight() {
+        while (tape[p]) {
+            const zero = tape.indexOf(0, p);
+            if (zero >= 0) {
+                p = zero;
+            } else {
+                p = tape.length - 1;
+                move(1);
+            }
+        }
+    }
+";
+
+const SEARCH_LEFT: &str = "
+    function searchLeft() {
+        while (tape[p]) {
+            const zero = tape.lastIndexOf(0, p);
+            if (zero >= 0) {
+                p = zero;
+            } else {
+                p = 0;
+                move(-1);
+            }
+        }
+    }
+";
+
+const AT: &str = "
+    function at(offset) {
+        const target = p + offset;
+        return target >= 0 && target < tape.length ? target : outside(target);
+    }
+";
+
+/// The branch for the end of the input follows.
+const READ: &str = "
+    function read() {
+        if (i < bytes.length) {
+            tape[p] = bytes[i++];
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile, compile_file};
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    /// Compares with the file in programs/translated, `BLESS=1` writes it.
+    fn golden(name: &str, code: &str) {
+        let path = Path::new("programs/translated").join(name);
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(&path, code).unwrap();
+        }
+        assert_eq!(code, std::fs::read_to_string(&path).unwrap(), "{:?}", path);
+    }
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn infinite() -> ExecutionConfig {
+        ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .cell_size(CellSize::U16)
+            .eof(EofBehavior::Error)
+            .build()
+    }
+
+    #[test]
+    fn golden_files() {
+        let config = ExecutionConfig::default();
+        golden(
+            "cat.js",
+            &translate(&compile(",[.,]").unwrap(), &config).unwrap(),
+        );
+        golden(
+            "hello.js",
+            &translate(&compile(HELLO).unwrap(), &config).unwrap(),
+        );
+        // every kind of op on a tape that has to grow to the left
+        let source = "+[-<]>>+++[>+>--<<-]<[-]+[>]<<[->>+<<]>>---.,+[-]+[<]";
+        golden(
+            "infinite.js",
+            &translate(&compile(source).unwrap(), &infinite()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let cells = Cells(CellSize::U8);
+        assert_eq!(add(cells, 255), "-= 1");
+        assert_eq!(add_times(cells, 3), "+= tape[p] * 3");
+        let cells = Cells(CellSize::U32);
+        assert_eq!(add_times(cells, -3), "-= Math.imul(tape[p], 3)");
+        assert_eq!(add_times(cells, -1), "-= tape[p]");
+    }
+
+    /// Runs translations with node, if it is installed. The input is passed
+    /// as the bytes of stdin.
+    #[test]
+    fn runs() {
+        let wrap = ExecutionConfig::builder()
+            .tape(TapeMode::Wrap)
+            .tape_size(4)
+            .build();
+        let rot13 = compile_file("programs/rot13.bf", &Pipeline::default()).unwrap();
+        let rot13_in = std::fs::read("programs/rot13.in").unwrap();
+        let programs = [
+            (
+                compile(HELLO).unwrap(),
+                ExecutionConfig::default(),
+                Vec::new(),
+                "Hello World!\n".into(),
+            ),
+            (
+                rot13,
+                ExecutionConfig::builder()
+                    .eof(EofBehavior::MinusOne)
+                    .build(),
+                rot13_in,
+                std::fs::read_to_string("programs/rot13.out").unwrap(),
+            ),
+            // both searches have to wrap around to find the zero
+            (
+                compile(">+>+>+<<[>]+.<->>[<]+.").unwrap(),
+                wrap,
+                Vec::new(),
+                "\u{1}\u{1}".into(),
+            ),
+            (
+                compile("+[-<<+>>]<<.<<<<<<<<+.").unwrap(),
+                infinite(),
+                Vec::new(),
+                "\u{1}\u{1}".into(),
+            ),
+            (
+                compile(",.,.").unwrap(),
+                infinite(),
+                b"a".to_vec(),
+                "uncaught: unexpected end of input".into(),
+            ),
+        ];
+        if Command::new("node").arg("--version").output().is_err() {
+            return eprintln!("no node, skipping");
+        }
+        let script = std::env::temp_dir().join("brainfuck-translate.js");
+        for (program, config, input, expected) in programs.iter() {
+            let mut code = translate(program, config).unwrap();
+            code += "try {\n    process.stdout.write(run(require(\"fs\").readFileSync(0)));\n";
+            code += "} catch (err) {\n    process.stdout.write(\"uncaught: \" + err.message);\n}\n";
+            std::fs::write(&script, &code).unwrap();
+            let mut node = Command::new("node")
+                .arg(&script)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            node.stdin.take().unwrap().write_all(input).unwrap();
+            let output = node.wait_with_output().unwrap();
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8_lossy(&output.stdout), *expected);
+        }
+    }
+}
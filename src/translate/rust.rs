@@ -0,0 +1,386 @@
+//! The Rust backend. The cells use wrapping arithmetic and the tape is a
+//! vector that grows or wraps like the one of [`crate::ExecutionConfig::run`],
+//! so the translation cannot panic. Runtime errors end the program with exit
+//! code 1.
+
+use super::{describe, structure, Cells, Code, Node, TranslateError};
+use crate::{EofBehavior, ExecutionConfig, Ops, Program, TapeMode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub(super) fn translate(
+    program: &Program,
+    config: &ExecutionConfig,
+) -> Result<String, TranslateError> {
+    let nodes = structure(program)?;
+    let moves = program.iter().any(|op| {
+        matches!(
+            op,
+            Ops::Move(_) | Ops::SearchZeroCell(_) | Ops::MoveClose(..) | Ops::ModMove(..)
+        )
+    });
+    let offsets = program
+        .iter()
+        .any(|op| matches!(op, Ops::ModAt(..) | Ops::AddTo(..)));
+    let reads = program.contains(&Ops::Read);
+    let prints = program.contains(&Ops::Print);
+
+    let mut code = Code::default();
+    code.text += "// Translated by brainfuck:\n";
+    for setting in describe(config).iter() {
+        code.text += &format!("// - {}\n", setting);
+    }
+    let cells = Cells(config.cell_size);
+    code.text += match reads {
+        true => "\nuse std::io::{Read, Write};\n",
+        false => "\nuse std::io::Write;\n",
+    };
+    code.text += &format!("\ntype Cell = u{};\n", cells.bits());
+    code.text += "\nstruct Machine {\n    cells: Vec<Cell>,\n    p: usize,\n";
+    if reads {
+        code.line(1, "input: std::io::Bytes<std::io::StdinLock<'static>>,");
+    }
+    code.text +=
+        "    output: std::io::BufWriter<std::io::StdoutLock<'static>>,\n}\n\nimpl Machine {\n";
+    let mut methods = Vec::new();
+    if moves || offsets {
+        methods.push(match config.tape {
+            TapeMode::Fixed => FIXED,
+            TapeMode::Grow => GROW,
+            TapeMode::Wrap => WRAP,
+            TapeMode::Infinite => INFINITE,
+        });
+    }
+    if moves {
+        methods.push(GO);
+    }
+    if offsets {
+        methods.push(AT);
+    }
+    if prints {
+        methods.push(WRITE);
+    }
+    code.text += &methods.join("\n");
+    if reads {
+        if !methods.is_empty() {
+            code.text += "\n";
+        }
+        code.text += READ;
+        code.text += match config.eof {
+            EofBehavior::Zero => "            None => self.cells[self.p] = 0,\n",
+            EofBehavior::MinusOne => "            None => self.cells[self.p] = Cell::MAX,\n",
+            EofBehavior::Unchanged => "            None => {}\n",
+            EofBehavior::Error => "            None => return Err(\"unexpected end of input\"),\n",
+        };
+        code.text += "        }\n        Ok(())\n    }\n";
+    }
+    code.text += "}\n";
+    code.text += "\nfn main() {\n    let mut machine = Machine {\n";
+    code.line(2, &format!("cells: vec![0; {}],", config.tape_size));
+    code.line(2, "p: 0,");
+    if reads {
+        code.line(2, "input: std::io::stdin().lock().bytes(),");
+    }
+    code.text += MAIN;
+    code.text += "\nfn run(m: &mut Machine) -> Result<(), &'static str> {\n";
+    // the end of the program is the end of the function
+    let nodes = match nodes.split_last() {
+        Some((Node::Op(Ops::End), nodes)) => nodes,
+        _ => &nodes[..],
+    };
+    block(&mut code, cells, nodes, 1);
+    code.line(1, "Ok(())");
+    code.text += "}\n";
+    Ok(code.text)
+}
+
+fn block(code: &mut Code, cells: Cells, nodes: &[Node], depth: usize) {
+    for node in nodes {
+        let op = match node {
+            Node::Op(op) => *op,
+            Node::Loop(body) | Node::If(body) => {
+                let keyword = match node {
+                    Node::Loop(_) => "while",
+                    _ => "if",
+                };
+                code.line(depth, &format!("{} m.cells[m.p] != 0 {{", keyword));
+                block(code, cells, body, depth + 1);
+                code.line(depth, "}");
+                continue;
+            }
+        };
+        match op {
+            Ops::Move(offset) => code.line(depth, &format!("m.go({})?;", offset)),
+            Ops::Mod(value) => code.line(
+                depth,
+                &format!("m.cells[m.p] = m.cells[m.p].{};", add(cells, value)),
+            ),
+            Ops::ModAt(offset, value) => {
+                code.line(depth, &format!("let i = m.at({})?;", offset));
+                code.line(
+                    depth,
+                    &format!("m.cells[i] = m.cells[i].{};", add(cells, value)),
+                );
+            }
+            Ops::AddTo(offset, factor) => {
+                // the loop this replaces never touches other cells if it is skipped
+                code.line(depth, "if m.cells[m.p] != 0 {");
+                code.line(depth + 1, &format!("let i = m.at({})?;", offset));
+                code.line(
+                    depth + 1,
+                    &format!("m.cells[i] = m.cells[i].{};", add_times(cells, factor)),
+                );
+                code.line(depth, "}");
+            }
+            Ops::SetCell(value) => code.line(
+                depth,
+                &format!("m.cells[m.p] = {};", cells.truncate(i64::from(value))),
+            ),
+            Ops::SearchZeroCell(step) => {
+                code.line(depth, "while m.cells[m.p] != 0 {");
+                code.line(depth + 1, &format!("m.go({})?;", step));
+                code.line(depth, "}");
+            }
+            Ops::Print => code.line(depth, "m.write()?;"),
+            Ops::Read => code.line(depth, "m.read()?;"),
+            Ops::Break => code.line(depth, "// breakpoint"),
+            Ops::End => code.line(depth, "return Ok(());"),
+            // structure nests the loops and splits the fused pairs
+            Ops::LoopOpen(_)
+            | Ops::BalancedLoop(..)
+            | Ops::LoopClose(_)
+            | Ops::If(_)
+            | Ops::MoveClose(..)
+            | Ops::ModMove(..) => unreachable!(),
+            // translate rejects programs with procedures and dumps
+            Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call | Ops::Dump => unreachable!(),
+        }
+    }
+}
+
+/// `wrapping_add(value)` or `wrapping_sub(value)`, whichever is shorter.
+fn add(cells: Cells, value: i32) -> String {
+    match cells.wrap(value) {
+        (false, value) => format!("wrapping_add({})", value),
+        (true, value) => format!("wrapping_sub({})", value),
+    }
+}
+
+/// Like [`add`], but adds `factor` times the current cell.
+fn add_times(cells: Cells, factor: i32) -> String {
+    let (subtract, factor) = cells.wrap(factor);
+    let method = if subtract {
+        "wrapping_sub"
+    } else {
+        "wrapping_add"
+    };
+    match factor {
+        1 => format!("{}(m.cells[m.p])", method),
+        _ => format!("{}(m.cells[m.p].wrapping_mul({}))", method, factor),
+    }
+}
+
+/// `outside(target)` is the index of the cell at `target`, which is past one
+/// of the ends of the tape.
+const FIXED: &str = "    fn outside(&mut self, _target: usize) -> Result<usize, &'static str> {
+        Err(\"pointer moved out of bounds\")
+    }
+";
+
+const GROW: &str = "    fn outside(&mut self, target: usize) -> Result<usize, &'static str> {
+        if (target as isize) < 0 {
+            return Err(\"pointer moved out of bounds\");
+        }
+        let len = (target + 1).max(self.cells.len() * 2);
+        self.cells.resize(len, 0);
+        Ok(target)
+    }
+";
+
+const WRAP: &str = "    fn outside(&mut self, target: usize) -> Result<usize, &'static str> {
+        Ok((target as isize).rem_euclid(self.cells.len() as isize) as usize)
+    }
+";
+
+const INFINITE: &str = "    fn outside(&mut self, target: usize) -> Result<usize, &'static str> {
+        if (target as isize) >= 0 {
+            let len = (target + 1).max(self.cells.len() * 2);
+            self.cells.resize(len, 0);
+            return Ok(target);
+        }
+        // prepend zeroed cells, which shifts every existing cell to the right
+        let extra = target.wrapping_neg().max(self.cells.len());
+        self.cells.splice(0..0, std::iter::repeat(0).take(extra));
+        // the pointer is below 0 as well if it moved there
+        self.p = self.p.wrapping_add(extra);
+        Ok(target.wrapping_add(extra))
+    }
+";
+
+/// A move below 0 wraps around to a huge value, so a single comparison
+/// catches both ends of the tape.
+const GO: &str = "    fn go(&mut self, offset: isize) -> Result<(), &'static str> {
+        self.p = self.p.wrapping_add(offset as usize);
+        if self.p >= self.cells.len() {
+            self.p = self.outside(self.p)?;
+        }
+        Ok(())
+    }
+";
+
+const AT: &str = "    fn at(&mut self, offset: isize) -> Result<usize, &'static str> {
+        let target = self.p.wrapping_add(offset as usize);
+        if target < self.cells.len() {
+            return Ok(target);
+        }
+        self.outside(target)
+    }
+";
+
+const WRITE: &str = "    fn write(&mut self) -> Result<(), &'static str> {
+        let byte = self.cells[self.p] as u8;
+        self.output
+            .write_all(&[byte])
+            .map_err(|_| \"cannot write the output\")
+    }
+";
+
+/// Flushes first, so that prompts are shown before the program waits. The
+/// arm for the end of the input follows.
+const READ: &str = "    fn read(&mut self) -> Result<(), &'static str> {
+        self.output
+            .flush()
+            .map_err(|_| \"cannot write the output\")?;
+        match self.input.next() {
+            Some(Ok(byte)) => self.cells[self.p] = Cell::from(byte),
+            Some(Err(_)) => return Err(\"cannot read the input\"),
+";
+
+/// The rest of `main`, after the fields of the machine that depend on the
+/// program.
+const MAIN: &str = "        output: std::io::BufWriter::new(std::io::stdout().lock()),
+    };
+    let result = run(&mut machine);
+    let flushed = machine.output.flush();
+    if let Err(msg) = result {
+        eprintln!(\"runtime error: {}\", msg);
+        std::process::exit(1);
+    }
+    if flushed.is_err() {
+        eprintln!(\"runtime error: cannot write the output\");
+        std::process::exit(1);
+    }
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile, compile_file, CellSize};
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Compares with the file in programs/translated, `BLESS=1` writes it.
+    fn golden(name: &str, code: &str) {
+        let path = Path::new("programs/translated").join(name);
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(&path, code).unwrap();
+        }
+        assert_eq!(code, std::fs::read_to_string(&path).unwrap(), "{:?}", path);
+    }
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    #[test]
+    fn golden_files() {
+        let config = ExecutionConfig::default();
+        golden(
+            "cat.rs",
+            &translate(&compile(",[.,]").unwrap(), &config).unwrap(),
+        );
+        golden(
+            "hello.rs",
+            &translate(&compile(HELLO).unwrap(), &config).unwrap(),
+        );
+        // every kind of op on a tape that has to grow to the left
+        let config = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .cell_size(CellSize::U16)
+            .eof(EofBehavior::Error)
+            .build();
+        let source = "+[-<]>>+++[>+>--<<-]<[-]+[>]<<[->>+<<]>>---.,+[-]";
+        golden(
+            "infinite.rs",
+            &translate(&compile(source).unwrap(), &config).unwrap(),
+        );
+    }
+
+    /// Builds translations with rustc, if it is installed, with overflow
+    /// checks to show that they do not panic.
+    #[test]
+    fn compiles() {
+        let dir = std::env::temp_dir();
+        let minus_one = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        let infinite = ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .cell_size(CellSize::U16)
+            .build();
+        let rot13 = compile_file("programs/rot13.bf", &Pipeline::default()).unwrap();
+        let programs = [
+            (
+                "hello",
+                compile(HELLO).unwrap(),
+                minus_one,
+                "",
+                "Hello World!\n".into(),
+            ),
+            (
+                "rot13",
+                rot13,
+                minus_one,
+                "programs/rot13.in",
+                std::fs::read_to_string("programs/rot13.out").unwrap(),
+            ),
+            // grows to the left with a move and with an offset
+            (
+                "infinite",
+                compile("+[-<<+>>]<<.<<<<<<<<+.").unwrap(),
+                infinite,
+                "",
+                "\u{1}\u{1}".into(),
+            ),
+        ];
+        for (name, program, config, input, expected) in programs.iter() {
+            let source = dir.join(format!("brainfuck-translate-{}.rs", name));
+            let binary = dir.join(format!("brainfuck-translate-rust-{}", name));
+            std::fs::write(&source, translate(program, config).unwrap()).unwrap();
+            let built = Command::new("rustc")
+                .args(["--edition=2021", "-D", "warnings", "-O"])
+                .args(["-C", "overflow-checks=on", "-o"])
+                .args([&binary, &source])
+                .status();
+            match built {
+                Ok(status) => assert!(status.success()),
+                Err(_) => return eprintln!("no rustc, skipping"),
+            }
+            let mut run = Command::new(&binary);
+            if !input.is_empty() {
+                run.stdin(std::fs::File::open(input).unwrap());
+            }
+            let output = run.output().unwrap();
+            assert!(output.status.success(), "{}", name);
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                *expected,
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,849 @@
+//! The WebAssembly backend. The tape starts at address 0 of the exported
+//! memory, the module imports `env.read`, which returns the next byte or -1
+//! at the end of the input, and `env.write`, which is passed the byte to
+//! write, and exports `run`. Runtime errors trap with `unreachable`.
+//!
+//! The instructions are built once and then written in the binary format or
+//! as text.
+
+use super::{describe, structure, Code, Node, TranslateError};
+use crate::{CellSize, EofBehavior, ExecutionConfig, Ops, Program, TapeMode};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::convert::TryFrom;
+
+// the functions, the imports come first
+const READ: u32 = 0;
+const WRITE: u32 = 1;
+const OUTSIDE: u32 = 3;
+const FUNCTIONS: [&str; 4] = ["$read", "$write", "$run", "$outside"];
+
+// the globals
+const LEN: u32 = 0;
+const SHIFT: u32 = 1;
+const GLOBALS: [&str; 2] = ["$len", "$shift"];
+
+// the locals of run
+const P: u32 = 0;
+const T: u32 = 1;
+const RUN_LOCALS: [&str; 2] = ["$p", "$t"];
+
+// the parameter and the locals of outside
+const TARGET: u32 = 0;
+const N: u32 = 1;
+const PAGES: u32 = 2;
+const OUTSIDE_LOCALS: [&str; 3] = ["$target", "$n", "$pages"];
+
+#[derive(Clone, Copy, Debug)]
+enum Instr {
+    /// An instruction without immediates, as its encoding and its name.
+    Plain(&'static [u8], &'static str),
+    Br(u32),
+    BrIf(u32),
+    Call(u32),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Const(i32),
+    /// Loads the cell at the address on the stack.
+    Load(CellSize),
+    /// Stores the low bits of the value on the stack in the cell below it.
+    Store(CellSize),
+}
+
+use Instr::*;
+
+const BLOCK: Instr = Plain(&[0x02, 0x40], "block");
+const LOOP: Instr = Plain(&[0x03, 0x40], "loop");
+const IF: Instr = Plain(&[0x04, 0x40], "if");
+const ELSE: Instr = Plain(&[0x05], "else");
+const END: Instr = Plain(&[0x0b], "end");
+const RETURN: Instr = Plain(&[0x0f], "return");
+const UNREACHABLE: Instr = Plain(&[0x00], "unreachable");
+const NOP: Instr = Plain(&[0x01], "nop");
+const SELECT: Instr = Plain(&[0x1b], "select");
+const EQZ: Instr = Plain(&[0x45], "i32.eqz");
+const EQ: Instr = Plain(&[0x46], "i32.eq");
+const LT_S: Instr = Plain(&[0x48], "i32.lt_s");
+const GT_S: Instr = Plain(&[0x4a], "i32.gt_s");
+const GT_U: Instr = Plain(&[0x4b], "i32.gt_u");
+const GE_S: Instr = Plain(&[0x4e], "i32.ge_s");
+const GE_U: Instr = Plain(&[0x4f], "i32.ge_u");
+const ADD: Instr = Plain(&[0x6a], "i32.add");
+const SUB: Instr = Plain(&[0x6b], "i32.sub");
+const MUL: Instr = Plain(&[0x6c], "i32.mul");
+const REM_S: Instr = Plain(&[0x6f], "i32.rem_s");
+const REM_U: Instr = Plain(&[0x70], "i32.rem_u");
+const AND: Instr = Plain(&[0x71], "i32.and");
+const SHL: Instr = Plain(&[0x74], "i32.shl");
+const SHR_U: Instr = Plain(&[0x76], "i32.shr_u");
+const MEMORY_SIZE: Instr = Plain(&[0x3f, 0x00], "memory.size");
+const MEMORY_GROW: Instr = Plain(&[0x40, 0x00], "memory.grow");
+const MEMORY_COPY: Instr = Plain(&[0xfc, 0x0a, 0x00, 0x00], "memory.copy");
+const MEMORY_FILL: Instr = Plain(&[0xfc, 0x0b, 0x00], "memory.fill");
+
+struct Module {
+    config: ExecutionConfig,
+    pages: u32,
+    run: Vec<Instr>,
+    /// Only programs that move need it.
+    outside: Option<Vec<Instr>>,
+}
+
+/// The module in the binary format.
+pub(super) fn binary(
+    program: &Program,
+    config: &ExecutionConfig,
+) -> Result<Vec<u8>, TranslateError> {
+    let module = module(program, config)?;
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+
+    // read, write, run and outside, one type each
+    let types: [(&[u8], &[u8]); 4] = [
+        (&[], &[0x7f]),
+        (&[0x7f], &[]),
+        (&[], &[]),
+        (&[0x7f], &[0x7f]),
+    ];
+    section(&mut bytes, 1, types.len(), |out| {
+        for (params, results) in types.iter() {
+            out.push(0x60);
+            bytes_vec(out, params);
+            bytes_vec(out, results);
+        }
+    });
+    section(&mut bytes, 2, 2, |out| {
+        // their types have the same indices as they do
+        for (name, function) in [("read", READ), ("write", WRITE)].iter() {
+            bytes_vec(out, b"env");
+            bytes_vec(out, name.as_bytes());
+            out.push(0x00);
+            unsigned(out, *function);
+        }
+    });
+    let functions = 1 + module.outside.is_some() as usize;
+    section(&mut bytes, 3, functions, |out| {
+        out.extend([2, 3].iter().take(functions))
+    });
+    section(&mut bytes, 5, 1, |out| {
+        out.push(0x00);
+        unsigned(out, module.pages);
+    });
+    let globals = module.globals();
+    section(&mut bytes, 6, globals.len(), |out| {
+        for &value in &globals {
+            out.extend([0x7f, 0x01, 0x41]);
+            signed(out, value);
+            out.push(0x0b);
+        }
+    });
+    section(&mut bytes, 7, 2, |out| {
+        bytes_vec(out, b"run");
+        out.extend([0x00, 0x02]);
+        bytes_vec(out, b"memory");
+        out.extend([0x02, 0x00]);
+    });
+    section(&mut bytes, 10, functions, |out| {
+        body(out, RUN_LOCALS.len() as u32, &module.run);
+        if let Some(outside) = &module.outside {
+            body(out, OUTSIDE_LOCALS.len() as u32 - 1, outside);
+        }
+    });
+    Ok(bytes)
+}
+
+/// The module as text, in the order of the binary format.
+pub(super) fn text(program: &Program, config: &ExecutionConfig) -> Result<String, TranslateError> {
+    let module = module(program, config)?;
+    let mut code = Code::default();
+    code.text += ";; Translated by brainfuck:\n";
+    for setting in describe(config).iter() {
+        code.text += &format!(";; - {}\n", setting);
+    }
+    code.text += "(module\n";
+    code.line(1, "(import \"env\" \"read\" (func $read (result i32)))");
+    code.line(1, "(import \"env\" \"write\" (func $write (param i32)))");
+    code.line(1, &format!("(memory (export \"memory\") {})", module.pages));
+    for (value, name) in module.globals().iter().zip(GLOBALS.iter()) {
+        code.line(
+            1,
+            &format!("(global {} (mut i32) (i32.const {}))", name, value),
+        );
+    }
+    code.line(
+        1,
+        "(func $run (export \"run\") (local $p i32) (local $t i32)",
+    );
+    instrs(&mut code, &module.run, &RUN_LOCALS);
+    code.line(1, ")");
+    if let Some(outside) = &module.outside {
+        code.line(
+            1,
+            "(func $outside (param $target i32) (result i32) (local $n i32) (local $pages i32)",
+        );
+        instrs(&mut code, outside, &OUTSIDE_LOCALS);
+        code.line(1, ")");
+    }
+    code.text += ")\n";
+    Ok(code.text)
+}
+
+fn module(program: &Program, config: &ExecutionConfig) -> Result<Module, TranslateError> {
+    let nodes = structure(program)?;
+    let bytes = config.tape_size as u64 * width(config.cell_size) as u64;
+    // indices have to stay positive as i32, so that the end of a tape that
+    // grows to the left can be told apart
+    if bytes > 1 << 31 {
+        return Err(TranslateError::Unsupported("have a tape larger than 2 GiB"));
+    }
+    let far = program.iter().any(|op| match *op {
+        Ops::Move(offset)
+        | Ops::ModAt(offset, _)
+        | Ops::AddTo(offset, _)
+        | Ops::SearchZeroCell(offset)
+        | Ops::MoveClose(offset, _)
+        | Ops::ModMove(_, offset) => i32::try_from(offset).is_err(),
+        _ => false,
+    });
+    if far {
+        return Err(TranslateError::Unsupported("move by 2^31 cells or more"));
+    }
+    let moves = program.iter().any(|op| {
+        matches!(
+            op,
+            Ops::Move(_)
+                | Ops::SearchZeroCell(_)
+                | Ops::ModAt(..)
+                | Ops::AddTo(..)
+                | Ops::MoveClose(..)
+                | Ops::ModMove(..)
+        )
+    });
+    let mut run = Body {
+        config: *config,
+        instrs: Vec::new(),
+    };
+    // the end of the program is the end of the function
+    let nodes = match nodes.split_last() {
+        Some((Node::Op(Ops::End), nodes)) => nodes,
+        _ => &nodes[..],
+    };
+    run.block(nodes);
+    let outside = match moves {
+        true => {
+            let mut outside = Body {
+                config: *config,
+                instrs: Vec::new(),
+            };
+            outside.outside();
+            Some(outside.instrs)
+        }
+        false => None,
+    };
+    Ok(Module {
+        config: *config,
+        pages: ((bytes + 0xffff) >> 16) as u32,
+        run: run.instrs,
+        outside,
+    })
+}
+
+impl Module {
+    /// The initial values of the globals.
+    fn globals(&self) -> Vec<i32> {
+        let mut globals = vec![self.config.tape_size as i32];
+        if self.config.tape == TapeMode::Infinite {
+            globals.push(0);
+        }
+        globals
+    }
+}
+
+fn width(size: CellSize) -> u32 {
+    match size {
+        CellSize::U8 => 1,
+        CellSize::U16 => 2,
+        CellSize::U32 => 4,
+    }
+}
+
+struct Body {
+    config: ExecutionConfig,
+    instrs: Vec<Instr>,
+}
+
+impl Body {
+    fn push(&mut self, instrs: &[Instr]) {
+        self.instrs.extend_from_slice(instrs);
+    }
+
+    /// Turns the number of cells on the stack into bytes.
+    fn bytes(&mut self) {
+        let shift = width(self.config.cell_size).trailing_zeros();
+        if shift > 0 {
+            self.push(&[Const(shift as i32), SHL]);
+        }
+    }
+
+    /// The address of the cell whose index is in `local`.
+    fn address(&mut self, local: u32) {
+        self.push(&[LocalGet(local)]);
+        self.bytes();
+    }
+
+    fn load(&mut self, local: u32) {
+        self.address(local);
+        self.push(&[Load(self.config.cell_size)]);
+    }
+
+    /// Adds `offset` to the data pointer.
+    fn go(&mut self, offset: isize) {
+        let offset = offset as i32;
+        self.push(&[LocalGet(P), Const(offset), ADD, LocalTee(P)]);
+        // a pointer below 0 is a huge unsigned number
+        self.push(&[
+            GlobalGet(LEN),
+            GE_U,
+            IF,
+            LocalGet(P),
+            Call(OUTSIDE),
+            LocalSet(P),
+            END,
+        ]);
+    }
+
+    /// Stores the index of the cell `offset` cells from the data pointer in
+    /// `$t`.
+    fn at(&mut self, offset: isize) {
+        let offset = offset as i32;
+        self.push(&[LocalGet(P), Const(offset), ADD, LocalTee(T)]);
+        self.push(&[
+            GlobalGet(LEN),
+            GE_U,
+            IF,
+            LocalGet(T),
+            Call(OUTSIDE),
+            LocalSet(T),
+        ]);
+        if self.config.tape == TapeMode::Infinite {
+            // the cells were shifted to the right if the tape grew to the left
+            self.push(&[GlobalGet(SHIFT), LocalGet(P), ADD, LocalSet(P)]);
+        }
+        self.push(&[END]);
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        let size = self.config.cell_size;
+        for node in nodes {
+            let op = match node {
+                Node::Op(op) => *op,
+                Node::Loop(body) => {
+                    self.push(&[BLOCK]);
+                    self.load(P);
+                    self.push(&[EQZ, BrIf(0), LOOP]);
+                    self.block(body);
+                    self.load(P);
+                    self.push(&[BrIf(0), END, END]);
+                    continue;
+                }
+                Node::If(body) => {
+                    self.load(P);
+                    self.push(&[IF]);
+                    self.block(body);
+                    self.push(&[END]);
+                    continue;
+                }
+            };
+            match op {
+                Ops::Move(offset) => self.go(offset),
+                Ops::Mod(value) => {
+                    self.address(P);
+                    self.load(P);
+                    self.push(&[Const(value), ADD, Store(size)]);
+                }
+                Ops::ModAt(offset, value) => {
+                    self.at(offset);
+                    self.address(T);
+                    self.load(T);
+                    self.push(&[Const(value), ADD, Store(size)]);
+                }
+                Ops::AddTo(offset, factor) => {
+                    // the loop this replaces never touches other cells if it is skipped
+                    self.load(P);
+                    self.push(&[IF]);
+                    self.at(offset);
+                    self.address(T);
+                    self.load(T);
+                    self.load(P);
+                    match factor {
+                        1 => self.push(&[ADD]),
+                        -1 => self.push(&[SUB]),
+                        _ => self.push(&[Const(factor), MUL, ADD]),
+                    }
+                    self.push(&[Store(size), END]);
+                }
+                Ops::SetCell(value) => {
+                    self.address(P);
+                    self.push(&[Const(value as i32), Store(size)]);
+                }
+                Ops::SearchZeroCell(step) => {
+                    self.push(&[BLOCK, LOOP]);
+                    self.load(P);
+                    self.push(&[EQZ, BrIf(1)]);
+                    self.go(step);
+                    self.push(&[Br(0), END, END]);
+                }
+                Ops::Print => {
+                    self.load(P);
+                    if size != CellSize::U8 {
+                        self.push(&[Const(0xff), AND]);
+                    }
+                    self.push(&[Call(WRITE)]);
+                }
+                Ops::Read => {
+                    self.push(&[Call(READ), LocalTee(T), Const(0), GE_S, IF]);
+                    self.address(P);
+                    self.push(&[LocalGet(T), Store(size)]);
+                    match self.config.eof {
+                        EofBehavior::Zero | EofBehavior::MinusOne => {
+                            let value = match self.config.eof {
+                                EofBehavior::Zero => 0,
+                                _ => -1,
+                            };
+                            self.push(&[ELSE]);
+                            self.address(P);
+                            self.push(&[Const(value), Store(size)]);
+                        }
+                        EofBehavior::Unchanged => {}
+                        EofBehavior::Error => self.push(&[ELSE, UNREACHABLE]),
+                    }
+                    self.push(&[END]);
+                }
+                Ops::Break => self.push(&[NOP]),
+                Ops::End => self.push(&[RETURN]),
+                // structure nests the loops and splits the fused pairs
+                Ops::LoopOpen(_)
+                | Ops::BalancedLoop(..)
+                | Ops::LoopClose(_)
+                | Ops::If(_)
+                | Ops::MoveClose(..)
+                | Ops::ModMove(..) => unreachable!(),
+                // translate rejects programs with procedures and dumps
+                Ops::ProcDefStart(_) | Ops::ProcDefEnd | Ops::Call | Ops::Dump => unreachable!(),
+            }
+        }
+    }
+
+    /// `outside(target)` is the index of the cell at `target`, which is past
+    /// one of the ends of the tape. The memory grows in pages of 64 KiB, which
+    /// start zeroed; the cells past the end of the tape are never written.
+    fn outside(&mut self) {
+        match self.config.tape {
+            TapeMode::Fixed => self.push(&[UNREACHABLE]),
+            TapeMode::Grow => {
+                self.push(&[LocalGet(TARGET), Const(0), LT_S, IF, UNREACHABLE, END]);
+                self.grow_right();
+                self.push(&[LocalGet(TARGET)]);
+            }
+            TapeMode::Wrap => self.push(&[
+                LocalGet(TARGET),
+                GlobalGet(LEN),
+                REM_S,
+                GlobalGet(LEN),
+                ADD,
+                GlobalGet(LEN),
+                REM_U,
+            ]),
+            TapeMode::Infinite => {
+                self.push(&[LocalGet(TARGET), Const(0), GE_S, IF]);
+                self.grow_right();
+                self.push(&[Const(0), GlobalSet(SHIFT), LocalGet(TARGET), RETURN, END]);
+                // prepend `$n` zeroed cells, which shifts every existing cell to
+                // the right
+                self.push(&[Const(0), LocalGet(TARGET), SUB, LocalTee(N)]);
+                self.push(&[
+                    GlobalGet(LEN),
+                    LocalGet(N),
+                    GlobalGet(LEN),
+                    GT_U,
+                    SELECT,
+                    LocalSet(N),
+                ]);
+                self.push(&[GlobalGet(LEN), LocalGet(N), ADD]);
+                self.reserve();
+                self.push(&[LocalGet(N)]);
+                self.bytes();
+                self.push(&[Const(0), GlobalGet(LEN)]);
+                self.bytes();
+                self.push(&[MEMORY_COPY, Const(0), Const(0), LocalGet(N)]);
+                self.bytes();
+                self.push(&[MEMORY_FILL]);
+                self.push(&[GlobalGet(LEN), LocalGet(N), ADD, GlobalSet(LEN)]);
+                self.push(&[
+                    LocalGet(N),
+                    GlobalSet(SHIFT),
+                    LocalGet(TARGET),
+                    LocalGet(N),
+                    ADD,
+                ]);
+            }
+        }
+    }
+
+    /// Makes the tape at least `$target + 1` cells and twice as long.
+    fn grow_right(&mut self) {
+        self.push(&[
+            LocalGet(TARGET),
+            Const(1),
+            ADD,
+            LocalTee(N),
+            GlobalGet(LEN),
+            Const(1),
+            SHL,
+        ]);
+        self.push(&[
+            LocalGet(N),
+            GlobalGet(LEN),
+            Const(1),
+            SHL,
+            GT_U,
+            SELECT,
+            LocalTee(N),
+        ]);
+        self.reserve();
+        self.push(&[LocalGet(N), GlobalSet(LEN)]);
+    }
+
+    /// Grows the memory to hold the number of cells on the stack.
+    fn reserve(&mut self) {
+        self.bytes();
+        self.push(&[
+            Const(0xffff),
+            ADD,
+            Const(16),
+            SHR_U,
+            MEMORY_SIZE,
+            SUB,
+            LocalTee(PAGES),
+        ]);
+        self.push(&[
+            Const(0),
+            GT_S,
+            IF,
+            LocalGet(PAGES),
+            MEMORY_GROW,
+            Const(-1),
+            EQ,
+        ]);
+        self.push(&[IF, UNREACHABLE, END, END]);
+    }
+}
+
+fn instrs(code: &mut Code, instrs: &[Instr], locals: &[&str]) {
+    let mut depth = 2;
+    for instr in instrs {
+        let line = match *instr {
+            Plain(_, name) => name.to_string(),
+            Br(label) => format!("br {}", label),
+            BrIf(label) => format!("br_if {}", label),
+            Call(function) => format!("call {}", FUNCTIONS[function as usize]),
+            LocalGet(local) => format!("local.get {}", locals[local as usize]),
+            LocalSet(local) => format!("local.set {}", locals[local as usize]),
+            LocalTee(local) => format!("local.tee {}", locals[local as usize]),
+            GlobalGet(global) => format!("global.get {}", GLOBALS[global as usize]),
+            GlobalSet(global) => format!("global.set {}", GLOBALS[global as usize]),
+            Const(value) => format!("i32.const {}", value),
+            Load(CellSize::U8) => "i32.load8_u".to_string(),
+            Load(CellSize::U16) => "i32.load16_u".to_string(),
+            Load(CellSize::U32) => "i32.load".to_string(),
+            Store(CellSize::U8) => "i32.store8".to_string(),
+            Store(CellSize::U16) => "i32.store16".to_string(),
+            Store(CellSize::U32) => "i32.store".to_string(),
+        };
+        match line.as_str() {
+            "end" => depth -= 1,
+            "else" => {
+                code.line(depth - 1, &line);
+                continue;
+            }
+            _ => {}
+        }
+        code.line(depth, &line);
+        if let "block" | "loop" | "if" = line.as_str() {
+            depth += 1;
+        }
+    }
+}
+
+/// Appends section `id` with `count` entries, which `entries` writes.
+fn section(out: &mut Vec<u8>, id: u8, count: usize, entries: impl FnOnce(&mut Vec<u8>)) {
+    let mut content = Vec::new();
+    unsigned(&mut content, count as u32);
+    entries(&mut content);
+    out.push(id);
+    unsigned(out, content.len() as u32);
+    out.extend(content);
+}
+
+/// A function body with `locals` locals of type i32.
+fn body(out: &mut Vec<u8>, locals: u32, instrs: &[Instr]) {
+    let mut code = vec![1];
+    unsigned(&mut code, locals);
+    code.push(0x7f);
+    for instr in instrs {
+        match *instr {
+            Plain(bytes, _) => code.extend_from_slice(bytes),
+            Br(label) => immediate(&mut code, 0x0c, label),
+            BrIf(label) => immediate(&mut code, 0x0d, label),
+            Call(function) => immediate(&mut code, 0x10, function),
+            LocalGet(local) => immediate(&mut code, 0x20, local),
+            LocalSet(local) => immediate(&mut code, 0x21, local),
+            LocalTee(local) => immediate(&mut code, 0x22, local),
+            GlobalGet(global) => immediate(&mut code, 0x23, global),
+            GlobalSet(global) => immediate(&mut code, 0x24, global),
+            Const(value) => {
+                code.push(0x41);
+                signed(&mut code, value);
+            }
+            // the alignment and the offset
+            Load(size) | Store(size) => {
+                let opcode = match (instr, size) {
+                    (Load(_), CellSize::U8) => 0x2d,
+                    (Load(_), CellSize::U16) => 0x2f,
+                    (Load(_), CellSize::U32) => 0x28,
+                    (_, CellSize::U8) => 0x3a,
+                    (_, CellSize::U16) => 0x3b,
+                    (_, CellSize::U32) => 0x36,
+                };
+                code.extend([opcode, width(size).trailing_zeros() as u8, 0]);
+            }
+        }
+    }
+    code.push(0x0b);
+    unsigned(out, code.len() as u32);
+    out.extend(code);
+}
+
+fn immediate(out: &mut Vec<u8>, opcode: u8, value: u32) {
+    out.push(opcode);
+    unsigned(out, value);
+}
+
+fn bytes_vec(out: &mut Vec<u8>, bytes: &[u8]) {
+    unsigned(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+/// LEB128.
+fn unsigned(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.push(byte);
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128, which ends once the rest is only the sign.
+fn signed(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let sign = byte & 0x40 != 0;
+        if (value == 0 && !sign) || (value == -1 && sign) {
+            return out.push(byte);
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::{compile, compile_file};
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    /// Compares with the file in programs/translated, `BLESS=1` writes it.
+    fn golden(name: &str, code: &str) {
+        let path = Path::new("programs/translated").join(name);
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(&path, code).unwrap();
+        }
+        assert_eq!(code, std::fs::read_to_string(&path).unwrap(), "{:?}", path);
+    }
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn infinite() -> ExecutionConfig {
+        ExecutionConfig::builder()
+            .tape(TapeMode::Infinite)
+            .tape_size(4)
+            .cell_size(CellSize::U16)
+            .eof(EofBehavior::Error)
+            .build()
+    }
+
+    #[test]
+    fn golden_files() {
+        let config = ExecutionConfig::default();
+        golden(
+            "cat.wat",
+            &text(&compile(",[.,]").unwrap(), &config).unwrap(),
+        );
+        // every kind of op on a tape that has to grow to the left
+        let source = "+[-<]>>+++[>+>--<<-]<[-]+[>]<<[->>+<<]>>---.,+[-]";
+        golden(
+            "infinite.wat",
+            &text(&compile(source).unwrap(), &infinite()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn leb128() {
+        let encode = |value: i32| {
+            let mut bytes = Vec::new();
+            signed(&mut bytes, value);
+            bytes
+        };
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(63), [0x3f]);
+        assert_eq!(encode(64), [0xc0, 0x00]);
+        assert_eq!(encode(-1), [0x7f]);
+        assert_eq!(encode(-65), [0xbf, 0x7f]);
+        assert_eq!(encode(i32::MIN), [0x80, 0x80, 0x80, 0x80, 0x78]);
+        let mut bytes = Vec::new();
+        unsigned(&mut bytes, 30000);
+        assert_eq!(bytes, [0xb0, 0xea, 0x01]);
+    }
+
+    #[test]
+    fn too_large() {
+        let program = compile("+").unwrap();
+        let config = ExecutionConfig::builder()
+            .tape_size(1 << 30)
+            .cell_size(CellSize::U32)
+            .build();
+        assert!(binary(&program, &config).is_err());
+        let program = Program::new(vec![Ops::Move(1 << 40), Ops::End]).unwrap();
+        assert!(binary(&program, &ExecutionConfig::default()).is_err());
+    }
+
+    /// Validates and runs modules with node, if it is installed. The imports
+    /// read stdin and collect the output, a trap is written as `trap`.
+    #[test]
+    fn runs() {
+        if Command::new("node").arg("--version").output().is_err() {
+            return eprintln!("no node, skipping");
+        }
+        let grow = ExecutionConfig::builder()
+            .tape(TapeMode::Grow)
+            .tape_size(1)
+            .cell_size(CellSize::U32)
+            .build();
+        let wrap = ExecutionConfig::builder()
+            .tape(TapeMode::Wrap)
+            .tape_size(4)
+            .build();
+        let rot13 = compile_file("programs/rot13.bf", &Pipeline::default()).unwrap();
+        let minus_one = ExecutionConfig::builder()
+            .eof(EofBehavior::MinusOne)
+            .build();
+        // carries a counter 100 cells to the right 200 times, past the first
+        // page, then wraps the 32 bit cell around to 65
+        let far = format!(
+            "{}[-[-{}+{}]{}]-{}.",
+            "+".repeat(200),
+            ">".repeat(100),
+            "<".repeat(100),
+            ">".repeat(100),
+            "+".repeat(66)
+        );
+        let programs = [
+            (
+                compile(HELLO).unwrap(),
+                ExecutionConfig::default(),
+                Vec::new(),
+                "Hello World!\n".to_string(),
+            ),
+            (
+                rot13,
+                minus_one,
+                std::fs::read("programs/rot13.in").unwrap(),
+                std::fs::read_to_string("programs/rot13.out").unwrap(),
+            ),
+            (compile(&far).unwrap(), grow, Vec::new(), "A".into()),
+            (
+                compile(">+>+>+<<[>]+.<->>[<]+.").unwrap(),
+                wrap,
+                Vec::new(),
+                "\u{1}\u{1}".into(),
+            ),
+            (
+                compile("+[-<<+>>]<<.<<<<<<<<+.").unwrap(),
+                infinite(),
+                Vec::new(),
+                "\u{1}\u{1}".into(),
+            ),
+            (
+                compile(",.,.").unwrap(),
+                infinite(),
+                b"a".to_vec(),
+                "atrap".into(),
+            ),
+            (
+                compile("<").unwrap(),
+                ExecutionConfig::default(),
+                Vec::new(),
+                "trap".into(),
+            ),
+        ];
+        let module = std::env::temp_dir().join("brainfuck-translate.wasm");
+        for (program, config, input, expected) in programs.iter() {
+            std::fs::write(&module, binary(program, config).unwrap()).unwrap();
+            let mut node = Command::new("node")
+                .args(["-e", HOST])
+                .arg(&module)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            node.stdin.take().unwrap().write_all(input).unwrap();
+            let output = node.wait_with_output().unwrap();
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8_lossy(&output.stdout), *expected);
+        }
+    }
+
+    const HOST: &str = "
+        const fs = require('fs');
+        const bytes = fs.readFileSync(process.argv[1]);
+        if (!WebAssembly.validate(bytes)) {
+            throw new Error('invalid module');
+        }
+        const input = fs.readFileSync(0);
+        const output = [];
+        let i = 0;
+        const env = {
+            read: () => (i < input.length ? input[i++] : -1),
+            write: (byte) => output.push(byte),
+        };
+        const instance = new WebAssembly.Instance(new WebAssembly.Module(bytes), { env });
+        try {
+            instance.exports.run();
+        } catch (err) {
+            if (!(err instanceof WebAssembly.RuntimeError)) {
+                throw err;
+            }
+            output.push(...Buffer.from('trap'));
+        }
+        process.stdout.write(Buffer.from(output));
+    ";
+}
@@ -0,0 +1,89 @@
+//! The entry point of a browser playground, for the library built for
+//! wasm32-unknown-unknown with the `wasm` feature.
+//!
+//! [`run`] works on strings only and never panics on a program, whatever it
+//! does: the input comes from memory, the output is collected, and `max_ops`
+//! keeps a program that does not end from hanging the page. There is no
+//! clock to enforce a timeout with on that target.
+//!
+//! ```
+//! let result = brainfuck::wasm::run(",[.,]", "hi", 1000);
+//! assert_eq!((result.output.as_str(), result.error), ("hi", None));
+//! ```
+
+use crate::{compile, Error, ExecutionConfig, Position, RuntimeError, StringInputOutput};
+use alloc::format;
+use alloc::string::{String, ToString};
+
+/// What [`run`] returns.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunResult {
+    /// Everything the program printed, also if it stopped with an error.
+    pub output: String,
+    /// Why the program did not compile or stopped, with the position in the
+    /// source where there is one.
+    pub error: Option<String>,
+    /// Whether the program used up `max_ops`, the error says so as well.
+    pub out_of_fuel: bool,
+}
+
+/// Compiles and runs `source`, `,` reads the chars of `input`. The program
+/// stops after `max_ops` ops.
+pub fn run(source: &str, input: &str, max_ops: u64) -> RunResult {
+    let program = match compile(source) {
+        Ok(program) => program,
+        Err(errors) => {
+            return RunResult {
+                error: Some(Error::Compile(errors).to_string()),
+                ..RunResult::default()
+            }
+        }
+    };
+    let config = ExecutionConfig::builder().max_ops(max_ops).build();
+    let mut in_out = StringInputOutput::with_input(input);
+    let result = config.run(&program, &mut in_out);
+    let error = result.err().map(
+        |err| match err.ip().and_then(|ip| program.source_span(ip)) {
+            Some(span) => format!("{} ({})", err, Position::in_source(source, span.start)),
+            None => err.to_string(),
+        },
+    );
+    RunResult {
+        output: in_out.into_output(),
+        error,
+        out_of_fuel: matches!(result, Err(RuntimeError::FuelExhausted { .. })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    #[test]
+    fn hello_world() {
+        let result = run(HELLO, "", 10_000);
+        assert_eq!(result.output, "Hello World!\n");
+        assert_eq!(result.error, None);
+        assert!(!result.out_of_fuel);
+    }
+
+    #[test]
+    fn errors() {
+        let result = run("+[", "", 100);
+        assert_eq!(result.output, "");
+        assert!(result.error.unwrap().contains("line 1, column 2"));
+        let result = run("+.<", "", 100);
+        assert_eq!(result.output, "\u{1}");
+        assert_eq!(
+            result.error.as_deref(),
+            Some("pointer moved out of bounds to cell -1 at instruction 2 (line 1, column 3)")
+        );
+        assert!(!result.out_of_fuel);
+        let result = run(".+[.]", "", 100);
+        assert_eq!(result.output.len(), 50);
+        assert!(result.out_of_fuel);
+        assert!(result.error.is_some());
+    }
+}
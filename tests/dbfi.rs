@@ -0,0 +1,49 @@
+//! dbfi, a brainfuck interpreter written in brainfuck, which reads a
+//! program, then its input after a `!`.
+
+use brainfuck::optimizer::{OptLevel, Pipeline};
+use brainfuck::{
+    compile_with, execute, run_source, EofBehavior, ExecutionConfig, StringInputOutput,
+};
+
+const PROGRAM: &str = "++++++++[>++++++++<-]>+.!";
+
+fn dbfi() -> String {
+    std::fs::read_to_string("programs/dbfi.bf").unwrap()
+}
+
+fn run(source: &str, input: &str, config: &ExecutionConfig) -> String {
+    let mut in_out = StringInputOutput::with_input(input);
+    run_source(source, &mut in_out, &Pipeline::default(), config).unwrap();
+    in_out.into_output()
+}
+
+#[test]
+fn levels() {
+    let dbfi = dbfi();
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let compiled = compile_with(&dbfi, &Pipeline::new(level)).unwrap();
+        let mut in_out = StringInputOutput::with_input(PROGRAM);
+        execute(&compiled, &mut in_out).unwrap();
+        assert_eq!(in_out.output(), "A", "at {:?}", level);
+    }
+}
+
+/// The `,` of the interpreted program reads the end of the input as the host
+/// does.
+#[test]
+fn eof() {
+    let dbfi = dbfi();
+    let with_eof = |eof| ExecutionConfig::builder().eof(eof).build();
+    assert_eq!(run(&dbfi, ",+.!", &with_eof(EofBehavior::Zero)), "\u{1}");
+    assert_eq!(run(&dbfi, ",+.!", &with_eof(EofBehavior::MinusOne)), "\0");
+    assert_eq!(run(&dbfi, ",.!x", &with_eof(EofBehavior::Unchanged)), "x");
+}
+
+/// dbfi running dbfi running the program.
+#[test]
+fn nested() {
+    let dbfi = dbfi();
+    let nested = format!("{}!{}", dbfi, PROGRAM);
+    assert_eq!(run(&dbfi, &nested, &ExecutionConfig::default()), "A");
+}
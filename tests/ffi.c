@@ -0,0 +1,48 @@
+/*
+ * Runs hello world through the C API:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *     cc -Iinclude -o target/ffi tests/ffi.c -Ltarget/release -lbrainfuck
+ *     LD_LIBRARY_PATH=target/release target/ffi
+ */
+#include <brainfuck.h>
+#include <stdio.h>
+#include <string.h>
+
+struct output {
+    char bytes[64];
+    size_t len;
+};
+
+static void collect(uint8_t byte, void *userdata) {
+    struct output *output = userdata;
+    if (output->len < sizeof(output->bytes) - 1) {
+        output->bytes[output->len++] = (char)byte;
+    }
+}
+
+int main(void) {
+    const char *hello = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+    struct output output = {{0}, 0};
+    char *err = NULL;
+    BfProgram *program = bf_compile(hello, &err);
+    int code;
+    if (!program) {
+        fprintf(stderr, "cannot compile: %s\n", err);
+        bf_string_free(err);
+        return 1;
+    }
+    code = bf_run(program, NULL, 0, collect, &output);
+    bf_program_free(program);
+    if (code != BF_OK || strcmp(output.bytes, "Hello World!\n") != 0) {
+        fprintf(stderr, "bf_run returned %d and printed '%s'\n", code, output.bytes);
+        return 1;
+    }
+    if (bf_compile("+[", &err) || !strstr(err, "line 1, column 2")) {
+        fprintf(stderr, "compiled an unmatched [\n");
+        return 1;
+    }
+    bf_string_free(err);
+    puts("ok");
+    return 0;
+}